@@ -0,0 +1,300 @@
+#![deny(warnings)]
+#![warn(unused_extern_crates)]
+#![deny(clippy::todo)]
+#![deny(clippy::unimplemented)]
+#![deny(clippy::unwrap_used)]
+#![deny(clippy::expect_used)]
+#![deny(clippy::panic)]
+#![deny(clippy::unreachable)]
+#![deny(clippy::needless_pass_by_value)]
+#![deny(clippy::trivially_copy_pass_by_ref)]
+
+//! Derive macros for `scim_proto`. Use them through the `derive` feature of
+//! that crate, rather than depending on this one directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr};
+
+/// Implement `ScimResource`, and the conversions to and from
+/// `ScimEntryGeneric` it needs, for a struct with named fields.
+///
+/// ```ignore
+/// #[derive(ScimResource)]
+/// #[scim(schema = "urn:example:params:scim:schemas:Device", resource_type = "Device")]
+/// struct Device {
+///     entry: ScimEntry,
+///     #[scim(rename = "serialNumber", required)]
+///     serial: String,
+///     display_name: Option<String>,
+///     #[scim(extra)]
+///     extra: AttrMap<ScimValue>,
+/// }
+/// ```
+///
+/// The struct attribute takes the schema URN, and the resource type, which
+/// defaults to the struct's name.
+///
+/// One field holds the `ScimEntry` with the common attributes. It is the
+/// field named `entry`, or the one marked `#[scim(entry)]`. A field marked
+/// `#[scim(extra)]`, which must be an `AttrMap<ScimValue>`, gets
+/// every attribute no other field takes.
+///
+/// Each other field is an attribute, named as the field in camelCase unless
+/// renamed. Lookup is case-insensitive. A `required` attribute that is
+/// missing is an error; other missing attributes take their type's default.
+#[proc_macro_derive(ScimResource, attributes(scim))]
+pub fn derive_scim_resource(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<String>,
+    required: bool,
+    entry: bool,
+    extra: bool,
+}
+
+struct Attribute {
+    ident: Ident,
+    name: String,
+    required: bool,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let mut schema = None;
+    let mut resource_type = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("scim")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("schema") {
+                schema = Some(meta.value()?.parse::<LitStr>()?);
+            } else if meta.path.is_ident("resource_type") {
+                resource_type = Some(meta.value()?.parse::<LitStr>()?);
+            } else {
+                return Err(meta.error("unknown scim attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    let schema = schema.ok_or_else(|| {
+        syn::Error::new(
+            Span::call_site(),
+            "ScimResource needs #[scim(schema = \"...\")]",
+        )
+    })?;
+    let name = &input.ident;
+    let resource_type =
+        resource_type.unwrap_or_else(|| LitStr::new(&name.to_string(), name.span()));
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "ScimResource needs a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "ScimResource can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut entry: Option<Ident> = None;
+    let mut extra: Option<Ident> = None;
+    let mut attributes = Vec::new();
+    for field in fields {
+        let Some(ident) = field.ident.clone() else {
+            continue;
+        };
+        let attrs = field_attrs(field)?;
+        if attrs.entry || (ident == "entry" && !attrs.extra) {
+            if entry.is_some() {
+                return Err(syn::Error::new_spanned(&ident, "duplicate entry field"));
+            }
+            entry = Some(ident);
+        } else if attrs.extra {
+            if extra.is_some() {
+                return Err(syn::Error::new_spanned(&ident, "duplicate extra field"));
+            }
+            extra = Some(ident);
+        } else {
+            let name = attrs.rename.unwrap_or_else(|| attribute_name(&ident));
+            attributes.push(Attribute {
+                ident,
+                name,
+                required: attrs.required,
+            });
+        }
+    }
+    let entry = entry.ok_or_else(|| {
+        syn::Error::new_spanned(
+            &input.ident,
+            "ScimResource needs a ScimEntry field, named `entry` or marked #[scim(entry)]",
+        )
+    })?;
+
+    let takes = attributes.iter().map(|a| {
+        let Attribute {
+            ident,
+            name,
+            required,
+        } = a;
+        if *required {
+            quote! {
+                #ident: ::scim_proto::__private::take_attr(&mut attrs, #name)?
+                    .ok_or_else(|| {
+                        ::scim_proto::error::ScimError::MissingRequiredAttribute(#name.to_string())
+                    })?
+            }
+        } else {
+            quote! {
+                #ident: ::scim_proto::__private::take_attr(&mut attrs, #name)?
+                    .unwrap_or_default()
+            }
+        }
+    });
+    let puts = attributes.iter().map(|a| {
+        let Attribute { ident, name, .. } = a;
+        quote! {
+            ::scim_proto::__private::put_attr(&mut attrs, #name, &value.#ident)?;
+        }
+    });
+    let (take_extra, new_attrs) = match &extra {
+        Some(extra) => (
+            quote! { #extra: attrs, },
+            quote! { let mut attrs = value.#extra; },
+        ),
+        None => (
+            quote! {},
+            quote! { let mut attrs = ::scim_proto::__private::AttrMap::new(); },
+        ),
+    };
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::core::convert::TryFrom<::scim_proto::ScimEntryGeneric>
+            for #name #ty_generics #where_clause
+        {
+            type Error = ::scim_proto::error::ScimError;
+
+            fn try_from(
+                entry: ::scim_proto::ScimEntryGeneric,
+            ) -> ::core::result::Result<Self, Self::Error> {
+                let mut attrs = entry.attrs;
+                ::core::result::Result::Ok(#name {
+                    #entry: ::scim_proto::ScimEntry {
+                        schemas: entry.schemas,
+                        id: entry.id,
+                        external_id: entry.external_id,
+                        meta: entry.meta,
+                    },
+                    #(#takes,)*
+                    #take_extra
+                })
+            }
+        }
+
+        impl #impl_generics ::core::convert::TryFrom<#name #ty_generics>
+            for ::scim_proto::ScimEntryGeneric #where_clause
+        {
+            type Error = ::scim_proto::error::ScimError;
+
+            fn try_from(
+                value: #name #ty_generics,
+            ) -> ::core::result::Result<Self, Self::Error> {
+                #new_attrs
+                #(#puts)*
+                ::core::result::Result::Ok(::scim_proto::ScimEntryGeneric {
+                    schemas: value.#entry.schemas,
+                    id: value.#entry.id,
+                    external_id: value.#entry.external_id,
+                    meta: value.#entry.meta,
+                    attrs,
+                })
+            }
+        }
+
+        impl #impl_generics ::scim_proto::resource::ScimResource
+            for #name #ty_generics #where_clause
+        {
+            const SCHEMA_URN: &'static str = #schema;
+
+            fn resource_type() -> &'static str {
+                #resource_type
+            }
+
+            fn into_entry(
+                self,
+            ) -> ::core::result::Result<::scim_proto::ScimEntryGeneric, ::scim_proto::error::ScimError>
+            {
+                ::scim_proto::ScimEntryGeneric::try_from(self)
+            }
+        }
+    })
+}
+
+fn field_attrs(field: &syn::Field) -> syn::Result<FieldAttrs> {
+    let mut attrs = FieldAttrs::default();
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("scim")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                attrs.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("required") {
+                attrs.required = true;
+            } else if meta.path.is_ident("entry") {
+                attrs.entry = true;
+            } else if meta.path.is_ident("extra") {
+                attrs.extra = true;
+            } else {
+                return Err(meta.error("unknown scim attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    if attrs.entry && (attrs.extra || attrs.required || attrs.rename.is_some()) {
+        return Err(syn::Error::new_spanned(
+            &field.ident,
+            "an entry field can't have other scim attributes",
+        ));
+    }
+    if attrs.extra && (attrs.required || attrs.rename.is_some()) {
+        return Err(syn::Error::new_spanned(
+            &field.ident,
+            "an extra field can't have other scim attributes",
+        ));
+    }
+    Ok(attrs)
+}
+
+// display_name becomes displayName. A trailing underscore, as in type_, is
+// dropped, and ref_ becomes $ref, as serde renames them in this crate.
+fn attribute_name(ident: &Ident) -> String {
+    let ident = ident.to_string();
+    let ident = ident.strip_prefix("r#").unwrap_or(&ident);
+    if ident == "ref_" {
+        return "$ref".to_string();
+    }
+    let mut name = String::with_capacity(ident.len());
+    let mut upper = false;
+    for c in ident.trim_end_matches('_').chars() {
+        if c == '_' {
+            upper = !name.is_empty();
+        } else if upper {
+            name.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
@@ -0,0 +1,28 @@
+//! The map attributes are held in, keyed by name.
+//!
+//! By default this is a `BTreeMap`, so attributes are ordered by name. With
+//! the `indexmap` feature it is an `IndexMap`, which keeps the order
+//! attributes were read or inserted in, so an entry is written back in the
+//! order it was read. This is for systems that diff serialised entries.
+
+#[cfg(not(feature = "indexmap"))]
+pub use std::collections::btree_map::{IntoIter, Iter, IterMut};
+
+#[cfg(feature = "indexmap")]
+pub use indexmap::map::{IntoIter, Iter, IterMut};
+
+#[cfg(not(feature = "indexmap"))]
+pub type AttrMap<V> = std::collections::BTreeMap<String, V>;
+
+#[cfg(feature = "indexmap")]
+pub type AttrMap<V> = indexmap::IndexMap<String, V>;
+
+/// Remove the attribute with exactly the name `key`, keeping the order of
+/// the others.
+pub(crate) fn remove<V>(attrs: &mut AttrMap<V>, key: &str) -> Option<V> {
+    #[cfg(not(feature = "indexmap"))]
+    return attrs.remove(key);
+
+    #[cfg(feature = "indexmap")]
+    return attrs.shift_remove(key);
+}
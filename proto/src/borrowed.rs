@@ -0,0 +1,317 @@
+//! Entries borrowed from the text they were read from, for pipelines that
+//! filter or project entries without keeping them. Attribute names and
+//! string values borrow from the input where they can; only strings with
+//! escapes in them, which can't be borrowed as they are, are copied.
+//!
+//! Values are read as JSON gives them, without the date time, reference and
+//! binary forms a schema would promote strings to. [`ScimEntryRef::to_entry`]
+//! gives the owned [`ScimEntryGeneric`] for anything more.
+
+use crate::convert;
+use crate::error::ScimError;
+use crate::redact::AttrPairs;
+use crate::ScimEntryGeneric;
+use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+// A string borrowed from the input if the deserializer allows it. Serde's own
+// Cow always copies when nested in a map or sequence.
+struct Str<'a>(Cow<'a, str>);
+
+impl<'de> Deserialize<'de> for Str<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StrVisitor;
+
+        impl<'de> Visitor<'de> for StrVisitor {
+            type Value = Str<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a string")
+            }
+
+            fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+                Ok(Str(Cow::Borrowed(v)))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(Str(Cow::Owned(v.to_string())))
+            }
+
+            fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+                Ok(Str(Cow::Owned(v)))
+            }
+        }
+
+        deserializer.deserialize_str(StrVisitor)
+    }
+}
+
+/// A simple value of a [`ScimEntryRef`].
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ScimAttrRef<'a> {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Decimal(f64),
+    String(Cow<'a, str>),
+}
+
+impl<'a> ScimAttrRef<'a> {
+    pub fn is_null(&self) -> bool {
+        matches!(self, ScimAttrRef::Null)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ScimAttrRef::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Whether the string of this value is borrowed from the input.
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self, ScimAttrRef::String(Cow::Borrowed(_)))
+    }
+}
+
+pub type ScimComplexAttrRef<'a> = BTreeMap<Cow<'a, str>, ScimAttrRef<'a>>;
+
+/// A value of a [`ScimEntryRef`], shaped as a [`crate::ScimValue`] is.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ScimValueRef<'a> {
+    Simple(ScimAttrRef<'a>),
+    Complex(ScimComplexAttrRef<'a>),
+    MultiSimple(Vec<ScimAttrRef<'a>>),
+    MultiComplex(Vec<ScimComplexAttrRef<'a>>),
+    Extension(BTreeMap<Cow<'a, str>, ScimValueRef<'a>>),
+}
+
+impl<'a> ScimValueRef<'a> {
+    /// The sub-attribute `name` of a complex value, in any case.
+    pub fn sub_attr(&self, name: &str) -> Option<&ScimAttrRef<'a>> {
+        match self {
+            ScimValueRef::Complex(attrs) => find(attrs, name),
+            _ => None,
+        }
+    }
+}
+
+fn find<'b, 'a, V>(attrs: &'b BTreeMap<Cow<'a, str>, V>, name: &str) -> Option<&'b V> {
+    attrs
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v)
+}
+
+// Read directly rather than untagged, which would buffer every value and
+// copy its strings.
+impl<'de> Deserialize<'de> for ScimValueRef<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = ScimValueRef<'de>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a SCIM attribute value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(ScimValueRef::Simple(ScimAttrRef::Null))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        self.visit_unit()
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(ScimValueRef::Simple(ScimAttrRef::Bool(v)))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(ScimValueRef::Simple(ScimAttrRef::Integer(v)))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(ScimValueRef::Simple(match i64::try_from(v) {
+            Ok(v) => ScimAttrRef::Integer(v),
+            Err(_) => ScimAttrRef::Decimal(v as f64),
+        }))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(ScimValueRef::Simple(ScimAttrRef::Decimal(v)))
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        Ok(ScimValueRef::Simple(ScimAttrRef::String(Cow::Borrowed(v))))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(ScimValueRef::Simple(ScimAttrRef::String(Cow::Owned(
+            v.to_string(),
+        ))))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        Ok(ScimValueRef::Simple(ScimAttrRef::String(Cow::Owned(v))))
+    }
+
+    // Values of an array are all simple or all complex. An empty array is
+    // read as simple, as it is for a ScimValue.
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut simple = Vec::new();
+        let mut complex = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            match value {
+                ScimValueRef::Simple(attr) if complex.is_empty() => simple.push(attr),
+                ScimValueRef::Complex(attrs) if simple.is_empty() => complex.push(attrs),
+                _ => {
+                    return Err(de::Error::custom(
+                        "values of an array must be all simple or all complex",
+                    ))
+                }
+            }
+        }
+        Ok(if complex.is_empty() {
+            ScimValueRef::MultiSimple(simple)
+        } else {
+            ScimValueRef::MultiComplex(complex)
+        })
+    }
+
+    // An object of simple values is complex; any other is an extension.
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut attrs = BTreeMap::new();
+        while let Some((Str(name), value)) = map.next_entry::<Str<'de>, ScimValueRef<'de>>()? {
+            attrs.insert(name, value);
+        }
+        if attrs.values().all(|v| matches!(v, ScimValueRef::Simple(_))) {
+            Ok(ScimValueRef::Complex(
+                attrs
+                    .into_iter()
+                    .filter_map(|(name, value)| match value {
+                        ScimValueRef::Simple(attr) => Some((name, attr)),
+                        _ => None,
+                    })
+                    .collect(),
+            ))
+        } else {
+            Ok(ScimValueRef::Extension(attrs))
+        }
+    }
+}
+
+/// An entry borrowed from the JSON it was read from, with `schemas`, `id`
+/// and `meta` held among its attributes as they are in the JSON.
+#[derive(Serialize, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct ScimEntryRef<'a> {
+    pub attrs: BTreeMap<Cow<'a, str>, ScimValueRef<'a>>,
+}
+
+/// The values of sensitive attributes aren't shown, as for
+/// [`ScimEntryGeneric`].
+impl fmt::Debug for ScimEntryRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScimEntryRef")
+            .field("attrs", &AttrPairs(self.attrs.iter()))
+            .finish()
+    }
+}
+
+impl<'de> Deserialize<'de> for ScimEntryRef<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match ScimValueRef::deserialize(deserializer)? {
+            ScimValueRef::Complex(attrs) => Ok(ScimEntryRef {
+                attrs: attrs
+                    .into_iter()
+                    .map(|(name, attr)| (name, ScimValueRef::Simple(attr)))
+                    .collect(),
+            }),
+            ScimValueRef::Extension(attrs) => Ok(ScimEntryRef { attrs }),
+            _ => Err(de::Error::custom("an entry must be an object")),
+        }
+    }
+}
+
+impl<'a> ScimEntryRef<'a> {
+    /// The attribute `name`, in any case.
+    pub fn attr(&self, name: &str) -> Option<&ScimValueRef<'a>> {
+        find(&self.attrs, name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ScimValueRef<'a>)> {
+        self.attrs
+            .iter()
+            .map(|(name, value)| (name.as_ref(), value))
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        match self.attr("id")? {
+            ScimValueRef::Simple(attr) => attr.as_str(),
+            _ => None,
+        }
+    }
+
+    /// The URNs `schemas` lists.
+    pub fn schemas(&self) -> impl Iterator<Item = &str> {
+        let schemas = match self.attr("schemas") {
+            Some(ScimValueRef::MultiSimple(schemas)) => schemas.as_slice(),
+            _ => &[],
+        };
+        schemas.iter().filter_map(ScimAttrRef::as_str)
+    }
+
+    /// The owned entry this borrows, for when it needs to be kept or
+    /// changed.
+    pub fn to_entry(&self) -> Result<ScimEntryGeneric, ScimError> {
+        convert::convert(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::RFC7643_USER;
+
+    #[test]
+    fn borrowed_entry() {
+        let u: ScimEntryRef =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        assert_eq!(u.id(), Some("2819c223-7f76-453a-919d-413861904646"));
+        assert_eq!(
+            u.schemas().collect::<Vec<_>>(),
+            vec![crate::constants::SCIM_SCHEMA_USER]
+        );
+
+        let name = u.attr("NAME").expect("No name");
+        let given = name.sub_attr("givenName").expect("No givenName");
+        assert_eq!(given.as_str(), Some("Barbara"));
+        assert!(given.is_borrowed());
+        assert!(u.attrs.keys().all(|k| matches!(k, Cow::Borrowed(_))));
+        assert!(matches!(u.attr("emails"), Some(ScimValueRef::MultiComplex(v)) if v.len() == 2));
+
+        let owned: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        assert_eq!(u.to_entry(), Ok(owned));
+
+        // Escaped strings can't be borrowed.
+        let v: ScimValueRef =
+            serde_json::from_str(r#"{"a": "x\ny", "b": "xy"}"#).expect("Failed to parse");
+        assert!(!v.sub_attr("a").expect("No a").is_borrowed());
+        assert!(v.sub_attr("b").expect("No b").is_borrowed());
+
+        assert!(serde_json::from_str::<ScimValueRef>(r#"[1, {"a": 1}]"#).is_err());
+        assert!(serde_json::from_str::<ScimEntryRef>("[]").is_err());
+    }
+}
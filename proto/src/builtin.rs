@@ -0,0 +1,298 @@
+use crate::constants::{SCIM_SCHEMA_ENTERPRISE_USER, SCIM_SCHEMA_GROUP, SCIM_SCHEMA_USER};
+use crate::registry::{ResourceTypeDefinition, SchemaExtension, SchemaRegistry};
+use crate::schema::{
+    AttributeType, Mutability, Returned, ScimAttributeDefinition, ScimSchema, Uniqueness,
+};
+
+// https://datatracker.ietf.org/doc/html/rfc7643#section-8.7.1
+// https://datatracker.ietf.org/doc/html/rfc7643#section-4.3
+
+fn attr(name: &str, type_: AttributeType) -> ScimAttributeDefinition {
+    ScimAttributeDefinition::new(name, type_)
+}
+
+fn string(name: &str) -> ScimAttributeDefinition {
+    attr(name, AttributeType::String)
+}
+
+fn canonical(name: &str, values: &[&str]) -> ScimAttributeDefinition {
+    ScimAttributeDefinition {
+        canonical_values: values.iter().map(|v| v.to_string()).collect(),
+        ..string(name)
+    }
+}
+
+fn reference(name: &str, types: &[&str]) -> ScimAttributeDefinition {
+    ScimAttributeDefinition {
+        reference_types: types.iter().map(|t| t.to_string()).collect(),
+        ..attr(name, AttributeType::Reference)
+    }
+}
+
+fn complex(
+    name: &str,
+    multi_valued: bool,
+    sub_attributes: Vec<ScimAttributeDefinition>,
+) -> ScimAttributeDefinition {
+    ScimAttributeDefinition {
+        multi_valued,
+        sub_attributes,
+        ..attr(name, AttributeType::Complex)
+    }
+}
+
+fn read_only(def: ScimAttributeDefinition) -> ScimAttributeDefinition {
+    ScimAttributeDefinition {
+        mutability: Mutability::ReadOnly,
+        sub_attributes: def.sub_attributes.into_iter().map(read_only).collect(),
+        ..def
+    }
+}
+
+/// The value, display, type and primary sub-attributes shared by emails,
+/// phoneNumbers and the other multi-valued attributes of a User.
+fn multi_valued(
+    name: &str,
+    value: ScimAttributeDefinition,
+    types: &[&str],
+) -> ScimAttributeDefinition {
+    complex(
+        name,
+        true,
+        vec![
+            value,
+            string("display"),
+            canonical("type", types),
+            attr("primary", AttributeType::Boolean),
+        ],
+    )
+}
+
+/// The RFC7643 `urn:ietf:params:scim:schemas:core:2.0:User` schema.
+pub fn user_schema() -> ScimSchema {
+    ScimSchema {
+        id: SCIM_SCHEMA_USER.to_string(),
+        name: Some("User".to_string()),
+        description: Some("User Account".to_string()),
+        attributes: vec![
+            ScimAttributeDefinition {
+                required: true,
+                uniqueness: Uniqueness::Server,
+                ..string("userName")
+            },
+            complex(
+                "name",
+                false,
+                vec![
+                    string("formatted"),
+                    string("familyName"),
+                    string("givenName"),
+                    string("middleName"),
+                    string("honorificPrefix"),
+                    string("honorificSuffix"),
+                ],
+            ),
+            string("displayName"),
+            string("nickName"),
+            reference("profileUrl", &["external"]),
+            string("title"),
+            string("userType"),
+            string("preferredLanguage"),
+            string("locale"),
+            string("timezone"),
+            attr("active", AttributeType::Boolean),
+            ScimAttributeDefinition {
+                case_exact: true,
+                mutability: Mutability::WriteOnly,
+                returned: Returned::Never,
+                ..string("password")
+            },
+            multi_valued("emails", string("value"), &["work", "home", "other"]),
+            multi_valued(
+                "phoneNumbers",
+                string("value"),
+                &["work", "home", "mobile", "fax", "pager", "other"],
+            ),
+            multi_valued(
+                "ims",
+                string("value"),
+                &["aim", "gtalk", "icq", "xmpp", "msn", "skype", "qq", "yahoo"],
+            ),
+            multi_valued(
+                "photos",
+                reference("value", &["external"]),
+                &["photo", "thumbnail"],
+            ),
+            complex(
+                "addresses",
+                true,
+                vec![
+                    string("formatted"),
+                    string("streetAddress"),
+                    string("locality"),
+                    string("region"),
+                    string("postalCode"),
+                    string("country"),
+                    canonical("type", &["work", "home", "other"]),
+                    attr("primary", AttributeType::Boolean),
+                ],
+            ),
+            read_only(complex(
+                "groups",
+                true,
+                vec![
+                    string("value"),
+                    reference("$ref", &["User", "Group"]),
+                    string("display"),
+                    canonical("type", &["direct", "indirect"]),
+                ],
+            )),
+            multi_valued("entitlements", string("value"), &[]),
+            multi_valued("roles", string("value"), &[]),
+            multi_valued(
+                "x509Certificates",
+                attr("value", AttributeType::Binary),
+                &[],
+            ),
+        ],
+        meta: None,
+    }
+}
+
+/// The RFC7643 `urn:ietf:params:scim:schemas:core:2.0:Group` schema.
+pub fn group_schema() -> ScimSchema {
+    let immutable = |def: ScimAttributeDefinition| ScimAttributeDefinition {
+        mutability: Mutability::Immutable,
+        ..def
+    };
+
+    ScimSchema {
+        id: SCIM_SCHEMA_GROUP.to_string(),
+        name: Some("Group".to_string()),
+        description: Some("Group".to_string()),
+        attributes: vec![
+            string("displayName"),
+            complex(
+                "members",
+                true,
+                vec![
+                    immutable(string("value")),
+                    immutable(reference("$ref", &["User", "Group"])),
+                    immutable(canonical("type", &["User", "Group"])),
+                ],
+            ),
+        ],
+        meta: None,
+    }
+}
+
+/// The RFC7643 `urn:ietf:params:scim:schemas:extension:enterprise:2.0:User`
+/// extension schema.
+pub fn enterprise_user_schema() -> ScimSchema {
+    ScimSchema {
+        id: SCIM_SCHEMA_ENTERPRISE_USER.to_string(),
+        name: Some("EnterpriseUser".to_string()),
+        description: Some("Enterprise User".to_string()),
+        attributes: vec![
+            string("employeeNumber"),
+            string("costCenter"),
+            string("organization"),
+            string("division"),
+            string("department"),
+            complex(
+                "manager",
+                false,
+                vec![
+                    string("value"),
+                    reference("$ref", &["User"]),
+                    read_only(string("displayName")),
+                ],
+            ),
+        ],
+        meta: None,
+    }
+}
+
+/// The `User` and `Group` resource types, with the enterprise extension as
+/// an optional extension of `User`.
+pub fn resource_types() -> Vec<ResourceTypeDefinition> {
+    vec![
+        ResourceTypeDefinition {
+            schemas: Vec::new(),
+            id: Some("User".to_string()),
+            name: "User".to_string(),
+            endpoint: "/Users".to_string(),
+            description: Some("User Account".to_string()),
+            schema: SCIM_SCHEMA_USER.to_string(),
+            schema_extensions: vec![SchemaExtension {
+                schema: SCIM_SCHEMA_ENTERPRISE_USER.to_string(),
+                required: false,
+            }],
+            meta: None,
+        },
+        ResourceTypeDefinition {
+            schemas: Vec::new(),
+            id: Some("Group".to_string()),
+            name: "Group".to_string(),
+            endpoint: "/Groups".to_string(),
+            description: Some("Group".to_string()),
+            schema: SCIM_SCHEMA_GROUP.to_string(),
+            schema_extensions: Vec::new(),
+            meta: None,
+        },
+    ]
+}
+
+impl SchemaRegistry {
+    /// A registry holding the RFC7643 User, Group and EnterpriseUser schemas
+    /// and the User and Group resource types.
+    pub fn core() -> Self {
+        let mut registry = SchemaRegistry::new();
+        for schema in [user_schema(), group_schema(), enterprise_user_schema()] {
+            registry.insert_schema(schema);
+        }
+        for resource_type in resource_types() {
+            registry.insert_resource_type(resource_type);
+        }
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{RFC7643_SCHEMA_GROUP, RFC7643_USER};
+    use crate::ScimEntryGeneric;
+
+    #[test]
+    fn builtin_schemas_valid() {
+        assert_eq!(user_schema().check_definition(), Ok(()));
+        assert_eq!(group_schema().check_definition(), Ok(()));
+        assert_eq!(enterprise_user_schema().check_definition(), Ok(()));
+
+        let rfc = ScimSchema::parse(RFC7643_SCHEMA_GROUP).expect("Failed to parse group schema");
+        let group = group_schema();
+        assert_eq!(rfc.id, group.id);
+        for (l, r) in rfc.attributes.iter().zip(group.attributes.iter()) {
+            assert_eq!(l.name, r.name);
+            assert_eq!(l.type_, r.type_);
+            assert_eq!(l.mutability, r.mutability);
+            assert_eq!(l.sub_attributes.len(), r.sub_attributes.len());
+        }
+    }
+
+    #[test]
+    fn builtin_registry() {
+        let registry = SchemaRegistry::core();
+        assert!(registry.schema(SCIM_SCHEMA_ENTERPRISE_USER).is_some());
+        assert!(registry
+            .resolve("User", "manager.displayName")
+            .map(|def| def.mutability == Mutability::ReadOnly)
+            .unwrap_or(false));
+
+        let u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let schema = registry.merged("User").expect("Failed to merge user");
+        assert_eq!(schema.validate(&u), Ok(()));
+    }
+}
@@ -0,0 +1,615 @@
+use crate::error::ScimType;
+use crate::messages::{status_string, ScimErrorResponse, ScimMessageSchema};
+use crate::redact::Attrs;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io;
+use url::Url;
+
+// https://datatracker.ietf.org/doc/html/rfc7644#section-3.7
+
+const BULK_ID_PREFIX: &str = "bulkId:";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum BulkMethod {
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkOperation {
+    pub method: BulkMethod,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bulk_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+/// The values of sensitive attributes in `data`, such as `password`, aren't
+/// shown, see [`crate::redact`].
+impl fmt::Debug for BulkOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BulkOperation")
+            .field("method", &self.method)
+            .field("bulk_id", &self.bulk_id)
+            .field("version", &self.version)
+            .field("path", &self.path)
+            .field("data", &self.data.as_ref().map(Attrs))
+            .finish()
+    }
+}
+
+impl BulkOperation {
+    /// The bulkIds of other operations that this operation refers to, either
+    /// in its path or anywhere within its data.
+    pub fn bulk_id_references(&self) -> BTreeSet<&str> {
+        let mut refs = BTreeSet::new();
+
+        self.path
+            .split('/')
+            .filter_map(|seg| seg.strip_prefix(BULK_ID_PREFIX))
+            .for_each(|id| {
+                refs.insert(id);
+            });
+
+        if let Some(data) = &self.data {
+            collect_bulk_id_references(data, &mut refs);
+        }
+
+        refs
+    }
+}
+
+fn collect_bulk_id_references<'a>(value: &'a Value, refs: &mut BTreeSet<&'a str>) {
+    match value {
+        Value::String(s) => {
+            if let Some(id) = s.strip_prefix(BULK_ID_PREFIX) {
+                refs.insert(id);
+            }
+        }
+        Value::Array(values) => values
+            .iter()
+            .for_each(|v| collect_bulk_id_references(v, refs)),
+        Value::Object(map) => map
+            .values()
+            .for_each(|v| collect_bulk_id_references(v, refs)),
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkRequest {
+    pub schemas: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fail_on_errors: Option<u32>,
+    #[serde(rename = "Operations")]
+    pub operations: Vec<BulkOperation>,
+}
+
+impl BulkRequest {
+    pub fn new(operations: Vec<BulkOperation>) -> Self {
+        BulkRequest {
+            schemas: ScimMessageSchema::BulkRequest.schemas(),
+            fail_on_errors: None,
+            operations,
+        }
+    }
+
+    /// Determine an order in which the operations can be applied such that
+    /// every `bulkId:` reference is created before it is used. Operations
+    /// with no dependency between them retain their request order.
+    pub fn execution_order(&self) -> Result<Vec<usize>, BulkError> {
+        let mut defined: BTreeMap<&str, usize> = BTreeMap::new();
+        for (idx, op) in self.operations.iter().enumerate() {
+            if let Some(bulk_id) = op.bulk_id.as_deref() {
+                if defined.insert(bulk_id, idx).is_some() {
+                    return Err(BulkError::DuplicateBulkId(bulk_id.to_string()));
+                }
+            }
+        }
+
+        // For each operation, the operations it depends on.
+        let mut depends_on: Vec<BTreeSet<usize>> = Vec::with_capacity(self.operations.len());
+        // For each operation, the operations that depend on it.
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); self.operations.len()];
+
+        for (idx, op) in self.operations.iter().enumerate() {
+            let mut deps = BTreeSet::new();
+            for bulk_id in op.bulk_id_references() {
+                let dep =
+                    defined
+                        .get(bulk_id)
+                        .copied()
+                        .ok_or_else(|| BulkError::UnknownBulkId {
+                            operation: idx,
+                            bulk_id: bulk_id.to_string(),
+                        })?;
+                if deps.insert(dep) {
+                    dependents[dep].push(idx);
+                }
+            }
+            depends_on.push(deps);
+        }
+
+        let mut remaining: Vec<usize> = depends_on.iter().map(|d| d.len()).collect();
+        let mut ready: BTreeSet<usize> = remaining
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, count)| (*count == 0).then_some(idx))
+            .collect();
+        let mut order = Vec::with_capacity(self.operations.len());
+
+        while let Some(idx) = ready.pop_first() {
+            order.push(idx);
+            for dependent in dependents[idx].iter().copied() {
+                remaining[dependent] -= 1;
+                if remaining[dependent] == 0 {
+                    ready.insert(dependent);
+                }
+            }
+        }
+
+        if order.len() == self.operations.len() {
+            Ok(order)
+        } else {
+            Err(BulkError::CircularReference(
+                self.find_cycle(&depends_on, &remaining),
+            ))
+        }
+    }
+
+    fn find_cycle(&self, depends_on: &[BTreeSet<usize>], remaining: &[usize]) -> Vec<String> {
+        // Any operation still waiting on a dependency is either part of, or
+        // downstream of, a cycle. Walking dependencies from it must revisit a node.
+        let mut path: Vec<usize> = Vec::new();
+        let mut current = remaining.iter().position(|count| *count > 0);
+
+        while let Some(idx) = current {
+            if let Some(start) = path.iter().position(|p| *p == idx) {
+                return path[start..]
+                    .iter()
+                    .filter_map(|p| self.operations[*p].bulk_id.clone())
+                    .collect();
+            }
+            path.push(idx);
+            current = depends_on[idx]
+                .iter()
+                .copied()
+                .find(|dep| remaining[*dep] > 0);
+        }
+
+        Vec::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BulkError {
+    /// Two operations in the request declare the same bulkId.
+    DuplicateBulkId(String),
+    /// An operation refers to a bulkId that no operation declares.
+    UnknownBulkId { operation: usize, bulk_id: String },
+    /// The bulkIds that form a reference cycle.
+    CircularReference(Vec<String>),
+    /// The request contains more operations than `maxOperations`.
+    TooManyOperations { max: usize, found: usize },
+    /// The request, or a single operation of it, exceeds `maxPayloadSize` bytes.
+    PayloadTooLarge { max: usize, found: usize },
+}
+
+impl BulkError {
+    pub fn scim_type(&self) -> Option<ScimType> {
+        match self {
+            BulkError::DuplicateBulkId(_) => Some(ScimType::Uniqueness),
+            BulkError::UnknownBulkId { .. } => Some(ScimType::InvalidValue),
+            BulkError::TooManyOperations { .. } => Some(ScimType::TooMany),
+            BulkError::CircularReference(_) | BulkError::PayloadTooLarge { .. } => None,
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        match self {
+            // RFC7644 3.7.1 - a provider that can't resolve a circular reference
+            // returns 409 Conflict.
+            BulkError::CircularReference(_) => 409,
+            // RFC7644 3.7.4 - exceeding either bulk limit is 413 Payload Too Large.
+            BulkError::TooManyOperations { .. } | BulkError::PayloadTooLarge { .. } => 413,
+            _ => self.scim_type().map(|t| t.status()).unwrap_or(400),
+        }
+    }
+}
+
+impl fmt::Display for BulkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BulkError::DuplicateBulkId(id) => write!(f, "bulkId '{}' is not unique", id),
+            BulkError::UnknownBulkId { operation, bulk_id } => write!(
+                f,
+                "operation {} references unknown bulkId '{}'",
+                operation, bulk_id
+            ),
+            BulkError::CircularReference(ids) => {
+                write!(f, "circular bulkId reference: {}", ids.join(" -> "))
+            }
+            BulkError::TooManyOperations { max, found } => write!(
+                f,
+                "too many bulk operations: {}, maxOperations is {}",
+                found, max
+            ),
+            BulkError::PayloadTooLarge { max, found } => write!(
+                f,
+                "bulk payload too large: {} bytes, maxPayloadSize is {}",
+                found, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BulkError {}
+
+impl From<BulkError> for ScimErrorResponse {
+    fn from(err: BulkError) -> Self {
+        let mut resp = ScimErrorResponse::new(err.status());
+        resp.scim_type = err.scim_type();
+        resp.detail = Some(err.to_string());
+        resp
+    }
+}
+
+/// The bulk limits a service provider advertises in its ServiceProviderConfig.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BulkLimits {
+    pub max_operations: usize,
+    pub max_payload_size: usize,
+}
+
+impl BulkLimits {
+    /// Check the size of a raw request body before it is parsed.
+    pub fn check_payload_size(&self, len: usize) -> Result<(), BulkError> {
+        if len > self.max_payload_size {
+            Err(BulkError::PayloadTooLarge {
+                max: self.max_payload_size,
+                found: len,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn check(&self, req: &BulkRequest) -> Result<(), BulkError> {
+        if req.operations.len() > self.max_operations {
+            return Err(BulkError::TooManyOperations {
+                max: self.max_operations,
+                found: req.operations.len(),
+            });
+        }
+        self.check_payload_size(json_len(req))
+    }
+
+    /// Split a request into batches that each fit within these limits.
+    /// Operations linked by bulkId references are kept in the same batch, in
+    /// an order where references are created before they are used.
+    pub fn split(&self, req: BulkRequest) -> Result<Vec<BulkRequest>, BulkError> {
+        Ok(self
+            .split_indexed(req)?
+            .into_iter()
+            .map(|(batch, _)| batch)
+            .collect())
+    }
+
+    /// As [`BulkLimits::split`], with the index in `req` of each operation
+    /// of a batch.
+    pub fn split_indexed(
+        &self,
+        req: BulkRequest,
+    ) -> Result<Vec<(BulkRequest, Vec<usize>)>, BulkError> {
+        let order = req.execution_order()?;
+
+        // Group operations that are connected through bulkId references.
+        let mut component: Vec<usize> = (0..req.operations.len()).collect();
+
+        let defined: BTreeMap<&str, usize> = req
+            .operations
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, op)| op.bulk_id.as_deref().map(|id| (id, idx)))
+            .collect();
+        for (idx, op) in req.operations.iter().enumerate() {
+            for dep in op
+                .bulk_id_references()
+                .into_iter()
+                .filter_map(|id| defined.get(id))
+            {
+                let (a, b) = (root(&mut component, idx), root(&mut component, *dep));
+                component[a] = b;
+            }
+        }
+
+        let mut groups: Vec<(usize, Vec<usize>)> = Vec::new();
+        for idx in order {
+            let r = root(&mut component, idx);
+            match groups.iter_mut().find(|(g, _)| *g == r) {
+                Some((_, members)) => members.push(idx),
+                None => groups.push((r, vec![idx])),
+            }
+        }
+
+        let envelope = BulkRequest {
+            schemas: req.schemas.clone(),
+            fail_on_errors: req.fail_on_errors,
+            operations: Vec::new(),
+        };
+        let envelope_len = json_len(&envelope);
+        let op_lens: Vec<usize> = req.operations.iter().map(json_len).collect();
+        // Each operation after the first in a batch adds a separating comma.
+        let group_len = |members: &[usize]| -> usize {
+            members.iter().map(|idx| op_lens[*idx]).sum::<usize>() + members.len() - 1
+        };
+
+        let mut slots: Vec<Option<BulkOperation>> = req.operations.into_iter().map(Some).collect();
+        let mut batches: Vec<(BulkRequest, Vec<usize>)> = Vec::new();
+        let mut batch_len = envelope_len;
+
+        for (_, members) in groups {
+            let len = group_len(&members);
+            if members.len() > self.max_operations {
+                return Err(BulkError::TooManyOperations {
+                    max: self.max_operations,
+                    found: members.len(),
+                });
+            }
+            if envelope_len + len > self.max_payload_size {
+                return Err(BulkError::PayloadTooLarge {
+                    max: self.max_payload_size,
+                    found: envelope_len + len,
+                });
+            }
+
+            let fits = batches.last().map(|(batch, _)| {
+                batch.operations.len() + members.len() <= self.max_operations
+                    && batch_len + 1 + len <= self.max_payload_size
+            });
+
+            if fits == Some(true) {
+                batch_len += 1 + len;
+            } else {
+                batches.push((envelope.clone(), Vec::new()));
+                batch_len = envelope_len + len;
+            }
+
+            if let Some((batch, indexes)) = batches.last_mut() {
+                batch
+                    .operations
+                    .extend(members.iter().filter_map(|idx| slots[*idx].take()));
+                indexes.extend(members);
+            }
+        }
+
+        Ok(batches)
+    }
+}
+
+/// Find the representative of an operation's component, compressing the path as we go.
+fn root(component: &mut [usize], mut idx: usize) -> usize {
+    while component[idx] != idx {
+        component[idx] = component[component[idx]];
+        idx = component[idx];
+    }
+    idx
+}
+
+struct ByteCounter(usize);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The length of the compact JSON encoding of a value, without allocating it.
+fn json_len<T: Serialize>(value: &T) -> usize {
+    let mut counter = ByteCounter(0);
+    // Serializing these types to JSON can't fail, and even if it did, a
+    // partial count is still a lower bound.
+    let _ = serde_json::to_writer(&mut counter, value);
+    counter.0
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkOperationResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<Url>,
+    pub method: BulkMethod,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bulk_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<Value>,
+    #[serde(with = "status_string")]
+    pub status: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkResponse {
+    pub schemas: Vec<String>,
+    #[serde(rename = "Operations")]
+    pub operations: Vec<BulkOperationResponse>,
+}
+
+impl BulkResponse {
+    pub fn new(operations: Vec<BulkOperationResponse>) -> Self {
+        BulkResponse {
+            schemas: ScimMessageSchema::BulkResponse.schemas(),
+            operations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // From RFC7644 3.7.2, with the group listed before the user it references.
+    const BULK_REQUEST: &str = r#"
+    {
+      "schemas": ["urn:ietf:params:scim:api:messages:2.0:BulkRequest"],
+      "Operations": [
+        {
+          "method": "POST",
+          "path": "/Groups",
+          "bulkId": "ytrewq",
+          "data": {
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:Group"],
+            "displayName": "Tour Guides",
+            "members": [{ "type": "User", "value": "bulkId:qwerty" }]
+          }
+        },
+        {
+          "method": "POST",
+          "path": "/Users",
+          "bulkId": "qwerty",
+          "data": {
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+            "userName": "Alice"
+          }
+        },
+        {
+          "method": "DELETE",
+          "path": "/Users/b7c14771-226c-4d05-8860-134711653041",
+          "version": "W/\"0ee8add0a938e1a\""
+        }
+      ]
+    }
+    "#;
+
+    #[test]
+    fn bulk_execution_order() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let req: BulkRequest =
+            serde_json::from_str(BULK_REQUEST).expect("Failed to parse bulk request");
+
+        tracing::trace!(?req);
+
+        assert_eq!(req.execution_order(), Ok(vec![1, 0, 2]));
+    }
+
+    #[test]
+    fn bulk_execution_order_errors() {
+        let post = |bulk_id: &str, member: &str| BulkOperation {
+            method: BulkMethod::Post,
+            bulk_id: Some(bulk_id.to_string()),
+            version: None,
+            path: "/Groups".to_string(),
+            data: Some(serde_json::json!({
+                "displayName": bulk_id,
+                "members": [{ "value": format!("bulkId:{}", member) }]
+            })),
+        };
+
+        let req = BulkRequest::new(vec![post("a", "b"), post("b", "c"), post("c", "b")]);
+        let err = req.execution_order().expect_err("Cycle not detected");
+        assert_eq!(
+            err,
+            BulkError::CircularReference(vec!["b".into(), "c".into()])
+        );
+
+        let resp = ScimErrorResponse::from(err);
+        assert_eq!(resp.status, 409);
+
+        let req = BulkRequest::new(vec![post("a", "z")]);
+        assert_eq!(
+            req.execution_order(),
+            Err(BulkError::UnknownBulkId {
+                operation: 0,
+                bulk_id: "z".to_string()
+            })
+        );
+
+        let req = BulkRequest::new(vec![post("a", "a")]);
+        assert_eq!(
+            req.execution_order(),
+            Err(BulkError::CircularReference(vec!["a".into()]))
+        );
+
+        let req = BulkRequest::new(vec![post("a", "b"), post("a", "b")]);
+        assert_eq!(
+            req.execution_order(),
+            Err(BulkError::DuplicateBulkId("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn bulk_limits() {
+        let delete = |id: usize| BulkOperation {
+            method: BulkMethod::Delete,
+            bulk_id: None,
+            version: None,
+            path: format!("/Users/{}", id),
+            data: None,
+        };
+
+        let req: BulkRequest =
+            serde_json::from_str(BULK_REQUEST).expect("Failed to parse bulk request");
+        let len = serde_json::to_vec(&req).map(|v| v.len()).unwrap_or(0);
+        assert_eq!(json_len(&req), len);
+
+        let limits = BulkLimits {
+            max_operations: 2,
+            max_payload_size: len,
+        };
+        let err = limits.check(&req).expect_err("maxOperations not enforced");
+        assert_eq!(err.status(), 413);
+        assert_eq!(err.scim_type(), Some(ScimType::TooMany));
+        assert_eq!(
+            err.to_string(),
+            "too many bulk operations: 3, maxOperations is 2"
+        );
+        assert_eq!(
+            limits.check_payload_size(len + 1),
+            Err(BulkError::PayloadTooLarge {
+                max: len,
+                found: len + 1
+            })
+        );
+
+        // The group and the user it references must stay together.
+        let batches = limits.split(req).expect("Failed to split bulk request");
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].operations[0].bulk_id.as_deref(), Some("qwerty"));
+        assert_eq!(batches[0].operations[1].bulk_id.as_deref(), Some("ytrewq"));
+        assert_eq!(batches[1].operations.len(), 1);
+
+        let req = BulkRequest::new((0..10).map(delete).collect());
+        let limits = BulkLimits {
+            max_operations: 10,
+            max_payload_size: 200,
+        };
+        let batches = limits.split(req).expect("Failed to split bulk request");
+        assert!(batches.len() > 1);
+        assert_eq!(
+            batches.iter().map(|b| b.operations.len()).sum::<usize>(),
+            10
+        );
+        for batch in batches.iter() {
+            assert_eq!(limits.check(batch), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use crate::convert;
+use crate::error::ScimError;
+use crate::ScimEntryGeneric;
+use serde_json::{Number, Value};
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, UtcOffset};
+
+/// Write `value` in a canonical form, so that values that mean the same to
+/// SCIM give the same bytes, for hashing. The form is JSON without
+/// whitespace, with:
+///
+/// * object keys lowercased, as attribute names are case insensitive, and
+///   sorted,
+/// * numbers without a fraction written as integers, so `2.0` is `2`,
+/// * strings that are RFC3339 date times converted to UTC.
+///
+/// It isn't meant to be read back: lowercased names aren't the names a
+/// service provider sent.
+pub fn canonical_bytes(value: &Value) -> Vec<u8> {
+    let mut out = String::new();
+    write_value(value, &mut out);
+    out.into_bytes()
+}
+
+fn write_value(value: &Value, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => write_number(n, out),
+        Value::String(s) => write_string(s, out),
+        Value::Array(values) => {
+            out.push('[');
+            for (idx, value) in values.iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                write_value(value, out);
+            }
+            out.push(']');
+        }
+        Value::Object(attrs) => {
+            let mut attrs: Vec<_> = attrs
+                .iter()
+                .map(|(name, value)| (name.to_ascii_lowercase(), value))
+                .collect();
+            // Stable, so that names differing only by case keep their order.
+            attrs.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            out.push('{');
+            for (idx, (name, value)) in attrs.into_iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                write_json_string(&name, out);
+                out.push(':');
+                write_value(value, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+// Integers up to 2^53 are exact as f64, so larger decimals keep their form.
+const MAX_EXACT: f64 = 9_007_199_254_740_992.0;
+
+fn write_number(n: &Number, out: &mut String) {
+    if let Some(i) = n.as_i64() {
+        out.push_str(&i.to_string());
+    } else if let Some(u) = n.as_u64() {
+        out.push_str(&u.to_string());
+    } else if let Some(f) = n.as_f64() {
+        if f.fract() == 0.0 && f.abs() <= MAX_EXACT {
+            out.push_str(&(f as i64).to_string());
+        } else {
+            out.push_str(&f.to_string());
+        }
+    }
+}
+
+fn write_string(s: &str, out: &mut String) {
+    let utc = OffsetDateTime::parse(s, &Rfc3339)
+        .ok()
+        .and_then(|dt| dt.to_offset(UtcOffset::UTC).format(&Rfc3339).ok());
+    write_json_string(utc.as_deref().unwrap_or(s), out);
+}
+
+fn write_json_string(s: &str, out: &mut String) {
+    // Serialising a str can't fail.
+    out.push_str(&serde_json::to_string(s).unwrap_or_default());
+}
+
+// Sort the values of every array by their canonical form, innermost first,
+// and drop null attributes, which are unassigned, RFC7643 section 2.5.
+fn unorder(value: &mut Value) {
+    match value {
+        Value::Array(values) => {
+            values.iter_mut().for_each(unorder);
+            values.sort_by_cached_key(canonical_bytes);
+        }
+        Value::Object(attrs) => {
+            attrs.retain(|_, v| !v.is_null());
+            attrs.values_mut().for_each(unorder);
+        }
+        _ => {}
+    }
+}
+
+// FNV-1a, which unlike the std hashers is the same across releases and
+// platforms, so versions survive upgrades.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+impl ScimEntryGeneric {
+    /// The entry in the canonical form of [`canonical_bytes`], for hashing.
+    /// Entries that only differ in the case of attribute names, the form of
+    /// numbers, the offset of date times, or the order of attributes give the
+    /// same bytes.
+    pub fn canonical_bytes(&self) -> Result<Vec<u8>, ScimError> {
+        let value = convert::to_value(self)?;
+        Ok(canonical_bytes(&value))
+    }
+
+    // The entry without its meta, which describes the entry rather than
+    // being part of it.
+    fn content(&self) -> Result<Value, ScimError> {
+        let mut value = convert::to_value(self)?;
+        if let Value::Object(fields) = &mut value {
+            fields.remove("meta");
+        }
+        Ok(value)
+    }
+
+    /// A weak entity tag for the content of the entry, such as
+    /// `W/"a330bc54f0671c9e"`, for `meta.version`. The meta itself isn't
+    /// part of the content.
+    pub fn version(&self) -> Result<String, ScimError> {
+        let value = self.content()?;
+        Ok(format!("W/\"{:016x}\"", fnv1a(&canonical_bytes(&value))))
+    }
+
+    /// Whether the entries hold the same data, as a sync engine deciding
+    /// whether to push an update needs. Besides what [`canonical_bytes`]
+    /// ignores, the order of values in arrays, such as of multi-valued
+    /// attributes and `schemas`, null attributes, and the meta are ignored.
+    pub fn semantically_equals(&self, other: &ScimEntryGeneric) -> bool {
+        let unordered = |entry: &ScimEntryGeneric| {
+            entry.content().map(|mut value| {
+                unorder(&mut value);
+                canonical_bytes(&value)
+            })
+        };
+        match (unordered(self), unordered(other)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Bring `meta.version` up to date with the content, and set
+    /// `meta.lastModified` if it changed. Returns whether it changed; an
+    /// entry without meta is left as it is.
+    pub fn touch(&mut self) -> Result<bool, ScimError> {
+        let Some(mut meta) = self.meta.take() else {
+            return Ok(false);
+        };
+        let touched = meta.touch(self);
+        self.meta = Some(meta);
+        touched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::RFC7643_USER;
+    use crate::{ScimAttr, ScimMeta, ScimResourceType, ScimValue};
+    use serde_json::json;
+    use url::Url;
+
+    #[test]
+    fn canonical_form() {
+        let a = json!({
+            "userName": "bjensen",
+            "count": 2.0,
+            "ratio": 0.5,
+            "when": "2011-08-01T20:29:49+02:00",
+            "name": {"givenName": "Barbara", "familyName": "Jensen"},
+            "emails": [{"value": "a@example.com"}, {"value": "b@example.com"}],
+        });
+        let b = json!({
+            "emails": [{"VALUE": "a@example.com"}, {"value": "b@example.com"}],
+            "name": {"familyName": "Jensen", "givenName": "Barbara"},
+            "when": "2011-08-01T18:29:49Z",
+            "ratio": 0.5,
+            "count": 2,
+            "USERNAME": "bjensen",
+        });
+        assert_eq!(canonical_bytes(&a), canonical_bytes(&b));
+        assert_eq!(
+            String::from_utf8(canonical_bytes(&a)).expect("Invalid utf8"),
+            r#"{"count":2,"emails":[{"value":"a@example.com"},{"value":"b@example.com"}],"name":{"familyname":"Jensen","givenname":"Barbara"},"ratio":0.5,"username":"bjensen","when":"2011-08-01T18:29:49Z"}"#
+        );
+
+        // Values themselves keep their case, and arrays their order.
+        let c = json!({"userName": "BJensen"});
+        assert_ne!(
+            canonical_bytes(&c),
+            canonical_bytes(&json!({"userName": "bjensen"}))
+        );
+        assert_ne!(
+            canonical_bytes(&json!([1, 2])),
+            canonical_bytes(&json!([2, 1]))
+        );
+
+        let u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let mut v: Value = serde_json::from_str(RFC7643_USER).expect("Failed to parse");
+        v["meta"]["created"] = json!("2010-01-22T20:56:22-08:00");
+        let shifted: ScimEntryGeneric = serde_json::from_value(v).expect("Failed to parse");
+        assert_eq!(
+            u.canonical_bytes().expect("Failed to serialise"),
+            shifted.canonical_bytes().expect("Failed to serialise")
+        );
+    }
+
+    #[test]
+    fn semantic_equality() {
+        let u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let mut v: Value = serde_json::from_str(RFC7643_USER).expect("Failed to parse");
+        v["emails"].as_array_mut().expect("No emails").reverse();
+        if let Value::Object(attrs) = &mut v {
+            let user_name = attrs.remove("userName").expect("No userName");
+            attrs.insert("USERNAME".to_string(), user_name);
+        }
+        v["meta"]["version"] = json!("W/\"0\"");
+        let other: ScimEntryGeneric = serde_json::from_value(v).expect("Failed to parse");
+        assert_ne!(u, other);
+        assert!(u.semantically_equals(&other));
+        let mut nulled = other.clone();
+        nulled.insert_attr("costCenter", ScimValue::Simple(ScimAttr::Null));
+        assert!(u.semantically_equals(&nulled));
+
+        let mut changed = other.clone();
+        changed.insert_attr(
+            "nickName",
+            ScimValue::Simple(ScimAttr::String("Barb".to_string())),
+        );
+        assert!(!u.semantically_equals(&changed));
+        // Values keep their case.
+        let mut changed = other;
+        changed.insert_attr(
+            "userName",
+            ScimValue::Simple(ScimAttr::String("BJENSEN@example.com".to_string())),
+        );
+        assert!(!u.semantically_equals(&changed));
+    }
+
+    #[test]
+    fn meta_version() {
+        let mut u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let location = Url::parse("https://example.com/v2/Users/2819c223").expect("Invalid url");
+        let meta = ScimMeta::new(ScimResourceType::User, location);
+        assert_eq!(meta.created, meta.last_modified);
+        u.meta = Some(meta);
+
+        let version = u.version().expect("Failed to hash");
+        assert!(version.starts_with("W/\"") && version.len() == 20);
+        // The hash is stable, so versions survive a restart.
+        assert_eq!(fnv1a(b"scim"), 0xf777_e818_b52e_2cd9);
+
+        assert_eq!(u.touch(), Ok(true));
+        let meta = u.meta.clone().expect("No meta");
+        assert_eq!(meta.version, version);
+        // The first version isn't a modification.
+        assert_eq!(meta.last_modified, meta.created);
+        assert_eq!(u.touch(), Ok(false));
+
+        u.insert_attr(
+            "nickName",
+            ScimValue::Simple(ScimAttr::String("B".to_string())),
+        );
+        assert_eq!(u.touch(), Ok(true));
+        let touched = u.meta.clone().expect("No meta");
+        assert_ne!(touched.version, version);
+        assert!(touched.last_modified >= meta.last_modified);
+        assert_eq!(touched.created, meta.created);
+        assert_eq!(u.version(), Ok(touched.version));
+    }
+}
@@ -0,0 +1,372 @@
+use crate::attr_map::{self, AttrMap};
+use crate::convert;
+use crate::error::ScimError;
+use crate::messages::{ScimPatchOpKind, ScimPatchOperation};
+use crate::schema::{find_attr, key_of, Mutability, ScimSchema};
+use crate::{ScimAttr, ScimEntryGeneric, ScimValue};
+
+/// A change to one attribute of an entry, named by its path, such as
+/// `displayName`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttrChange {
+    Added {
+        path: String,
+        value: ScimValue,
+    },
+    Removed {
+        path: String,
+        value: ScimValue,
+    },
+    Modified {
+        path: String,
+        old: ScimValue,
+        new: ScimValue,
+    },
+}
+
+impl AttrChange {
+    pub fn path(&self) -> &str {
+        match self {
+            AttrChange::Added { path, .. }
+            | AttrChange::Removed { path, .. }
+            | AttrChange::Modified { path, .. } => path,
+        }
+    }
+
+    /// The PATCH operation that makes this change.
+    pub fn to_patch_operation(&self) -> Result<ScimPatchOperation, ScimError> {
+        let value = |value: &ScimValue| convert::to_value(value).map(Some);
+        Ok(match self {
+            AttrChange::Added { path, value: new } => ScimPatchOperation {
+                op: ScimPatchOpKind::Add,
+                path: Some(path.clone()),
+                value: value(new)?,
+            },
+            AttrChange::Removed { path, .. } => ScimPatchOperation {
+                op: ScimPatchOpKind::Remove,
+                path: Some(path.clone()),
+                value: None,
+            },
+            AttrChange::Modified { path, new, .. } => ScimPatchOperation {
+                op: ScimPatchOpKind::Replace,
+                path: Some(path.clone()),
+                value: value(new)?,
+            },
+        })
+    }
+}
+
+fn external_id_value(external_id: &Option<String>) -> Option<ScimValue> {
+    external_id
+        .as_ref()
+        .map(|e| ScimValue::Simple(ScimAttr::String(e.clone())))
+}
+
+fn change(path: &str, old: Option<ScimValue>, new: Option<ScimValue>) -> Option<AttrChange> {
+    let path = path.to_string();
+    // A null is unassigned, RFC7643 section 2.5.
+    let (old, new) = (old.filter(|v| !v.is_null()), new.filter(|v| !v.is_null()));
+    match (old, new) {
+        (None, Some(value)) => Some(AttrChange::Added { path, value }),
+        (Some(value), None) => Some(AttrChange::Removed { path, value }),
+        (Some(old), Some(new)) if old != new => Some(AttrChange::Modified { path, old, new }),
+        _ => None,
+    }
+}
+
+fn simple(attrs: &AttrMap<ScimAttr>) -> AttrMap<ScimValue> {
+    attrs
+        .iter()
+        .map(|(name, attr)| (name.clone(), ScimValue::Simple(attr.clone())))
+        .collect()
+}
+
+// The attributes of a complex value or extension, for comparing them one
+// at a time.
+fn sub_attrs(value: &ScimValue) -> Option<AttrMap<ScimValue>> {
+    match value {
+        ScimValue::Complex(attrs) => Some(simple(attrs)),
+        ScimValue::Extension(attrs) => Some(attrs.clone()),
+        _ => None,
+    }
+}
+
+// Compare two sets of attributes, whose names are case insensitive, adding
+// `prefix` to each path.
+fn diff_attrs(
+    old: &AttrMap<ScimValue>,
+    new: &AttrMap<ScimValue>,
+    prefix: &str,
+    nested: bool,
+    changes: &mut Vec<AttrChange>,
+) {
+    for (name, old_value) in old.iter() {
+        let path = format!("{}{}", prefix, name);
+        let Some(new_value) = find_attr(new, name) else {
+            changes.extend(change(&path, Some(old_value.clone()), None));
+            continue;
+        };
+        if old_value == new_value {
+            continue;
+        }
+
+        // Complex values and extensions are compared by sub-attribute, one
+        // level down.
+        match (nested, sub_attrs(old_value), sub_attrs(new_value)) {
+            (true, Some(old_sub), Some(new_sub)) => {
+                let separator = if name.contains(':') { ":" } else { "." };
+                let prefix = format!("{}{}", path, separator);
+                diff_attrs(&old_sub, &new_sub, &prefix, false, changes);
+            }
+            _ => changes.extend(change(
+                &path,
+                Some(old_value.clone()),
+                Some(new_value.clone()),
+            )),
+        }
+    }
+
+    for (name, new_value) in new.iter() {
+        if find_attr(old, name).is_none() {
+            let path = format!("{}{}", prefix, name);
+            changes.extend(change(&path, None, Some(new_value.clone())));
+        }
+    }
+}
+
+impl ScimEntryGeneric {
+    /// The changes that turn this entry into `other`. Complex attributes
+    /// and extensions are compared by sub-attribute, giving paths such as
+    /// `name.givenName`, while multi-valued attributes are compared as a
+    /// whole. The id, meta and schemas aren't compared.
+    pub fn diff(&self, other: &ScimEntryGeneric) -> Vec<AttrChange> {
+        let mut changes = Vec::new();
+        changes.extend(change(
+            "externalId",
+            external_id_value(&self.external_id),
+            external_id_value(&other.external_id),
+        ));
+        diff_attrs(&self.attrs, &other.attrs, "", true, &mut changes);
+        changes
+    }
+
+    /// Replace this entry's attributes with those of `incoming`, as a PUT
+    /// does in RFC7644 section 3.5.1, and return the changes made. Attributes
+    /// `incoming` omits, or sets to null, are cleared.
+    ///
+    /// With `schema`, the schema of the entry's own attributes, readOnly
+    /// attributes are kept as they are, and so are immutable attributes that
+    /// already have a value; use [`ScimSchema::check_replace`] first to
+    /// reject requests that try to change them. The id and meta are never
+    /// replaced.
+    pub fn replace_with(
+        &mut self,
+        incoming: &ScimEntryGeneric,
+        schema: Option<&ScimSchema>,
+    ) -> Vec<AttrChange> {
+        let mutability = |name: &str| {
+            schema
+                .and_then(|s| s.attribute(name))
+                .map(|def| def.mutability)
+                .unwrap_or_default()
+        };
+
+        let mut changes = Vec::new();
+        changes.extend(change(
+            "externalId",
+            external_id_value(&self.external_id),
+            external_id_value(&incoming.external_id),
+        ));
+        self.external_id = incoming.external_id.clone();
+        self.schemas = incoming.schemas.clone();
+
+        // Clear what incoming omits.
+        let omitted: Vec<String> = self
+            .attrs
+            .keys()
+            .filter(|name| {
+                find_attr(&incoming.attrs, name)
+                    .map(ScimValue::is_null)
+                    .unwrap_or(true)
+            })
+            .filter(|name| {
+                !matches!(
+                    mutability(name),
+                    Mutability::ReadOnly | Mutability::Immutable
+                )
+            })
+            .cloned()
+            .collect();
+        for name in omitted {
+            if let Some(value) = attr_map::remove(&mut self.attrs, &name) {
+                changes.extend(change(&name, Some(value), None));
+            }
+        }
+
+        for (name, value) in incoming.attrs.iter().filter(|(_, v)| !v.is_null()) {
+            let key = key_of(&self.attrs, name);
+            let keep = match mutability(name) {
+                Mutability::ReadOnly => true,
+                Mutability::Immutable => key.is_some(),
+                Mutability::ReadWrite | Mutability::WriteOnly => false,
+            };
+            if keep {
+                continue;
+            }
+
+            // Inserting over the old value keeps the attribute's place.
+            let key = key.unwrap_or_else(|| name.clone());
+            let old = self.attrs.insert(key.clone(), value.clone());
+            changes.extend(change(&key, old, Some(value.clone())));
+        }
+
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::user_schema;
+    use crate::constants::RFC7643_USER;
+
+    fn string(s: &str) -> ScimValue {
+        ScimValue::Simple(ScimAttr::String(s.to_string()))
+    }
+
+    #[test]
+    fn entry_replace_with() {
+        let stored: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let incoming: ScimEntryGeneric = serde_json::from_value(serde_json::json!({
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+            "id": "2819c223-7f76-453a-919d-413861904646",
+            "externalId": "701984",
+            "UserName": "bjensen@example.com",
+            "displayName": "Babs",
+            "active": true,
+            "groups": [],
+        }))
+        .expect("Failed to parse entry");
+
+        let mut entry = stored.clone();
+        let changes = entry.replace_with(&incoming, None);
+        assert!(changes.contains(&AttrChange::Modified {
+            path: "displayName".to_string(),
+            old: string("Babs Jensen"),
+            new: string("Babs"),
+        }));
+        assert!(changes
+            .iter()
+            .any(|c| matches!(c, AttrChange::Removed { path, .. } if path == "nickName")));
+        assert!(!changes.iter().any(|c| c.path() == "userName"));
+        assert!(!changes.iter().any(|c| c.path() == "externalId"));
+        // The stored name of an attribute is kept.
+        assert!(entry.attrs.contains_key("userName"));
+        assert!(!entry.attrs.contains_key("nickName"));
+        assert_eq!(entry.id, stored.id);
+        assert_eq!(entry.meta, stored.meta);
+
+        let schema = user_schema();
+        let mut entry = stored.clone();
+        let changes = entry.replace_with(&incoming, Some(&schema));
+        // groups is readOnly, so neither cleared nor replaced.
+        assert!(!changes.iter().any(|c| c.path() == "groups"));
+        assert_eq!(entry.attr("groups"), stored.attr("groups"));
+
+        let mut again = entry.clone();
+        assert!(again.replace_with(&incoming, Some(&schema)).is_empty());
+
+        // A null clears the attribute, as omitting it does.
+        let mut nulled = incoming.clone();
+        nulled.insert_attr("displayName", ScimValue::Simple(ScimAttr::Null));
+        let changes = again.replace_with(&nulled, Some(&schema));
+        assert_eq!(
+            changes,
+            vec![AttrChange::Removed {
+                path: "displayName".to_string(),
+                value: string("Babs"),
+            }]
+        );
+        assert!(!again.contains_attr("displayName"));
+    }
+
+    #[test]
+    fn entry_diff() {
+        let old: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let mut new = old.clone();
+        assert!(old.diff(&new).is_empty());
+
+        new.set_path("name.givenName", string("Babs"))
+            .expect("Failed to set path");
+        new.remove_path("name.middleName")
+            .expect("Failed to remove path");
+        new.remove_path("nickName").expect("Failed to remove path");
+        new.set_path(
+            r#"emails[type eq "work"].primary"#,
+            ScimValue::Simple(ScimAttr::Bool(false)),
+        )
+        .expect("Failed to set path");
+        new.set_path(
+            "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User:department",
+            string("Tour Operations"),
+        )
+        .expect("Failed to set path");
+        new.external_id = None;
+
+        let changes = old.diff(&new);
+        // Attributes are compared in the order of the attribute map.
+        let mut paths: Vec<_> = changes.iter().map(AttrChange::path).collect();
+        paths.sort_unstable();
+        assert_eq!(
+            paths,
+            vec![
+                "emails",
+                "externalId",
+                "name.givenName",
+                "name.middleName",
+                "nickName",
+                "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User",
+            ]
+        );
+        assert!(changes.contains(&AttrChange::Modified {
+            path: "name.givenName".to_string(),
+            old: string("Barbara"),
+            new: string("Babs"),
+        }));
+
+        let op = |path: &str| {
+            changes
+                .iter()
+                .find(|c| c.path() == path)
+                .expect("No change")
+                .to_patch_operation()
+                .expect("Failed to build patch")
+        };
+        assert_eq!(op("externalId").op, ScimPatchOpKind::Remove);
+        let given_name = op("name.givenName");
+        assert_eq!(given_name.op, ScimPatchOpKind::Replace);
+        assert_eq!(given_name.value, Some(serde_json::json!("Babs")));
+        assert_eq!(
+            op("urn:ietf:params:scim:schemas:extension:enterprise:2.0:User").op,
+            ScimPatchOpKind::Add
+        );
+
+        // Once both have the extension, it is compared by attribute.
+        let mut newer = new.clone();
+        newer
+            .set_path(
+                "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User:department",
+                string("Theme Park"),
+            )
+            .expect("Failed to set path");
+        assert_eq!(
+            new.diff(&newer)
+                .iter()
+                .map(AttrChange::path)
+                .collect::<Vec<_>>(),
+            vec!["urn:ietf:params:scim:schemas:extension:enterprise:2.0:User:department"]
+        );
+    }
+}
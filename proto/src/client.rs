@@ -0,0 +1,867 @@
+//! An async client of a SCIM service provider, with the `client` feature.
+//! Requests and responses are `application/scim+json`, read and written
+//! with the types of this crate, and error responses are returned as
+//! [`ScimErrorResponse`]s.
+
+pub mod auth;
+pub mod bulk;
+pub mod discovery;
+pub mod retry;
+pub mod sync;
+
+use crate::bulk::BulkError;
+use crate::constants::SCIM_CONTENT_TYPE;
+use crate::error::{ErrorSource, ScimError};
+use crate::group::Group;
+use crate::messages::{
+    ScimErrorResponse, ScimListResponse, ScimPatchRequest, ScimQueryParams, ScimSearchRequest,
+};
+use crate::reference::{RefBuilder, GROUPS_ENDPOINT, USERS_ENDPOINT};
+use crate::schema::SchemaViolation;
+use crate::user::{User, UserParseOptions};
+use crate::ScimEntryGeneric;
+use auth::Authenticator;
+use discovery::Discovery;
+use futures_core::Stream;
+use reqwest::header::{ACCEPT, CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH, RETRY_AFTER};
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use retry::RetryPolicy;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use url::Url;
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request couldn't be sent, or its response couldn't be read.
+    Http(reqwest::Error),
+    /// The service provider responded with an error.
+    Scim(ScimErrorResponse),
+    /// The resource has changed since the version a replace or patch was
+    /// conditional on, so it should be read again and the change retried.
+    PreconditionFailed(ScimErrorResponse),
+    /// A response body that isn't the message expected, with the error it
+    /// failed to be read with when there is one.
+    InvalidResponse(Option<ErrorSource>),
+    /// A request that can't be made, such as replacing a resource without
+    /// an id.
+    Request(ScimError),
+    /// The credentials to authenticate a request couldn't be had, such as
+    /// when a token endpoint refuses the client's.
+    Auth(String),
+    /// Bulk operations that can't be sent within the service provider's
+    /// limits, or whose bulkIds don't resolve.
+    Bulk(BulkError),
+}
+
+impl ClientError {
+    pub(crate) fn invalid_response<E: std::error::Error + Send + Sync + 'static>(err: E) -> Self {
+        ClientError::InvalidResponse(Some(ErrorSource::new(err)))
+    }
+
+    /// The status of an error response.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ClientError::Scim(resp) | ClientError::PreconditionFailed(resp) => Some(resp.status),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Http(err) => write!(f, "request failed: {}", err),
+            ClientError::Scim(resp) => {
+                write!(f, "service provider responded {}", resp.status)?;
+                match (&resp.detail, resp.scim_type) {
+                    (Some(detail), _) => write!(f, ": {}", detail),
+                    (None, Some(scim_type)) => write!(f, ": {}", scim_type),
+                    (None, None) => Ok(()),
+                }
+            }
+            ClientError::PreconditionFailed(_) => {
+                f.write_str("resource has changed since the version given")
+            }
+            ClientError::InvalidResponse(_) => {
+                f.write_str("invalid response from service provider")
+            }
+            ClientError::Request(err) => err.fmt(f),
+            ClientError::Auth(reason) => write!(f, "authentication failed: {}", reason),
+            ClientError::Bulk(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Http(err) => Some(err),
+            ClientError::Request(err) => Some(err),
+            ClientError::Bulk(err) => Some(err),
+            ClientError::InvalidResponse(Some(err)) => Some(err.get()),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(err: reqwest::Error) -> Self {
+        ClientError::Http(err)
+    }
+}
+
+impl From<ScimError> for ClientError {
+    fn from(err: ScimError) -> Self {
+        ClientError::Request(err)
+    }
+}
+
+// The error response of a failed request. Not every failure comes from the
+// SCIM layer, such as those of a proxy, so a body that isn't an error
+// response is kept as its detail.
+fn error_response(status: u16, body: &[u8]) -> ScimErrorResponse {
+    if let Ok(resp) = serde_json::from_slice::<ScimErrorResponse>(body) {
+        return resp;
+    }
+    let mut resp = ScimErrorResponse::new(status);
+    resp.detail = std::str::from_utf8(body)
+        .ok()
+        .map(str::trim)
+        .filter(|detail| !detail.is_empty())
+        .map(str::to_string);
+    resp
+}
+
+/// A client of the service provider at a base URL, such as
+/// `https://example.com/scim/v2`. It is cheap to clone, and clones share
+/// their connections.
+#[derive(Debug, Clone)]
+pub struct ScimClient {
+    http: reqwest::Client,
+    refs: RefBuilder,
+    user_options: UserParseOptions,
+    auth: Option<Arc<dyn Authenticator>>,
+    retry: Option<RetryPolicy>,
+    discovery: Arc<Mutex<Option<Arc<Discovery>>>>,
+    max_url_len: usize,
+}
+
+// Many servers and proxies refuse URLs longer than this.
+const DEFAULT_MAX_URL_LEN: usize = 2048;
+
+impl ScimClient {
+    pub fn new(base: &Url) -> Result<Self, ScimError> {
+        ScimClient::with_client(reqwest::Client::new(), base)
+    }
+
+    /// A client making its requests with `http`, for its timeouts, proxy
+    /// and TLS settings.
+    pub fn with_client(http: reqwest::Client, base: &Url) -> Result<Self, ScimError> {
+        Ok(ScimClient {
+            http,
+            refs: RefBuilder::new(base)?,
+            user_options: UserParseOptions::default(),
+            auth: None,
+            retry: None,
+            discovery: Arc::default(),
+            max_url_len: DEFAULT_MAX_URL_LEN,
+        })
+    }
+
+    /// How to read the users the service provider returns, such as the
+    /// `active` to give users without one.
+    pub fn user_options(mut self, options: UserParseOptions) -> Self {
+        self.user_options = options;
+        self
+    }
+
+    /// Authenticate each request with `auth`, such as an
+    /// [`auth::Bearer`] token.
+    pub fn auth(mut self, auth: impl Authenticator + 'static) -> Self {
+        self.auth = Some(Arc::new(auth));
+        self
+    }
+
+    /// Retry requests that are throttled or fail with a 5xx status, as
+    /// `policy` says. Requests aren't retried without one.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// List with a POST to `.search`, rather than a GET, when the URL of
+    /// the GET would be longer than `len` bytes, as it can be with a long
+    /// filter. Defaults to 2048; 0 always uses `.search`.
+    pub fn max_url_len(mut self, len: usize) -> Self {
+        self.max_url_len = len;
+        self
+    }
+
+    /// The base URL, which always ends with `/`.
+    pub fn base(&self) -> &Url {
+        self.refs.base()
+    }
+
+    fn resource_url(&self, endpoint: &str, id: &str) -> Url {
+        self.refs.resource(endpoint, id)
+    }
+
+    fn endpoint_url(&self, endpoint: &str) -> Url {
+        let mut url = self.refs.base().clone();
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty().push(endpoint);
+        }
+        url
+    }
+
+    fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        self.http
+            .request(method, url)
+            .header(ACCEPT, SCIM_CONTENT_TYPE)
+    }
+
+    fn with_body(
+        req: RequestBuilder,
+        body: &impl Serialize,
+    ) -> Result<RequestBuilder, ClientError> {
+        let body = serde_json::to_vec(body)
+            .map_err(|err| ScimError::from(SchemaViolation::unreadable("", err)))?;
+        Ok(req.header(CONTENT_TYPE, SCIM_CONTENT_TYPE).body(body))
+    }
+
+    async fn authenticate(&self, req: RequestBuilder) -> Result<RequestBuilder, ClientError> {
+        match &self.auth {
+            Some(auth) => auth.authenticate(req).await,
+            None => Ok(req),
+        }
+    }
+
+    // Send a request, returning the response if it succeeded, or if it was
+    // conditional on a version that is still current.
+    async fn send(&self, req: RequestBuilder) -> Result<Response, ClientError> {
+        let resp = self.send_retried(req).await?;
+        if resp.status().is_success() || resp.status() == StatusCode::NOT_MODIFIED {
+            return Ok(resp);
+        }
+        let status = resp.status();
+        let body = resp.bytes().await?;
+        let err = error_response(status.as_u16(), &body);
+        Err(if status == StatusCode::PRECONDITION_FAILED {
+            ClientError::PreconditionFailed(err)
+        } else {
+            ClientError::Scim(err)
+        })
+    }
+
+    async fn send_retried(&self, req: RequestBuilder) -> Result<Response, ClientError> {
+        let Some(policy) = &self.retry else {
+            return self.send_authenticated(req).await;
+        };
+        // A request with a streamed body can't be sent again.
+        let Some(method) = req
+            .try_clone()
+            .and_then(|req| req.build().ok())
+            .map(|req| req.method().clone())
+        else {
+            return self.send_authenticated(req).await;
+        };
+        policy.start();
+        let mut retries = 0;
+        loop {
+            let Some(attempt) = req.try_clone() else {
+                return self.send_authenticated(req).await;
+            };
+            let resp = self.send_authenticated(attempt).await?;
+            let retry_after = resp
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok());
+            match policy.delay(&method, retries, resp.status(), retry_after) {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => return Ok(resp),
+            }
+            retries += 1;
+        }
+    }
+
+    // Refused credentials are renewed once, if the authenticator can.
+    async fn send_authenticated(&self, req: RequestBuilder) -> Result<Response, ClientError> {
+        let retry = self.auth.as_ref().and_then(|_| req.try_clone());
+        let mut resp = self.authenticate(req).await?.send().await?;
+        if resp.status() == StatusCode::UNAUTHORIZED {
+            if let (Some(auth), Some(req)) = (&self.auth, retry) {
+                if auth.invalidate() {
+                    resp = auth.authenticate(req).await?.send().await?;
+                }
+            }
+        }
+        Ok(resp)
+    }
+
+    async fn read<T: DeserializeOwned>(resp: Response) -> Result<T, ClientError> {
+        let body = resp.bytes().await?;
+        serde_json::from_slice(&body).map_err(ClientError::invalid_response)
+    }
+
+    async fn read_user(&self, resp: Response) -> Result<User, ClientError> {
+        let etag = etag(&resp);
+        let body = resp.text().await?;
+        let mut user =
+            User::parse(&body, &self.user_options).map_err(ClientError::invalid_response)?;
+        track_version(&mut user.entry, etag);
+        Ok(user)
+    }
+
+    async fn read_group(resp: Response) -> Result<Group, ClientError> {
+        let etag = etag(&resp);
+        let mut group: Group = ScimClient::read(resp).await?;
+        track_version(&mut group.entry, etag);
+        Ok(group)
+    }
+
+    fn user_from_value(&self, value: Value) -> Result<User, ClientError> {
+        User::from_value(value, &self.user_options, &mut Vec::new())
+            .map_err(ClientError::invalid_response)
+    }
+
+    fn group_from_value(&self, value: Value) -> Result<Group, ClientError> {
+        serde_json::from_value(value).map_err(ClientError::invalid_response)
+    }
+
+    async fn list_page(
+        &self,
+        endpoint: &str,
+        query: ScimQueryParams,
+    ) -> Result<ScimListResponse<Value>, ClientError> {
+        let url = query_url(self.endpoint_url(endpoint), &query)?;
+        let req = if url.as_str().len() <= self.max_url_len {
+            self.request(Method::GET, url)
+        } else {
+            let mut url = self.endpoint_url(endpoint);
+            if let Ok(mut segments) = url.path_segments_mut() {
+                segments.push(".search");
+            }
+            let req = self.request(Method::POST, url);
+            ScimClient::with_body(req, &ScimSearchRequest::from(query))?
+        };
+        ScimClient::read(self.send(req).await?).await
+    }
+
+    fn list<T>(
+        &self,
+        endpoint: &'static str,
+        query: ScimQueryParams,
+        read: fn(&ScimClient, Value) -> Result<T, ClientError>,
+    ) -> ListStream<'_, T> {
+        ListStream {
+            client: self,
+            endpoint,
+            next: Some(query),
+            page: Vec::new().into_iter(),
+            pending: None,
+            fetch: fetch_page,
+            read,
+        }
+    }
+
+    /// The users matching `query`, a [`crate::query::ListQuery`] or its
+    /// parameters, following its pages as the stream is read. Pages are
+    /// requested by `startIndex`, or by cursor if `query` has one; an empty
+    /// cursor requests the first page.
+    pub fn list_users(&self, query: impl Into<ScimQueryParams>) -> ListStream<'_, User> {
+        self.list(USERS_ENDPOINT, query.into(), ScimClient::user_from_value)
+    }
+
+    /// The groups matching `query`, as [`ScimClient::list_users`].
+    pub fn list_groups(&self, query: impl Into<ScimQueryParams>) -> ListStream<'_, Group> {
+        self.list(GROUPS_ENDPOINT, query.into(), ScimClient::group_from_value)
+    }
+
+    // Send a patch, or make it with a GET and PUT where PATCH isn't
+    // supported, returning the response if it has the resource; a service
+    // provider may respond to a PATCH with 204 No Content instead.
+    async fn patch(
+        &self,
+        endpoint: &str,
+        id: &str,
+        version: Option<&str>,
+        patch: &ScimPatchRequest,
+    ) -> Result<Option<Response>, ClientError> {
+        if !self.patch_supported() {
+            let resp = self.replace_patched(endpoint, id, version, patch).await?;
+            return Ok(Some(resp));
+        }
+        let req = self.request(Method::PATCH, self.resource_url(endpoint, id));
+        let req = if_match(req, version);
+        let req = ScimClient::with_body(req, patch)?;
+        let resp = self.send(req).await?;
+        Ok(Some(resp).filter(|resp| resp.status() != StatusCode::NO_CONTENT))
+    }
+
+    // The resource a conditional GET responds with, unless not modified.
+    fn modified(resp: Response) -> Option<Response> {
+        Some(resp).filter(|resp| resp.status() != StatusCode::NOT_MODIFIED)
+    }
+
+    // Make a patch with a GET and a PUT, for a service provider that doesn't
+    // support PATCH. The PUT is conditional on the version read, so that a
+    // change made in between fails rather than being lost.
+    async fn replace_patched(
+        &self,
+        endpoint: &str,
+        id: &str,
+        version: Option<&str>,
+        patch: &ScimPatchRequest,
+    ) -> Result<Response, ClientError> {
+        let req = self.request(Method::GET, self.resource_url(endpoint, id));
+        let resp = self.send(req).await?;
+        let etag = etag(&resp);
+        let mut entry: ScimEntryGeneric = ScimClient::read(resp).await?;
+        let read = entry
+            .meta
+            .take()
+            .map(|meta| meta.version)
+            .filter(|read| !read.is_empty())
+            .or(etag);
+        if let (Some(version), Some(read)) = (version, &read) {
+            if version != read {
+                return Err(ClientError::PreconditionFailed(ScimErrorResponse::new(
+                    StatusCode::PRECONDITION_FAILED.as_u16(),
+                )));
+            }
+        }
+
+        entry.apply_patch(patch)?;
+        let req = self.request(Method::PUT, self.resource_url(endpoint, id));
+        let req = if_match(req, read.as_deref());
+        let req = ScimClient::with_body(req, &entry)?;
+        self.send(req).await
+    }
+
+    async fn delete(&self, endpoint: &str, id: &str) -> Result<(), ClientError> {
+        let req = self.request(Method::DELETE, self.resource_url(endpoint, id));
+        self.send(req).await.map(|_| ())
+    }
+
+    pub async fn get_user(&self, id: &str) -> Result<User, ClientError> {
+        let req = self.request(Method::GET, self.resource_url(USERS_ENDPOINT, id));
+        let resp = self.send(req).await?;
+        self.read_user(resp).await
+    }
+
+    /// The user `id`, unless its version is still `version`.
+    pub async fn get_user_if_changed(
+        &self,
+        id: &str,
+        version: &str,
+    ) -> Result<Option<User>, ClientError> {
+        let req = self
+            .request(Method::GET, self.resource_url(USERS_ENDPOINT, id))
+            .header(IF_NONE_MATCH, version);
+        match ScimClient::modified(self.send(req).await?) {
+            Some(resp) => self.read_user(resp).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Create `user`, returning it as the service provider stored it, with
+    /// its id and meta. The password is sent, if it has one.
+    pub async fn create_user(&self, user: &User) -> Result<User, ClientError> {
+        let req = self.request(Method::POST, self.endpoint_url(USERS_ENDPOINT));
+        let req = ScimClient::with_body(req, &user.to_request()?)?;
+        let resp = self.send(req).await?;
+        self.read_user(resp).await
+    }
+
+    /// Replace the user with the id of `user`. If `user` has a version,
+    /// from when it was read, the replace fails with
+    /// [`ClientError::PreconditionFailed`] if the user has changed since.
+    pub async fn replace_user(&self, user: &User) -> Result<User, ClientError> {
+        let id = resource_id(&user.entry.id)?;
+        let req = self.request(Method::PUT, self.resource_url(USERS_ENDPOINT, &id));
+        let req = if_match(req, entry_version(&user.entry));
+        let req = ScimClient::with_body(req, &user.to_request()?)?;
+        let resp = self.send(req).await?;
+        self.read_user(resp).await
+    }
+
+    /// Modify the user `id`, returning the user if the service provider
+    /// responds with it. With a `version`, the patch fails with
+    /// [`ClientError::PreconditionFailed`] if the user has changed since.
+    /// If discovery found that PATCH isn't supported, the user is read,
+    /// patched here, and replaced.
+    pub async fn patch_user(
+        &self,
+        id: &str,
+        version: Option<&str>,
+        patch: &ScimPatchRequest,
+    ) -> Result<Option<User>, ClientError> {
+        match self.patch(USERS_ENDPOINT, id, version, patch).await? {
+            Some(resp) => self.read_user(resp).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn delete_user(&self, id: &str) -> Result<(), ClientError> {
+        self.delete(USERS_ENDPOINT, id).await
+    }
+
+    pub async fn get_group(&self, id: &str) -> Result<Group, ClientError> {
+        let req = self.request(Method::GET, self.resource_url(GROUPS_ENDPOINT, id));
+        ScimClient::read_group(self.send(req).await?).await
+    }
+
+    /// The group `id`, unless its version is still `version`.
+    pub async fn get_group_if_changed(
+        &self,
+        id: &str,
+        version: &str,
+    ) -> Result<Option<Group>, ClientError> {
+        let req = self
+            .request(Method::GET, self.resource_url(GROUPS_ENDPOINT, id))
+            .header(IF_NONE_MATCH, version);
+        match ScimClient::modified(self.send(req).await?) {
+            Some(resp) => ScimClient::read_group(resp).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Create `group`, returning it as the service provider stored it.
+    pub async fn create_group(&self, group: &Group) -> Result<Group, ClientError> {
+        let req = self.request(Method::POST, self.endpoint_url(GROUPS_ENDPOINT));
+        let req = ScimClient::with_body(req, group)?;
+        ScimClient::read_group(self.send(req).await?).await
+    }
+
+    /// Replace the group with the id of `group`, if it is still the
+    /// version `group` has, as [`ScimClient::replace_user`].
+    pub async fn replace_group(&self, group: &Group) -> Result<Group, ClientError> {
+        let id = resource_id(&group.entry.id)?;
+        let req = self.request(Method::PUT, self.resource_url(GROUPS_ENDPOINT, &id));
+        let req = if_match(req, entry_version(&group.entry));
+        let req = ScimClient::with_body(req, group)?;
+        ScimClient::read_group(self.send(req).await?).await
+    }
+
+    /// Modify the group `id`, as [`ScimClient::patch_user`].
+    pub async fn patch_group(
+        &self,
+        id: &str,
+        version: Option<&str>,
+        patch: &ScimPatchRequest,
+    ) -> Result<Option<Group>, ClientError> {
+        match self.patch(GROUPS_ENDPOINT, id, version, patch).await? {
+            Some(resp) => ScimClient::read_group(resp).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn delete_group(&self, id: &str) -> Result<(), ClientError> {
+        self.delete(GROUPS_ENDPOINT, id).await
+    }
+}
+
+type PageFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<ScimListResponse<Value>, ClientError>> + Send + 'a>>;
+
+fn fetch_page<'a>(
+    client: &'a ScimClient,
+    endpoint: &'static str,
+    query: ScimQueryParams,
+) -> PageFuture<'a> {
+    Box::pin(client.list_page(endpoint, query))
+}
+
+/// The resources of a list, requested a page at a time as they are read.
+/// A page that fails ends the stream after its error.
+pub struct ListStream<'a, T> {
+    client: &'a ScimClient,
+    endpoint: &'static str,
+    // The query of the page to request once this one is read.
+    next: Option<ScimQueryParams>,
+    page: std::vec::IntoIter<Value>,
+    pending: Option<PageFuture<'a>>,
+    fetch: fn(&'a ScimClient, &'static str, ScimQueryParams) -> PageFuture<'a>,
+    read: fn(&ScimClient, Value) -> Result<T, ClientError>,
+}
+
+impl<T> Stream for ListStream<'_, T> {
+    type Item = Result<T, ClientError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(value) = this.page.next() {
+                return Poll::Ready(Some((this.read)(this.client, value)));
+            }
+
+            if let Some(pending) = &mut this.pending {
+                let list = match pending.as_mut().poll(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(list) => list,
+                };
+                this.pending = None;
+                match list {
+                    Ok(list) => {
+                        this.next = this.next.take().and_then(|query| query.next_page(&list));
+                        this.page = list.resources.into_iter();
+                        continue;
+                    }
+                    Err(err) => {
+                        this.next = None;
+                        return Poll::Ready(Some(Err(err)));
+                    }
+                }
+            }
+
+            // The query is kept until its page arrives, to find the next.
+            let Some(query) = this.next.clone() else {
+                return Poll::Ready(None);
+            };
+            this.pending = Some((this.fetch)(this.client, this.endpoint, query));
+        }
+    }
+}
+
+// The URL of a GET with the parameters of `query`.
+fn query_url(mut url: Url, query: &ScimQueryParams) -> Result<Url, ScimError> {
+    let Ok(Value::Object(params)) = serde_json::to_value(query) else {
+        return Err(ScimError::InvalidSyntax);
+    };
+    if params.is_empty() {
+        return Ok(url);
+    }
+    let mut pairs = url.query_pairs_mut();
+    for (name, value) in params {
+        match value {
+            Value::String(value) => pairs.append_pair(&name, &value),
+            value => pairs.append_pair(&name, &value.to_string()),
+        };
+    }
+    drop(pairs);
+    Ok(url)
+}
+
+fn etag(resp: &Response) -> Option<String> {
+    let etag = resp.headers().get(ETAG)?.to_str().ok()?;
+    Some(etag.to_string())
+}
+
+// A resource's version is in its meta, but some service providers only
+// send it as the response's ETag.
+fn track_version(entry: &mut crate::ScimEntry, etag: Option<String>) {
+    if let (Some(meta), Some(etag)) = (&mut entry.meta, etag) {
+        if meta.version.is_empty() {
+            meta.version = etag;
+        }
+    }
+}
+
+fn entry_version(entry: &crate::ScimEntry) -> Option<&str> {
+    let meta = entry.meta.as_ref()?;
+    Some(meta.version.as_str()).filter(|version| !version.is_empty())
+}
+
+fn if_match(req: RequestBuilder, version: Option<&str>) -> RequestBuilder {
+    match version {
+        Some(version) => req.header(IF_MATCH, version),
+        None => req,
+    }
+}
+
+// Builders give new resources a nil id, which can't be replaced.
+fn resource_id(id: &crate::ScimId) -> Result<String, ScimError> {
+    if id.is_nil() {
+        Err(ScimError::NoTarget)
+    } else {
+        Ok(id.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ScimType;
+
+    #[test]
+    fn client_urls() {
+        let base = Url::parse("https://example.com/scim/v2").expect("Failed to parse URL");
+        let client = ScimClient::new(&base).expect("Failed to create client");
+        assert_eq!(
+            client.endpoint_url(USERS_ENDPOINT).as_str(),
+            "https://example.com/scim/v2/Users"
+        );
+        assert_eq!(
+            client.resource_url(GROUPS_ENDPOINT, "a/b c").as_str(),
+            "https://example.com/scim/v2/Groups/a%2Fb%20c"
+        );
+        assert_eq!(
+            resource_id(&crate::ScimId::default()),
+            Err(ScimError::NoTarget)
+        );
+
+        let query = crate::query::ListQuery::new()
+            .filter("userName sw \"b&j\"".parse().expect("Invalid filter"))
+            .attributes(["userName", "emails"])
+            .count(10);
+        assert_eq!(
+            query_url(client.endpoint_url(USERS_ENDPOINT), &query.to_params())
+                .expect("Failed to render query")
+                .as_str(),
+            "https://example.com/scim/v2/Users\
+             ?attributes=userName%2Cemails&count=10&filter=userName+sw+%22b%26j%22"
+        );
+        assert_eq!(
+            query_url(
+                client.endpoint_url(USERS_ENDPOINT),
+                &ScimQueryParams::default()
+            )
+            .expect("Failed to render query")
+            .as_str(),
+            "https://example.com/scim/v2/Users"
+        );
+    }
+
+    #[test]
+    fn client_error_responses() {
+        let resp = error_response(
+            409,
+            br#"{
+                "schemas": ["urn:ietf:params:scim:api:messages:2.0:Error"],
+                "scimType": "uniqueness",
+                "detail": "userName is taken",
+                "status": 409
+            }"#,
+        );
+        assert_eq!(resp.scim_type, Some(ScimType::Uniqueness));
+        assert_eq!(
+            ClientError::Scim(resp).to_string(),
+            "service provider responded 409: userName is taken"
+        );
+
+        let resp = error_response(502, b"<html>Bad Gateway</html>\n");
+        assert_eq!(resp.status, 502);
+        assert_eq!(resp.detail.as_deref(), Some("<html>Bad Gateway</html>"));
+        assert_eq!(error_response(404, b"").detail, None);
+
+        let err = ClientError::PreconditionFailed(error_response(412, b""));
+        assert_eq!(err.status(), Some(412));
+    }
+
+    #[test]
+    fn client_versions() {
+        let mut group: Group = serde_json::from_value(serde_json::json!({
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:Group"],
+            "id": "e9e30dba",
+            "displayName": "Tour Guides",
+            "members": [],
+            "meta": {
+                "resourceType": "Group",
+                "created": "2010-01-23T04:56:22Z",
+                "lastModified": "2011-05-13T04:42:34Z",
+                "location": "https://example.com/v2/Groups/e9e30dba",
+                "version": ""
+            }
+        }))
+        .expect("Failed to parse group");
+        assert_eq!(entry_version(&group.entry), None);
+
+        track_version(&mut group.entry, Some("W/\"3694e05e9dff594\"".to_string()));
+        assert_eq!(entry_version(&group.entry), Some("W/\"3694e05e9dff594\""));
+        track_version(&mut group.entry, Some("W/\"3694e05e9dff595\"".to_string()));
+        assert_eq!(entry_version(&group.entry), Some("W/\"3694e05e9dff594\""));
+
+        group.entry.meta = None;
+        track_version(&mut group.entry, Some("W/\"3694e05e9dff594\"".to_string()));
+        assert_eq!(entry_version(&group.entry), None);
+    }
+
+    // Canned pages of the numbers 1 to 5, chosen by the endpoint listed. Any
+    // other request fails, so that it shows in what the stream reads.
+    #[allow(clippy::needless_pass_by_value)]
+    fn canned_page(
+        _: &ScimClient,
+        endpoint: &'static str,
+        query: ScimQueryParams,
+    ) -> PageFuture<'static> {
+        let page = |values: &[u64], next_cursor: Option<&str>| {
+            let mut list = ScimListResponse::new(values.iter().map(|v| Value::from(*v)).collect());
+            list.total_results = 5;
+            list.items_per_page = Some(2);
+            list.start_index = query.start_index;
+            list.next_cursor = next_cursor.map(str::to_string);
+            Ok(list)
+        };
+        let start = query.start_index.unwrap_or(1);
+        let list = match (endpoint, query.cursor.as_deref()) {
+            ("Cursor", Some("")) => page(&[1, 2], Some("VZUTiy")),
+            ("Cursor", Some("VZUTiy")) => page(&[3, 4, 5], None),
+            ("Empty", None) if start == 1 => page(&[], None),
+            ("Failing", None) if start == 1 => page(&[1, 2], None),
+            ("Paged", None) if start <= 5 => {
+                let values: Vec<u64> = (start..=5).take(2).collect();
+                page(&values, None)
+            }
+            _ => Err(ClientError::InvalidResponse(None)),
+        };
+        Box::pin(std::future::ready(list))
+    }
+
+    fn read_canned(endpoint: &'static str, query: ScimQueryParams) -> Vec<Option<u64>> {
+        struct Noop;
+        impl std::task::Wake for Noop {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        let base = Url::parse("https://example.com/scim/v2").expect("Failed to parse URL");
+        let client = ScimClient::new(&base).expect("Failed to create client");
+        let mut stream = ListStream {
+            client: &client,
+            endpoint,
+            next: Some(query),
+            page: Vec::new().into_iter(),
+            pending: None,
+            fetch: canned_page,
+            read: |_, value| Ok(value),
+        };
+
+        let waker = std::task::Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        let mut values = Vec::new();
+        while let Poll::Ready(Some(value)) = Pin::new(&mut stream).poll_next(&mut cx) {
+            values.push(value.ok().and_then(|v| v.as_u64()));
+        }
+        assert!(stream.next.is_none() && stream.pending.is_none());
+        values
+    }
+
+    #[test]
+    fn client_list_stream() {
+        let all: Vec<_> = (1..=5).map(Some).collect();
+        let query = ScimQueryParams {
+            count: Some(2),
+            ..Default::default()
+        };
+        // The last page is short, and its end is the end of the results.
+        assert_eq!(read_canned("Paged", query.clone()), all);
+        // A page that fails ends the stream after its error.
+        assert_eq!(
+            read_canned("Failing", query.clone()),
+            vec![Some(1), Some(2), None]
+        );
+        // An empty page ends the stream though the results aren't all read.
+        assert_eq!(read_canned("Empty", query.clone()), Vec::new());
+
+        let query = ScimQueryParams {
+            cursor: Some(String::new()),
+            ..query
+        };
+        assert_eq!(read_canned("Cursor", query), all);
+    }
+}
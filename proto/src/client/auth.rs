@@ -0,0 +1,298 @@
+//! How a [`super::ScimClient`] authenticates its requests. Service
+//! providers mostly take a bearer token, which provisioning jobs that run
+//! for longer than a token lives get from an OAuth2 token endpoint, see
+//! [`ClientCredentials`].
+
+use super::ClientError;
+use crate::redact::Secret;
+use reqwest::{RequestBuilder, StatusCode};
+use serde::Deserialize;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use url::Url;
+
+pub type AuthFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<RequestBuilder, ClientError>> + Send + 'a>>;
+
+/// Sets the headers that authenticate a request, before each is sent.
+pub trait Authenticator: fmt::Debug + Send + Sync {
+    fn authenticate<'a>(&'a self, req: RequestBuilder) -> AuthFuture<'a>;
+
+    /// Called when the service provider refuses a request's credentials
+    /// with 401 Unauthorized. Returns whether there may be new credentials
+    /// to try, in which case the request is sent once more.
+    fn invalidate(&self) -> bool {
+        false
+    }
+}
+
+/// A fixed bearer token, as RFC6750.
+pub struct Bearer {
+    token: String,
+}
+
+impl Bearer {
+    pub fn new(token: impl Into<String>) -> Self {
+        Bearer {
+            token: token.into(),
+        }
+    }
+}
+
+impl fmt::Debug for Bearer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Bearer").field(&Secret(&self.token)).finish()
+    }
+}
+
+impl Authenticator for Bearer {
+    fn authenticate<'a>(&'a self, req: RequestBuilder) -> AuthFuture<'a> {
+        Box::pin(async move { Ok(req.bearer_auth(&self.token)) })
+    }
+}
+
+/// A username and password, as RFC7617.
+pub struct Basic {
+    username: String,
+    password: String,
+}
+
+impl Basic {
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Basic {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+}
+
+impl fmt::Debug for Basic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Basic")
+            .field("username", &self.username)
+            .field("password", &Secret(&self.password))
+            .finish()
+    }
+}
+
+impl Authenticator for Basic {
+    fn authenticate<'a>(&'a self, req: RequestBuilder) -> AuthFuture<'a> {
+        Box::pin(async move { Ok(req.basic_auth(&self.username, Some(&self.password))) })
+    }
+}
+
+// Tokens are renewed this long before they expire, so that one doesn't
+// expire on its way to the service provider.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// Bearer tokens from an OAuth2 token endpoint, with the client credentials
+/// grant of RFC6749 section 4.4. A token is requested on first use, and
+/// again once it expires or the service provider refuses it.
+pub struct ClientCredentials {
+    http: reqwest::Client,
+    token_url: Url,
+    client_id: String,
+    client_secret: String,
+    scope: Option<String>,
+    token: Mutex<Option<Token>>,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc6749#section-5.1
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: String,
+    expires_in: Option<u64>,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc6749#section-5.2
+#[derive(Deserialize)]
+struct TokenError {
+    error: String,
+    error_description: Option<String>,
+}
+
+#[derive(Clone)]
+struct Token {
+    value: String,
+    // Without an expiry, a token is used until it is refused.
+    renew_at: Option<Instant>,
+}
+
+impl Token {
+    fn new(resp: TokenResponse, now: Instant) -> Result<Self, ClientError> {
+        if !resp.token_type.eq_ignore_ascii_case("bearer") {
+            return Err(ClientError::Auth(format!(
+                "unsupported token type '{}'",
+                resp.token_type
+            )));
+        }
+        Ok(Token {
+            value: resp.access_token,
+            // An expiry too far away to represent is treated as none.
+            renew_at: resp.expires_in.and_then(|secs| {
+                now.checked_add(Duration::from_secs(secs).saturating_sub(EXPIRY_MARGIN))
+            }),
+        })
+    }
+
+    fn fresh(&self, now: Instant) -> bool {
+        self.renew_at.map(|at| now < at).unwrap_or(true)
+    }
+}
+
+impl ClientCredentials {
+    pub fn new(
+        token_url: Url,
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+    ) -> Self {
+        ClientCredentials {
+            http: reqwest::Client::new(),
+            token_url,
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+            scope: None,
+            token: Mutex::new(None),
+        }
+    }
+
+    /// The scope to request, space separated.
+    pub fn scope(mut self, scope: impl Into<String>) -> Self {
+        self.scope = Some(scope.into());
+        self
+    }
+
+    /// Request tokens with `http`, rather than a client of its own.
+    pub fn with_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    fn cached(&self) -> Option<String> {
+        let token = self.token.lock().unwrap_or_else(PoisonError::into_inner);
+        token
+            .as_ref()
+            .filter(|token| token.fresh(Instant::now()))
+            .map(|token| token.value.clone())
+    }
+
+    async fn fetch(&self) -> Result<String, ClientError> {
+        let mut form = vec![("grant_type", "client_credentials")];
+        if let Some(scope) = &self.scope {
+            form.push(("scope", scope.as_str()));
+        }
+        let resp = self
+            .http
+            .post(self.token_url.clone())
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&form)
+            .send()
+            .await?;
+        let status = resp.status();
+        let body = resp.bytes().await?;
+        if !status.is_success() {
+            return Err(ClientError::Auth(token_error(status, &body)));
+        }
+
+        let resp = serde_json::from_slice(&body).map_err(ClientError::invalid_response)?;
+        let token = Token::new(resp, Instant::now())?;
+        let value = token.value.clone();
+        *self.token.lock().unwrap_or_else(PoisonError::into_inner) = Some(token);
+        Ok(value)
+    }
+}
+
+fn token_error(status: StatusCode, body: &[u8]) -> String {
+    let status = status.as_u16();
+    match serde_json::from_slice::<TokenError>(body) {
+        Ok(TokenError {
+            error,
+            error_description: Some(description),
+        }) => format!(
+            "token endpoint responded {}: {}: {}",
+            status, error, description
+        ),
+        Ok(TokenError { error, .. }) => format!("token endpoint responded {}: {}", status, error),
+        Err(_) => format!("token endpoint responded {}", status),
+    }
+}
+
+impl fmt::Debug for ClientCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientCredentials")
+            .field("token_url", &self.token_url.as_str())
+            .field("client_id", &self.client_id)
+            .field("client_secret", &Secret(&self.client_secret))
+            .field("scope", &self.scope)
+            .finish()
+    }
+}
+
+impl Authenticator for ClientCredentials {
+    fn authenticate<'a>(&'a self, req: RequestBuilder) -> AuthFuture<'a> {
+        Box::pin(async move {
+            let token = match self.cached() {
+                Some(token) => token,
+                None => self.fetch().await?,
+            };
+            Ok(req.bearer_auth(token))
+        })
+    }
+
+    fn invalidate(&self) -> bool {
+        *self.token.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_credentials_token() {
+        let now = Instant::now();
+        let resp = serde_json::from_str(
+            r#"{"access_token": "2YotnFZFEjr1zCsicMWpAA", "token_type": "Bearer", "expires_in": 3600}"#,
+        )
+        .expect("Failed to parse token response");
+        let token = Token::new(resp, now).expect("Failed to accept token");
+        assert!(token.fresh(now + Duration::from_secs(3500)));
+        assert!(!token.fresh(now + Duration::from_secs(3580)));
+
+        let resp = serde_json::from_str(&format!(
+            r#"{{"access_token": "2YotnFZFEjr1zCsicMWpAA", "token_type": "Bearer", "expires_in": {}}}"#,
+            u64::MAX
+        ))
+        .expect("Failed to parse token response");
+        let token = Token::new(resp, now).expect("Failed to accept token");
+        assert!(token.renew_at.is_none());
+
+        let resp = serde_json::from_str(
+            r#"{"access_token": "2YotnFZFEjr1zCsicMWpAA", "token_type": "mac"}"#,
+        )
+        .expect("Failed to parse token response");
+        assert!(matches!(Token::new(resp, now), Err(ClientError::Auth(_))));
+
+        assert_eq!(
+            token_error(
+                StatusCode::BAD_REQUEST,
+                br#"{"error": "invalid_client", "error_description": "unknown client"}"#
+            ),
+            "token endpoint responded 400: invalid_client: unknown client"
+        );
+
+        let auth = ClientCredentials::new(
+            Url::parse("https://auth.example.com/token").expect("Failed to parse URL"),
+            "provisioner",
+            "s3cr3t",
+        );
+        assert!(!format!("{:?}", auth).contains("s3cr3t"));
+        assert!(!format!("{:?}", Basic::new("bjensen", "s3cr3t")).contains("s3cr3t"));
+        assert!(!format!("{:?}", Bearer::new("s3cr3t")).contains("s3cr3t"));
+    }
+}
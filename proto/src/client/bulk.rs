@@ -0,0 +1,376 @@
+//! Bulk requests, RFC7644 section 3.7. A [`BulkBuilder`] collects
+//! operations, and [`ScimClient::bulk`] sends them in as many requests as
+//! the service provider's limits need, with the result of each operation
+//! returned by the [`BulkRef`] it was added as.
+
+use super::{entry_version, error_response, resource_id, ClientError, ScimClient};
+use crate::bulk::{BulkMethod, BulkOperation, BulkOperationResponse, BulkRequest, BulkResponse};
+use crate::convert;
+use crate::error::ScimError;
+use crate::group::Group;
+use crate::messages::{ScimErrorResponse, ScimPatchOpKind, ScimPatchOperation, ScimPatchRequest};
+use crate::reference::{GROUPS_ENDPOINT, USERS_ENDPOINT};
+use crate::user::User;
+use reqwest::Method;
+use serde_json::Value;
+
+pub const BULK_ENDPOINT: &str = "Bulk";
+
+/// An operation added to a [`BulkBuilder`], to find its result by, and for
+/// a create, to refer to the resource it creates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkRef {
+    index: usize,
+    bulk_id: Option<String>,
+}
+
+impl BulkRef {
+    /// The id by which other operations of the same bulk request can refer
+    /// to the resource this one creates, such as `bulkId:2`, or `None` if
+    /// this isn't a create.
+    pub fn id(&self) -> Option<String> {
+        self.bulk_id.as_ref().map(|id| format!("bulkId:{}", id))
+    }
+}
+
+/// The operations of a bulk request, in order. Ids given to its methods may
+/// be those of existing resources, or the [`BulkRef::id`] of a create.
+#[derive(Debug, Clone, Default)]
+pub struct BulkBuilder {
+    operations: Vec<BulkOperation>,
+    fail_on_errors: Option<u32>,
+    created: usize,
+}
+
+impl BulkBuilder {
+    pub fn new() -> Self {
+        BulkBuilder::default()
+    }
+
+    /// Stop once this many operations have failed, leaving the rest
+    /// unprocessed.
+    pub fn fail_on_errors(mut self, errors: u32) -> Self {
+        self.fail_on_errors = Some(errors);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    fn push(&mut self, operation: BulkOperation) -> BulkRef {
+        self.operations.push(operation);
+        BulkRef {
+            index: self.operations.len() - 1,
+            bulk_id: self.operations.last().and_then(|op| op.bulk_id.clone()),
+        }
+    }
+
+    /// Create a resource at `endpoint`, such as `Users`, from `data`.
+    pub fn create(&mut self, endpoint: &str, data: Value) -> BulkRef {
+        self.created += 1;
+        self.push(BulkOperation {
+            method: BulkMethod::Post,
+            bulk_id: Some(self.created.to_string()),
+            version: None,
+            path: format!("/{}", endpoint),
+            data: Some(data),
+        })
+    }
+
+    /// Replace the resource `id` at `endpoint`, if it is still `version`.
+    pub fn replace(
+        &mut self,
+        endpoint: &str,
+        id: &str,
+        version: Option<&str>,
+        data: Value,
+    ) -> BulkRef {
+        self.push(BulkOperation {
+            method: BulkMethod::Put,
+            bulk_id: None,
+            version: version.map(str::to_string),
+            path: format!("/{}/{}", endpoint, id),
+            data: Some(data),
+        })
+    }
+
+    /// Modify the resource `id` at `endpoint`, if it is still `version`.
+    pub fn patch(
+        &mut self,
+        endpoint: &str,
+        id: &str,
+        version: Option<&str>,
+        patch: &ScimPatchRequest,
+    ) -> Result<BulkRef, ScimError> {
+        let data = convert::to_value(patch)?;
+        Ok(self.push(BulkOperation {
+            method: BulkMethod::Patch,
+            bulk_id: None,
+            version: version.map(str::to_string),
+            path: format!("/{}/{}", endpoint, id),
+            data: Some(data),
+        }))
+    }
+
+    /// Delete the resource `id` at `endpoint`, if it is still `version`.
+    pub fn delete(&mut self, endpoint: &str, id: &str, version: Option<&str>) -> BulkRef {
+        self.push(BulkOperation {
+            method: BulkMethod::Delete,
+            bulk_id: None,
+            version: version.map(str::to_string),
+            path: format!("/{}/{}", endpoint, id),
+            data: None,
+        })
+    }
+
+    /// Create `user`, with its password if it has one.
+    pub fn create_user(&mut self, user: &User) -> Result<BulkRef, ScimError> {
+        Ok(self.create(USERS_ENDPOINT, user.to_request()?))
+    }
+
+    /// Replace the user with the id of `user`, if it is still the version
+    /// `user` has.
+    pub fn replace_user(&mut self, user: &User) -> Result<BulkRef, ScimError> {
+        let id = resource_id(&user.entry.id)?;
+        let data = user.to_request()?;
+        Ok(self.replace(USERS_ENDPOINT, &id, entry_version(&user.entry), data))
+    }
+
+    pub fn delete_user(&mut self, id: &str) -> BulkRef {
+        self.delete(USERS_ENDPOINT, id, None)
+    }
+
+    pub fn create_group(&mut self, group: &Group) -> Result<BulkRef, ScimError> {
+        let data = convert::to_value(group)?;
+        Ok(self.create(GROUPS_ENDPOINT, data))
+    }
+
+    /// Replace the group with the id of `group`, if it is still the version
+    /// `group` has.
+    pub fn replace_group(&mut self, group: &Group) -> Result<BulkRef, ScimError> {
+        let id = resource_id(&group.entry.id)?;
+        let data = convert::to_value(group)?;
+        Ok(self.replace(GROUPS_ENDPOINT, &id, entry_version(&group.entry), data))
+    }
+
+    pub fn delete_group(&mut self, id: &str) -> BulkRef {
+        self.delete(GROUPS_ENDPOINT, id, None)
+    }
+
+    /// Add the resource `member` to the group `group`, either of which may
+    /// be created by this request, as in RFC7644 section 3.7.2.
+    pub fn add_member(&mut self, group: &str, member: &str) -> Result<BulkRef, ScimError> {
+        let patch = ScimPatchRequest::new(vec![ScimPatchOperation {
+            op: ScimPatchOpKind::Add,
+            path: Some("members".to_string()),
+            value: Some(serde_json::json!([{ "value": member }])),
+        }]);
+        self.patch(GROUPS_ENDPOINT, group, None, &patch)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BulkOutcome {
+    Done(BulkOperationResponse),
+    /// The service provider refused the operation.
+    Failed(ScimErrorResponse),
+    /// The operation wasn't processed, as `failOnErrors` was reached or
+    /// its request couldn't be sent.
+    NotRun,
+}
+
+/// The outcome of each operation of a [`BulkBuilder`].
+#[derive(Debug)]
+pub struct BulkResults {
+    outcomes: Vec<BulkOutcome>,
+    error: Option<ClientError>,
+}
+
+impl BulkResults {
+    pub fn get(&self, op: &BulkRef) -> &BulkOutcome {
+        self.outcomes.get(op.index).unwrap_or(&BulkOutcome::NotRun)
+    }
+
+    /// The outcomes, in the order the operations were added.
+    pub fn iter(&self) -> std::slice::Iter<'_, BulkOutcome> {
+        self.outcomes.iter()
+    }
+
+    /// Whether every operation was done.
+    pub fn is_success(&self) -> bool {
+        self.outcomes
+            .iter()
+            .all(|outcome| matches!(outcome, BulkOutcome::Done(_)))
+    }
+
+    /// The error of a bulk request that couldn't be sent. The operations
+    /// of it and the requests after it weren't run.
+    pub fn error(&self) -> Option<&ClientError> {
+        self.error.as_ref()
+    }
+
+    // Record the responses to a request of `batch`, whose operations are
+    // those added at `indexes`, returning how many failed.
+    fn record(&mut self, batch: &[BulkOperation], indexes: &[usize], resp: BulkResponse) -> u32 {
+        let mut failed = 0;
+        for (pos, resp) in resp.operations.into_iter().enumerate() {
+            // Responses are matched by bulkId where they have one, and by
+            // position otherwise.
+            let pos = resp
+                .bulk_id
+                .as_ref()
+                .and_then(|id| batch.iter().position(|op| op.bulk_id.as_ref() == Some(id)))
+                .unwrap_or(pos);
+            let Some(index) = indexes.get(pos) else {
+                continue;
+            };
+            self.outcomes[*index] = if (200..300).contains(&resp.status) {
+                BulkOutcome::Done(resp)
+            } else {
+                failed += 1;
+                BulkOutcome::Failed(operation_error(&resp))
+            };
+        }
+        failed
+    }
+}
+
+fn operation_error(resp: &BulkOperationResponse) -> ScimErrorResponse {
+    let body = resp
+        .response
+        .as_ref()
+        .and_then(|body| serde_json::to_vec(body).ok())
+        .unwrap_or_default();
+    error_response(resp.status, &body)
+}
+
+impl ScimClient {
+    /// Send the operations of `bulk`, in as few requests as the limits of
+    /// the service provider allow, found by [`ScimClient::discover`].
+    /// Operations linked by bulkIds are sent in the same request.
+    pub async fn bulk(&self, bulk: BulkBuilder) -> Result<BulkResults, ClientError> {
+        let discovery = self.discover().await?;
+        let limits = discovery
+            .config
+            .bulk_limits()
+            .ok_or(ClientError::Request(ScimError::NotImplemented))?;
+
+        let mut req = BulkRequest::new(bulk.operations.clone());
+        req.fail_on_errors = bulk.fail_on_errors;
+        let batches = limits.split_indexed(req).map_err(ClientError::Bulk)?;
+
+        let mut results = BulkResults {
+            outcomes: vec![BulkOutcome::NotRun; bulk.operations.len()],
+            error: None,
+        };
+        let mut failed = 0;
+        for (mut batch, indexes) in batches {
+            if let Some(max) = bulk.fail_on_errors {
+                if failed >= max {
+                    break;
+                }
+                batch.fail_on_errors = Some(max - failed);
+            }
+
+            let req = self.request(Method::POST, self.endpoint_url(BULK_ENDPOINT));
+            let resp = match ScimClient::with_body(req, &batch) {
+                Ok(req) => self.send(req).await,
+                Err(err) => Err(err),
+            };
+            let resp = match resp {
+                Ok(resp) => ScimClient::read::<BulkResponse>(resp).await,
+                Err(err) => Err(err),
+            };
+            match resp {
+                Ok(resp) => failed += results.record(&batch.operations, &indexes, resp),
+                Err(err) => {
+                    results.error = Some(err);
+                    break;
+                }
+            }
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bulk::BulkLimits;
+
+    #[test]
+    fn bulk_builder_results() {
+        let mut bulk = BulkBuilder::new();
+        let group = bulk.create(
+            GROUPS_ENDPOINT,
+            serde_json::json!({"displayName": "Tour Guides"}),
+        );
+        let user = bulk.create(USERS_ENDPOINT, serde_json::json!({"userName": "bjensen"}));
+        let member = bulk
+            .add_member(
+                &group.id().expect("Create has no bulkId"),
+                &user.id().expect("Create has no bulkId"),
+            )
+            .expect("Failed to add member");
+        let delete = bulk.delete_user("b7c14771");
+        assert_eq!(member.id(), None);
+        assert_eq!(bulk.operations[2].path, "/Groups/bulkId:1");
+        assert_eq!(
+            bulk.operations[2]
+                .bulk_id_references()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["1", "2"]
+        );
+
+        let limits = BulkLimits {
+            max_operations: 3,
+            max_payload_size: 1 << 20,
+        };
+        let batches = limits
+            .split_indexed(BulkRequest::new(bulk.operations.clone()))
+            .expect("Failed to split");
+        assert_eq!(batches.len(), 2);
+        let (batch, indexes) = &batches[0];
+        assert_eq!(indexes, &vec![0, 1, 2]);
+
+        let mut results = BulkResults {
+            outcomes: vec![BulkOutcome::NotRun; bulk.len()],
+            error: None,
+        };
+        let resp: BulkResponse = serde_json::from_value(serde_json::json!({
+            "schemas": ["urn:ietf:params:scim:api:messages:2.0:BulkResponse"],
+            "Operations": [
+                {"method": "POST", "bulkId": "2", "status": "201",
+                 "location": "https://example.com/v2/Users/92b725cd"},
+                {"method": "POST", "bulkId": "1", "status": "201",
+                 "location": "https://example.com/v2/Groups/e9e30dba"},
+                {"method": "PATCH", "status": "400", "response": {
+                    "schemas": ["urn:ietf:params:scim:api:messages:2.0:Error"],
+                    "scimType": "invalidValue",
+                    "detail": "members is invalid",
+                    "status": "400"
+                }}
+            ]
+        }))
+        .expect("Failed to parse response");
+        assert_eq!(results.record(&batch.operations, indexes, resp), 1);
+
+        assert!(matches!(
+            results.get(&user),
+            BulkOutcome::Done(resp) if resp.location.as_ref().map(|l| l.path()) == Some("/v2/Users/92b725cd")
+        ));
+        assert!(matches!(results.get(&group), BulkOutcome::Done(_)));
+        assert!(matches!(
+            results.get(&member),
+            BulkOutcome::Failed(err) if err.detail.as_deref() == Some("members is invalid")
+        ));
+        assert_eq!(results.get(&delete), &BulkOutcome::NotRun);
+        assert!(!results.is_success());
+    }
+}
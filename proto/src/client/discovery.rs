@@ -0,0 +1,118 @@
+//! What a service provider supports, from its `ServiceProviderConfig`,
+//! `ResourceTypes` and `Schemas` endpoints, RFC7644 section 4. A
+//! [`super::ScimClient`] adapts to it once discovered, such as by making a
+//! change with a GET and PUT where PATCH isn't supported.
+
+use super::{ClientError, ScimClient};
+use crate::config::ServiceProviderConfig;
+use crate::messages::ScimListResponse;
+use crate::registry::{ResourceTypeDefinition, SchemaRegistry};
+use crate::schema::ScimSchema;
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::sync::{Arc, PoisonError};
+
+pub const SERVICE_PROVIDER_CONFIG_ENDPOINT: &str = "ServiceProviderConfig";
+pub const RESOURCE_TYPES_ENDPOINT: &str = "ResourceTypes";
+pub const SCHEMAS_ENDPOINT: &str = "Schemas";
+
+#[derive(Debug, Clone)]
+pub struct Discovery {
+    pub config: ServiceProviderConfig,
+    /// The schemas and resource types the service provider describes.
+    /// Those it describes in a way this crate can't read are left out.
+    pub registry: SchemaRegistry,
+}
+
+impl ScimClient {
+    /// Fetch what the service provider supports, once; later calls, from
+    /// this client or its clones, return what was fetched.
+    pub async fn discover(&self) -> Result<Arc<Discovery>, ClientError> {
+        if let Some(discovery) = self.discovered() {
+            return Ok(discovery);
+        }
+
+        let config = self.get(SERVICE_PROVIDER_CONFIG_ENDPOINT).await?;
+        let mut registry = SchemaRegistry::new();
+        for schema in self.list_all::<ScimSchema>(SCHEMAS_ENDPOINT).await? {
+            registry.insert_schema(schema);
+        }
+        for resource_type in self
+            .list_all::<ResourceTypeDefinition>(RESOURCE_TYPES_ENDPOINT)
+            .await?
+        {
+            registry.insert_resource_type(resource_type);
+        }
+
+        let discovery = Arc::new(Discovery { config, registry });
+        *self
+            .discovery
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(discovery.clone());
+        Ok(discovery)
+    }
+
+    /// What [`ScimClient::discover`] found, if it has been called.
+    pub fn discovered(&self) -> Option<Arc<Discovery>> {
+        self.discovery
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    // Without discovery, PATCH is assumed to be supported.
+    pub(crate) fn patch_supported(&self) -> bool {
+        self.discovered()
+            .map(|discovery| discovery.config.patch.supported)
+            .unwrap_or(true)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, endpoint: &str) -> Result<T, ClientError> {
+        let req = self.request(Method::GET, self.endpoint_url(endpoint));
+        ScimClient::read(self.send(req).await?).await
+    }
+
+    // The resources of a discovery endpoint, skipping any that don't parse.
+    async fn list_all<T: DeserializeOwned>(&self, endpoint: &str) -> Result<Vec<T>, ClientError> {
+        let body: Value = self.get(endpoint).await?;
+        Ok(resources(body)?
+            .into_iter()
+            .filter_map(|value| serde_json::from_value(value).ok())
+            .collect())
+    }
+}
+
+// Discovery endpoints respond with a list response, but some service
+// providers respond with a bare array.
+fn resources(body: Value) -> Result<Vec<Value>, ClientError> {
+    match body {
+        Value::Array(resources) => Ok(resources),
+        body => serde_json::from_value::<ScimListResponse<Value>>(body)
+            .map(|list| list.resources)
+            .map_err(ClientError::invalid_response),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discovery_resources() {
+        let list = serde_json::json!({
+            "schemas": ["urn:ietf:params:scim:api:messages:2.0:ListResponse"],
+            "totalResults": 1,
+            "Resources": [{"name": "User", "endpoint": "/Users", "schema": "urn:ietf:params:scim:schemas:core:2.0:User"}]
+        });
+        let bare = serde_json::json!([{"name": "User", "endpoint": "/Users", "schema": "urn:ietf:params:scim:schemas:core:2.0:User"}]);
+        assert_eq!(
+            resources(list).expect("Failed to read list"),
+            resources(bare).expect("Failed to read array")
+        );
+        assert!(matches!(
+            resources(serde_json::json!({"detail": "nope"})),
+            Err(ClientError::InvalidResponse(_))
+        ));
+    }
+}
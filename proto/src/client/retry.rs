@@ -0,0 +1,259 @@
+//! Retries of requests that a service provider throttled, with 429 Too Many
+//! Requests, or failed to serve, with a 5xx status. Service providers such
+//! as Azure AD throttle provisioning traffic heavily, and say when to try
+//! again with `Retry-After`; otherwise the wait doubles with each retry,
+//! with jitter so that clients throttled together don't retry together.
+
+use reqwest::{Method, StatusCode};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use time::format_description::well_known::Rfc2822;
+use time::OffsetDateTime;
+
+/// When to retry a request, and how long to wait first. Clones share their
+/// retry budget, as clones of a [`super::ScimClient`] share it.
+///
+/// A request that fails with a 5xx status may still have been carried out,
+/// so a retried create can create a resource twice. Only idempotent
+/// requests, such as GET, PUT and DELETE, are retried after a 5xx status
+/// unless [`RetryPolicy::non_idempotent`] is set.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    server_errors: bool,
+    non_idempotent: bool,
+    budget: Arc<RetryBudget>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            server_errors: true,
+            non_idempotent: false,
+            budget: Arc::new(RetryBudget::new(10, 0.1)),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        RetryPolicy::default()
+    }
+
+    /// The most times to retry a request.
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = retries;
+        self
+    }
+
+    /// The wait before the first retry, which doubles with each retry.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// The longest to wait before a retry. A request with a `Retry-After`
+    /// longer than this isn't retried.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Whether to retry 5xx responses as well as 429 Too Many Requests.
+    pub fn server_errors(mut self, retry: bool) -> Self {
+        self.server_errors = retry;
+        self
+    }
+
+    /// Whether to retry 5xx responses to POST and PATCH requests too, which
+    /// the service provider may have carried out before failing.
+    pub fn non_idempotent(mut self, retry: bool) -> Self {
+        self.non_idempotent = retry;
+        self
+    }
+
+    /// Limit retries across all requests, so that a service provider that
+    /// is down isn't sent several times the requests it would be. At most
+    /// `retries` can be made at once, and each request allows `per_request`
+    /// more, such as 0.1 for one retry in ten requests.
+    pub fn budget(mut self, retries: u32, per_request: f64) -> Self {
+        self.budget = Arc::new(RetryBudget::new(retries, per_request));
+        self
+    }
+
+    /// Called once for each request, before it is first sent.
+    pub(crate) fn start(&self) {
+        self.budget.deposit();
+    }
+
+    /// How long to wait before retrying a `method` request that was refused
+    /// with `status` after `retries` retries, if it should be.
+    pub(crate) fn delay(
+        &self,
+        method: &Method,
+        retries: u32,
+        status: StatusCode,
+        retry_after: Option<&str>,
+    ) -> Option<Duration> {
+        let retryable = status == StatusCode::TOO_MANY_REQUESTS
+            || (self.server_errors
+                && (self.non_idempotent || method.is_idempotent())
+                && status.is_server_error());
+        if !retryable || retries >= self.max_retries {
+            return None;
+        }
+
+        let delay = match retry_after
+            .and_then(|value| parse_retry_after(value, OffsetDateTime::now_utc()))
+        {
+            Some(delay) if delay > self.max_delay => return None,
+            Some(delay) => delay,
+            None => self.backoff(retries, jitter()),
+        };
+        self.budget.withdraw().then_some(delay)
+    }
+
+    // Half the exponential delay, and a random part of the other half.
+    fn backoff(&self, retries: u32, jitter: f64) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retries))
+            .min(self.max_delay);
+        delay / 2 + (delay / 2).mul_f64(jitter)
+    }
+}
+
+// A number in [0, 1), from the random keys std gives each hasher, to save
+// a dependency on a random number generator.
+fn jitter() -> f64 {
+    let bits = RandomState::new().build_hasher().finish() >> 11;
+    bits as f64 / (1u64 << 53) as f64
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP-date, RFC9110
+/// section 10.2.3. A date in the past means to retry now.
+fn parse_retry_after(value: &str, now: OffsetDateTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = OffsetDateTime::parse(value, &Rfc2822).ok()?;
+    Some((at - now).try_into().unwrap_or(Duration::ZERO))
+}
+
+// A token bucket of retries, refilled by requests.
+#[derive(Debug)]
+struct RetryBudget {
+    capacity: f64,
+    per_request: f64,
+    tokens: Mutex<f64>,
+}
+
+impl RetryBudget {
+    fn new(retries: u32, per_request: f64) -> Self {
+        RetryBudget {
+            capacity: f64::from(retries),
+            per_request,
+            tokens: Mutex::new(f64::from(retries)),
+        }
+    }
+
+    fn deposit(&self) {
+        let mut tokens = self.tokens.lock().unwrap_or_else(PoisonError::into_inner);
+        *tokens = (*tokens + self.per_request).min(self.capacity);
+    }
+
+    fn withdraw(&self) -> bool {
+        let mut tokens = self.tokens.lock().unwrap_or_else(PoisonError::into_inner);
+        if *tokens < 1.0 {
+            return false;
+        }
+        *tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delays() {
+        let now = OffsetDateTime::parse("Wed, 21 Oct 2015 07:28:00 GMT", &Rfc2822)
+            .expect("Failed to parse date");
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+
+        let policy = RetryPolicy::new().budget(2, 0.5);
+        assert_eq!(policy.backoff(0, 0.0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2, 0.5), Duration::from_secs(3));
+        assert_eq!(policy.backoff(10, 0.0), Duration::from_secs(30));
+
+        assert_eq!(
+            policy.delay(&Method::GET, 0, StatusCode::BAD_REQUEST, None),
+            None
+        );
+        assert_eq!(
+            policy.delay(&Method::GET, 3, StatusCode::SERVICE_UNAVAILABLE, None),
+            None
+        );
+        assert_eq!(
+            policy.delay(&Method::GET, 0, StatusCode::TOO_MANY_REQUESTS, Some("3600")),
+            None
+        );
+        assert_eq!(
+            policy.delay(&Method::GET, 0, StatusCode::TOO_MANY_REQUESTS, Some("5")),
+            Some(Duration::from_secs(5))
+        );
+        assert!(policy
+            .delay(&Method::GET, 1, StatusCode::BAD_GATEWAY, None)
+            .is_some());
+        // The budget of two retries is spent, until requests refill it.
+        assert_eq!(
+            policy.delay(&Method::GET, 0, StatusCode::TOO_MANY_REQUESTS, Some("5")),
+            None
+        );
+        policy.clone().start();
+        policy.start();
+        assert!(policy
+            .delay(&Method::GET, 0, StatusCode::TOO_MANY_REQUESTS, Some("5"))
+            .is_some());
+
+        let policy = RetryPolicy::new();
+        assert_eq!(
+            policy.delay(&Method::POST, 0, StatusCode::BAD_GATEWAY, None),
+            None
+        );
+        assert!(policy
+            .delay(&Method::POST, 0, StatusCode::TOO_MANY_REQUESTS, None)
+            .is_some());
+        assert!(policy
+            .non_idempotent(true)
+            .delay(&Method::POST, 0, StatusCode::BAD_GATEWAY, None)
+            .is_some());
+
+        let throttled = RetryPolicy::new().server_errors(false);
+        assert_eq!(
+            throttled.delay(&Method::GET, 0, StatusCode::INTERNAL_SERVER_ERROR, None),
+            None
+        );
+    }
+}
@@ -0,0 +1,633 @@
+//! Reconciling the users and groups of a service provider with a desired
+//! set, as a provisioning connector does. Resources are matched by their
+//! externalId; those that differ are patched, those missing are created,
+//! and those the service provider has but the desired set doesn't are
+//! deleted. Resources without an externalId weren't provisioned this way,
+//! and are left as they are.
+
+use super::{entry_version, resource_id, ClientError, ScimClient};
+use crate::builtin::{group_schema, user_schema};
+use crate::change::AttrChange;
+use crate::convert;
+use crate::error::ScimError;
+use crate::filter::ScimFilter;
+use crate::group::Group;
+use crate::messages::ScimPatchRequest;
+use crate::query::ListQuery;
+use crate::reference::{GROUPS_ENDPOINT, USERS_ENDPOINT};
+use crate::resource::ScimResource;
+use crate::schema::{Mutability, ScimSchema};
+use crate::user::User;
+use crate::{ScimEntry, ScimValue};
+use futures_core::Stream;
+use reqwest::Method;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::pin::Pin;
+
+/// What to do with a desired resource whose userName, or a group's
+/// displayName, is taken by a remote resource without an externalId.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Report the conflict, and leave both as they are.
+    #[default]
+    Skip,
+    /// Take over the remote resource, setting its externalId, and make
+    /// changes without checking that resources haven't changed since they
+    /// were listed.
+    Overwrite,
+    /// Make no changes if there are conflicts, and stop at the first change
+    /// to a resource that has changed since it was listed.
+    Abort,
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+    dry_run: bool,
+    conflicts: ConflictPolicy,
+    delete: bool,
+    filter: Option<ScimFilter>,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        SyncOptions {
+            dry_run: false,
+            conflicts: ConflictPolicy::default(),
+            delete: true,
+            filter: None,
+        }
+    }
+}
+
+impl SyncOptions {
+    pub fn new() -> Self {
+        SyncOptions::default()
+    }
+
+    /// Plan the changes, without making them.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn conflicts(mut self, policy: ConflictPolicy) -> Self {
+        self.conflicts = policy;
+        self
+    }
+
+    /// Whether to delete remote resources with an externalId that isn't
+    /// desired. Defaults to true.
+    pub fn delete(mut self, delete: bool) -> Self {
+        self.delete = delete;
+        self
+    }
+
+    /// Only reconcile the remote resources matching `filter`, such as
+    /// those of one department, so that others are never deleted.
+    pub fn filter(mut self, filter: ScimFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+}
+
+/// A change to a remote resource, to make it as desired.
+#[derive(Debug, Clone)]
+pub enum SyncChange<T> {
+    Create {
+        external_id: String,
+        resource: T,
+    },
+    Patch {
+        external_id: String,
+        id: String,
+        /// The version the resource was listed at.
+        version: Option<String>,
+        changes: Vec<AttrChange>,
+    },
+    Delete {
+        external_id: String,
+        id: String,
+    },
+}
+
+impl<T> SyncChange<T> {
+    pub fn external_id(&self) -> &str {
+        match self {
+            SyncChange::Create { external_id, .. }
+            | SyncChange::Patch { external_id, .. }
+            | SyncChange::Delete { external_id, .. } => external_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// A remote resource without an externalId has the desired resource's
+    /// userName, or displayName.
+    Name,
+    /// Several remote resources have the externalId, so which to change
+    /// isn't known.
+    ExternalId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncConflict {
+    pub external_id: String,
+    /// The id of the remote resource in the way.
+    pub id: String,
+    pub kind: ConflictKind,
+}
+
+/// The changes that reconcile a service provider with a desired set, in
+/// the order they are made: deletes first, then patches, then creates, so
+/// that the userNames of deleted and renamed resources are free to be
+/// taken.
+#[derive(Debug, Clone)]
+pub struct SyncPlan<T> {
+    pub changes: Vec<SyncChange<T>>,
+    /// Desired resources left as they are, for the reason given.
+    pub conflicts: Vec<SyncConflict>,
+    /// The remote id of each desired resource that already exists, by
+    /// externalId.
+    pub matched: BTreeMap<String, String>,
+}
+
+#[derive(Debug)]
+pub enum SyncOutcome {
+    Done,
+    /// Created, with the id the service provider gave it.
+    Created(String),
+    Failed(ClientError),
+    /// Not made, in a dry run or once the sync stopped.
+    NotRun,
+}
+
+#[derive(Debug)]
+pub struct SyncReport<T> {
+    pub plan: SyncPlan<T>,
+    /// The outcome of each of the plan's changes, in the same order.
+    pub outcomes: Vec<SyncOutcome>,
+}
+
+impl<T> SyncReport<T> {
+    /// Whether every change was made, or would be in a dry run, and no
+    /// desired resource was left out for a conflict.
+    pub fn is_success(&self) -> bool {
+        self.plan.conflicts.is_empty()
+            && !self
+                .outcomes
+                .iter()
+                .any(|outcome| matches!(outcome, SyncOutcome::Failed(_)))
+    }
+
+    /// The remote id of a desired resource, such as to list a user synced
+    /// earlier as a member of a desired group.
+    pub fn id(&self, external_id: &str) -> Option<&str> {
+        if let Some(id) = self.plan.matched.get(external_id) {
+            return Some(id);
+        }
+        self.plan
+            .changes
+            .iter()
+            .zip(&self.outcomes)
+            .find_map(|(change, outcome)| match (change, outcome) {
+                (SyncChange::Create { external_id: e, .. }, SyncOutcome::Created(id))
+                    if e == external_id =>
+                {
+                    Some(id.as_str())
+                }
+                _ => None,
+            })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&SyncChange<T>, &SyncOutcome)> {
+        self.plan.changes.iter().zip(&self.outcomes)
+    }
+}
+
+// What syncing needs of a resource type, beyond its entry.
+trait Synced: ScimResource + Clone {
+    const ENDPOINT: &'static str;
+
+    fn scim_entry(&self) -> &ScimEntry;
+
+    // The attribute service providers keep unique, which a resource that
+    // wasn't provisioned by a sync may already have.
+    fn name(&self) -> &str;
+
+    fn schema() -> ScimSchema;
+
+    fn read(client: &ScimClient, value: Value) -> Result<Self, ClientError>;
+
+    fn body(&self) -> Result<Value, ScimError>;
+}
+
+impl Synced for User {
+    const ENDPOINT: &'static str = USERS_ENDPOINT;
+
+    fn scim_entry(&self) -> &ScimEntry {
+        &self.entry
+    }
+
+    fn name(&self) -> &str {
+        &self.user_name
+    }
+
+    fn schema() -> ScimSchema {
+        user_schema()
+    }
+
+    fn read(client: &ScimClient, value: Value) -> Result<Self, ClientError> {
+        client.user_from_value(value)
+    }
+
+    fn body(&self) -> Result<Value, ScimError> {
+        self.to_request()
+    }
+}
+
+impl Synced for Group {
+    const ENDPOINT: &'static str = GROUPS_ENDPOINT;
+
+    fn scim_entry(&self) -> &ScimEntry {
+        &self.entry
+    }
+
+    fn name(&self) -> &str {
+        &self.display_name
+    }
+
+    fn schema() -> ScimSchema {
+        group_schema()
+    }
+
+    fn read(client: &ScimClient, value: Value) -> Result<Self, ClientError> {
+        client.group_from_value(value)
+    }
+
+    fn body(&self) -> Result<Value, ScimError> {
+        convert::to_value(self)
+    }
+}
+
+impl ScimClient {
+    /// Make the service provider's users those of `desired`, each of which
+    /// must have a unique externalId. The password of a user is only sent
+    /// when it is created.
+    pub async fn sync_users(
+        &self,
+        desired: impl IntoIterator<Item = User>,
+        options: &SyncOptions,
+    ) -> Result<SyncReport<User>, ClientError> {
+        self.sync(desired.into_iter().collect(), options).await
+    }
+
+    /// Make the service provider's groups those of `desired`, as
+    /// [`ScimClient::sync_users`]. Members are remote ids, which
+    /// [`SyncReport::id`] gives for users synced first.
+    pub async fn sync_groups(
+        &self,
+        desired: impl IntoIterator<Item = Group>,
+        options: &SyncOptions,
+    ) -> Result<SyncReport<Group>, ClientError> {
+        self.sync(desired.into_iter().collect(), options).await
+    }
+
+    async fn sync<T: Synced>(
+        &self,
+        desired: Vec<T>,
+        options: &SyncOptions,
+    ) -> Result<SyncReport<T>, ClientError> {
+        let mut query = ListQuery::new();
+        if let Some(filter) = &options.filter {
+            query = query.filter(filter.clone());
+        }
+        let mut stream = self.list(T::ENDPOINT, query.to_params(), T::read);
+        let mut remote = Vec::new();
+        while let Some(resource) =
+            std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await
+        {
+            remote.push(resource?);
+        }
+
+        let plan = plan(desired, &remote, options)?;
+        let abort = options.conflicts == ConflictPolicy::Abort;
+        let mut stopped = options.dry_run || (abort && !plan.conflicts.is_empty());
+        let mut outcomes = Vec::with_capacity(plan.changes.len());
+        for change in &plan.changes {
+            if stopped {
+                outcomes.push(SyncOutcome::NotRun);
+                continue;
+            }
+            let outcome = match self.sync_change(change, options).await {
+                Ok(outcome) => outcome,
+                Err(err) => SyncOutcome::Failed(err),
+            };
+            stopped = abort
+                && matches!(
+                    outcome,
+                    SyncOutcome::Failed(ClientError::PreconditionFailed(_))
+                );
+            outcomes.push(outcome);
+        }
+        Ok(SyncReport { plan, outcomes })
+    }
+
+    async fn sync_change<T: Synced>(
+        &self,
+        change: &SyncChange<T>,
+        options: &SyncOptions,
+    ) -> Result<SyncOutcome, ClientError> {
+        match change {
+            SyncChange::Create { resource, .. } => {
+                let req = self.request(Method::POST, self.endpoint_url(T::ENDPOINT));
+                let req = ScimClient::with_body(req, &resource.body()?)?;
+                let created: Value = ScimClient::read(self.send(req).await?).await?;
+                let id = created
+                    .get("id")
+                    .and_then(Value::as_str)
+                    .ok_or(ClientError::InvalidResponse(None))?;
+                Ok(SyncOutcome::Created(id.to_string()))
+            }
+            SyncChange::Patch {
+                id,
+                version,
+                changes,
+                ..
+            } => {
+                let patch = ScimPatchRequest::new(
+                    changes
+                        .iter()
+                        .map(AttrChange::to_patch_operation)
+                        .collect::<Result<_, _>>()?,
+                );
+                let version = match options.conflicts {
+                    ConflictPolicy::Overwrite => None,
+                    ConflictPolicy::Skip | ConflictPolicy::Abort => version.as_deref(),
+                };
+                self.patch(T::ENDPOINT, id, version, &patch).await?;
+                Ok(SyncOutcome::Done)
+            }
+            SyncChange::Delete { id, .. } => {
+                self.delete(T::ENDPOINT, id).await?;
+                Ok(SyncOutcome::Done)
+            }
+        }
+    }
+}
+
+// The changes that make `remote` as `desired`.
+fn plan<T: Synced>(
+    desired: Vec<T>,
+    remote: &[T],
+    options: &SyncOptions,
+) -> Result<SyncPlan<T>, ScimError> {
+    let mut wanted = BTreeSet::new();
+    for resource in &desired {
+        let external_id = resource
+            .scim_entry()
+            .external_id
+            .as_deref()
+            .ok_or_else(|| ScimError::MissingRequiredAttribute("externalId".to_string()))?;
+        if !wanted.insert(external_id.to_string()) {
+            return Err(ScimError::Uniqueness);
+        }
+    }
+
+    let mut by_external_id: BTreeMap<&str, Vec<&T>> = BTreeMap::new();
+    let mut by_name: BTreeMap<String, &T> = BTreeMap::new();
+    for resource in remote {
+        match &resource.scim_entry().external_id {
+            Some(external_id) => by_external_id
+                .entry(external_id)
+                .or_default()
+                .push(resource),
+            None => {
+                by_name
+                    .entry(resource.name().to_lowercase())
+                    .or_insert(resource);
+            }
+        }
+    }
+
+    let mut plan = SyncPlan {
+        changes: Vec::new(),
+        conflicts: Vec::new(),
+        matched: BTreeMap::new(),
+    };
+    let mut deletes = Vec::new();
+    let mut patches = Vec::new();
+    let mut creates = Vec::new();
+
+    for (external_id, resources) in &by_external_id {
+        match resources.as_slice() {
+            [_] if wanted.contains(*external_id) => {}
+            [resource] => {
+                if options.delete {
+                    deletes.push(SyncChange::Delete {
+                        external_id: external_id.to_string(),
+                        id: resource_id(&resource.scim_entry().id)?,
+                    });
+                }
+            }
+            resources => {
+                for resource in resources {
+                    plan.conflicts.push(SyncConflict {
+                        external_id: external_id.to_string(),
+                        id: resource_id(&resource.scim_entry().id)?,
+                        kind: ConflictKind::ExternalId,
+                    });
+                }
+            }
+        }
+    }
+
+    for resource in desired {
+        let external_id = resource
+            .scim_entry()
+            .external_id
+            .clone()
+            .unwrap_or_default();
+        let existing = match by_external_id.get(external_id.as_str()).map(Vec::as_slice) {
+            Some([existing]) => *existing,
+            Some(_) => continue,
+            None => match by_name.remove(&resource.name().to_lowercase()) {
+                Some(existing) if options.conflicts == ConflictPolicy::Overwrite => existing,
+                Some(existing) => {
+                    plan.conflicts.push(SyncConflict {
+                        external_id,
+                        id: resource_id(&existing.scim_entry().id)?,
+                        kind: ConflictKind::Name,
+                    });
+                    continue;
+                }
+                None => {
+                    creates.push(SyncChange::Create {
+                        external_id,
+                        resource,
+                    });
+                    continue;
+                }
+            },
+        };
+
+        let id = resource_id(&existing.scim_entry().id)?;
+        plan.matched.insert(external_id.clone(), id.clone());
+        let changes = diff(existing, &resource)?;
+        if !changes.is_empty() {
+            patches.push(SyncChange::Patch {
+                external_id,
+                id,
+                version: entry_version(existing.scim_entry()).map(str::to_string),
+                changes,
+            });
+        }
+    }
+
+    plan.changes = deletes.into_iter().chain(patches).chain(creates).collect();
+    Ok(plan)
+}
+
+// The changes that make `remote` as `desired`, leaving out those to
+// attributes clients can't compare: readOnly attributes, such as a user's
+// groups, are the service provider's, and writeOnly ones, such as
+// password, are never returned. Multi-valued attributes are compared
+// without regard to the order of their values, which service providers
+// don't keep.
+fn diff<T: Synced>(remote: &T, desired: &T) -> Result<Vec<AttrChange>, ScimError> {
+    let schema = T::schema();
+    let remote = remote.clone().into_entry()?;
+    let desired = desired.clone().into_entry()?;
+    Ok(remote
+        .diff(&desired)
+        .into_iter()
+        .filter(|change| {
+            let name = change.path().split('.').next().unwrap_or_default();
+            let comparable = schema.attribute(name).map_or(true, |def| {
+                !matches!(def.mutability, Mutability::ReadOnly | Mutability::WriteOnly)
+            });
+            let reordered =
+                matches!(change, AttrChange::Modified { old, new, .. } if same_values(old, new));
+            comparable && !reordered
+        })
+        .collect())
+}
+
+fn same_values(old: &ScimValue, new: &ScimValue) -> bool {
+    fn same<V: PartialEq>(old: &[V], new: &[V]) -> bool {
+        old.len() == new.len()
+            && old.iter().all(|value| new.contains(value))
+            && new.iter().all(|value| old.contains(value))
+    }
+    match (old, new) {
+        (ScimValue::MultiSimple(old), ScimValue::MultiSimple(new)) => same(old, new),
+        (ScimValue::MultiComplex(old), ScimValue::MultiComplex(new)) => same(old, new),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn user(user_name: &str, id: Option<Uuid>, external_id: Option<&str>) -> User {
+        let mut builder = User::builder(user_name).active(true);
+        if let Some(id) = id {
+            builder = builder.id(id);
+        }
+        if let Some(external_id) = external_id {
+            builder = builder.external_id(external_id);
+        }
+        builder.build().expect("Failed to build user")
+    }
+
+    #[test]
+    fn sync_plan() {
+        let [babs, jim, old, taken, twice, again] = [1, 2, 3, 4, 5, 6].map(Uuid::from_u128);
+        let remote = vec![
+            user("bjensen", Some(babs), Some("1")),
+            user("jim", Some(jim), Some("2")),
+            user("old", Some(old), Some("3")),
+            user("taken", Some(taken), None),
+            user("twice", Some(twice), Some("5")),
+            user("twice", Some(again), Some("5")),
+        ];
+        let mut desired_babs = user("bjensen", None, Some("1"));
+        desired_babs.display_name = Some("Babs".to_string());
+        let desired = vec![
+            desired_babs,
+            user("jim", None, Some("2")),
+            user("Taken", None, Some("4")),
+            user("twice", None, Some("5")),
+            user("new", None, Some("6")),
+        ];
+
+        let planned = plan(desired.clone(), &remote, &SyncOptions::new()).expect("Failed to plan");
+        let changes: Vec<_> = planned
+            .changes
+            .iter()
+            .map(|change| match change {
+                SyncChange::Create { external_id, .. } => format!("create {}", external_id),
+                SyncChange::Patch {
+                    external_id,
+                    changes,
+                    ..
+                } => format!(
+                    "patch {} {:?}",
+                    external_id,
+                    changes.iter().map(AttrChange::path).collect::<Vec<_>>()
+                ),
+                SyncChange::Delete { external_id, .. } => format!("delete {}", external_id),
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            vec!["delete 3", "patch 1 [\"displayName\"]", "create 6"]
+        );
+        assert_eq!(planned.matched.get("2"), Some(&jim.to_string()));
+        assert_eq!(
+            planned
+                .conflicts
+                .iter()
+                .map(|conflict| (conflict.external_id.as_str(), conflict.kind))
+                .collect::<Vec<_>>(),
+            vec![
+                ("5", ConflictKind::ExternalId),
+                ("5", ConflictKind::ExternalId),
+                ("4", ConflictKind::Name),
+            ]
+        );
+
+        // Overwriting adopts the user with the name, giving it the
+        // externalId.
+        let options = SyncOptions::new()
+            .conflicts(ConflictPolicy::Overwrite)
+            .delete(false);
+        let planned = plan(desired, &remote, &options).expect("Failed to plan");
+        assert!(!planned
+            .changes
+            .iter()
+            .any(|change| matches!(change, SyncChange::Delete { .. })));
+        assert!(planned.changes.iter().any(|change| matches!(
+            change,
+            SyncChange::Patch { id, changes, .. }
+                if *id == taken.to_string()
+                    && changes.iter().any(|c| c.path() == "externalId")
+        )));
+
+        assert!(matches!(
+            plan_users(vec![user("a", None, None)]),
+            Err(ScimError::MissingRequiredAttribute(name)) if name == "externalId"
+        ));
+        assert!(matches!(
+            plan_users(vec![user("a", None, Some("1")), user("b", None, Some("1"))]),
+            Err(ScimError::Uniqueness)
+        ));
+    }
+
+    fn plan_users(desired: Vec<User>) -> Result<SyncPlan<User>, ScimError> {
+        plan(desired, &[], &SyncOptions::new())
+    }
+}
@@ -0,0 +1,321 @@
+//! Generate typed resources from a SCIM schema document, for use from a
+//! build script:
+//!
+//! ```ignore
+//! // build.rs
+//! let schema = ScimSchema::parse(include_str!("MySchema.json")).unwrap();
+//! let code = scim_proto::codegen::generate(&schema, "MyResource").unwrap();
+//! std::fs::write(Path::new(&env::var("OUT_DIR")?).join("my_resource.rs"), code)?;
+//!
+//! // lib.rs
+//! include!(concat!(env!("OUT_DIR"), "/my_resource.rs"));
+//! ```
+//!
+//! The generated struct flattens a [`crate::ScimEntry`] header beside one
+//! field per attribute, and converts to and from
+//! [`crate::ScimEntryGeneric`]. The generated code only refers to this crate,
+//! so no other dependencies are needed.
+
+use crate::schema::{AttributeType, SchemaError, ScimAttributeDefinition, ScimSchema};
+use std::fmt::Write;
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "do", "dyn", "else",
+    "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop", "match",
+    "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "self", "static", "struct",
+    "super", "trait", "true", "try", "type", "typeof", "unsafe", "use", "virtual", "where",
+    "while", "yield",
+];
+
+const PRIVATE: &str = "::scim_proto::__private";
+
+/// Generate a struct named `name` for resources of `schema`, with a struct
+/// for each complex attribute named `name` followed by the attribute name.
+pub fn generate(schema: &ScimSchema, name: &str) -> Result<String, SchemaError> {
+    schema.check_definition()?;
+    if !is_type_name(name) {
+        return Err(SchemaError::InvalidName(name.to_string()));
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "// Generated by scim_proto::codegen from {}. Do not edit.\n",
+        schema.id
+    );
+
+    let mut fields = String::new();
+    let _ = writeln!(fields, "    #[serde(flatten)]");
+    let _ = writeln!(fields, "    pub entry: ::scim_proto::ScimEntry,");
+
+    // The common attributes are held by the entry.
+    let attributes = schema.attributes.iter().filter(|def| {
+        !["id", "externalId", "meta", "schemas"]
+            .iter()
+            .any(|common| def.name.eq_ignore_ascii_case(common))
+    });
+
+    for def in attributes {
+        let type_name = if def.type_ == AttributeType::Complex {
+            let type_name = format!("{}{}", name, to_pascal(&def.name));
+            write_struct(&mut out, &type_name, def);
+            Some(type_name)
+        } else {
+            None
+        };
+        write_field(&mut fields, def, type_name.as_deref());
+    }
+
+    write_doc(&mut out, "", schema.description.as_deref());
+    write_derive(&mut out);
+    let _ = writeln!(out, "pub struct {} {{\n{}}}\n", name, fields);
+
+    let _ = writeln!(
+        out,
+        r#"impl ::std::convert::TryFrom<::scim_proto::ScimEntryGeneric> for {name} {{
+    type Error = ::scim_proto::error::ScimError;
+
+    fn try_from(entry: ::scim_proto::ScimEntryGeneric) -> Result<Self, Self::Error> {{
+        {PRIVATE}::convert(&entry)
+    }}
+}}
+
+impl ::std::convert::TryFrom<{name}> for ::scim_proto::ScimEntryGeneric {{
+    type Error = ::scim_proto::error::ScimError;
+
+    fn try_from(resource: {name}) -> Result<Self, Self::Error> {{
+        {PRIVATE}::convert(&resource)
+    }}
+}}
+
+impl ::scim_proto::resource::ScimResource for {name} {{
+    const SCHEMA_URN: &'static str = "{urn}";
+
+    fn resource_type() -> &'static str {{
+        "{name}"
+    }}
+
+    fn into_entry(self) -> Result<::scim_proto::ScimEntryGeneric, ::scim_proto::error::ScimError> {{
+        ::scim_proto::ScimEntryGeneric::try_from(self)
+    }}
+}}"#,
+        urn = schema.id.escape_default(),
+    );
+
+    Ok(out)
+}
+
+fn write_struct(out: &mut String, name: &str, def: &ScimAttributeDefinition) {
+    write_doc(out, "", def.description.as_deref());
+    write_derive(out);
+    let _ = writeln!(out, "pub struct {} {{", name);
+    for sub in def.sub_attributes.iter() {
+        write_field(out, sub, None);
+    }
+    let _ = writeln!(out, "}}\n");
+}
+
+fn write_derive(out: &mut String) {
+    let _ = writeln!(
+        out,
+        "#[derive({PRIVATE}::serde::Serialize, {PRIVATE}::serde::Deserialize, Debug, Clone, PartialEq)]"
+    );
+    let _ = writeln!(
+        out,
+        "#[serde(crate = \"{PRIVATE}::serde\", rename_all = \"camelCase\")]"
+    );
+}
+
+fn write_doc(out: &mut String, indent: &str, doc: Option<&str>) {
+    for line in doc.into_iter().flat_map(str::lines) {
+        let _ = writeln!(out, "{}/// {}", indent, line.trim());
+    }
+}
+
+fn write_field(out: &mut String, def: &ScimAttributeDefinition, complex: Option<&str>) {
+    let field = to_snake(&def.name);
+    let base = match (def.type_, complex) {
+        (AttributeType::Complex, Some(type_name)) => type_name.to_string(),
+        (AttributeType::Complex, None) => format!("{PRIVATE}::serde_json::Value"),
+        (AttributeType::String | AttributeType::Binary, _) => "String".to_string(),
+        (AttributeType::Boolean, _) => "bool".to_string(),
+        (AttributeType::Decimal, _) => "f64".to_string(),
+        (AttributeType::Integer, _) => "i64".to_string(),
+        (AttributeType::DateTime, _) if !def.multi_valued => {
+            format!("{PRIVATE}::time::OffsetDateTime")
+        }
+        // serde has no RFC3339 helper for sequences of timestamps.
+        (AttributeType::DateTime, _) => "String".to_string(),
+        (AttributeType::Reference, _) => format!("{PRIVATE}::url::Url"),
+    };
+    let datetime = def.type_ == AttributeType::DateTime && !def.multi_valued;
+
+    write_doc(out, "    ", def.description.as_deref());
+
+    let mut attrs = Vec::new();
+    if to_camel(&field) != def.name {
+        attrs.push(format!("rename = \"{}\"", def.name));
+    }
+    // Some providers write `ref` or `reference`.
+    if def.name == "$ref" {
+        attrs.push("alias = \"ref\", alias = \"reference\"".to_string());
+    }
+    let ty = if def.multi_valued {
+        attrs.push("default, skip_serializing_if = \"Vec::is_empty\"".to_string());
+        format!("Vec<{}>", base)
+    } else if def.required {
+        if datetime {
+            attrs.push(format!("with = \"{PRIVATE}::datetime::lenient\""));
+        }
+        base
+    } else {
+        attrs.push("default, skip_serializing_if = \"Option::is_none\"".to_string());
+        if datetime {
+            attrs.push(format!("with = \"{PRIVATE}::datetime::lenient::option\""));
+        }
+        format!("Option<{}>", base)
+    };
+
+    if !attrs.is_empty() {
+        let _ = writeln!(out, "    #[serde({})]", attrs.join(", "));
+    }
+    let _ = writeln!(out, "    pub {}: {},", field, ty);
+}
+
+fn is_type_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|c| c.is_ascii_uppercase())
+        .unwrap_or(false)
+        && chars.all(|c| c.is_ascii_alphanumeric())
+}
+
+/// `x509Certificates` to `x509_certificates`, and `$ref` to `ref_`.
+fn to_snake(name: &str) -> String {
+    let mut out = String::new();
+    for c in name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+    {
+        if c.is_ascii_uppercase() {
+            if !out.is_empty() {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+
+    if KEYWORDS.contains(&out.as_str()) {
+        out.push('_');
+    }
+    out
+}
+
+/// The name serde's `rename_all = "camelCase"` gives a field.
+fn to_camel(field: &str) -> String {
+    let mut out = String::new();
+    let mut upper = false;
+    for c in field.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+pub(crate) fn to_pascal(name: &str) -> String {
+    let camel = to_camel(&to_snake(name));
+    let mut chars = camel.chars();
+    chars
+        .next()
+        .map(|c| c.to_ascii_uppercase().to_string() + chars.as_str())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::{group_schema, user_schema};
+    use crate::constants::RFC7643_USER;
+    use crate::ScimEntryGeneric;
+
+    // The code generated for the core User schema, checked in so that it is
+    // compiled with the tests.
+    mod generated {
+        include!("../testdata/codegen_user.rs");
+    }
+
+    #[test]
+    fn codegen_names() {
+        assert_eq!(to_snake("x509Certificates"), "x509_certificates");
+        assert_eq!(to_camel("x509_certificates"), "x509Certificates");
+        assert_eq!(to_snake("$ref"), "ref_");
+        assert_eq!(to_snake("type"), "type_");
+        assert_eq!(to_snake("userName"), "user_name");
+        assert_eq!(to_pascal("phoneNumbers"), "PhoneNumbers");
+    }
+
+    #[test]
+    fn codegen_group() {
+        let code = generate(&group_schema(), "Group").expect("Failed to generate");
+
+        assert!(code.contains("pub struct Group {"));
+        assert!(code.contains("pub struct GroupMembers {"));
+        assert!(code.contains("    pub members: Vec<GroupMembers>,"));
+        assert!(code.contains("    #[serde(rename = \"$ref\", alias = \"ref\", alias = \"reference\", default, skip_serializing_if = \"Option::is_none\")]\n    pub ref_: Option<::scim_proto::__private::url::Url>,"));
+        assert!(code
+            .contains("impl ::std::convert::TryFrom<::scim_proto::ScimEntryGeneric> for Group {"));
+
+        assert!(code.contains(
+            "const SCHEMA_URN: &'static str = \"urn:ietf:params:scim:schemas:core:2.0:Group\";"
+        ));
+
+        let code = generate(&user_schema(), "User").expect("Failed to generate");
+        assert!(code.contains("    pub user_name: String,"));
+        assert!(code.contains("    pub x509_certificates: Vec<UserX509Certificates>,"));
+
+        assert_eq!(
+            generate(&group_schema(), "group"),
+            Err(SchemaError::InvalidName("group".to_string()))
+        );
+    }
+
+    #[test]
+    fn codegen_user_roundtrip() {
+        assert_eq!(
+            generate(&user_schema(), "User").expect("Failed to generate"),
+            include_str!("../testdata/codegen_user.rs"),
+            "testdata/codegen_user.rs is out of date"
+        );
+
+        let entry: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let user = generated::User::try_from(entry.clone()).expect("Failed to convert entry");
+
+        assert_eq!(user.user_name, "bjensen@example.com");
+        assert_eq!(user.entry.external_id.as_deref(), Some("701984"));
+        assert_eq!(
+            user.name.as_ref().and_then(|n| n.given_name.as_deref()),
+            Some("Barbara")
+        );
+        assert_eq!(user.emails.len(), 2);
+        assert_eq!(user.emails[0].type_.as_deref(), Some("work"));
+        assert_eq!(user.emails[0].primary, Some(true));
+        assert_eq!(
+            user.groups[0].ref_.as_ref().map(|u| u.as_str()),
+            Some("https://example.com/v2/Groups/e9e30dba-f08f-4109-8486-d5c6a331660a")
+        );
+        assert_eq!(user.x509_certificates.len(), 1);
+
+        let back = ScimEntryGeneric::try_from(user).expect("Failed to convert user");
+        assert_eq!(back, entry);
+    }
+}
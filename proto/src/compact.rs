@@ -0,0 +1,245 @@
+//! The forms of entries and their values in formats that aren't human
+//! readable, such as CBOR, MessagePack and bincode, for compact transport
+//! between services.
+//!
+//! In JSON a value's variant is told by its shape, which needs a format
+//! that describes itself, and only a schema can tell a date time, binary
+//! value or reference from a string. In these formats each value is written
+//! as an enum instead, tagged by its variant, so it is read back exactly as
+//! it was written, and an entry is written as a struct of its common
+//! attributes and the map of the others.
+
+use crate::attr_map::AttrMap;
+use crate::{ScimAttr, ScimComplexAttr, ScimEntryGeneric, ScimId, ScimMeta, ScimValue};
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use time::OffsetDateTime;
+use url::Url;
+
+// Each is written as borrowed parts and read as owned ones, so that both are
+// derived from the one definition.
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "ScimAttr")]
+enum Attr<S, T, B, U> {
+    Null,
+    Bool(bool),
+    Decimal(f64),
+    Integer(i64),
+    String(S),
+    DateTime(T),
+    Binary(B),
+    Reference(U),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "ScimValue")]
+enum Value<A, C, MA, MC, E> {
+    Simple(A),
+    Complex(C),
+    MultiSimple(MA),
+    MultiComplex(MC),
+    Extension(E),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "ScimEntryGeneric")]
+struct Entry<S, I, X, M, A> {
+    schemas: S,
+    id: I,
+    external_id: X,
+    meta: M,
+    attrs: A,
+}
+
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+struct ByteBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for ByteBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> Visitor<'de> for BytesVisitor {
+            type Value = ByteBuf;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("bytes")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<ByteBuf, E> {
+                Ok(ByteBuf(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<ByteBuf, E> {
+                Ok(ByteBuf(v))
+            }
+
+            // Formats without bytes write them as a sequence.
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ByteBuf, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(b) = seq.next_element()? {
+                    bytes.push(b);
+                }
+                Ok(ByteBuf(bytes))
+            }
+        }
+
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+pub(crate) fn serialize_attr<S: Serializer>(
+    attr: &ScimAttr,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let attr: Attr<&str, &OffsetDateTime, Bytes<'_>, &Url> = match attr {
+        ScimAttr::Null => Attr::Null,
+        ScimAttr::Bool(b) => Attr::Bool(*b),
+        ScimAttr::Decimal(d) => Attr::Decimal(*d),
+        ScimAttr::Integer(i) => Attr::Integer(*i),
+        ScimAttr::String(s) => Attr::String(s),
+        ScimAttr::DateTime(dt) => Attr::DateTime(dt),
+        ScimAttr::Binary(b) => Attr::Binary(Bytes(b)),
+        ScimAttr::Reference(url) => Attr::Reference(url),
+    };
+    attr.serialize(serializer)
+}
+
+pub(crate) fn deserialize_attr<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<ScimAttr, D::Error> {
+    Ok(
+        match Attr::<String, OffsetDateTime, ByteBuf, Url>::deserialize(deserializer)? {
+            Attr::Null => ScimAttr::Null,
+            Attr::Bool(b) => ScimAttr::Bool(b),
+            Attr::Decimal(d) => ScimAttr::Decimal(d),
+            Attr::Integer(i) => ScimAttr::Integer(i),
+            Attr::String(s) => ScimAttr::String(s),
+            Attr::DateTime(dt) => ScimAttr::DateTime(dt),
+            Attr::Binary(b) => ScimAttr::Binary(b.0),
+            Attr::Reference(url) => ScimAttr::Reference(url),
+        },
+    )
+}
+
+type ValueRef<'a> = Value<
+    &'a ScimAttr,
+    &'a ScimComplexAttr,
+    &'a Vec<ScimAttr>,
+    &'a Vec<ScimComplexAttr>,
+    &'a AttrMap<ScimValue>,
+>;
+
+pub(crate) fn serialize_value<S: Serializer>(
+    value: &ScimValue,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let value: ValueRef<'_> = match value {
+        ScimValue::Simple(attr) => Value::Simple(attr),
+        ScimValue::Complex(attrs) => Value::Complex(attrs),
+        ScimValue::MultiSimple(values) => Value::MultiSimple(values),
+        ScimValue::MultiComplex(values) => Value::MultiComplex(values),
+        ScimValue::Extension(attrs) => Value::Extension(attrs),
+    };
+    value.serialize(serializer)
+}
+
+type ValueOwned =
+    Value<ScimAttr, ScimComplexAttr, Vec<ScimAttr>, Vec<ScimComplexAttr>, AttrMap<ScimValue>>;
+
+pub(crate) fn deserialize_value<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<ScimValue, D::Error> {
+    Ok(match ValueOwned::deserialize(deserializer)? {
+        Value::Simple(attr) => ScimValue::Simple(attr),
+        Value::Complex(attrs) => ScimValue::Complex(attrs),
+        Value::MultiSimple(values) => ScimValue::MultiSimple(values),
+        Value::MultiComplex(values) => ScimValue::MultiComplex(values),
+        Value::Extension(attrs) => ScimValue::Extension(attrs),
+    })
+}
+
+pub(crate) fn serialize_entry<S: Serializer>(
+    entry: &ScimEntryGeneric,
+    schemas: impl Serialize,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    Entry {
+        schemas,
+        id: &entry.id,
+        external_id: &entry.external_id,
+        meta: &entry.meta,
+        attrs: &entry.attrs,
+    }
+    .serialize(serializer)
+}
+
+pub(crate) fn deserialize_entry<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<ScimEntryGeneric, D::Error> {
+    let entry = Entry::<
+        Vec<String>,
+        ScimId,
+        Option<String>,
+        Option<ScimMeta>,
+        AttrMap<ScimValue>,
+    >::deserialize(deserializer)?;
+    Ok(ScimEntryGeneric {
+        schemas: entry.schemas,
+        id: entry.id,
+        external_id: entry.external_id,
+        meta: entry.meta,
+        attrs: entry.attrs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::RFC7643_USER;
+    use crate::{ScimAttr, ScimEntryGeneric, ScimValue};
+
+    #[test]
+    fn compact_round_trip() {
+        let mut u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        u.promote();
+        u.insert_attr("loginCount", ScimValue::Simple(ScimAttr::Integer(7)));
+        u.insert_attr(
+            "thumbprint",
+            ScimValue::Simple(ScimAttr::Binary(vec![0, 1, 254, 255])),
+        );
+        let enterprise = "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User";
+        u.set_path(
+            &format!("{}:manager.value", enterprise),
+            ScimValue::Simple(ScimAttr::String("26118915".to_string())),
+        )
+        .expect("Failed to set path");
+        u.schemas.push(enterprise.to_string());
+
+        // Every value is read back as the variant it was written as, where
+        // JSON would give strings and decimals.
+        let bytes = bincode::serialize(&u).expect("Failed to serialise");
+        let back: ScimEntryGeneric = bincode::deserialize(&bytes).expect("Failed to deserialise");
+        assert_eq!(back, u);
+        assert_eq!(
+            back.attr("loginCount"),
+            Some(&ScimValue::Simple(ScimAttr::Integer(7)))
+        );
+        assert!(matches!(
+            back.attr("profileUrl"),
+            Some(ScimValue::Simple(ScimAttr::Reference(_)))
+        ));
+
+        let value = ScimValue::MultiSimple(vec![ScimAttr::Null, ScimAttr::Bool(true)]);
+        let bytes = bincode::serialize(&value).expect("Failed to serialise");
+        assert_eq!(bincode::deserialize::<ScimValue>(&bytes).ok(), Some(value));
+    }
+}
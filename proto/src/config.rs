@@ -0,0 +1,130 @@
+use crate::bulk::BulkLimits;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// https://datatracker.ietf.org/doc/html/rfc7643#section-5
+
+/// The features a service provider supports. Service providers often leave
+/// out what they don't support, so everything missing is read as
+/// unsupported.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceProviderConfig {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub documentation_uri: Option<String>,
+    #[serde(default)]
+    pub patch: Supported,
+    #[serde(default)]
+    pub bulk: BulkSupport,
+    #[serde(default)]
+    pub filter: FilterSupport,
+    #[serde(default)]
+    pub change_password: Supported,
+    #[serde(default)]
+    pub sort: Supported,
+    #[serde(default)]
+    pub etag: Supported,
+    #[serde(default)]
+    pub authentication_schemes: Vec<AuthenticationScheme>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Supported {
+    #[serde(default)]
+    pub supported: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkSupport {
+    #[serde(default)]
+    pub supported: bool,
+    #[serde(default)]
+    pub max_operations: u64,
+    #[serde(default)]
+    pub max_payload_size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterSupport {
+    #[serde(default)]
+    pub supported: bool,
+    #[serde(default)]
+    pub max_results: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticationScheme {
+    /// Such as `oauthbearertoken` or `httpbasic`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spec_uri: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub documentation_uri: Option<String>,
+    #[serde(default)]
+    pub primary: bool,
+}
+
+impl ServiceProviderConfig {
+    /// The limits of bulk requests, if they are supported.
+    pub fn bulk_limits(&self) -> Option<BulkLimits> {
+        if !self.bulk.supported || self.bulk.max_operations == 0 {
+            return None;
+        }
+        Some(BulkLimits {
+            max_operations: usize::try_from(self.bulk.max_operations).unwrap_or(usize::MAX),
+            max_payload_size: usize::try_from(self.bulk.max_payload_size).unwrap_or(usize::MAX),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_provider_config() {
+        let config: ServiceProviderConfig = serde_json::from_str(
+            r#"{
+                "schemas": ["urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig"],
+                "patch": {"supported": true},
+                "bulk": {"supported": true, "maxOperations": 1000, "maxPayloadSize": 1048576},
+                "filter": {"supported": true, "maxResults": 200},
+                "authenticationSchemes": [{
+                    "type": "oauthbearertoken",
+                    "name": "OAuth Bearer Token",
+                    "description": "Authentication scheme using the OAuth Bearer Token Standard"
+                }]
+            }"#,
+        )
+        .expect("Failed to parse config");
+        assert!(config.patch.supported);
+        assert!(!config.etag.supported);
+        assert_eq!(config.filter.max_results, 200);
+        assert_eq!(config.authentication_schemes[0].kind, "oauthbearertoken");
+        assert_eq!(
+            config.bulk_limits(),
+            Some(BulkLimits {
+                max_operations: 1000,
+                max_payload_size: 1048576
+            })
+        );
+
+        let config: ServiceProviderConfig =
+            serde_json::from_str(r#"{"bulk": {"supported": false}}"#)
+                .expect("Failed to parse config");
+        assert!(!config.patch.supported);
+        assert_eq!(config.bulk_limits(), None);
+    }
+}
@@ -1,4 +1,7 @@
 pub const SCIM_CONTENT_TYPE: &str = "application/scim+json";
+// https://datatracker.ietf.org/doc/html/rfc7644#section-3.1 - providers should
+// also accept application/json from clients.
+pub const JSON_CONTENT_TYPE: &str = "application/json";
 
 pub const SCIM_SCHEMA_PREIX: &str = "urn:ietf:params:scim:api:";
 
@@ -8,6 +11,18 @@ pub const SCIM_SCHEMA_PREIX: &str = "urn:ietf:params:scim:api:";
 
 pub const SCIM_SCHEMA_USER: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
 pub const SCIM_SCHEMA_GROUP: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+pub const SCIM_SCHEMA_ENTERPRISE_USER: &str =
+    "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User";
+pub const SCIM_SCHEMA_SERVICE_PROVIDER_CONFIG: &str =
+    "urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig";
+
+// https://datatracker.ietf.org/doc/html/rfc7644#section-3.1
+pub const SCIM_MESSAGE_LIST_RESPONSE: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+pub const SCIM_MESSAGE_ERROR: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+pub const SCIM_MESSAGE_BULK_REQUEST: &str = "urn:ietf:params:scim:api:messages:2.0:BulkRequest";
+pub const SCIM_MESSAGE_BULK_RESPONSE: &str = "urn:ietf:params:scim:api:messages:2.0:BulkResponse";
+pub const SCIM_MESSAGE_PATCH_OP: &str = "urn:ietf:params:scim:api:messages:2.0:PatchOp";
+pub const SCIM_MESSAGE_SEARCH_REQUEST: &str = "urn:ietf:params:scim:api:messages:2.0:SearchRequest";
 
 #[cfg(test)]
 pub(crate) const RFC7643_USER: &str = r#"
@@ -155,3 +170,83 @@ pub(crate) const RFC7643_GROUP: &str = r#"
   }
 }
 "#;
+
+// https://datatracker.ietf.org/doc/html/rfc7643#section-8.7.1
+#[cfg(test)]
+pub(crate) const RFC7643_SCHEMA_GROUP: &str = r#"
+{
+  "id" : "urn:ietf:params:scim:schemas:core:2.0:Group",
+  "name" : "Group",
+  "description" : "Group",
+  "attributes" : [
+    {
+      "name" : "displayName",
+      "type" : "string",
+      "multiValued" : false,
+      "description" : "A human-readable name for the Group. REQUIRED.",
+      "required" : false,
+      "caseExact" : false,
+      "mutability" : "readWrite",
+      "returned" : "default",
+      "uniqueness" : "none"
+    },
+    {
+      "name" : "members",
+      "type" : "complex",
+      "multiValued" : true,
+      "description" : "A list of members of the Group.",
+      "required" : false,
+      "subAttributes" : [
+        {
+          "name" : "value",
+          "type" : "string",
+          "multiValued" : false,
+          "description" : "Identifier of the member of this Group.",
+          "required" : false,
+          "caseExact" : false,
+          "mutability" : "immutable",
+          "returned" : "default",
+          "uniqueness" : "none"
+        },
+        {
+          "name" : "$ref",
+          "type" : "reference",
+          "referenceTypes" : [
+            "User",
+            "Group"
+          ],
+          "multiValued" : false,
+          "description" : "The URI corresponding to a SCIM resource that is a member of this Group.",
+          "required" : false,
+          "caseExact" : false,
+          "mutability" : "immutable",
+          "returned" : "default",
+          "uniqueness" : "none"
+        },
+        {
+          "name" : "type",
+          "type" : "string",
+          "multiValued" : false,
+          "description" : "A label indicating the type of resource, e.g., 'User' or 'Group'.",
+          "required" : false,
+          "caseExact" : false,
+          "canonicalValues" : [
+            "User",
+            "Group"
+          ],
+          "mutability" : "immutable",
+          "returned" : "default",
+          "uniqueness" : "none"
+        }
+      ],
+      "mutability" : "readWrite",
+      "returned" : "default"
+    }
+  ],
+  "meta" : {
+    "resourceType" : "Schema",
+    "location" :
+      "/v2/Schemas/urn:ietf:params:scim:schemas:core:2.0:Group"
+  }
+}
+"#;
@@ -0,0 +1,148 @@
+use crate::constants::{JSON_CONTENT_TYPE, SCIM_CONTENT_TYPE};
+use crate::error::ScimError;
+
+// https://datatracker.ietf.org/doc/html/rfc7644#section-3.8
+
+/// The media types a SCIM client or server can exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScimMediaType {
+    Scim,
+    Json,
+}
+
+impl ScimMediaType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScimMediaType::Scim => SCIM_CONTENT_TYPE,
+            ScimMediaType::Json => JSON_CONTENT_TYPE,
+        }
+    }
+}
+
+/// Split a media type from its parameters, returning the lowercased
+/// `type/subtype` and the parameters as `(name, value)` pairs.
+fn split_media_type(value: &str) -> (String, Vec<(String, String)>) {
+    let mut parts = value.split(';');
+    let essence = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+    let params = parts
+        .filter_map(|p| {
+            let (name, value) = p.split_once('=')?;
+            Some((
+                name.trim().to_ascii_lowercase(),
+                value.trim().trim_matches('"').to_string(),
+            ))
+        })
+        .collect();
+    (essence, params)
+}
+
+/// Check the `Content-Type` of a request or response body. Both
+/// `application/scim+json` and `application/json` are accepted, and any
+/// charset must be UTF-8.
+pub fn check_content_type(value: &str) -> Result<ScimMediaType, ScimError> {
+    let (essence, params) = split_media_type(value);
+
+    let charset_ok = params
+        .iter()
+        .filter(|(name, _)| name == "charset")
+        .all(|(_, v)| v.eq_ignore_ascii_case("utf-8") || v.eq_ignore_ascii_case("utf8"));
+
+    match essence.as_str() {
+        SCIM_CONTENT_TYPE if charset_ok => Ok(ScimMediaType::Scim),
+        JSON_CONTENT_TYPE if charset_ok => Ok(ScimMediaType::Json),
+        _ => Err(ScimError::UnsupportedMediaType),
+    }
+}
+
+/// Choose the media type to respond with from an `Accept` header, preferring
+/// `application/scim+json`. A missing header accepts anything.
+pub fn negotiate_accept(accept: Option<&str>) -> Result<ScimMediaType, ScimError> {
+    let Some(accept) = accept else {
+        return Ok(ScimMediaType::Scim);
+    };
+
+    let mut best: Option<(f32, ScimMediaType)> = None;
+
+    for range in accept.split(',') {
+        let (essence, params) = split_media_type(range);
+        let quality = params
+            .iter()
+            .find(|(name, _)| name == "q")
+            .and_then(|(_, q)| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        if quality <= 0.0 {
+            continue;
+        }
+
+        let media_type = match essence.as_str() {
+            SCIM_CONTENT_TYPE | "*/*" | "application/*" => ScimMediaType::Scim,
+            JSON_CONTENT_TYPE => ScimMediaType::Json,
+            _ => continue,
+        };
+
+        let better = match best {
+            None => true,
+            Some((q, current)) => {
+                quality > q
+                    || (quality == q
+                        && media_type == ScimMediaType::Scim
+                        && current == ScimMediaType::Json)
+            }
+        };
+        if better {
+            best = Some((quality, media_type));
+        }
+    }
+
+    best.map(|(_, media_type)| media_type)
+        .ok_or(ScimError::NotAcceptable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_type_check() {
+        assert_eq!(
+            check_content_type("application/scim+json"),
+            Ok(ScimMediaType::Scim)
+        );
+        assert_eq!(
+            check_content_type("Application/JSON; charset=UTF-8"),
+            Ok(ScimMediaType::Json)
+        );
+        assert_eq!(
+            check_content_type("application/json; charset=latin1"),
+            Err(ScimError::UnsupportedMediaType)
+        );
+        assert_eq!(
+            check_content_type("text/plain"),
+            Err(ScimError::UnsupportedMediaType)
+        );
+        assert_eq!(ScimError::UnsupportedMediaType.status(), 415);
+    }
+
+    #[test]
+    fn content_type_negotiate() {
+        assert_eq!(negotiate_accept(None), Ok(ScimMediaType::Scim));
+        assert_eq!(negotiate_accept(Some("*/*")), Ok(ScimMediaType::Scim));
+        assert_eq!(
+            negotiate_accept(Some("application/json")),
+            Ok(ScimMediaType::Json)
+        );
+        assert_eq!(
+            negotiate_accept(Some("application/json, application/scim+json")),
+            Ok(ScimMediaType::Scim)
+        );
+        assert_eq!(
+            negotiate_accept(Some("application/scim+json;q=0.5, application/json")),
+            Ok(ScimMediaType::Json)
+        );
+        assert_eq!(
+            negotiate_accept(Some("text/html, application/scim+json;q=0")),
+            Err(ScimError::NotAcceptable)
+        );
+    }
+}
@@ -0,0 +1,338 @@
+//! Converting resources to and from JSON values. The errors serde_json
+//! gives for a `Value` don't say where in it they are, so values are read
+//! through a deserializer that keeps the path of each value it reads, and a
+//! value that doesn't convert is a [`ScimError::InvalidValue`] at the path
+//! of the innermost value that failed, such as `emails[1].value`.
+//!
+//! Fields flattened into a struct are buffered by serde before they are
+//! read, so a failure in one of them is at the path of the struct.
+
+use crate::error::{ErrorSource, ScimError};
+use crate::schema::SchemaViolation;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Deserializer, MapAccess, SeqAccess};
+use serde::de::{IntoDeserializer, Visitor};
+use serde::Serialize;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::fmt;
+
+/// Parse a JSON document as a `T`. A document that isn't JSON is a
+/// [`ScimError::InvalidJson`].
+pub(crate) fn parse<T: DeserializeOwned>(json: &str) -> Result<T, ScimError> {
+    let value =
+        serde_json::from_str(json).map_err(|e| ScimError::InvalidJson(ErrorSource::new(e)))?;
+    from_value(value)
+}
+
+pub(crate) fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, ScimError> {
+    from_value_at(value, "")
+}
+
+/// Read `value`, which is at `path`, as a `T`.
+pub(crate) fn from_value_at<T: DeserializeOwned>(value: Value, path: &str) -> Result<T, ScimError> {
+    let failed = RefCell::new(None);
+    let result = T::deserialize(Tracked {
+        value,
+        path: Path::Root(path),
+        failed: &failed,
+    });
+    result.map_err(|err| {
+        let path = failed.into_inner().unwrap_or_else(|| path.to_string());
+        SchemaViolation::unreadable(path, err).into()
+    })
+}
+
+pub(crate) fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, ScimError> {
+    serde_json::to_value(value).map_err(|err| SchemaViolation::unreadable("", err).into())
+}
+
+/// Convert between two types with the same JSON form, such as a resource
+/// and a [`crate::ScimEntryGeneric`]. Generated resources use this too.
+pub fn convert<T, U>(value: &T) -> Result<U, ScimError>
+where
+    T: Serialize + ?Sized,
+    U: DeserializeOwned,
+{
+    from_value(to_value(value)?)
+}
+
+// The path of a value, built as the deserializer descends into it.
+#[derive(Clone, Copy)]
+enum Path<'a> {
+    Root(&'a str),
+    Key(&'a Path<'a>, &'a str),
+    Index(&'a Path<'a>, usize),
+}
+
+impl fmt::Display for Path<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Path::Root(path) => f.write_str(path),
+            Path::Key(Path::Root(""), key) => f.write_str(key),
+            // The attributes of an extension follow its URN after a colon.
+            Path::Key(parent @ (Path::Root(urn) | Path::Key(Path::Root(""), urn)), key)
+                if is_urn(urn) =>
+            {
+                write!(f, "{}:{}", parent, key)
+            }
+            Path::Key(parent, key) => write!(f, "{}.{}", parent, key),
+            Path::Index(parent, idx) => write!(f, "{}[{}]", parent, idx),
+        }
+    }
+}
+
+fn is_urn(name: &str) -> bool {
+    name.get(..4)
+        .map(|p| p.eq_ignore_ascii_case("urn:"))
+        .unwrap_or(false)
+}
+
+struct Tracked<'a> {
+    value: Value,
+    path: Path<'a>,
+    // The path of the first value to fail, which is the innermost, as the
+    // error is passed out through the values that hold it.
+    failed: &'a RefCell<Option<String>>,
+}
+
+impl Tracked<'_> {
+    fn track<T>(
+        path: Path<'_>,
+        failed: &RefCell<Option<String>>,
+        result: Result<T, serde_json::Error>,
+    ) -> Result<T, serde_json::Error> {
+        if result.is_err() {
+            failed.borrow_mut().get_or_insert_with(|| path.to_string());
+        }
+        result
+    }
+}
+
+macro_rules! forward {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                let result = self.value.$method(visitor);
+                Tracked::track(self.path, self.failed, result)
+            }
+        )*
+    };
+}
+
+macro_rules! descend {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, Self::Error> {
+                match self.value {
+                    Value::Object(_) | Value::Array(_) => self.deserialize_any(visitor),
+                    value => {
+                        let result = value.$method($($arg,)* visitor);
+                        Tracked::track(self.path, self.failed, result)
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Tracked<'_> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let path = &self.path;
+        let result = match self.value {
+            Value::Object(attrs) => visitor.visit_map(TrackedMap {
+                entries: attrs.into_iter(),
+                pending: None,
+                path,
+                failed: self.failed,
+            }),
+            Value::Array(values) => visitor.visit_seq(TrackedSeq {
+                values: values.into_iter().enumerate(),
+                path,
+                failed: self.failed,
+            }),
+            value => value.deserialize_any(visitor),
+        };
+        Tracked::track(self.path, self.failed, result)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let result = self.value.deserialize_enum(name, variants, visitor);
+        Tracked::track(self.path, self.failed, result)
+    }
+
+    descend! {
+        deserialize_seq()
+        deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_map()
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+    }
+
+    forward! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char deserialize_str
+        deserialize_string deserialize_bytes deserialize_byte_buf deserialize_unit
+        deserialize_identifier deserialize_ignored_any
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let result = self.value.deserialize_unit_struct(name, visitor);
+        Tracked::track(self.path, self.failed, result)
+    }
+}
+
+struct TrackedMap<'a> {
+    entries: serde_json::map::IntoIter,
+    // The entry whose key has been read, and whose value is to be.
+    pending: Option<(String, Value)>,
+    path: &'a Path<'a>,
+    failed: &'a RefCell<Option<String>>,
+}
+
+impl<'de> MapAccess<'de> for TrackedMap<'_> {
+    type Error = serde_json::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        let result = seed.deserialize(key.as_str().into_deserializer()).map(Some);
+        self.pending = Some((key, value));
+        result
+    }
+
+    fn next_value_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        let (key, value) = self
+            .pending
+            .take()
+            .ok_or_else(|| de::Error::custom("value is missing"))?;
+        seed.deserialize(Tracked {
+            value,
+            path: Path::Key(self.path, &key),
+            failed: self.failed,
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct TrackedSeq<'a> {
+    values: std::iter::Enumerate<std::vec::IntoIter<Value>>,
+    path: &'a Path<'a>,
+    failed: &'a RefCell<Option<String>>,
+}
+
+impl<'de> SeqAccess<'de> for TrackedSeq<'_> {
+    type Error = serde_json::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        let Some((idx, value)) = self.values.next() else {
+            return Ok(None);
+        };
+        seed.deserialize(Tracked {
+            value,
+            path: Path::Index(self.path, idx),
+            failed: self.failed,
+        })
+        .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::ViolationKind;
+    use crate::ScimEntryGeneric;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+    use std::error::Error;
+
+    fn failed_at<T: DeserializeOwned>(value: Value, path: &str) -> Option<String> {
+        match from_value_at::<T>(value, path) {
+            Err(ScimError::InvalidValue(violation)) => Some(violation.path),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn convert_paths() {
+        let value = json!({"a": [{"b": 1}, {"b": "x"}]});
+        assert_eq!(
+            failed_at::<BTreeMap<String, Vec<BTreeMap<String, u32>>>>(value.clone(), ""),
+            Some("a[1].b".to_string())
+        );
+        assert_eq!(
+            failed_at::<BTreeMap<String, Vec<BTreeMap<String, u32>>>>(value, "attrs"),
+            Some("attrs.a[1].b".to_string())
+        );
+
+        let urn = "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User";
+        let value = json!({ urn: {"manager": {"value": true}} });
+        assert_eq!(
+            failed_at::<BTreeMap<String, BTreeMap<String, BTreeMap<String, String>>>>(value, ""),
+            Some(format!("{}:manager.value", urn))
+        );
+    }
+
+    #[test]
+    fn convert_sources() {
+        let err = parse::<ScimEntryGeneric>("{").expect_err("Parsed a broken document");
+        assert!(matches!(err, ScimError::InvalidJson(_)));
+        assert!(err
+            .source()
+            .map(|e| e.is::<serde_json::Error>())
+            .unwrap_or(false));
+
+        let err = from_value::<u32>(json!("x")).expect_err("Read a string as a number");
+        assert!(matches!(
+            &err,
+            ScimError::InvalidValue(v) if matches!(v.kind, ViolationKind::Unreadable { .. })
+        ));
+        assert!(err
+            .source()
+            .map(|e| e.is::<serde_json::Error>())
+            .unwrap_or(false));
+    }
+}
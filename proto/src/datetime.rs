@@ -0,0 +1,208 @@
+//! Parsing of dateTime values. RFC7643 section 2.3.5 requires RFC3339, but
+//! some service providers send ISO 8601 forms it doesn't allow: no offset,
+//! a space for the `T`, offsets without a colon, or more than nanosecond
+//! precision. These are accepted when parsing leniently, while date times
+//! are always written as RFC3339.
+
+use crate::error::ScimError;
+use crate::schema::SchemaViolation;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// Which forms of date time to accept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DateTimeParsing {
+    /// Only RFC3339.
+    Strict,
+    /// RFC3339, and the common ISO 8601 forms near it. A date time without
+    /// an offset is taken as UTC, and a date alone as its start.
+    #[default]
+    Lenient,
+}
+
+pub fn parse(s: &str, mode: DateTimeParsing) -> Result<OffsetDateTime, ScimError> {
+    let err = match OffsetDateTime::parse(s, &Rfc3339) {
+        Ok(dt) => return Ok(dt),
+        Err(err) => err,
+    };
+    let normalised = match mode {
+        DateTimeParsing::Strict => None,
+        DateTimeParsing::Lenient => normalise(s.trim()),
+    };
+    let Some(normalised) = normalised else {
+        return Err(SchemaViolation::unreadable("", err).into());
+    };
+    OffsetDateTime::parse(&normalised, &Rfc3339)
+        .map_err(|err| SchemaViolation::unreadable("", err).into())
+}
+
+/// Write a date time as RFC3339.
+pub fn format(dt: &OffsetDateTime) -> Result<String, ScimError> {
+    dt.format(&Rfc3339)
+        .map_err(|err| SchemaViolation::unreadable("", err).into())
+}
+
+// Rewrite an ISO 8601 date time to RFC3339, or None if it isn't one.
+fn normalise(s: &str) -> Option<String> {
+    let date = s.get(..10)?;
+    let rest = &s[10..];
+    if rest.is_empty() {
+        return Some(format!("{}T00:00:00Z", date));
+    }
+
+    let mut chars = rest.chars();
+    if !matches!(chars.next(), Some('T' | 't' | ' ')) {
+        return None;
+    }
+    let rest = chars.as_str();
+
+    // The time has no signs, so any sign or Z starts the offset.
+    let (time, offset) = match rest.find(['Z', 'z', '+', '-']) {
+        Some(idx) => rest.split_at(idx),
+        None => (rest, ""),
+    };
+    let offset = match offset {
+        "" | "Z" | "z" => "Z".to_string(),
+        o if o.len() == 3 => format!("{}:00", o),
+        // Only a sign and four digits, which can be split by byte.
+        o if o.len() == 5 && o[1..].bytes().all(|b| b.is_ascii_digit()) => {
+            format!("{}:{}", &o[..3], &o[3..])
+        }
+        o => o.to_string(),
+    };
+
+    let (time, fraction) = match time.split_once('.') {
+        Some((time, fraction)) => (time, fraction.get(..9).unwrap_or(fraction)),
+        None => (time, ""),
+    };
+    let time = if time.len() == 5 {
+        format!("{}:00", time)
+    } else {
+        time.to_string()
+    };
+
+    Some(if fraction.is_empty() {
+        format!("{}T{}{}", date, time, offset)
+    } else {
+        format!("{}T{}.{}{}", date, time, fraction, offset)
+    })
+}
+
+/// For `#[serde(with = "..")]` on an `OffsetDateTime`, which reads leniently
+/// and writes RFC3339.
+pub mod lenient {
+    use super::{format, parse, DateTimeParsing};
+    use serde::{de, ser, Deserialize, Deserializer, Serializer};
+    use time::OffsetDateTime;
+
+    pub fn serialize<S: Serializer>(dt: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error> {
+        let s = format(dt).map_err(|_| ser::Error::custom("date time can't be formatted"))?;
+        serializer.serialize_str(&s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<OffsetDateTime, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        parse(&s, DateTimeParsing::Lenient)
+            .map_err(|_| de::Error::custom(format_args!("invalid date time '{}'", s)))
+    }
+
+    /// As [`lenient`](self), for an `Option<OffsetDateTime>`.
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serializer};
+        use time::OffsetDateTime;
+
+        pub fn serialize<S: Serializer>(
+            dt: &Option<OffsetDateTime>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match dt {
+                Some(dt) => super::serialize(dt, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<OffsetDateTime>, D::Error> {
+            #[derive(Deserialize)]
+            struct Wrap(#[serde(with = "super")] OffsetDateTime);
+
+            Ok(Option::<Wrap>::deserialize(deserializer)?.map(|Wrap(dt)| dt))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rfc3339(s: &str) -> OffsetDateTime {
+        OffsetDateTime::parse(s, &Rfc3339).expect("Invalid datetime")
+    }
+
+    #[test]
+    fn lenient_datetime() {
+        let expected = rfc3339("2011-08-01T18:29:49Z");
+        for s in [
+            "2011-08-01T18:29:49Z",
+            "2011-08-01T18:29:49",
+            "2011-08-01 18:29:49",
+            "2011-08-01t18:29:49z",
+            "2011-08-01T20:29:49+0200",
+            "2011-08-01T20:29:49+02",
+            "2011-08-01T16:29:49-02:00",
+            " 2011-08-01T18:29:49Z ",
+        ] {
+            assert_eq!(parse(s, DateTimeParsing::Lenient), Ok(expected), "{}", s);
+        }
+        assert_eq!(
+            parse("2011-08-01T18:29:49.1234567891Z", DateTimeParsing::Lenient),
+            Ok(rfc3339("2011-08-01T18:29:49.123456789Z"))
+        );
+        assert_eq!(
+            parse("2011-08-01T18:29", DateTimeParsing::Lenient),
+            Ok(rfc3339("2011-08-01T18:29:00Z"))
+        );
+        assert_eq!(
+            parse("2011-08-01", DateTimeParsing::Lenient),
+            Ok(rfc3339("2011-08-01T00:00:00Z"))
+        );
+
+        assert_eq!(
+            parse("2011-08-01T18:29:49Z", DateTimeParsing::Strict),
+            Ok(expected)
+        );
+        assert!(parse("2011-08-01 18:29:49", DateTimeParsing::Strict).is_err());
+        for s in [
+            "",
+            "yesterday",
+            "2011-08-01X18:29:49",
+            "2011-08-01T25:00:00Z",
+            // A five byte offset that isn't five characters.
+            "2011-08-01T00:00+1é1",
+            "2011-08-01T00:00é",
+        ] {
+            assert!(parse(s, DateTimeParsing::Lenient).is_err(), "{}", s);
+        }
+
+        assert_eq!(
+            format(&rfc3339("2011-08-01T20:29:49+02:00")),
+            Ok("2011-08-01T20:29:49+02:00".to_string())
+        );
+
+        // Meta is read leniently, and written back as RFC3339.
+        let meta: crate::ScimMeta = serde_json::from_value(serde_json::json!({
+            "resourceType": "User",
+            "created": "2010-01-23 04:56:22",
+            "lastModified": "2011-05-13T04:42:34.1234567891+0000",
+            "location": "https://example.com/v2/Users/2819c223",
+            "version": "W/\"a330bc54f0671c9\""
+        }))
+        .expect("Failed to parse meta");
+        let json = serde_json::to_value(&meta).expect("Failed to serialise meta");
+        assert_eq!(json["created"], "2010-01-23T04:56:22Z");
+        assert_eq!(json["lastModified"], "2011-05-13T04:42:34.123456789Z");
+    }
+}
@@ -0,0 +1,208 @@
+//! Deserialising attribute values directly from the input. Deriving these
+//! as untagged enums would buffer every value, then try each variant against
+//! the buffer in turn, so an attribute was built once for every variant it
+//! wasn't as well as the one it was.
+//!
+//! Values are read as the untagged enums read them: every number is a
+//! decimal, and strings stay strings until promoted by a schema. Formats
+//! that aren't human readable have forms of their own; see the compact
+//! module.
+
+use crate::attr_map::AttrMap;
+use crate::compact;
+use crate::{ScimAttr, ScimValue};
+use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::fmt;
+
+struct AttrVisitor;
+
+impl<'de> Visitor<'de> for AttrVisitor {
+    type Value = ScimAttr;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a simple attribute value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<ScimAttr, E> {
+        Ok(ScimAttr::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<ScimAttr, E> {
+        Ok(ScimAttr::Null)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<ScimAttr, E> {
+        Ok(ScimAttr::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<ScimAttr, E> {
+        Ok(ScimAttr::Decimal(v as f64))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<ScimAttr, E> {
+        Ok(ScimAttr::Decimal(v as f64))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<ScimAttr, E> {
+        Ok(ScimAttr::Decimal(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<ScimAttr, E> {
+        Ok(ScimAttr::String(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<ScimAttr, E> {
+        Ok(ScimAttr::String(v))
+    }
+}
+
+impl<'de> Deserialize<'de> for ScimAttr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return compact::deserialize_attr(deserializer);
+        }
+        deserializer.deserialize_any(AttrVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = ScimValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an attribute value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<ScimValue, E> {
+        AttrVisitor.visit_unit().map(ScimValue::Simple)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<ScimValue, E> {
+        AttrVisitor.visit_none().map(ScimValue::Simple)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<ScimValue, E> {
+        AttrVisitor.visit_bool(v).map(ScimValue::Simple)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<ScimValue, E> {
+        AttrVisitor.visit_i64(v).map(ScimValue::Simple)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<ScimValue, E> {
+        AttrVisitor.visit_u64(v).map(ScimValue::Simple)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<ScimValue, E> {
+        AttrVisitor.visit_f64(v).map(ScimValue::Simple)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<ScimValue, E> {
+        AttrVisitor.visit_str(v).map(ScimValue::Simple)
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<ScimValue, E> {
+        AttrVisitor.visit_string(v).map(ScimValue::Simple)
+    }
+
+    // The values of an array are all simple or all complex. An empty array
+    // is simple.
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ScimValue, A::Error> {
+        let mut simple = Vec::new();
+        let mut complex = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            match value {
+                ScimValue::Simple(attr) if complex.is_empty() => simple.push(attr),
+                ScimValue::Complex(attrs) if simple.is_empty() => complex.push(attrs),
+                _ => {
+                    return Err(de::Error::custom(
+                        "the values of an array must be all simple or all complex",
+                    ))
+                }
+            }
+        }
+        Ok(if complex.is_empty() {
+            ScimValue::MultiSimple(simple)
+        } else {
+            ScimValue::MultiComplex(complex)
+        })
+    }
+
+    // An object is complex if all its values are simple, and an extension
+    // otherwise.
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<ScimValue, A::Error> {
+        let mut attrs: AttrMap<ScimValue> = AttrMap::new();
+        while let Some((name, value)) = map.next_entry::<String, ScimValue>()? {
+            attrs.insert(name, value);
+        }
+        if !attrs.values().all(|v| matches!(v, ScimValue::Simple(_))) {
+            return Ok(ScimValue::Extension(attrs));
+        }
+        Ok(ScimValue::Complex(
+            attrs
+                .into_iter()
+                .filter_map(|(name, value)| match value {
+                    ScimValue::Simple(attr) => Some((name, attr)),
+                    _ => None,
+                })
+                .collect(),
+        ))
+    }
+}
+
+impl<'de> Deserialize<'de> for ScimValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return compact::deserialize_value(deserializer);
+        }
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn value_deserialize() {
+        let value: ScimValue = serde_json::from_value(json!({
+            "a": [1, "x", null],
+            "b": [{"c": true}],
+            "d": {"e": 2.5},
+            "f": [],
+            "g": {}
+        }))
+        .expect("Failed to parse");
+        let attrs = match value {
+            ScimValue::Extension(attrs) => attrs,
+            _ => AttrMap::new(),
+        };
+        assert_eq!(attrs.len(), 5);
+        assert_eq!(
+            attrs["a"],
+            ScimValue::MultiSimple(vec![
+                ScimAttr::Decimal(1.0),
+                ScimAttr::String("x".to_string()),
+                ScimAttr::Null
+            ])
+        );
+        assert!(
+            matches!(&attrs["b"], ScimValue::MultiComplex(v) if v[0]["c"] == ScimAttr::Bool(true))
+        );
+        assert!(matches!(&attrs["d"], ScimValue::Complex(v) if v["e"] == ScimAttr::Decimal(2.5)));
+        assert_eq!(attrs["f"], ScimValue::MultiSimple(Vec::new()));
+        assert_eq!(attrs["g"], ScimValue::Complex(AttrMap::new()));
+
+        for bad in [json!([1, {"a": 1}]), json!([[1]]), json!([{"a": {"b": 1}}])] {
+            assert!(
+                serde_json::from_value::<ScimValue>(bad.clone()).is_err(),
+                "{}",
+                bad
+            );
+        }
+        assert!(serde_json::from_value::<ScimAttr>(json!([1])).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+//! A compact, human readable form of resources, for `Display`. Each
+//! attribute is on a line of its own, with sub-attributes indented below it
+//! and the values of multi-valued attributes listed with a `-`:
+//!
+//! ```text
+//! schemas:
+//!   - urn:ietf:params:scim:schemas:core:2.0:User
+//! id: 2819c223-7f76-453a-919d-413861904646
+//! emails:
+//!   - type: work
+//!     value: bjensen@example.com
+//! name:
+//!   familyName: Jensen
+//! userName: bjensen
+//! ```
+
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+// The common attributes come first, and meta, which is the least
+// interesting, last.
+fn rank(name: &str) -> usize {
+    ["schemas", "id", "externalId"]
+        .iter()
+        .position(|n| *n == name)
+        .unwrap_or(if name == "meta" { 4 } else { 3 })
+}
+
+/// Write `resource`, which must serialise to a JSON object, in the form
+/// above.
+pub(crate) fn write_resource<T: Serialize>(
+    f: &mut fmt::Formatter<'_>,
+    resource: &T,
+) -> fmt::Result {
+    let Ok(Value::Object(attrs)) = serde_json::to_value(resource) else {
+        return Err(fmt::Error);
+    };
+    let mut attrs: Vec<_> = attrs.iter().collect();
+    attrs.sort_by_key(|(name, _)| rank(name));
+
+    let mut writer = Writer { f, started: false };
+    for (name, value) in attrs {
+        writer.attr(name, value, 0)?;
+    }
+    Ok(())
+}
+
+struct Writer<'a, 'b> {
+    f: &'a mut fmt::Formatter<'b>,
+    started: bool,
+}
+
+impl Writer<'_, '_> {
+    // Lines are separated rather than ended, so there is no trailing newline.
+    fn line(&mut self, indent: usize) -> fmt::Result {
+        if self.started {
+            self.f.write_str("\n")?;
+        }
+        self.started = true;
+        write!(self.f, "{:1$}", "", indent)
+    }
+
+    fn attr(&mut self, name: &str, value: &Value, indent: usize) -> fmt::Result {
+        self.line(indent)?;
+        write!(self.f, "{}:", name)?;
+        self.value(value, indent)
+    }
+
+    // The value of an attribute at `indent`, after its name.
+    fn value(&mut self, value: &Value, indent: usize) -> fmt::Result {
+        match value {
+            Value::Object(attrs) if !attrs.is_empty() => {
+                for (name, value) in attrs.iter() {
+                    self.attr(name, value, indent + 2)?;
+                }
+                Ok(())
+            }
+            Value::Array(values) if !values.is_empty() => {
+                for value in values.iter() {
+                    self.line(indent + 2)?;
+                    self.f.write_str("-")?;
+                    self.item(value, indent + 4)?;
+                }
+                Ok(())
+            }
+            Value::String(s) => write!(self.f, " {}", s),
+            value => write!(self.f, " {}", value),
+        }
+    }
+
+    // A value of a multi-valued attribute, after its `-`. The first
+    // sub-attribute of a complex value shares the line.
+    fn item(&mut self, value: &Value, indent: usize) -> fmt::Result {
+        match value {
+            Value::Object(attrs) if !attrs.is_empty() => {
+                for (idx, (name, value)) in attrs.iter().enumerate() {
+                    if idx == 0 {
+                        write!(self.f, " {}:", name)?;
+                        self.value(value, indent)?;
+                    } else {
+                        self.attr(name, value, indent)?;
+                    }
+                }
+                Ok(())
+            }
+            value => self.value(value, indent - 2),
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use crate::datetime::{self, DateTimeParsing};
+use crate::error::ScimError;
+use crate::schema::{
+    find_attr, split_patch_path, AttributeType, JsonType, SchemaViolation, ScimAttributeDefinition,
+    ScimSchema, ViolationKind,
+};
+use crate::{ScimAttr, ScimComplexAttr, ScimEntryGeneric, ScimValue};
+use time::OffsetDateTime;
+
+/// An entry of a resource type only known at runtime, read through the
+/// schema that describes it. Accessors take an attribute path such as
+/// `userName` or `name.givenName`, and return [`ScimError::InvalidPath`] for
+/// attributes the schema doesn't define and [`ScimError::InvalidAttribute`]
+/// when the attribute isn't of the requested type. A value that isn't of the
+/// form its definition gives is a [`ScimError::InvalidValue`] saying where.
+#[derive(Debug, Clone)]
+pub struct DynamicResource<'a> {
+    entry: ScimEntryGeneric,
+    schema: &'a ScimSchema,
+}
+
+impl<'a> DynamicResource<'a> {
+    /// Wrap an entry after validating it against `schema`.
+    pub fn new(
+        entry: ScimEntryGeneric,
+        schema: &'a ScimSchema,
+    ) -> Result<Self, Vec<SchemaViolation>> {
+        schema.validate(&entry)?;
+        Ok(DynamicResource { entry, schema })
+    }
+
+    pub fn entry(&self) -> &ScimEntryGeneric {
+        &self.entry
+    }
+
+    pub fn schema(&self) -> &'a ScimSchema {
+        self.schema
+    }
+
+    pub fn into_entry(self) -> ScimEntryGeneric {
+        self.entry
+    }
+
+    /// Find the definitions of an attribute path, as the attribute and the
+    /// sub-attribute if one is named.
+    fn resolve(
+        &self,
+        path: &str,
+    ) -> Result<
+        (
+            &'a ScimAttributeDefinition,
+            Option<&'a ScimAttributeDefinition>,
+        ),
+        ScimError,
+    > {
+        let (urn, attr, sub) = split_patch_path(path);
+        if urn
+            .map(|u| !u.eq_ignore_ascii_case(&self.schema.id))
+            .unwrap_or(false)
+        {
+            return Err(ScimError::InvalidPath);
+        }
+
+        let def = self.schema.attribute(attr).ok_or(ScimError::InvalidPath)?;
+        match sub {
+            Some(sub) => def
+                .sub_attribute(sub)
+                .map(|sub| (def, Some(sub)))
+                .ok_or(ScimError::InvalidPath),
+            None => Ok((def, None)),
+        }
+    }
+
+    fn single(&self, path: &str, types: &[AttributeType]) -> Result<Option<&ScimAttr>, ScimError> {
+        let (def, sub) = self.resolve(path)?;
+        let leaf = sub.unwrap_or(def);
+        if def.multi_valued || leaf.multi_valued || !types.contains(&leaf.type_) {
+            return Err(ScimError::InvalidAttribute);
+        }
+
+        match (find_attr(&self.entry.attrs, &def.name), sub) {
+            (None, _) => Ok(None),
+            (Some(ScimValue::Simple(attr)), None) => Ok(Some(attr)),
+            (Some(ScimValue::Complex(attrs)), Some(sub)) => Ok(find_attr(attrs, &sub.name)),
+            (Some(value), None) => Err(invalid(path, expected_simple(value))),
+            (Some(value), Some(_)) => Err(expected_complex(path, value)),
+        }
+    }
+
+    /// The values of a multi-valued attribute, such as `emails.value` or a
+    /// multi-valued string.
+    pub fn get_multi(&self, path: &str) -> Result<Vec<&ScimAttr>, ScimError> {
+        let (def, sub) = self.resolve(path)?;
+        let leaf = sub.unwrap_or(def);
+        if !def.multi_valued || leaf.type_ == AttributeType::Complex {
+            return Err(ScimError::InvalidAttribute);
+        }
+
+        match (find_attr(&self.entry.attrs, &def.name), sub) {
+            (None, _) => Ok(Vec::new()),
+            (Some(ScimValue::MultiSimple(values)), None) => Ok(values.iter().collect()),
+            (Some(ScimValue::MultiComplex(values)), Some(sub)) => Ok(values
+                .iter()
+                .filter_map(|attrs| find_attr(attrs, &sub.name))
+                .collect()),
+            (Some(value), _) => Err(expected_multi(path, leaf.type_, value)),
+        }
+    }
+
+    /// A string, reference or binary attribute.
+    pub fn get_str(&self, path: &str) -> Result<Option<&str>, ScimError> {
+        let types = [
+            AttributeType::String,
+            AttributeType::Reference,
+            AttributeType::Binary,
+        ];
+        self.single(path, &types)?
+            .map(|attr| attr_str(path, attr))
+            .transpose()
+    }
+
+    pub fn get_bool(&self, path: &str) -> Result<Option<bool>, ScimError> {
+        match self.single(path, &[AttributeType::Boolean])? {
+            Some(ScimAttr::Bool(b)) => Ok(Some(*b)),
+            Some(attr) => Err(invalid_type(path, AttributeType::Boolean, attr)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_integer(&self, path: &str) -> Result<Option<i64>, ScimError> {
+        match self.single(path, &[AttributeType::Integer])? {
+            Some(ScimAttr::Integer(i)) => Ok(Some(*i)),
+            // JSON numbers are decoded as decimals.
+            Some(ScimAttr::Decimal(d)) if d.fract() == 0.0 => Ok(Some(*d as i64)),
+            Some(attr) => Err(invalid_type(path, AttributeType::Integer, attr)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_decimal(&self, path: &str) -> Result<Option<f64>, ScimError> {
+        match self.single(path, &[AttributeType::Decimal])? {
+            Some(ScimAttr::Decimal(d)) => Ok(Some(*d)),
+            Some(ScimAttr::Integer(i)) => Ok(Some(*i as f64)),
+            Some(attr) => Err(invalid_type(path, AttributeType::Decimal, attr)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_datetime(&self, path: &str) -> Result<Option<OffsetDateTime>, ScimError> {
+        match self.single(path, &[AttributeType::DateTime])? {
+            Some(ScimAttr::DateTime(dt)) => Ok(Some(*dt)),
+            Some(attr @ ScimAttr::String(s)) => datetime::parse(s, DateTimeParsing::Lenient)
+                .map(Some)
+                .map_err(|err| {
+                    let found = JsonType::of_attr(attr);
+                    let kind = ViolationKind::InvalidType {
+                        expected: AttributeType::DateTime,
+                        found,
+                    };
+                    SchemaViolation::new(path, kind).with_source(err).into()
+                }),
+            Some(attr) => Err(invalid_type(path, AttributeType::DateTime, attr)),
+            None => Ok(None),
+        }
+    }
+
+    /// As [`DynamicResource::get_multi`], for string, reference or binary
+    /// attributes.
+    pub fn get_multi_str(&self, path: &str) -> Result<Vec<&str>, ScimError> {
+        let (def, sub) = self.resolve(path)?;
+        match sub.unwrap_or(def).type_ {
+            AttributeType::String | AttributeType::Reference | AttributeType::Binary => {}
+            _ => return Err(ScimError::InvalidAttribute),
+        }
+        self.get_multi(path)?
+            .into_iter()
+            .map(|attr| attr_str(path, attr))
+            .collect()
+    }
+
+    pub fn get_complex(&self, path: &str) -> Result<Option<&ScimComplexAttr>, ScimError> {
+        let (def, sub) = self.resolve(path)?;
+        if sub.is_some() || def.multi_valued || def.type_ != AttributeType::Complex {
+            return Err(ScimError::InvalidAttribute);
+        }
+
+        match find_attr(&self.entry.attrs, &def.name) {
+            Some(ScimValue::Complex(attrs)) => Ok(Some(attrs)),
+            Some(value) => Err(expected_complex(path, value)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_multi_complex(&self, path: &str) -> Result<&[ScimComplexAttr], ScimError> {
+        let (def, sub) = self.resolve(path)?;
+        if sub.is_some() || !def.multi_valued || def.type_ != AttributeType::Complex {
+            return Err(ScimError::InvalidAttribute);
+        }
+
+        match find_attr(&self.entry.attrs, &def.name) {
+            Some(ScimValue::MultiComplex(values)) => Ok(values),
+            Some(value) => Err(expected_multi(path, AttributeType::Complex, value)),
+            None => Ok(&[]),
+        }
+    }
+}
+
+fn invalid(path: &str, kind: ViolationKind) -> ScimError {
+    SchemaViolation::new(path, kind).into()
+}
+
+fn invalid_type(path: &str, expected: AttributeType, found: &ScimAttr) -> ScimError {
+    let found = JsonType::of_attr(found);
+    invalid(path, ViolationKind::InvalidType { expected, found })
+}
+
+fn expected_complex(path: &str, value: &ScimValue) -> ScimError {
+    let found = JsonType::of_value(value);
+    invalid(path, ViolationKind::ExpectedComplex { found })
+}
+
+fn expected_multi(path: &str, expected: AttributeType, value: &ScimValue) -> ScimError {
+    let found = JsonType::of_value(value);
+    invalid(path, ViolationKind::ExpectedMultiValue { expected, found })
+}
+
+fn expected_simple(value: &ScimValue) -> ViolationKind {
+    match value {
+        ScimValue::MultiSimple(_) | ScimValue::MultiComplex(_) => {
+            ViolationKind::ExpectedSingleValue
+        }
+        value => ViolationKind::ExpectedSimple {
+            found: JsonType::of_value(value),
+        },
+    }
+}
+
+fn attr_str<'a>(path: &str, attr: &'a ScimAttr) -> Result<&'a str, ScimError> {
+    attr.as_str()
+        .ok_or_else(|| invalid_type(path, AttributeType::String, attr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::user_schema;
+    use crate::constants::RFC7643_USER;
+
+    #[test]
+    fn dynamic_resource() {
+        let schema = user_schema();
+        let u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let r = DynamicResource::new(u, &schema).expect("Invalid user");
+
+        assert_eq!(r.get_str("userName"), Ok(Some("bjensen@example.com")));
+        assert_eq!(r.get_str("name.givenName"), Ok(Some("Barbara")));
+        assert_eq!(
+            r.get_str("urn:ietf:params:scim:schemas:core:2.0:User:nickName"),
+            Ok(Some("Babs"))
+        );
+        assert_eq!(r.get_str("userType"), Ok(Some("Employee")));
+        assert_eq!(r.get_str("honorificPrefix"), Err(ScimError::InvalidPath));
+        assert_eq!(r.get_str("name.honorificPrefix"), Ok(Some("Ms.")));
+        assert_eq!(r.get_bool("active"), Ok(Some(true)));
+        assert_eq!(r.get_bool("userName"), Err(ScimError::InvalidAttribute));
+        assert_eq!(r.get_str("emails"), Err(ScimError::InvalidAttribute));
+        assert_eq!(r.get_str("missing"), Err(ScimError::InvalidPath));
+
+        assert_eq!(
+            r.get_multi_str("emails.value"),
+            Ok(vec!["bjensen@example.com", "babs@jensen.org"])
+        );
+        assert_eq!(r.get_multi("emails.primary").map(|v| v.len()), Ok(1));
+        assert_eq!(r.get_multi_complex("groups").map(|g| g.len()), Ok(3));
+        assert!(r
+            .get_complex("name")
+            .map(|n| n.map(|n| n.contains_key("familyName")).unwrap_or(false))
+            .unwrap_or(false));
+        assert_eq!(r.get_multi_str("roles.value"), Ok(Vec::new()));
+    }
+}
@@ -0,0 +1,561 @@
+// https://datatracker.ietf.org/doc/html/rfc7644#section-3.12
+
+use crate::messages::ScimErrorResponse;
+use crate::schema::SchemaViolation;
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The `scimType` error keywords from RFC7644 Table 9.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScimType {
+    InvalidFilter,
+    TooMany,
+    Uniqueness,
+    Mutability,
+    InvalidSyntax,
+    InvalidPath,
+    NoTarget,
+    InvalidValue,
+    InvalidVers,
+    Sensitive,
+}
+
+impl ScimType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScimType::InvalidFilter => "invalidFilter",
+            ScimType::TooMany => "tooMany",
+            ScimType::Uniqueness => "uniqueness",
+            ScimType::Mutability => "mutability",
+            ScimType::InvalidSyntax => "invalidSyntax",
+            ScimType::InvalidPath => "invalidPath",
+            ScimType::NoTarget => "noTarget",
+            ScimType::InvalidValue => "invalidValue",
+            ScimType::InvalidVers => "invalidVers",
+            ScimType::Sensitive => "sensitive",
+        }
+    }
+
+    const ALL: [ScimType; 10] = [
+        ScimType::InvalidFilter,
+        ScimType::TooMany,
+        ScimType::Uniqueness,
+        ScimType::Mutability,
+        ScimType::InvalidSyntax,
+        ScimType::InvalidPath,
+        ScimType::NoTarget,
+        ScimType::InvalidValue,
+        ScimType::InvalidVers,
+        ScimType::Sensitive,
+    ];
+
+    /// The HTTP status that RFC7644 pairs with this error keyword.
+    pub fn status(&self) -> u16 {
+        match self {
+            ScimType::Uniqueness => 409,
+            ScimType::Sensitive => 403,
+            ScimType::InvalidFilter
+            | ScimType::TooMany
+            | ScimType::Mutability
+            | ScimType::InvalidSyntax
+            | ScimType::InvalidPath
+            | ScimType::NoTarget
+            | ScimType::InvalidValue
+            | ScimType::InvalidVers => 400,
+        }
+    }
+}
+
+impl fmt::Display for ScimType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ScimType {
+    type Err = ScimError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ScimType::ALL
+            .into_iter()
+            .find(|t| t.as_str().eq_ignore_ascii_case(s))
+            .ok_or(ScimError::InvalidSyntax)
+    }
+}
+
+impl Serialize for ScimType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ScimType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse()
+            .map_err(|_| de::Error::custom(format!("unknown scimType {}", s)))
+    }
+}
+
+/// The error of another crate that a [`ScimError`] was caused by, such as
+/// the `serde_json::Error` of a document that doesn't parse. It is returned
+/// as the error's [`std::error::Error::source`]. Sources are ignored when
+/// errors are compared, so that they compare as they did without them.
+#[derive(Clone)]
+pub struct ErrorSource(Arc<dyn std::error::Error + Send + Sync>);
+
+impl ErrorSource {
+    pub fn new<E: std::error::Error + Send + Sync + 'static>(err: E) -> Self {
+        ErrorSource(Arc::new(err))
+    }
+
+    pub fn get(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        &*self.0
+    }
+}
+
+impl PartialEq for ErrorSource {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for ErrorSource {}
+
+impl fmt::Debug for ErrorSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for ErrorSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScimError {
+    /// A filter that doesn't parse, saying where and why.
+    InvalidFilter {
+        /// The byte offset in the filter at which parsing failed.
+        offset: usize,
+        /// What the filter could have had there.
+        expected: String,
+        /// What it had instead, or `None` at the end of the filter.
+        found: Option<char>,
+    },
+    InvalidSyntax,
+    /// A document that isn't JSON, with the error it failed with.
+    InvalidJson(ErrorSource),
+    InvalidPath,
+    InvalidAttribute,
+    /// A value that isn't of the form its attribute must be, saying which
+    /// attribute and what was expected of it.
+    InvalidValue(SchemaViolation),
+    /// A required attribute that is missing, by name.
+    MissingRequiredAttribute(String),
+    NoTarget,
+    Uniqueness,
+    /// An attribute, by name, that its mutability doesn't allow to be
+    /// changed.
+    Mutability(String),
+    TooMany,
+    InvalidVersion,
+    Sensitive,
+    PreconditionFailed,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    NotAcceptable,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    NotImplemented,
+    Internal,
+}
+
+impl ScimError {
+    /// The `scimType` keyword for this error, if the RFC defines one.
+    pub fn scim_type(&self) -> Option<ScimType> {
+        match self {
+            ScimError::InvalidFilter { .. } => Some(ScimType::InvalidFilter),
+            ScimError::InvalidSyntax | ScimError::InvalidJson(_) => Some(ScimType::InvalidSyntax),
+            ScimError::InvalidPath => Some(ScimType::InvalidPath),
+            ScimError::InvalidAttribute
+            | ScimError::InvalidValue(_)
+            | ScimError::MissingRequiredAttribute(_) => Some(ScimType::InvalidValue),
+            ScimError::NoTarget => Some(ScimType::NoTarget),
+            ScimError::Uniqueness => Some(ScimType::Uniqueness),
+            ScimError::Mutability(_) => Some(ScimType::Mutability),
+            ScimError::TooMany => Some(ScimType::TooMany),
+            ScimError::InvalidVersion => Some(ScimType::InvalidVers),
+            ScimError::Sensitive => Some(ScimType::Sensitive),
+            ScimError::PreconditionFailed
+            | ScimError::PayloadTooLarge
+            | ScimError::UnsupportedMediaType
+            | ScimError::NotAcceptable
+            | ScimError::Unauthorized
+            | ScimError::Forbidden
+            | ScimError::NotFound
+            | ScimError::NotImplemented
+            | ScimError::Internal => None,
+        }
+    }
+
+    /// The HTTP status a service provider should respond with for this error.
+    pub fn status(&self) -> u16 {
+        if let Some(scim_type) = self.scim_type() {
+            return scim_type.status();
+        }
+
+        match self {
+            ScimError::PreconditionFailed => 412,
+            ScimError::PayloadTooLarge => 413,
+            ScimError::UnsupportedMediaType => 415,
+            ScimError::NotAcceptable => 406,
+            ScimError::Unauthorized => 401,
+            ScimError::Forbidden => 403,
+            ScimError::NotFound => 404,
+            ScimError::NotImplemented => 501,
+            _ => 500,
+        }
+    }
+}
+
+impl fmt::Display for ScimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScimError::InvalidFilter {
+                offset,
+                expected,
+                found: Some(found),
+            } => write!(
+                f,
+                "invalid filter at offset {}: expected {}, found '{}'",
+                offset, expected, found
+            ),
+            ScimError::InvalidFilter {
+                offset,
+                expected,
+                found: None,
+            } => write!(
+                f,
+                "invalid filter at offset {}: expected {}, found the end of the filter",
+                offset, expected
+            ),
+            ScimError::InvalidSyntax => f.write_str("invalid syntax"),
+            ScimError::InvalidJson(source) => write!(f, "invalid JSON: {}", source),
+            ScimError::InvalidPath => f.write_str("invalid attribute path"),
+            ScimError::InvalidAttribute => f.write_str("invalid attribute value"),
+            ScimError::InvalidValue(violation) => violation.fmt(f),
+            ScimError::MissingRequiredAttribute(name) => {
+                write!(f, "required attribute '{}' is missing", name)
+            }
+            ScimError::NoTarget => f.write_str("the path matched no values"),
+            ScimError::Uniqueness => f.write_str("a value is not unique"),
+            ScimError::Mutability(name) => write!(f, "attribute '{}' can't be changed", name),
+            ScimError::TooMany => f.write_str("too many results"),
+            ScimError::InvalidVersion => f.write_str("invalid version"),
+            ScimError::Sensitive => f.write_str("sensitive information in the request"),
+            ScimError::PreconditionFailed => f.write_str("the resource has changed"),
+            ScimError::PayloadTooLarge => f.write_str("payload too large"),
+            ScimError::UnsupportedMediaType => f.write_str("unsupported media type"),
+            ScimError::NotAcceptable => f.write_str("no acceptable representation"),
+            ScimError::Unauthorized => f.write_str("not authenticated"),
+            ScimError::Forbidden => f.write_str("not permitted"),
+            ScimError::NotFound => f.write_str("resource not found"),
+            ScimError::NotImplemented => f.write_str("not implemented"),
+            ScimError::Internal => f.write_str("internal error"),
+        }
+    }
+}
+
+impl std::error::Error for ScimError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScimError::InvalidJson(source) => Some(source.get()),
+            ScimError::InvalidValue(violation) => violation.source(),
+            _ => None,
+        }
+    }
+}
+
+/// The error response for `err`, with its status, its `scimType` if it has
+/// one, and a `detail` saying what went wrong, so that handlers can `?`
+/// errors into a response.
+impl From<&ScimError> for ScimErrorResponse {
+    fn from(err: &ScimError) -> Self {
+        let mut resp = ScimErrorResponse::new(err.status());
+        resp.scim_type = err.scim_type();
+        resp.detail = Some(err.to_string());
+        resp
+    }
+}
+
+impl From<ScimError> for ScimErrorResponse {
+    fn from(err: ScimError) -> Self {
+        ScimErrorResponse::from(&err)
+    }
+}
+
+impl From<SchemaViolation> for ScimError {
+    fn from(violation: SchemaViolation) -> Self {
+        ScimError::InvalidValue(violation)
+    }
+}
+
+/// Every error found in a request, each with the attribute path it was
+/// found at, for APIs that check everything before failing. There is always
+/// at least one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScimErrors {
+    errors: Vec<(String, ScimError)>,
+}
+
+impl ScimErrors {
+    pub fn new(path: impl Into<String>, error: ScimError) -> Self {
+        ScimErrors {
+            errors: vec![(path.into(), error)],
+        }
+    }
+
+    /// The errors for `violations`, or `None` if there are none.
+    pub fn from_violations(violations: Vec<SchemaViolation>) -> Option<Self> {
+        if violations.is_empty() {
+            return None;
+        }
+        let errors = violations
+            .into_iter()
+            .map(|v| (v.path.clone(), ScimError::InvalidValue(v)))
+            .collect();
+        Some(ScimErrors { errors })
+    }
+
+    pub fn push(&mut self, path: impl Into<String>, error: ScimError) {
+        self.errors.push((path.into(), error));
+    }
+
+    pub fn first(&self) -> (&str, &ScimError) {
+        let (path, error) = &self.errors[0];
+        (path, error)
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Always false, as there is always at least one error.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ScimError)> {
+        self.errors
+            .iter()
+            .map(|(path, error)| (path.as_str(), error))
+    }
+
+    pub fn into_vec(self) -> Vec<(String, ScimError)> {
+        self.errors
+    }
+
+    fn shared<T: PartialEq>(&self, f: impl Fn(&ScimError) -> T) -> Option<T> {
+        let first = f(self.first().1);
+        self.errors
+            .iter()
+            .all(|(_, error)| f(error) == first)
+            .then_some(first)
+    }
+}
+
+impl From<ScimError> for ScimErrors {
+    fn from(error: ScimError) -> Self {
+        ScimErrors::new("", error)
+    }
+}
+
+impl IntoIterator for ScimErrors {
+    type Item = (String, ScimError);
+    type IntoIter = std::vec::IntoIter<(String, ScimError)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.into_iter()
+    }
+}
+
+/// Each error on a line of its own. An invalid value already says which
+/// attribute it is of; other errors are preceded by their path.
+impl fmt::Display for ScimErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (path, error)) in self.errors.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            match error {
+                ScimError::InvalidValue(_) => write!(f, "{}", error)?,
+                _ if path.is_empty() => write!(f, "{}", error)?,
+                _ => write!(f, "{}: {}", path, error)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ScimErrors {}
+
+/// One response for all of the errors, with every one of them in `detail`.
+/// The status and `scimType` are those the errors share; errors that differ
+/// are a `400 Bad Request` without a `scimType`.
+impl From<&ScimErrors> for ScimErrorResponse {
+    fn from(errs: &ScimErrors) -> Self {
+        let mut resp = ScimErrorResponse::new(errs.shared(ScimError::status).unwrap_or(400));
+        resp.scim_type = errs.shared(ScimError::scim_type).flatten();
+        resp.detail = Some(errs.to_string());
+        resp
+    }
+}
+
+impl From<ScimErrors> for ScimErrorResponse {
+    fn from(errs: ScimErrors) -> Self {
+        ScimErrorResponse::from(&errs)
+    }
+}
+
+#[cfg(feature = "http")]
+mod http_status {
+    use super::{ScimError, ScimType};
+    use http::StatusCode;
+
+    impl ScimType {
+        pub fn status_code(&self) -> StatusCode {
+            StatusCode::from_u16(self.status()).unwrap_or(StatusCode::BAD_REQUEST)
+        }
+    }
+
+    impl ScimError {
+        pub fn status_code(&self) -> StatusCode {
+            StatusCode::from_u16(self.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+
+    impl From<ScimType> for StatusCode {
+        fn from(scim_type: ScimType) -> Self {
+            scim_type.status_code()
+        }
+    }
+
+    impl From<&ScimError> for StatusCode {
+        fn from(err: &ScimError) -> Self {
+            err.status_code()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scim_error_status() {
+        let filter = ScimError::InvalidFilter {
+            offset: 11,
+            expected: "\"(\"".to_string(),
+            found: None,
+        };
+        assert_eq!(filter.status(), 400);
+        assert_eq!(filter.scim_type(), Some(ScimType::InvalidFilter));
+        assert_eq!(ScimError::Uniqueness.status(), 409);
+        assert_eq!(ScimError::Sensitive.status(), 403);
+        assert_eq!(ScimError::PreconditionFailed.status(), 412);
+        assert_eq!(ScimError::PreconditionFailed.scim_type(), None);
+        assert_eq!(ScimError::PayloadTooLarge.status(), 413);
+        assert_eq!(ScimError::Internal.status(), 500);
+
+        let err: Box<dyn std::error::Error> = Box::new(ScimError::InvalidPath);
+        assert_eq!(err.to_string(), "invalid attribute path");
+
+        let err = ScimError::Mutability("id".to_string());
+        assert_eq!(err.scim_type(), Some(ScimType::Mutability));
+        assert_eq!(err.to_string(), "attribute 'id' can't be changed");
+        let err = ScimError::MissingRequiredAttribute("userName".to_string());
+        assert_eq!(err.status(), 400);
+        assert_eq!(err.to_string(), "required attribute 'userName' is missing");
+    }
+
+    #[test]
+    fn scim_error_response() {
+        fn handler() -> Result<(), ScimErrorResponse> {
+            Err(ScimError::Uniqueness)?
+        }
+
+        let resp = handler().expect_err("Handler succeeded");
+        assert_eq!(resp.status, 409);
+        assert_eq!(resp.scim_type, Some(ScimType::Uniqueness));
+        assert_eq!(resp.detail.as_deref(), Some("a value is not unique"));
+
+        let resp = ScimErrorResponse::from(ScimError::NotFound);
+        assert_eq!(resp.status, 404);
+        assert_eq!(resp.scim_type, None);
+    }
+
+    #[test]
+    fn scim_errors_response() {
+        let mut errs = ScimErrors::from_violations(vec![SchemaViolation::new(
+            "userName",
+            crate::schema::ViolationKind::MissingRequired,
+        )])
+        .expect("No violations");
+        errs.push("emails[0].value", ScimError::Uniqueness);
+        assert_eq!(errs.len(), 2);
+        assert_eq!(errs.first().0, "userName");
+
+        let resp = ScimErrorResponse::from(&errs);
+        assert_eq!(resp.status, 400);
+        assert_eq!(resp.scim_type, None);
+        assert_eq!(
+            resp.detail.as_deref(),
+            Some(
+                "required attribute 'userName' is missing\nemails[0].value: a value is not unique"
+            )
+        );
+
+        let resp = ScimErrorResponse::from(ScimErrors::from(ScimError::Uniqueness));
+        assert_eq!(resp.status, 409);
+        assert_eq!(resp.scim_type, Some(ScimType::Uniqueness));
+        assert!(ScimErrors::from_violations(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn scim_type_keywords() {
+        for scim_type in ScimType::ALL {
+            assert_eq!(scim_type.to_string().parse(), Ok(scim_type));
+        }
+        assert_eq!(
+            serde_json::to_string(&ScimType::InvalidVers).expect("Failed to serialise"),
+            r#""invalidVers""#
+        );
+        assert!(serde_json::from_str::<ScimType>(r#""teapot""#).is_err());
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn scim_error_status_code() {
+        use http::StatusCode;
+
+        assert_eq!(
+            StatusCode::from(&ScimError::Uniqueness),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            StatusCode::from(ScimType::InvalidVers),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            ScimError::PreconditionFailed.status_code(),
+            StatusCode::PRECONDITION_FAILED
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use crate::error::ScimError;
+use crate::{ScimEntryGeneric, ScimId};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Derives resource ids from `externalId`s, as name based (version 5) UUIDs
+/// within a namespace. The same externalId always maps to the same id, so a
+/// synchroniser that keys its source records on externalId produces the
+/// same ids on every run without storing them.
+///
+/// A UUID can't be turned back into its externalId, but see
+/// [`ExternalIdMapper::index`] for looking up the records an id came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExternalIdMapper {
+    namespace: Uuid,
+}
+
+impl ExternalIdMapper {
+    pub fn new(namespace: Uuid) -> Self {
+        ExternalIdMapper { namespace }
+    }
+
+    /// A mapper whose namespace is derived from a name for the source
+    /// system, such as its URL, so different sources don't share ids.
+    pub fn from_name(name: &str) -> Self {
+        ExternalIdMapper::new(Uuid::new_v5(&Uuid::NAMESPACE_URL, name.as_bytes()))
+    }
+
+    pub fn namespace(&self) -> Uuid {
+        self.namespace
+    }
+
+    /// The id for `external_id`. externalId is case exact, so ids that only
+    /// differ in case map to different ids.
+    pub fn id(&self, external_id: &str) -> Uuid {
+        Uuid::new_v5(&self.namespace, external_id.as_bytes())
+    }
+
+    /// Whether `id` is the one derived from `external_id`.
+    pub fn matches(&self, id: &ScimId, external_id: &str) -> bool {
+        *id == self.id(external_id)
+    }
+
+    /// Set the id of `entry` from its externalId, which it must have.
+    pub fn assign(&self, entry: &mut ScimEntryGeneric) -> Result<(), ScimError> {
+        let external_id = entry
+            .external_id
+            .as_deref()
+            .ok_or_else(|| ScimError::MissingRequiredAttribute("externalId".to_string()))?;
+        entry.id = self.id(external_id).into();
+        Ok(())
+    }
+
+    /// Map the ids of a set of externalIds back to them, such as those of
+    /// the records in a source system, to find which record an id from the
+    /// service provider belongs to.
+    pub fn index<I, S>(&self, external_ids: I) -> BTreeMap<Uuid, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        external_ids
+            .into_iter()
+            .map(|external_id| {
+                let external_id = external_id.into();
+                (self.id(&external_id), external_id)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::RFC7643_USER;
+
+    #[test]
+    fn external_id_mapping() {
+        let mapper = ExternalIdMapper::from_name("https://hr.example.com/");
+        assert_eq!(
+            mapper,
+            ExternalIdMapper::from_name("https://hr.example.com/")
+        );
+        assert_ne!(mapper, ExternalIdMapper::from_name("https://example.org/"));
+
+        // Uuid::NAMESPACE_DNS and "python.org", as in the Python docs.
+        let dns = ExternalIdMapper::new(Uuid::NAMESPACE_DNS);
+        assert_eq!(
+            dns.id("python.org").to_string(),
+            "886313e1-3b8a-5372-9b90-0c9aee199e5d"
+        );
+
+        let id = mapper.id("701984");
+        assert_eq!(id.get_version_num(), 5);
+        assert_eq!(id, mapper.id("701984"));
+        assert_ne!(id, mapper.id("701985"));
+
+        let mut u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        mapper.assign(&mut u).expect("Failed to assign id");
+        assert!(mapper.matches(&u.id, "701984"));
+        assert!(!mapper.matches(&u.id, "701985"));
+
+        u.external_id = None;
+        assert_eq!(
+            mapper.assign(&mut u),
+            Err(ScimError::MissingRequiredAttribute(
+                "externalId".to_string()
+            ))
+        );
+
+        let index = mapper.index(["701984", "701985"]);
+        assert_eq!(index.get(&id).map(String::as_str), Some("701984"));
+        assert_eq!(index.len(), 2);
+    }
+}
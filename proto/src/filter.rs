@@ -1,7 +1,16 @@
 #![allow(warnings)]
 
+use crate::datetime::{self, DateTimeParsing};
+use crate::error::ScimError;
+use crate::schema::{find_attr, ScimAttributeDefinition, ScimSchema};
+use crate::{ScimAttr, ScimComplexAttr, ScimEntryGeneric, ScimId, ScimValue};
 use serde_json::Value;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
 use std::str::FromStr;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AttrPath {
@@ -28,6 +37,250 @@ pub enum ScimFilter {
     LessOrEqual(AttrPath, Value),
 }
 
+impl FromStr for ScimFilter {
+    type Err = ScimError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        scimfilter::parse(s).map_err(|e| ScimError::InvalidFilter {
+            offset: e.location.offset,
+            expected: e.expected.to_string(),
+            found: s
+                .get(e.location.offset..)
+                .and_then(|rest| rest.chars().next()),
+        })
+    }
+}
+
+impl ScimFilter {
+    /// Evaluate this filter against an entry. String comparisons follow the
+    /// `caseExact` characteristic the schema gives each attribute, and
+    /// attributes the schema doesn't describe are compared case insensitively.
+    pub fn matches(&self, schema: &ScimSchema, entry: &ScimEntryGeneric) -> bool {
+        match self {
+            ScimFilter::Or(a, b) => a.matches(schema, entry) || b.matches(schema, entry),
+            ScimFilter::And(a, b) => a.matches(schema, entry) && b.matches(schema, entry),
+            ScimFilter::Not(f) => !f.matches(schema, entry),
+            ScimFilter::Present(path) => !path.values(entry).is_empty(),
+            ScimFilter::Equal(path, v) => path.any(schema, entry, |a, ce| {
+                compare(a, v, ce) == Some(Ordering::Equal)
+            }),
+            ScimFilter::NotEqual(path, v) => !path.any(schema, entry, |a, ce| {
+                compare(a, v, ce) == Some(Ordering::Equal)
+            }),
+            ScimFilter::Contains(path, v) => path.any(schema, entry, |a, ce| {
+                substr(a, v, ce, |a, v| a.contains(v))
+            }),
+            ScimFilter::StartsWith(path, v) => path.any(schema, entry, |a, ce| {
+                substr(a, v, ce, |a, v| a.starts_with(v))
+            }),
+            ScimFilter::EndsWith(path, v) => path.any(schema, entry, |a, ce| {
+                substr(a, v, ce, |a, v| a.ends_with(v))
+            }),
+            ScimFilter::Greater(path, v) => path.any(schema, entry, |a, ce| {
+                ordered(a) && compare(a, v, ce) == Some(Ordering::Greater)
+            }),
+            ScimFilter::Less(path, v) => path.any(schema, entry, |a, ce| {
+                ordered(a) && compare(a, v, ce) == Some(Ordering::Less)
+            }),
+            ScimFilter::GreaterOrEqual(path, v) => path.any(schema, entry, |a, ce| {
+                ordered(a) && matches!(compare(a, v, ce), Some(Ordering::Greater | Ordering::Equal))
+            }),
+            ScimFilter::LessOrEqual(path, v) => path.any(schema, entry, |a, ce| {
+                ordered(a) && matches!(compare(a, v, ce), Some(Ordering::Less | Ordering::Equal))
+            }),
+        }
+    }
+
+    /// Evaluate this filter against one value of a multi-valued attribute,
+    /// as in the value filter of a path such as `emails[type eq "work"]`.
+    /// The value's schema isn't known, so strings are compared case
+    /// insensitively.
+    pub(crate) fn matches_value(&self, attrs: &ScimComplexAttr) -> bool {
+        let schema = ScimSchema {
+            id: String::new(),
+            name: None,
+            description: None,
+            attributes: Vec::new(),
+            meta: None,
+        };
+        let entry = ScimEntryGeneric {
+            schemas: Vec::new(),
+            id: ScimId::default(),
+            external_id: None,
+            meta: None,
+            attrs: attrs
+                .iter()
+                .map(|(name, attr)| (name.clone(), ScimValue::Simple(attr.clone())))
+                .collect(),
+        };
+        self.matches(&schema, &entry)
+    }
+}
+
+impl fmt::Display for AttrPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.a)?;
+        if let Some(s) = &self.s {
+            write!(f, ".{}", s)?;
+        }
+        Ok(())
+    }
+}
+
+impl ScimFilter {
+    // Binds tighter as it increases: or, and, then everything else.
+    fn precedence(&self) -> u8 {
+        match self {
+            ScimFilter::Or(..) => 0,
+            ScimFilter::And(..) => 1,
+            _ => 2,
+        }
+    }
+
+    // Operands are grouped where the tree differs from how the filter
+    // would parse without the parentheses, as "or" and "and" associate left.
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>, min: u8) -> fmt::Result {
+        if self.precedence() < min {
+            write!(f, "({})", self)
+        } else {
+            fmt::Display::fmt(self, f)
+        }
+    }
+}
+
+/// The filter in the syntax of RFC7644 section 3.4.2.2, which parses back
+/// to the same filter. Values are written as JSON.
+impl fmt::Display for ScimFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (path, op, value) = match self {
+            ScimFilter::Or(a, b) | ScimFilter::And(a, b) => {
+                let p = self.precedence();
+                a.fmt_operand(f, p)?;
+                f.write_str(if p == 0 { " or " } else { " and " })?;
+                return b.fmt_operand(f, p + 1);
+            }
+            ScimFilter::Not(e) => return write!(f, "not ({})", e),
+            ScimFilter::Present(path) => return write!(f, "{} pr", path),
+            ScimFilter::Equal(path, v) => (path, "eq", v),
+            ScimFilter::NotEqual(path, v) => (path, "ne", v),
+            ScimFilter::Contains(path, v) => (path, "co", v),
+            ScimFilter::StartsWith(path, v) => (path, "sw", v),
+            ScimFilter::EndsWith(path, v) => (path, "ew", v),
+            ScimFilter::Greater(path, v) => (path, "gt", v),
+            ScimFilter::Less(path, v) => (path, "lt", v),
+            ScimFilter::GreaterOrEqual(path, v) => (path, "ge", v),
+            ScimFilter::LessOrEqual(path, v) => (path, "le", v),
+        };
+        write!(f, "{} {} {}", path, op, value)
+    }
+}
+
+impl AttrPath {
+    /// The definition this path refers to. A complex attribute without a
+    /// sub-attribute refers to its `value`.
+    fn definition<'a>(&self, schema: &'a ScimSchema) -> Option<&'a ScimAttributeDefinition> {
+        let def = schema.attribute(&self.a)?;
+        match &self.s {
+            Some(s) => def.sub_attribute(s),
+            None if !def.sub_attributes.is_empty() => def.sub_attribute("value"),
+            None => Some(def),
+        }
+    }
+
+    fn case_exact(&self, schema: &ScimSchema) -> bool {
+        match self.definition(schema) {
+            Some(def) => def.case_exact,
+            // id and externalId are caseExact in RFC7643 section 3.1
+            None => self.s.is_none() && (self.a == "id" || self.a == "externalId"),
+        }
+    }
+
+    fn values<'a>(&self, entry: &'a ScimEntryGeneric) -> Vec<Cow<'a, ScimAttr>> {
+        if self.s.is_none() {
+            if self.a.eq_ignore_ascii_case("id") {
+                return vec![Cow::Owned(ScimAttr::String(entry.id.to_string()))];
+            }
+            if self.a.eq_ignore_ascii_case("externalId") {
+                return entry
+                    .external_id
+                    .iter()
+                    .map(|e| Cow::Owned(ScimAttr::String(e.clone())))
+                    .collect();
+            }
+        }
+
+        let sub = self.s.as_deref().unwrap_or("value");
+        let mut values = match find_attr(&entry.attrs, &self.a) {
+            Some(ScimValue::Simple(a)) if self.s.is_none() => vec![Cow::Borrowed(a)],
+            Some(ScimValue::MultiSimple(a)) if self.s.is_none() => {
+                a.iter().map(Cow::Borrowed).collect()
+            }
+            Some(ScimValue::Complex(c)) => {
+                find_attr(c, sub).map(Cow::Borrowed).into_iter().collect()
+            }
+            Some(ScimValue::MultiComplex(c)) => c
+                .iter()
+                .filter_map(|c| find_attr(c, sub))
+                .map(Cow::Borrowed)
+                .collect(),
+            _ => Vec::new(),
+        };
+        values.retain(|a| !a.is_null());
+        values
+    }
+
+    fn any<F>(&self, schema: &ScimSchema, entry: &ScimEntryGeneric, f: F) -> bool
+    where
+        F: Fn(&ScimAttr, bool) -> bool,
+    {
+        let case_exact = self.case_exact(schema);
+        self.values(entry).iter().any(|a| f(a, case_exact))
+    }
+}
+
+fn fold(s: &str, case_exact: bool) -> Cow<'_, str> {
+    if case_exact {
+        Cow::Borrowed(s)
+    } else {
+        Cow::Owned(s.to_lowercase())
+    }
+}
+
+/// gt, lt, ge and le are not defined for boolean and binary attributes.
+fn ordered(a: &ScimAttr) -> bool {
+    !matches!(a, ScimAttr::Bool(_) | ScimAttr::Binary(_))
+}
+
+fn compare(a: &ScimAttr, v: &Value, case_exact: bool) -> Option<Ordering> {
+    match (a, v) {
+        (ScimAttr::String(a), Value::String(v)) => {
+            Some(fold(a, case_exact).cmp(&fold(v, case_exact)))
+        }
+        (ScimAttr::Reference(a), Value::String(v)) => Some(a.as_str().cmp(v.as_str())),
+        (ScimAttr::Bool(a), Value::Bool(v)) => Some(a.cmp(v)),
+        (ScimAttr::Integer(a), Value::Number(v)) => match v.as_i64() {
+            Some(v) => Some(a.cmp(&v)),
+            None => (*a as f64).partial_cmp(&v.as_f64()?),
+        },
+        (ScimAttr::Decimal(a), Value::Number(v)) => a.partial_cmp(&v.as_f64()?),
+        (ScimAttr::DateTime(a), Value::String(v)) => {
+            Some(a.cmp(&datetime::parse(v, DateTimeParsing::Lenient).ok()?))
+        }
+        _ => None,
+    }
+}
+
+fn substr<F>(a: &ScimAttr, v: &Value, case_exact: bool, f: F) -> bool
+where
+    F: Fn(&str, &str) -> bool,
+{
+    match (a, v) {
+        (ScimAttr::String(a), Value::String(v)) => f(&fold(a, case_exact), &fold(v, case_exact)),
+        (ScimAttr::Reference(a), Value::String(v)) => f(a.as_str(), v),
+        _ => false,
+    }
+}
+
 // separator()* "(" e:term() ")" separator()* { e }
 
 peg::parser! {
@@ -163,6 +416,22 @@ mod test {
         assert!(scimfilter::attrname(".abcd").is_err());
     }
 
+    #[test]
+    fn test_scimfilter_display() {
+        for f in [
+            r#"userName eq "bjensen""#,
+            "name.familyName co \"J\" and meta.lastModified gt \"2011-05-13T04:42:34Z\"",
+            "title pr or userType eq \"Intern\" and emails pr",
+            "(title pr or userType eq \"Intern\") and emails pr",
+            "title pr or (userType eq \"Intern\" or emails pr)",
+            "not (active eq false) and age ge 21",
+        ] {
+            let filter: ScimFilter = f.parse().expect("Invalid filter");
+            assert_eq!(filter.to_string(), f);
+            assert_eq!(filter.to_string().parse::<ScimFilter>(), Ok(filter));
+        }
+    }
+
     #[test]
     fn test_scimfilter_pres() {
         assert!(
@@ -504,4 +773,67 @@ mod test {
             ))
         );
     }
+
+    #[test]
+    fn test_scimfilter_matches_case_exact() {
+        use crate::constants::RFC7643_USER;
+        use crate::schema::AttributeType;
+
+        let u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+
+        let mut schema = ScimSchema {
+            id: "urn:ietf:params:scim:schemas:core:2.0:User".to_string(),
+            name: None,
+            description: None,
+            attributes: vec![
+                ScimAttributeDefinition::new("userName", AttributeType::String),
+                ScimAttributeDefinition::new("nickName", AttributeType::String),
+            ],
+            meta: None,
+        };
+        schema.attributes[1].case_exact = true;
+
+        let f = |s: &str| ScimFilter::from_str(s).expect("Failed to parse filter");
+
+        assert!(f("userName eq \"BJensen@Example.com\"").matches(&schema, &u));
+        assert!(f("userName sw \"BJENSEN\"").matches(&schema, &u));
+        assert!(f("nickName eq \"Babs\"").matches(&schema, &u));
+        assert!(!f("nickName eq \"babs\"").matches(&schema, &u));
+        assert!(f("externalId eq \"701984\"").matches(&schema, &u));
+        assert!(f("emails co \"JENSEN.ORG\"").matches(&schema, &u));
+        assert!(!f("emails.type eq \"work\" and not (title pr)").matches(&schema, &u));
+        assert!(f("emails.type eq \"WORK\"").matches(&schema, &u));
+        assert!(!f("nickName ne \"Babs\"").matches(&schema, &u));
+
+        let mut u = u;
+        u.attrs.insert(
+            "userName".to_string(),
+            ScimValue::Simple(ScimAttr::String("Ärzte".to_string())),
+        );
+        assert!(f("userName eq \"ÄRZTE\"").matches(&schema, &u));
+        assert!(f("userName sw \"är\"").matches(&schema, &u));
+
+        let err = ScimFilter::from_str("userName eq").expect_err("Filter is incomplete");
+        assert!(matches!(
+            err,
+            ScimError::InvalidFilter {
+                offset: 11,
+                found: None,
+                ..
+            }
+        ));
+        let err = ScimFilter::from_str("userName xx \"bjensen\"").expect_err("Unknown operator");
+        assert!(matches!(
+            err,
+            ScimError::InvalidFilter {
+                offset: 9,
+                found: Some('x'),
+                ..
+            }
+        ));
+        assert!(err
+            .to_string()
+            .starts_with("invalid filter at offset 9: expected "));
+    }
 }
@@ -0,0 +1,190 @@
+//! Entries as flat maps of paths to values, for CSV export, environment
+//! style configuration, and diffing line by line.
+//!
+//! Each key is the path of a simple value: sub-attributes follow a `.`, and
+//! values of an array their index in brackets, as in `emails[0].value`. An
+//! extension's attributes follow its URN and a `:`, as in
+//! `urn:ietf:params:scim:schemas:extension:enterprise:2.0:User:manager.value`.
+
+use crate::convert;
+use crate::error::ScimError;
+use crate::ScimEntryGeneric;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Flatten `value` to the paths of its simple values. Empty arrays and
+/// objects have no simple values, so they aren't kept.
+pub fn flatten(value: &Value) -> BTreeMap<String, Value> {
+    let mut flat = BTreeMap::new();
+    flatten_into(value, String::new(), false, &mut flat);
+    flat
+}
+
+fn flatten_into(value: &Value, path: String, urn: bool, flat: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(attrs) => {
+            let separator = if urn { ":" } else { "." };
+            for (name, value) in attrs.iter() {
+                // Only an extension, at the top, is named by a URN.
+                let (path, urn) = if path.is_empty() {
+                    (name.clone(), name.contains(':'))
+                } else {
+                    (format!("{}{}{}", path, separator, name), false)
+                };
+                flatten_into(value, path, urn, flat);
+            }
+        }
+        Value::Array(values) => {
+            for (idx, value) in values.iter().enumerate() {
+                flatten_into(value, format!("{}[{}]", path, idx), false, flat);
+            }
+        }
+        _ => {
+            flat.insert(path, value.clone());
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment<'a> {
+    Name(&'a str),
+    Index(usize),
+}
+
+fn segments(path: &str) -> Result<Vec<Segment<'_>>, ScimError> {
+    let mut segments = Vec::new();
+    // An extension's URN is everything up to the last ':'.
+    let rest = match path.rsplit_once(':') {
+        Some((urn, rest)) => {
+            segments.push(Segment::Name(urn));
+            rest
+        }
+        None => path,
+    };
+
+    for part in rest.split('.') {
+        let (name, mut indices) = part.split_once('[').unwrap_or((part, ""));
+        if name.is_empty() {
+            return Err(ScimError::InvalidPath);
+        }
+        segments.push(Segment::Name(name));
+        while !indices.is_empty() {
+            let (idx, rest) = indices.split_once(']').ok_or(ScimError::InvalidPath)?;
+            let idx = idx.parse().map_err(|_| ScimError::InvalidPath)?;
+            segments.push(Segment::Index(idx));
+            indices = match rest {
+                "" => "",
+                rest => rest.strip_prefix('[').ok_or(ScimError::InvalidPath)?,
+            };
+        }
+    }
+    Ok(segments)
+}
+
+// An index can't be more than the number of values, which bounds the
+// arrays a map can make.
+fn insert(
+    node: &mut Value,
+    segments: &[Segment<'_>],
+    value: Value,
+    max_index: usize,
+) -> Result<(), ScimError> {
+    let Some((segment, rest)) = segments.split_first() else {
+        // Two paths that name the same value.
+        if !node.is_null() {
+            return Err(ScimError::InvalidPath);
+        }
+        *node = value;
+        return Ok(());
+    };
+
+    if node.is_null() {
+        *node = match segment {
+            Segment::Name(_) => Value::Object(Map::new()),
+            Segment::Index(_) => Value::Array(Vec::new()),
+        };
+    }
+    match (segment, node) {
+        (Segment::Name(name), Value::Object(attrs)) => {
+            let child = attrs.entry(name.to_string()).or_insert(Value::Null);
+            insert(child, rest, value, max_index)
+        }
+        (Segment::Index(idx), Value::Array(values)) if *idx < max_index => {
+            if values.len() <= *idx {
+                values.resize(idx + 1, Value::Null);
+            }
+            insert(&mut values[*idx], rest, value, max_index)
+        }
+        _ => Err(ScimError::InvalidPath),
+    }
+}
+
+/// Rebuild the value [`flatten`] gave `flat`. Indices missing from an array
+/// are filled with nulls.
+pub fn unflatten(flat: &BTreeMap<String, Value>) -> Result<Value, ScimError> {
+    let mut value = Value::Object(Map::new());
+    for (path, v) in flat.iter() {
+        insert(&mut value, &segments(path)?, v.clone(), flat.len())?;
+    }
+    Ok(value)
+}
+
+impl ScimEntryGeneric {
+    /// The entry as the paths of its simple values, including the common
+    /// attributes such as `schemas[0]` and `meta.created`. See [`flatten`].
+    pub fn flatten(&self) -> Result<BTreeMap<String, Value>, ScimError> {
+        let value = convert::to_value(self)?;
+        Ok(flatten(&value))
+    }
+
+    /// The entry a map from [`ScimEntryGeneric::flatten`] was made from.
+    pub fn unflatten(flat: &BTreeMap<String, Value>) -> Result<Self, ScimError> {
+        convert::from_value(unflatten(flat)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::RFC7643_USER;
+    use serde_json::json;
+
+    #[test]
+    fn flatten_entry() {
+        let mut u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let enterprise = "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User";
+        u.set_path(
+            &format!("{}:manager.value", enterprise),
+            crate::ScimValue::Simple(crate::ScimAttr::String("26118915".to_string())),
+        )
+        .expect("Failed to set path");
+
+        let flat = u.flatten().expect("Failed to flatten");
+        assert_eq!(flat["userName"], json!("bjensen@example.com"));
+        assert_eq!(flat["name.givenName"], json!("Barbara"));
+        assert_eq!(flat["emails[1].type"], json!("home"));
+        assert_eq!(
+            flat["schemas[0]"],
+            json!(crate::constants::SCIM_SCHEMA_USER)
+        );
+        assert_eq!(
+            flat[&format!("{}:manager.value", enterprise)],
+            json!("26118915")
+        );
+        assert!(flat.keys().all(|k| !k.ends_with("emails")));
+
+        let back = ScimEntryGeneric::unflatten(&flat).expect("Failed to unflatten");
+        assert_eq!(back, u);
+
+        let flat = BTreeMap::from([
+            ("a[1][0]".to_string(), json!(1)),
+            ("b".to_string(), json!(true)),
+        ]);
+        assert_eq!(unflatten(&flat), Ok(json!({"a": [null, [1]], "b": true})));
+        for bad in ["a[x]", "a[0", "a.b", "a[5]", "b..c"] {
+            let flat = BTreeMap::from([("a".to_string(), json!(1)), (bad.to_string(), json!(2))]);
+            assert_eq!(unflatten(&flat), Err(ScimError::InvalidPath), "{}", bad);
+        }
+    }
+}
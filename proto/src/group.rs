@@ -1,24 +1,208 @@
-use crate::ScimEntry;
+use crate::attr_map::AttrMap;
+use crate::constants::SCIM_SCHEMA_GROUP;
+use crate::convert;
+use crate::error::ScimError;
+use crate::resource::ScimResource;
+use crate::schema::{SchemaViolation, ViolationKind};
+use crate::user::canonical_type;
+use crate::{ScimEntry, ScimEntryGeneric, ScimId, ScimValue};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use url::Url;
 use uuid::Uuid;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct Member {
-    value: Uuid,
+canonical_type! {
+    /// The kind of resource a group member is, from the `type` sub-attribute
+    /// of `members`.
+    MemberType { User => "User", Group => "Group" }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub value: ScimId,
     #[serde(rename = "$ref")]
-    ref_: Url,
-    display: String,
+    #[serde(alias = "ref", alias = "reference")]
+    pub ref_: Url,
+    pub display: String,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<MemberType>,
+}
+
+impl Member {
+    pub fn new(value: impl Into<ScimId>, ref_: Url, display: &str) -> Self {
+        Member {
+            value: value.into(),
+            ref_,
+            display: display.to_string(),
+            type_: None,
+        }
+    }
+
+    /// A member that is a User.
+    pub fn user(value: impl Into<ScimId>, ref_: Url, display: &str) -> Self {
+        Member {
+            type_: Some(MemberType::User),
+            ..Member::new(value, ref_, display)
+        }
+    }
+
+    /// A member that is a nested Group.
+    pub fn group(value: impl Into<ScimId>, ref_: Url, display: &str) -> Self {
+        Member {
+            type_: Some(MemberType::Group),
+            ..Member::new(value, ref_, display)
+        }
+    }
+
+    pub fn is_group(&self) -> bool {
+        self.type_ == Some(MemberType::Group)
+    }
+}
+
+impl<I: Into<ScimId>> From<(I, Url, &str)> for Member {
+    fn from((value, ref_, display): (I, Url, &str)) -> Self {
+        Member::new(value, ref_, display)
+    }
+}
+
+impl<I: Into<ScimId>> From<(I, Url, String)> for Member {
+    fn from((value, ref_, display): (I, Url, String)) -> Self {
+        Member {
+            value: value.into(),
+            ref_,
+            display,
+            type_: None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-struct Group {
+pub struct Group {
+    #[serde(flatten)]
+    pub entry: ScimEntry,
+
+    pub display_name: String,
+    pub members: Vec<Member>,
+    /// Attributes this type doesn't define, such as extensions, kept so that
+    /// they survive a round trip.
     #[serde(flatten)]
-    entry: ScimEntry,
+    pub extra: AttrMap<ScimValue>,
+}
+
+impl Group {
+    /// Start building a group. See [`GroupBuilder`].
+    pub fn builder(display_name: &str) -> GroupBuilder {
+        GroupBuilder {
+            group: Group {
+                entry: ScimEntry {
+                    schemas: vec![SCIM_SCHEMA_GROUP.to_string()],
+                    id: Uuid::nil().into(),
+                    external_id: None,
+                    meta: None,
+                },
+                display_name: display_name.to_string(),
+                members: Vec::new(),
+                extra: AttrMap::new(),
+            },
+        }
+    }
+
+    /// The members that are nested groups.
+    pub fn nested_groups(&self) -> impl Iterator<Item = &Member> {
+        self.members.iter().filter(|m| m.is_group())
+    }
+}
+
+impl TryFrom<ScimEntryGeneric> for Group {
+    type Error = ScimError;
 
-    display_name: String,
-    members: Vec<Member>,
+    fn try_from(entry: ScimEntryGeneric) -> Result<Self, Self::Error> {
+        convert::convert(&entry)
+    }
+}
+
+impl TryFrom<Group> for ScimEntryGeneric {
+    type Error = ScimError;
+
+    fn try_from(group: Group) -> Result<Self, Self::Error> {
+        convert::convert(&group)
+    }
+}
+
+impl ScimResource for Group {
+    const SCHEMA_URN: &'static str = SCIM_SCHEMA_GROUP;
+
+    fn resource_type() -> &'static str {
+        "Group"
+    }
+
+    fn into_entry(self) -> Result<ScimEntryGeneric, ScimError> {
+        ScimEntryGeneric::try_from(self)
+    }
+}
+
+/// The group in the compact form of [`ScimEntryGeneric`]'s `Display`.
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::display::write_resource(f, self)
+    }
+}
+
+/// Builds a [`Group`], with a nil id that the service provider replaces on
+/// creation.
+#[derive(Debug, Clone)]
+pub struct GroupBuilder {
+    group: Group,
+}
+
+impl GroupBuilder {
+    pub fn id(mut self, id: impl Into<ScimId>) -> Self {
+        self.group.entry.id = id.into();
+        self
+    }
+
+    pub fn external_id(mut self, external_id: &str) -> Self {
+        self.group.entry.external_id = Some(external_id.to_string());
+        self
+    }
+
+    pub fn member(mut self, member: impl Into<Member>) -> Self {
+        self.group.members.push(member.into());
+        self
+    }
+
+    pub fn members<I>(mut self, members: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Member>,
+    {
+        self.group
+            .members
+            .extend(members.into_iter().map(Into::into));
+        self
+    }
+
+    /// Check the required attributes and produce the group. A member may
+    /// only be listed once.
+    pub fn build(self) -> Result<Group, ScimError> {
+        let group = self.group;
+
+        if group.display_name.trim().is_empty() {
+            let missing = SchemaViolation::new("displayName", ViolationKind::MissingRequired);
+            return Err(missing.into());
+        }
+
+        let mut seen = std::collections::BTreeSet::new();
+        if let Some(m) = group.members.iter().find(|m| !seen.insert(&m.value)) {
+            let value = m.value.to_string();
+            let duplicate =
+                SchemaViolation::new("members", ViolationKind::DuplicateValue { value });
+            return Err(duplicate.into());
+        }
+
+        Ok(group)
+    }
 }
 
 #[cfg(test)]
@@ -36,5 +220,103 @@ mod tests {
 
         let s = serde_json::to_string_pretty(&g).expect("Failed to serialise RFC7643_USER");
         eprintln!("{}", s);
+
+        assert_eq!(g.members[0].type_, None);
+        assert_eq!(g.nested_groups().count(), 0);
+        assert!(g.extra.is_empty());
+
+        let mut v: serde_json::Value =
+            serde_json::from_str(RFC7643_GROUP).expect("Failed to parse RFC7643_GROUP");
+        v["costCode"] = serde_json::json!("4130");
+        let g: Group = serde_json::from_value(v.clone()).expect("Failed to parse group");
+        assert_eq!(g.extra.len(), 1);
+        let round_trip = serde_json::to_value(&g).expect("Failed to serialise");
+        assert_eq!(round_trip["costCode"], v["costCode"]);
+
+        // Members of service providers such as Azure AD have opaque ids.
+        v["members"][0]["value"] = serde_json::json!("abc123");
+        let g: Group = serde_json::from_value(v).expect("Failed to parse group");
+        assert_eq!(g.members[0].value, ScimId::from("abc123"));
+    }
+
+    #[test]
+    fn member_type() {
+        let mut v: serde_json::Value =
+            serde_json::from_str(RFC7643_GROUP).expect("Failed to parse RFC7643_GROUP");
+        v["members"][0]["type"] = serde_json::json!("User");
+        v["members"][1]["type"] = serde_json::json!("Group");
+
+        let g: Group = serde_json::from_value(v).expect("Failed to parse group");
+        assert_eq!(g.members[0].type_, Some(MemberType::User));
+        assert!(g.members[1].is_group());
+        assert_eq!(
+            g.nested_groups()
+                .map(|m| m.display.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Mandy Pepperidge"]
+        );
+
+        let v = serde_json::to_value(&g).expect("Failed to serialise");
+        assert_eq!(v["members"][0]["type"], "User");
+        assert_eq!(v["members"][1]["type"], "Group");
+
+        // Types are matched case insensitively, and others are kept.
+        let mut v = v;
+        v["members"][0]["type"] = serde_json::json!("user");
+        v["members"][1]["type"] = serde_json::json!("ServicePrincipal");
+        let g: Group = serde_json::from_value(v).expect("Failed to parse group");
+        assert_eq!(g.members[0].type_, Some(MemberType::User));
+        assert_eq!(
+            g.members[1].type_,
+            Some(MemberType::Custom("ServicePrincipal".to_string()))
+        );
+        let v = serde_json::to_value(&g).expect("Failed to serialise");
+        assert_eq!(v["members"][0]["type"], "User");
+        assert_eq!(v["members"][1]["type"], "ServicePrincipal");
+
+        let m = Member::new(Uuid::nil(), g.members[0].ref_.clone(), "Babs");
+        let v = serde_json::to_value(m).expect("Failed to serialise");
+        assert!(v.get("type").is_none());
+    }
+
+    #[test]
+    fn build_group() {
+        let a = Uuid::from_u128(1);
+        let b = Uuid::from_u128(2);
+        let url = |id: Uuid| {
+            Url::parse(&format!("https://example.com/v2/Users/{}", id)).expect("Invalid url")
+        };
+
+        let g = Group::builder("Tour Guides")
+            .member((a, url(a), "Babs Jensen"))
+            .members([Member::new(b, url(b), "Mandy Pepperidge")])
+            .build()
+            .expect("Failed to build group");
+
+        assert_eq!(g.display_name, "Tour Guides");
+        assert_eq!(g.entry.schemas, vec![SCIM_SCHEMA_GROUP]);
+        assert_eq!(g.members.len(), 2);
+        assert_eq!(g.members[0].display, "Babs Jensen");
+
+        let v = serde_json::to_value(&g).expect("Failed to serialise");
+        assert_eq!(v["members"][1]["$ref"], url(b).as_str());
+
+        let shown = g.to_string();
+        assert!(shown.contains("\ndisplayName: Tour Guides\n"));
+        assert!(shown.contains("\n  - $ref: https://example.com/v2/Users/"));
+
+        let value = a.to_string();
+        assert!(matches!(
+            Group::builder("Tour Guides")
+                .members([(a, url(a), "Babs"), (a, url(a), "Babs")])
+                .build(),
+            Err(ScimError::InvalidValue(v))
+                if v == SchemaViolation::new("members", ViolationKind::DuplicateValue { value })
+        ));
+        assert!(matches!(
+            Group::builder("").build(),
+            Err(ScimError::InvalidValue(v))
+                if v == SchemaViolation::new("displayName", ViolationKind::MissingRequired)
+        ));
     }
 }
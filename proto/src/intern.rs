@@ -0,0 +1,200 @@
+//! Entries with shared attribute names, for holding many entries at once,
+//! such as during a bulk load. A parsed entry has a string of its own for
+//! every attribute and sub-attribute name and every schema URN, though
+//! across hundreds of thousands of entries there are only a few hundred of
+//! them. An [`Interner`] keeps one of each, which the [`InternedEntry`]s it
+//! makes share.
+
+use crate::attr_map::AttrMap;
+use crate::redact::AttrPairs;
+use crate::{ScimAttr, ScimComplexAttr, ScimEntryGeneric, ScimId, ScimMeta, ScimValue};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+/// Attributes by shared name, in the order of the map they came from.
+pub type InternedAttrs<V> = Box<[(Arc<str>, V)]>;
+
+/// A [`ScimValue`] with shared names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InternedValue {
+    Simple(ScimAttr),
+    Complex(InternedAttrs<ScimAttr>),
+    MultiSimple(Box<[ScimAttr]>),
+    MultiComplex(Box<[InternedAttrs<ScimAttr>]>),
+    Extension(InternedAttrs<InternedValue>),
+}
+
+/// A [`ScimEntryGeneric`] with shared names, made by [`Interner::entry`].
+/// It is for storing entries; [`InternedEntry::to_entry`] gives the entry
+/// back to work with.
+#[derive(Clone, PartialEq, Eq)]
+pub struct InternedEntry {
+    pub schemas: Box<[Arc<str>]>,
+    pub id: ScimId,
+    pub external_id: Option<String>,
+    pub meta: Option<ScimMeta>,
+    pub attrs: InternedAttrs<InternedValue>,
+}
+
+/// The values of sensitive attributes aren't shown, as for
+/// [`ScimEntryGeneric`].
+impl fmt::Debug for InternedEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InternedEntry")
+            .field("schemas", &self.schemas)
+            .field("id", &self.id)
+            .field("external_id", &self.external_id)
+            .field("meta", &self.meta)
+            .field("attrs", &AttrPairs(self.attrs.iter().map(|(k, v)| (k, v))))
+            .finish()
+    }
+}
+
+/// The names shared by the entries it has made. Names are kept as they are
+/// written, so names that differ only in case are kept apart.
+#[derive(Debug, Default, Clone)]
+pub struct Interner {
+    names: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    /// The shared copy of `name`.
+    pub fn intern(&mut self, name: &str) -> Arc<str> {
+        if let Some(name) = self.names.get(name) {
+            return name.clone();
+        }
+        let name: Arc<str> = Arc::from(name);
+        self.names.insert(name.clone());
+        name
+    }
+
+    /// The number of names kept.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    fn attrs<V, I>(
+        &mut self,
+        attrs: AttrMap<V>,
+        mut value: impl FnMut(&mut Self, V) -> I,
+    ) -> InternedAttrs<I> {
+        attrs
+            .into_iter()
+            .map(|(name, v)| (self.intern(&name), value(self, v)))
+            .collect()
+    }
+
+    fn complex(&mut self, attrs: ScimComplexAttr) -> InternedAttrs<ScimAttr> {
+        self.attrs(attrs, |_, attr| attr)
+    }
+
+    fn value(&mut self, value: ScimValue) -> InternedValue {
+        match value {
+            ScimValue::Simple(attr) => InternedValue::Simple(attr),
+            ScimValue::Complex(attrs) => InternedValue::Complex(self.complex(attrs)),
+            ScimValue::MultiSimple(values) => InternedValue::MultiSimple(values.into()),
+            ScimValue::MultiComplex(values) => InternedValue::MultiComplex(
+                values
+                    .into_iter()
+                    .map(|attrs| self.complex(attrs))
+                    .collect(),
+            ),
+            ScimValue::Extension(attrs) => {
+                InternedValue::Extension(self.attrs(attrs, Interner::value))
+            }
+        }
+    }
+
+    /// `entry`, with its names shared with the other entries of this
+    /// interner.
+    pub fn entry(&mut self, entry: ScimEntryGeneric) -> InternedEntry {
+        InternedEntry {
+            schemas: entry.schemas.iter().map(|s| self.intern(s)).collect(),
+            id: entry.id,
+            external_id: entry.external_id,
+            meta: entry.meta,
+            attrs: self.attrs(entry.attrs, Interner::value),
+        }
+    }
+}
+
+fn to_map<V, O>(attrs: &InternedAttrs<V>, value: impl Fn(&V) -> O) -> AttrMap<O> {
+    attrs
+        .iter()
+        .map(|(name, v)| (name.to_string(), value(v)))
+        .collect()
+}
+
+impl InternedValue {
+    pub fn to_value(&self) -> ScimValue {
+        match self {
+            InternedValue::Simple(attr) => ScimValue::Simple(attr.clone()),
+            InternedValue::Complex(attrs) => ScimValue::Complex(to_map(attrs, ScimAttr::clone)),
+            InternedValue::MultiSimple(values) => ScimValue::MultiSimple(values.to_vec()),
+            InternedValue::MultiComplex(values) => ScimValue::MultiComplex(
+                values
+                    .iter()
+                    .map(|attrs| to_map(attrs, ScimAttr::clone))
+                    .collect(),
+            ),
+            InternedValue::Extension(attrs) => {
+                ScimValue::Extension(to_map(attrs, InternedValue::to_value))
+            }
+        }
+    }
+}
+
+impl InternedEntry {
+    /// The attribute `name`, in any case.
+    pub fn attr(&self, name: &str) -> Option<&InternedValue> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    pub fn to_entry(&self) -> ScimEntryGeneric {
+        ScimEntryGeneric {
+            schemas: self.schemas.iter().map(|s| s.to_string()).collect(),
+            id: self.id.clone(),
+            external_id: self.external_id.clone(),
+            meta: self.meta.clone(),
+            attrs: to_map(&self.attrs, InternedValue::to_value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::RFC7643_USER;
+
+    #[test]
+    fn interned_entries() {
+        let u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let mut interner = Interner::new();
+        let a = interner.entry(u.clone());
+        let names = interner.len();
+        let b = interner.entry(u.clone());
+        assert_eq!(interner.len(), names);
+
+        assert!(Arc::ptr_eq(&a.schemas[0], &b.schemas[0]));
+        let emails = |e: &InternedEntry| match e.attr("EMAILS") {
+            Some(InternedValue::MultiComplex(values)) => values.clone(),
+            _ => Box::default(),
+        };
+        assert!(Arc::ptr_eq(&emails(&a)[0][0].0, &emails(&b)[0][0].0));
+
+        assert_eq!(a.to_entry(), u);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::schema::{AttributeType, Mutability, ScimAttributeDefinition, ScimSchema};
+use serde_json::{json, Map, Value};
+
+// https://json-schema.org/draft/2020-12/json-schema-core
+
+pub const JSON_SCHEMA_DRAFT_2020_12: &str = "https://json-schema.org/draft/2020-12/schema";
+
+impl ScimSchema {
+    /// Convert this schema to a JSON Schema (draft 2020-12) document that
+    /// validates resources of this schema, such as the output of
+    /// [`crate::registry::SchemaRegistry::merged`]. JSON Schema property names
+    /// are case sensitive, so only the spelling used by the schema is
+    /// described.
+    pub fn to_json_schema(&self) -> Value {
+        let mut schema = Map::new();
+        schema.insert("$schema".to_string(), json!(JSON_SCHEMA_DRAFT_2020_12));
+        schema.insert("$id".to_string(), json!(self.id));
+        if let Value::Object(body) = self.json_schema_body() {
+            schema.extend(body);
+        }
+        Value::Object(schema)
+    }
+
+    /// The JSON Schema of a resource, without the `$schema` and `$id`
+    /// keywords, so it can be embedded in other documents.
+    pub(crate) fn json_schema_body(&self) -> Value {
+        let mut properties = Map::new();
+        properties.insert(
+            "schemas".to_string(),
+            json!({ "type": "array", "items": { "type": "string" }, "minItems": 1 }),
+        );
+        properties.insert(
+            "id".to_string(),
+            json!({ "type": "string", "readOnly": true }),
+        );
+        properties.insert("externalId".to_string(), json!({ "type": "string" }));
+        properties.insert(
+            "meta".to_string(),
+            json!({ "type": "object", "readOnly": true }),
+        );
+
+        let mut schema = object_schema(&self.attributes, properties);
+        if let Value::Object(schema) = &mut schema {
+            if let Some(title) = &self.name {
+                schema.insert("title".to_string(), json!(title));
+            }
+            if let Some(description) = &self.description {
+                schema.insert("description".to_string(), json!(description));
+            }
+            let mut required = vec![json!("schemas")];
+            if let Some(Value::Array(attrs)) = schema.remove("required") {
+                required.extend(attrs);
+            }
+            schema.insert("required".to_string(), Value::Array(required));
+        }
+        schema
+    }
+}
+
+fn object_schema(
+    attributes: &[ScimAttributeDefinition],
+    mut properties: Map<String, Value>,
+) -> Value {
+    let required: Vec<_> = attributes
+        .iter()
+        .filter(|def| def.required)
+        .map(|def| json!(def.name))
+        .collect();
+
+    for def in attributes {
+        properties.insert(def.name.clone(), attribute_schema(def));
+    }
+
+    let mut schema = Map::new();
+    schema.insert("type".to_string(), json!("object"));
+    schema.insert("properties".to_string(), Value::Object(properties));
+    if !required.is_empty() {
+        schema.insert("required".to_string(), Value::Array(required));
+    }
+    Value::Object(schema)
+}
+
+fn value_schema(def: &ScimAttributeDefinition) -> Value {
+    match def.type_ {
+        AttributeType::String => json!({ "type": "string" }),
+        AttributeType::Boolean => json!({ "type": "boolean" }),
+        AttributeType::Decimal => json!({ "type": "number" }),
+        AttributeType::Integer => json!({ "type": "integer" }),
+        AttributeType::DateTime => json!({ "type": "string", "format": "date-time" }),
+        AttributeType::Binary => json!({ "type": "string", "contentEncoding": "base64" }),
+        AttributeType::Reference => json!({ "type": "string", "format": "uri-reference" }),
+        AttributeType::Complex => object_schema(&def.sub_attributes, Map::new()),
+    }
+}
+
+fn attribute_schema(def: &ScimAttributeDefinition) -> Value {
+    let mut schema = if def.multi_valued {
+        json!({ "type": "array", "items": value_schema(def) })
+    } else {
+        value_schema(def)
+    };
+
+    if let Value::Object(schema) = &mut schema {
+        if let Some(description) = &def.description {
+            schema.insert("description".to_string(), json!(description));
+        }
+        // canonicalValues are only suggestions, so they can't be an enum.
+        if !def.canonical_values.is_empty() {
+            schema.insert("examples".to_string(), json!(def.canonical_values));
+        }
+        match def.mutability {
+            Mutability::ReadOnly => {
+                schema.insert("readOnly".to_string(), json!(true));
+            }
+            Mutability::WriteOnly => {
+                schema.insert("writeOnly".to_string(), json!(true));
+            }
+            Mutability::ReadWrite | Mutability::Immutable => {}
+        }
+    }
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builtin::{group_schema, user_schema};
+    use serde_json::json;
+
+    #[test]
+    fn json_schema_group() {
+        let schema = group_schema().to_json_schema();
+
+        assert_eq!(schema["$schema"], super::JSON_SCHEMA_DRAFT_2020_12);
+        assert_eq!(schema["$id"], "urn:ietf:params:scim:schemas:core:2.0:Group");
+        assert_eq!(schema["required"], json!(["schemas"]));
+        assert_eq!(
+            schema["properties"]["displayName"],
+            json!({ "type": "string" })
+        );
+
+        let members = &schema["properties"]["members"];
+        assert_eq!(members["type"], "array");
+        assert_eq!(members["items"]["type"], "object");
+        assert_eq!(
+            members["items"]["properties"]["$ref"],
+            json!({ "type": "string", "format": "uri-reference" })
+        );
+        assert_eq!(
+            members["items"]["properties"]["type"]["examples"],
+            json!(["User", "Group"])
+        );
+    }
+
+    #[test]
+    fn json_schema_user() {
+        let schema = user_schema().to_json_schema();
+
+        assert_eq!(schema["required"], json!(["schemas", "userName"]));
+        assert_eq!(schema["properties"]["password"]["writeOnly"], true);
+        assert_eq!(schema["properties"]["groups"]["readOnly"], true);
+        assert_eq!(schema["properties"]["active"], json!({ "type": "boolean" }));
+        assert_eq!(
+            schema["properties"]["x509Certificates"]["items"]["properties"]["value"]
+                ["contentEncoding"],
+            "base64"
+        );
+    }
+}
@@ -0,0 +1,495 @@
+use crate::error::ScimError;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+// https://datatracker.ietf.org/doc/html/rfc5646#section-2.1
+
+// Tags registered before RFC4646 that don't follow the ABNF, section 2.2.8.
+const GRANDFATHERED: &[&str] = &[
+    "en-gb-oed",
+    "i-ami",
+    "i-bnn",
+    "i-default",
+    "i-enochian",
+    "i-hak",
+    "i-klingon",
+    "i-lux",
+    "i-mingo",
+    "i-navajo",
+    "i-pwn",
+    "i-tao",
+    "i-tay",
+    "i-tsu",
+    "sgn-be-fr",
+    "sgn-be-nl",
+    "sgn-ch-de",
+    "art-lojban",
+    "cel-gaulish",
+    "no-bok",
+    "no-nyn",
+    "zh-guoyu",
+    "zh-hakka",
+    "zh-min",
+    "zh-min-nan",
+    "zh-xiang",
+];
+
+/// An RFC5646 (BCP 47) language tag, such as `en-US`, `fr-CA` or
+/// `zh-Hant-TW`, as used by the `locale` attribute. The tag is kept as it
+/// was given, and compared case insensitively.
+#[derive(Debug, Clone)]
+pub struct Locale {
+    tag: String,
+    language: Option<String>,
+    script: Option<String>,
+    region: Option<String>,
+    variants: Vec<String>,
+}
+
+fn is_alpha(s: &str, min: usize, max: usize) -> bool {
+    (min..=max).contains(&s.len()) && s.bytes().all(|b| b.is_ascii_alphabetic())
+}
+
+fn is_alphanum(s: &str, min: usize, max: usize) -> bool {
+    (min..=max).contains(&s.len()) && s.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+fn is_digit(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn is_variant(s: &str) -> bool {
+    is_alphanum(s, 5, 8)
+        || (s.len() == 4 && s.as_bytes()[0].is_ascii_digit() && is_alphanum(s, 4, 4))
+}
+
+/// `x-` followed by at least one subtag of up to eight characters.
+fn is_private_use(subtags: &[&str]) -> bool {
+    subtags.len() > 1 && subtags[1..].iter().all(|s| is_alphanum(s, 1, 8))
+}
+
+impl Locale {
+    pub fn parse(tag: &str) -> Result<Self, ScimError> {
+        let lower = tag.to_ascii_lowercase();
+        let mut locale = Locale {
+            tag: tag.to_string(),
+            language: None,
+            script: None,
+            region: None,
+            variants: Vec::new(),
+        };
+
+        if GRANDFATHERED.contains(&lower.as_str()) {
+            return Ok(locale);
+        }
+
+        let subtags: Vec<&str> = lower.split('-').collect();
+        if subtags[0] == "x" {
+            return if is_private_use(&subtags) {
+                Ok(locale)
+            } else {
+                Err(ScimError::InvalidAttribute)
+            };
+        }
+
+        let mut rest = subtags.as_slice();
+
+        // language = 2*3ALPHA ["-" extlang] / 4ALPHA / 5*8ALPHA
+        match rest.split_first() {
+            Some((language, tail)) if is_alpha(language, 2, 8) => {
+                locale.language = Some(language.to_string());
+                rest = tail;
+                if language.len() <= 3 {
+                    // extlang = 3ALPHA *2("-" 3ALPHA)
+                    let extlangs = rest
+                        .iter()
+                        .take(3)
+                        .take_while(|s| is_alpha(s, 3, 3))
+                        .count();
+                    rest = &rest[extlangs..];
+                }
+            }
+            _ => return Err(ScimError::InvalidAttribute),
+        }
+
+        if let Some((script, tail)) = rest.split_first() {
+            if is_alpha(script, 4, 4) {
+                locale.script = Some(script.to_string());
+                rest = tail;
+            }
+        }
+
+        if let Some((region, tail)) = rest.split_first() {
+            if is_alpha(region, 2, 2) || is_digit(region, 3) {
+                locale.region = Some(region.to_string());
+                rest = tail;
+            }
+        }
+
+        while let Some((variant, tail)) = rest.split_first() {
+            if !is_variant(variant) {
+                break;
+            }
+            // Variants must not repeat, section 2.2.5.
+            if locale.variants.iter().any(|v| v == variant) {
+                return Err(ScimError::InvalidAttribute);
+            }
+            locale.variants.push(variant.to_string());
+            rest = tail;
+        }
+
+        // extension = singleton 1*("-" (2*8alphanum))
+        let mut singletons = Vec::new();
+        while let Some((singleton, tail)) = rest.split_first() {
+            if singleton.len() != 1 || *singleton == "x" || !is_alphanum(singleton, 1, 1) {
+                break;
+            }
+            if singletons.contains(singleton) {
+                return Err(ScimError::InvalidAttribute);
+            }
+            singletons.push(*singleton);
+
+            let len = tail.iter().take_while(|s| is_alphanum(s, 2, 8)).count();
+            if len == 0 {
+                return Err(ScimError::InvalidAttribute);
+            }
+            rest = &tail[len..];
+        }
+
+        match rest.first() {
+            None => Ok(locale),
+            Some(&"x") if is_private_use(rest) => Ok(locale),
+            Some(_) => Err(ScimError::InvalidAttribute),
+        }
+    }
+
+    /// The tag as it was given.
+    pub fn as_str(&self) -> &str {
+        &self.tag
+    }
+
+    /// The primary language subtag, in lower case. Private use and
+    /// grandfathered tags have none.
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    pub fn script(&self) -> Option<&str> {
+        self.script.as_deref()
+    }
+
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+
+    pub fn variants(&self) -> impl Iterator<Item = &str> {
+        self.variants.iter().map(String::as_str)
+    }
+}
+
+impl PartialEq for Locale {
+    fn eq(&self, other: &Self) -> bool {
+        self.tag.eq_ignore_ascii_case(&other.tag)
+    }
+}
+
+impl Eq for Locale {}
+
+impl FromStr for Locale {
+    type Err = ScimError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Locale::parse(s)
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.tag)
+    }
+}
+
+impl Serialize for Locale {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.tag)
+    }
+}
+
+impl<'de> Deserialize<'de> for Locale {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let tag = String::deserialize(deserializer)?;
+        Locale::parse(&tag).map_err(|_| D::Error::custom(format!("invalid language tag '{}'", tag)))
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7231#section-5.3.5
+
+/// One language range of a [`PreferredLanguage`], with its weight.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LanguageRange {
+    range: String,
+    quality: u16,
+}
+
+impl LanguageRange {
+    /// The range, such as `en-US` or the `*` wildcard.
+    pub fn range(&self) -> &str {
+        &self.range
+    }
+
+    /// The weight of the range in thousandths, 1000 when no weight is given.
+    pub fn quality(&self) -> u16 {
+        self.quality
+    }
+
+    pub fn is_wildcard(&self) -> bool {
+        self.range == "*"
+    }
+
+    /// The primary language of the range, `en` for `en-US`.
+    pub fn language(&self) -> Option<&str> {
+        if self.is_wildcard() {
+            None
+        } else {
+            self.range.split('-').next()
+        }
+    }
+}
+
+// qvalue = ( "0" [ "." 0*3DIGIT ] ) / ( "1" [ "." 0*3("0") ] )
+fn parse_qvalue(q: &str) -> Option<u16> {
+    let (int, frac) = q.split_once('.').unwrap_or((q, ""));
+    if frac.len() > 3 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let thousandths = format!("{:0<3}", frac).parse::<u16>().ok()?;
+    match int {
+        "0" => Some(thousandths),
+        "1" if thousandths == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+fn parse_range(item: &str) -> Option<LanguageRange> {
+    let mut parts = item.split(';');
+    let range = parts.next()?.trim();
+
+    // language-range = (1*8ALPHA *("-" 1*8alphanum)) / "*"
+    let mut subtags = range.split('-');
+    let valid = range == "*"
+        || (subtags.next().map(|s| is_alpha(s, 1, 8)).unwrap_or(false)
+            && subtags.all(|s| is_alphanum(s, 1, 8)));
+    if !valid {
+        return None;
+    }
+
+    let quality = match parts.next() {
+        None => 1000,
+        Some(weight) => {
+            let q = weight.trim();
+            let q = q.strip_prefix("q=").or_else(|| q.strip_prefix("Q="))?;
+            parse_qvalue(q)?
+        }
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some(LanguageRange {
+        range: range.to_string(),
+        quality,
+    })
+}
+
+/// The value of the `preferredLanguage` attribute, which is formatted as an
+/// HTTP `Accept-Language` header, such as `en-US, en;q=0.9, *;q=0.1`. The
+/// value is kept as it was given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreferredLanguage {
+    value: String,
+    ranges: Vec<LanguageRange>,
+}
+
+impl PreferredLanguage {
+    pub fn parse(value: &str) -> Result<Self, ScimError> {
+        let ranges = value
+            .split(',')
+            .map(str::trim)
+            // The list syntax of RFC7230 allows empty elements.
+            .filter(|item| !item.is_empty())
+            .map(|item| parse_range(item).ok_or(ScimError::InvalidAttribute))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if ranges.is_empty() {
+            return Err(ScimError::InvalidAttribute);
+        }
+
+        Ok(PreferredLanguage {
+            value: value.to_string(),
+            ranges,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+
+    /// The language ranges, most preferred first. Ranges of equal weight
+    /// keep the order they were given in.
+    pub fn ranges(&self) -> Vec<&LanguageRange> {
+        let mut ranges: Vec<_> = self.ranges.iter().collect();
+        ranges.sort_by_key(|range| std::cmp::Reverse(range.quality));
+        ranges
+    }
+
+    /// The primary language of the most preferred range that isn't the
+    /// wildcard or marked as not acceptable with `q=0`.
+    pub fn primary_language(&self) -> Option<&str> {
+        self.ranges()
+            .into_iter()
+            .filter(|range| range.quality > 0)
+            .find_map(LanguageRange::language)
+    }
+}
+
+impl FromStr for PreferredLanguage {
+    type Err = ScimError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PreferredLanguage::parse(s)
+    }
+}
+
+impl fmt::Display for PreferredLanguage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.value)
+    }
+}
+
+impl Serialize for PreferredLanguage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.value)
+    }
+}
+
+impl<'de> Deserialize<'de> for PreferredLanguage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        PreferredLanguage::parse(&value)
+            .map_err(|_| D::Error::custom(format!("invalid language range '{}'", value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_parse() {
+        for tag in [
+            "en",
+            "en-US",
+            "fr-CA",
+            "pt-BR",
+            "zh-Hant-TW",
+            "sr-Latn-RS",
+            "es-419",
+            "sl-rozaj-biske",
+            "de-CH-1901",
+            "zh-yue-HK",
+            "en-US-u-ca-gregory-x-private",
+            "x-whatever",
+            "i-klingon",
+            "en-GB-oed",
+        ] {
+            let locale = Locale::parse(tag).expect("Failed to parse tag");
+            assert_eq!(locale.as_str(), tag);
+        }
+
+        for tag in [
+            "",
+            "e",
+            "en-",
+            "en--US",
+            "toolongtag",
+            "en-US-US",
+            "1en",
+            "x",
+            "x-",
+            "en-a",
+            "en-a-bc-a-de",
+            "de-1901-1901",
+            "en-US-x-toolongtag",
+        ] {
+            assert_eq!(
+                Locale::parse(tag),
+                Err(ScimError::InvalidAttribute),
+                "{}",
+                tag
+            );
+        }
+
+        let locale = Locale::parse("zh-Hant-TW").expect("Failed to parse tag");
+        assert_eq!(locale.language(), Some("zh"));
+        assert_eq!(locale.script(), Some("hant"));
+        assert_eq!(locale.region(), Some("tw"));
+
+        let locale = Locale::parse("sl-rozaj-biske").expect("Failed to parse tag");
+        assert_eq!(
+            locale.variants().collect::<Vec<_>>(),
+            vec!["rozaj", "biske"]
+        );
+        assert_eq!(locale.region(), None);
+
+        assert_eq!(Locale::parse("EN-us"), Locale::parse("en-US"));
+        assert!(serde_json::from_str::<Locale>("\"en_US\"").is_err());
+        let locale: Locale = serde_json::from_str("\"pt-BR\"").expect("Failed to parse");
+        assert_eq!(
+            serde_json::to_string(&locale).expect("Failed to serialise"),
+            "\"pt-BR\""
+        );
+    }
+
+    #[test]
+    fn preferred_language_parse() {
+        let pl = PreferredLanguage::parse("en-US, en;q=0.9, *;q=0.1").expect("Failed to parse");
+        assert_eq!(pl.as_str(), "en-US, en;q=0.9, *;q=0.1");
+        assert_eq!(pl.primary_language(), Some("en"));
+        let ranges: Vec<_> = pl
+            .ranges()
+            .iter()
+            .map(|r| (r.range(), r.quality()))
+            .collect();
+        assert_eq!(ranges, vec![("en-US", 1000), ("en", 900), ("*", 100)]);
+
+        let pl = PreferredLanguage::parse("da, en-gb;q=0.8, en;q=0.7").expect("Failed to parse");
+        assert_eq!(pl.primary_language(), Some("da"));
+
+        let pl = PreferredLanguage::parse("fr;q=0.5, *, de;q=0").expect("Failed to parse");
+        assert_eq!(pl.primary_language(), Some("fr"));
+        assert_eq!(
+            PreferredLanguage::parse("*, de;q=0").map(|pl| pl.primary_language().is_none()),
+            Ok(true)
+        );
+
+        for value in [
+            "",
+            " , ",
+            "en;q=2",
+            "en;q=0.1234",
+            "en;q=1.5",
+            "en;x=1",
+            "en-",
+            "e n",
+        ] {
+            assert_eq!(
+                PreferredLanguage::parse(value),
+                Err(ScimError::InvalidAttribute),
+                "{}",
+                value
+            );
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use crate::codegen::to_pascal;
+use crate::schema::{AttributeType, SchemaError, ScimAttributeDefinition, ScimSchema};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+// https://datatracker.ietf.org/doc/html/rfc4512#section-4.1
+// https://datatracker.ietf.org/doc/html/rfc4517#section-3.3
+
+const SYNTAX_DIRECTORY_STRING: &str = "1.3.6.1.4.1.1466.115.121.1.15";
+const SYNTAX_BOOLEAN: &str = "1.3.6.1.4.1.1466.115.121.1.7";
+const SYNTAX_INTEGER: &str = "1.3.6.1.4.1.1466.115.121.1.27";
+const SYNTAX_GENERALIZED_TIME: &str = "1.3.6.1.4.1.1466.115.121.1.24";
+const SYNTAX_OCTET_STRING: &str = "1.3.6.1.4.1.1466.115.121.1.40";
+const SYNTAX_IA5_STRING: &str = "1.3.6.1.4.1.1466.115.121.1.26";
+
+/// How to number and name the definitions produced by
+/// [`ScimSchema::to_ldap`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LdapSchemaOptions {
+    /// Attribute types are numbered `<oid_arc>.1.n` and the object class is
+    /// `<oid_arc>.2.1`.
+    pub oid_arc: String,
+    /// The `n` of each attribute path such as `name.givenName`. When empty,
+    /// attribute types are numbered by their position in the schema, so
+    /// adding, removing or reordering a SCIM attribute renumbers every later
+    /// one; pin the numbers here before deploying the schema. When not empty,
+    /// every attribute must have an entry.
+    pub oids: BTreeMap<String, u32>,
+    /// Prefixed to generated names, so `name.givenName` becomes
+    /// `scimNameGivenName`.
+    pub name_prefix: String,
+    /// Names for attribute paths such as `name.givenName`, replacing the
+    /// generated name.
+    pub names: BTreeMap<String, String>,
+    /// The object class name, otherwise generated from the schema name.
+    pub object_class: Option<String>,
+    /// Emit a STRUCTURAL rather than an AUXILIARY object class.
+    pub structural: bool,
+}
+
+impl LdapSchemaOptions {
+    pub fn new(oid_arc: &str) -> Self {
+        LdapSchemaOptions {
+            oid_arc: oid_arc.to_string(),
+            oids: BTreeMap::new(),
+            name_prefix: "scim".to_string(),
+            names: BTreeMap::new(),
+            object_class: None,
+            structural: false,
+        }
+    }
+}
+
+/// RFC4512 `attributeTypes` and `objectClasses` values.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LdapSchema {
+    pub attribute_types: Vec<String>,
+    pub object_classes: Vec<String>,
+}
+
+fn is_oid(oid: &str) -> bool {
+    !oid.is_empty()
+        && oid
+            .split('.')
+            .all(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn is_descr(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|c| c.is_ascii_alphabetic())
+        .unwrap_or(false)
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Escape a qdstring, RFC4512 section 4.1.
+fn quote(s: &str) -> String {
+    s.replace('\\', "\\5C").replace('\'', "\\27")
+}
+
+/// The EQUALITY, ORDERING, SUBSTR and SYNTAX of an attribute type.
+fn matching(def: &ScimAttributeDefinition) -> String {
+    match def.type_ {
+        AttributeType::String if def.case_exact => format!(
+            "EQUALITY caseExactMatch SUBSTR caseExactSubstringsMatch SYNTAX {}",
+            SYNTAX_DIRECTORY_STRING
+        ),
+        AttributeType::String => format!(
+            "EQUALITY caseIgnoreMatch SUBSTR caseIgnoreSubstringsMatch SYNTAX {}",
+            SYNTAX_DIRECTORY_STRING
+        ),
+        AttributeType::Boolean => format!("EQUALITY booleanMatch SYNTAX {}", SYNTAX_BOOLEAN),
+        AttributeType::Integer => format!(
+            "EQUALITY integerMatch ORDERING integerOrderingMatch SYNTAX {}",
+            SYNTAX_INTEGER
+        ),
+        // LDAP has no decimal syntax, so these are held as strings.
+        AttributeType::Decimal => {
+            format!("EQUALITY caseExactMatch SYNTAX {}", SYNTAX_DIRECTORY_STRING)
+        }
+        AttributeType::DateTime => format!(
+            "EQUALITY generalizedTimeMatch ORDERING generalizedTimeOrderingMatch SYNTAX {}",
+            SYNTAX_GENERALIZED_TIME
+        ),
+        AttributeType::Binary => {
+            format!("EQUALITY octetStringMatch SYNTAX {}", SYNTAX_OCTET_STRING)
+        }
+        AttributeType::Reference | AttributeType::Complex => {
+            format!("EQUALITY caseExactIA5Match SYNTAX {}", SYNTAX_IA5_STRING)
+        }
+    }
+}
+
+impl ScimSchema {
+    /// Convert this schema to LDAP attribute types and an object class.
+    /// Complex attributes are flattened, giving an attribute type for each
+    /// sub-attribute. Values of a multi-valued complex attribute are not
+    /// correlated once flattened, so the sub-attributes of `emails` become
+    /// independent multi-valued attributes.
+    pub fn to_ldap(&self, options: &LdapSchemaOptions) -> Result<LdapSchema, SchemaError> {
+        if !is_oid(&options.oid_arc) {
+            return Err(SchemaError::InvalidOid(options.oid_arc.clone()));
+        }
+
+        let mut ldap = LdapSchema::default();
+        let mut must = Vec::new();
+        let mut may = Vec::new();
+
+        let leaves = self.attributes.iter().flat_map(|def| {
+            let subs: Vec<_> = if def.type_ == AttributeType::Complex {
+                def.sub_attributes
+                    .iter()
+                    .map(|sub| (def, Some(sub)))
+                    .collect()
+            } else {
+                vec![(def, None)]
+            };
+            subs
+        });
+
+        for (n, (def, sub)) in leaves.enumerate() {
+            let (path, generated, leaf) = match sub {
+                Some(sub) => (
+                    format!("{}.{}", def.name, sub.name),
+                    format!(
+                        "{}{}{}",
+                        options.name_prefix,
+                        to_pascal(&def.name),
+                        to_pascal(&sub.name)
+                    ),
+                    sub,
+                ),
+                None => (
+                    def.name.clone(),
+                    format!("{}{}", options.name_prefix, to_pascal(&def.name)),
+                    def,
+                ),
+            };
+
+            let name = options.names.get(&path).cloned().unwrap_or(generated);
+            if !is_descr(&name) {
+                return Err(SchemaError::InvalidName(name));
+            }
+
+            let number = if options.oids.is_empty() {
+                n + 1
+            } else {
+                match options.oids.get(&path) {
+                    Some(number) => *number as usize,
+                    None => return Err(SchemaError::MissingOid(path)),
+                }
+            };
+
+            let mut attribute_type = format!("( {}.1.{} NAME '{}'", options.oid_arc, number, name);
+            if let Some(description) = &leaf.description {
+                let _ = write!(attribute_type, " DESC '{}'", quote(description));
+            }
+            let _ = write!(attribute_type, " {}", matching(leaf));
+            if !(def.multi_valued || leaf.multi_valued) {
+                attribute_type.push_str(" SINGLE-VALUE");
+            }
+            attribute_type.push_str(" )");
+            ldap.attribute_types.push(attribute_type);
+
+            if sub.is_none() && def.required {
+                must.push(name);
+            } else {
+                may.push(name);
+            }
+        }
+
+        let class_name = match &options.object_class {
+            Some(name) => name.clone(),
+            None => {
+                let base = self
+                    .name
+                    .as_deref()
+                    .or_else(|| self.id.rsplit(':').next())
+                    .unwrap_or_default();
+                format!("{}{}", options.name_prefix, to_pascal(base))
+            }
+        };
+        if !is_descr(&class_name) {
+            return Err(SchemaError::InvalidName(class_name));
+        }
+
+        let mut object_class = format!("( {}.2.1 NAME '{}'", options.oid_arc, class_name);
+        if let Some(description) = &self.description {
+            let _ = write!(object_class, " DESC '{}'", quote(description));
+        }
+        let kind = if options.structural {
+            "STRUCTURAL"
+        } else {
+            "AUXILIARY"
+        };
+        let _ = write!(object_class, " SUP top {}", kind);
+        for (keyword, names) in [("MUST", &must), ("MAY", &may)] {
+            match names.as_slice() {
+                [] => {}
+                [name] => {
+                    let _ = write!(object_class, " {} {}", keyword, name);
+                }
+                names => {
+                    let _ = write!(object_class, " {} ( {} )", keyword, names.join(" $ "));
+                }
+            }
+        }
+        object_class.push_str(" )");
+        ldap.object_classes.push(object_class);
+
+        Ok(ldap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::{group_schema, user_schema};
+
+    #[test]
+    fn ldap_group() {
+        let options = LdapSchemaOptions::new("1.3.6.1.4.1.99999.1");
+        let ldap = group_schema().to_ldap(&options).expect("Failed to convert");
+
+        assert_eq!(
+            ldap.attribute_types,
+            vec![
+                "( 1.3.6.1.4.1.99999.1.1.1 NAME 'scimDisplayName' EQUALITY caseIgnoreMatch SUBSTR caseIgnoreSubstringsMatch SYNTAX 1.3.6.1.4.1.1466.115.121.1.15 SINGLE-VALUE )",
+                "( 1.3.6.1.4.1.99999.1.1.2 NAME 'scimMembersValue' EQUALITY caseIgnoreMatch SUBSTR caseIgnoreSubstringsMatch SYNTAX 1.3.6.1.4.1.1466.115.121.1.15 )",
+                "( 1.3.6.1.4.1.99999.1.1.3 NAME 'scimMembersRef' EQUALITY caseExactIA5Match SYNTAX 1.3.6.1.4.1.1466.115.121.1.26 )",
+                "( 1.3.6.1.4.1.99999.1.1.4 NAME 'scimMembersType' EQUALITY caseIgnoreMatch SUBSTR caseIgnoreSubstringsMatch SYNTAX 1.3.6.1.4.1.1466.115.121.1.15 )",
+            ]
+        );
+        assert_eq!(
+            ldap.object_classes,
+            vec!["( 1.3.6.1.4.1.99999.1.2.1 NAME 'scimGroup' DESC 'Group' SUP top AUXILIARY MAY ( scimDisplayName $ scimMembersValue $ scimMembersRef $ scimMembersType ) )"]
+        );
+    }
+
+    #[test]
+    fn ldap_user_options() {
+        let mut options = LdapSchemaOptions::new("1.2.3");
+        options
+            .names
+            .insert("userName".to_string(), "uid".to_string());
+        options.object_class = Some("scimPerson".to_string());
+        options.structural = true;
+
+        let ldap = user_schema().to_ldap(&options).expect("Failed to convert");
+        assert!(ldap.attribute_types[0].starts_with("( 1.2.3.1.1 NAME 'uid' "));
+        assert!(ldap.object_classes[0]
+            .starts_with("( 1.2.3.2.1 NAME 'scimPerson' DESC 'User Account' SUP top STRUCTURAL MUST uid MAY ( scimNameFormatted $ "));
+        assert!(ldap
+            .attribute_types
+            .iter()
+            .any(|a| a.contains("'scimActive' EQUALITY booleanMatch")));
+
+        options.oid_arc = "1.2.x".to_string();
+        assert_eq!(
+            user_schema().to_ldap(&options),
+            Err(SchemaError::InvalidOid("1.2.x".to_string()))
+        );
+        options.oid_arc = "1.2.3".to_string();
+        options
+            .names
+            .insert("title".to_string(), "not valid".to_string());
+        assert_eq!(
+            user_schema().to_ldap(&options),
+            Err(SchemaError::InvalidName("not valid".to_string()))
+        );
+    }
+
+    #[test]
+    fn ldap_pinned_oids() {
+        let mut options = LdapSchemaOptions::new("1.2.3");
+        options.oids = [
+            ("displayName", 7),
+            ("members.value", 1),
+            ("members.$ref", 2),
+        ]
+        .into_iter()
+        .map(|(path, n)| (path.to_string(), n))
+        .collect();
+        assert_eq!(
+            group_schema().to_ldap(&options),
+            Err(SchemaError::MissingOid("members.type".to_string()))
+        );
+
+        options.oids.insert("members.type".to_string(), 3);
+        let ldap = group_schema().to_ldap(&options).expect("Failed to convert");
+        assert!(ldap.attribute_types[0].starts_with("( 1.2.3.1.7 NAME 'scimDisplayName' "));
+        assert!(ldap.attribute_types[3].starts_with("( 1.2.3.1.3 NAME 'scimMembersType' "));
+    }
+}
@@ -10,43 +10,226 @@
 #![deny(clippy::needless_pass_by_value)]
 #![deny(clippy::trivially_copy_pass_by_ref)]
 
+use attr_map::AttrMap;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use schema::AttributeType;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::fmt;
+use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 use url::Url;
 use uuid::Uuid;
 
+// Lets code from the ScimResource derive and the codegen module in this
+// crate's tests name it as ::scim_proto.
+#[cfg(test)]
+extern crate self as scim_proto;
+
+pub mod attr_map;
+pub mod borrowed;
+pub mod builtin;
+pub mod bulk;
+pub mod canonical;
+pub mod change;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod codegen;
+mod compact;
+pub mod config;
 pub mod constants;
+pub mod content_type;
+mod convert;
+pub mod datetime;
+mod de;
+mod display;
+pub mod dynamic;
+pub mod error;
+pub mod external_id;
 pub mod filter;
+pub mod flatten;
 pub mod group;
+pub mod intern;
+pub mod json_schema;
+pub mod language;
+pub mod ldap;
+pub mod messages;
+pub mod openapi;
+pub mod path;
+#[cfg(feature = "phone")]
+pub mod phone;
+pub mod projection;
+pub mod query;
+pub mod redact;
+pub mod reference;
+pub mod registry;
+pub mod resource;
+pub mod schema;
+pub mod schema_diff;
+mod ser;
+pub mod shared;
+mod size;
 pub mod user;
+pub mod warning;
+#[cfg(feature = "x509")]
+pub mod x509;
+
+// Used by code from the codegen module and the ScimResource derive, so that
+// it only depends on this crate.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::attr_map::AttrMap;
+    pub use crate::convert::convert;
+    pub use crate::datetime;
+    pub use crate::resource::{put_attr, take_attr};
+    pub use serde;
+    pub use serde_json;
+    pub use time;
+    pub use url;
+}
 
 pub mod prelude {
+    pub use crate::bulk::{BulkLimits, BulkMethod, BulkOperation, BulkRequest, BulkResponse};
     pub use crate::constants::*;
-    pub use crate::{ScimAttr, ScimComplexAttr, ScimEntry, ScimMeta, ScimValue};
+    pub use crate::error::{ScimError, ScimErrors, ScimType};
+    pub use crate::group::{Group, Member, MemberType};
+    pub use crate::messages::{
+        ScimErrorResponse, ScimListResponse, ScimListResponseRef, ScimMessageSchema,
+        ScimQueryParams, ScimResponse, ScimSearchRequest, SortOrder,
+    };
+    pub use crate::query::{ListQuery, SortSpec};
+    pub use crate::resource::{Extended, ScimExtension, ScimResource};
+    pub use crate::user::User;
+    pub use crate::warning::{ScimWarning, WarningKind};
+    pub use crate::{
+        ScimAttr, ScimComplexAttr, ScimEntry, ScimEntryBuilder, ScimEntryCreate, ScimId, ScimMeta,
+        ScimValue,
+    };
     pub use serde_json::value::Number as JsonNumber;
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
-#[serde(untagged)]
+#[derive(Debug, Clone)]
 pub enum ScimAttr {
+    /// An explicit JSON null, which RFC7643 section 2.5 treats as
+    /// unassigned. In a PUT or PATCH it clears the attribute.
+    Null,
     Bool(bool),
     Decimal(f64),
     Integer(i64),
     String(String),
     // These can't be implicitly decoded because we may not know the intent, but we can *encode* them.
-    // That's why "String" is above this because it catches anything during deserialization before
-    // this point.
+    // That's why they are read as "String" (see the de module). See ScimAttr::promote_as and
+    // ScimAttr::promote for getting them from strings.
     DateTime(OffsetDateTime),
     Binary(Vec<u8>),
     Reference(Url),
 }
 
+impl ScimAttr {
+    pub fn is_null(&self) -> bool {
+        matches!(self, ScimAttr::Null)
+    }
+
+    /// A string, or the text of a reference.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ScimAttr::String(s) => Some(s),
+            ScimAttr::Reference(u) => Some(u.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ScimAttr::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// An integer, or a decimal without a fraction, as JSON numbers may be
+    /// read as either.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            ScimAttr::Integer(i) => Some(*i),
+            ScimAttr::Decimal(d) if d.fract() == 0.0 => Some(*d as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ScimAttr::Decimal(d) => Some(*d),
+            ScimAttr::Integer(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+
+    /// A date time, or a string that parses as one.
+    pub fn as_datetime(&self) -> Option<OffsetDateTime> {
+        match self {
+            ScimAttr::DateTime(dt) => Some(*dt),
+            ScimAttr::String(s) => datetime::parse(s, datetime::DateTimeParsing::Lenient).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            ScimAttr::Binary(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Convert this value to the form of an attribute of type `type_`:
+    /// strings to date times, references or (base64) binary, and numbers to
+    /// integers or decimals. A value that doesn't convert, or is already of
+    /// the type, is left as it is, for validation to report.
+    pub fn promote_as(&mut self, type_: AttributeType) {
+        let promoted = match (type_, &*self) {
+            (AttributeType::DateTime, ScimAttr::String(s)) => {
+                datetime::parse(s, datetime::DateTimeParsing::Lenient)
+                    .ok()
+                    .map(ScimAttr::DateTime)
+            }
+            // Relative references don't parse as a Url, and stay strings.
+            (AttributeType::Reference, ScimAttr::String(s)) => {
+                Url::parse(s).ok().map(ScimAttr::Reference)
+            }
+            (AttributeType::Binary, ScimAttr::String(s)) => {
+                STANDARD.decode(s).ok().map(ScimAttr::Binary)
+            }
+            (AttributeType::Integer, ScimAttr::Decimal(d)) if d.fract() == 0.0 => {
+                Some(ScimAttr::Integer(*d as i64))
+            }
+            (AttributeType::Decimal, ScimAttr::Integer(i)) => Some(ScimAttr::Decimal(*i as f64)),
+            _ => None,
+        };
+        if let Some(promoted) = promoted {
+            *self = promoted;
+        }
+    }
+
+    /// Convert a string that can only be one thing without a schema: an
+    /// RFC3339 date time, or an absolute http or https URL. Binary values
+    /// can't be told from strings, so need [`ScimAttr::promote_as`].
+    pub fn promote(&mut self) {
+        let ScimAttr::String(s) = &*self else {
+            return;
+        };
+        if OffsetDateTime::parse(s, &Rfc3339).is_ok() {
+            self.promote_as(AttributeType::DateTime);
+        } else if s.starts_with("http://") || s.starts_with("https://") {
+            self.promote_as(AttributeType::Reference);
+        }
+    }
+}
+
 impl Eq for ScimAttr {}
 
 impl PartialEq for ScimAttr {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
+            (ScimAttr::Null, ScimAttr::Null) => true,
             (ScimAttr::String(l), ScimAttr::String(r)) => l == r,
             (ScimAttr::Bool(l), ScimAttr::Bool(r)) => l == r,
             (ScimAttr::Decimal(l), ScimAttr::Decimal(r)) => l == r,
@@ -59,27 +242,120 @@ impl PartialEq for ScimAttr {
     }
 }
 
-pub type ScimComplexAttr = BTreeMap<String, ScimAttr>;
+pub type ScimComplexAttr = AttrMap<ScimAttr>;
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
-#[serde(untagged)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ScimValue {
     Simple(ScimAttr),
     Complex(ScimComplexAttr),
     MultiSimple(Vec<ScimAttr>),
     MultiComplex(Vec<ScimComplexAttr>),
+    /// The attributes of a schema extension, held under the extension's URN.
+    /// Unlike a complex attribute, these can be complex or multi-valued
+    /// themselves, such as the enterprise user's `manager`. An extension with
+    /// only simple attributes is read as [`ScimValue::Complex`].
+    Extension(AttrMap<ScimValue>),
+}
+
+impl ScimValue {
+    /// Whether this is an explicit null, which clears the attribute when it
+    /// is replaced.
+    pub fn is_null(&self) -> bool {
+        matches!(self, ScimValue::Simple(ScimAttr::Null))
+    }
+
+    /// The simple values, one for a single-valued attribute and each of a
+    /// multi-valued one. Complex values and extensions have none.
+    pub fn values(&self) -> std::slice::Iter<'_, ScimAttr> {
+        match self {
+            ScimValue::Simple(attr) => std::slice::from_ref(attr).iter(),
+            ScimValue::MultiSimple(values) => values.iter(),
+            ScimValue::Complex(_) | ScimValue::MultiComplex(_) | ScimValue::Extension(_) => {
+                [].iter()
+            }
+        }
+    }
+
+    /// As [`ScimValue::values`], for complex values.
+    pub fn complex_values(&self) -> std::slice::Iter<'_, ScimComplexAttr> {
+        match self {
+            ScimValue::Complex(attrs) => std::slice::from_ref(attrs).iter(),
+            ScimValue::MultiComplex(values) => values.iter(),
+            ScimValue::Simple(_) | ScimValue::MultiSimple(_) | ScimValue::Extension(_) => [].iter(),
+        }
+    }
 }
 
 impl ScimValue {
     pub fn len(&self) -> usize {
         match self {
-            ScimValue::Simple(_) | ScimValue::Complex(_) => 1,
+            ScimValue::Simple(_) | ScimValue::Complex(_) | ScimValue::Extension(_) => 1,
             ScimValue::MultiSimple(a) => a.len(),
             ScimValue::MultiComplex(a) => a.len(),
         }
     }
-}
 
+    /// Whether this has no value: a null, or an empty array or object.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            ScimValue::Simple(attr) => attr.is_null(),
+            ScimValue::Complex(attrs) => attrs.is_empty(),
+            ScimValue::Extension(attrs) => attrs.is_empty(),
+            ScimValue::MultiSimple(a) => a.is_empty(),
+            ScimValue::MultiComplex(a) => a.is_empty(),
+        }
+    }
+
+    pub fn as_attr(&self) -> Option<&ScimAttr> {
+        match self {
+            ScimValue::Simple(attr) => Some(attr),
+            _ => None,
+        }
+    }
+
+    /// See [`ScimAttr::as_str`].
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_attr().and_then(ScimAttr::as_str)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        self.as_attr().and_then(ScimAttr::as_bool)
+    }
+
+    /// See [`ScimAttr::as_i64`].
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_attr().and_then(ScimAttr::as_i64)
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_attr().and_then(ScimAttr::as_f64)
+    }
+
+    pub fn as_complex(&self) -> Option<&ScimComplexAttr> {
+        match self {
+            ScimValue::Complex(attrs) => Some(attrs),
+            _ => None,
+        }
+    }
+
+    /// The values of a multi-valued simple attribute.
+    pub fn as_multi(&self) -> Option<&[ScimAttr]> {
+        match self {
+            ScimValue::MultiSimple(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// The values of a multi-valued complex attribute. An empty array is
+    /// read as [`ScimValue::MultiSimple`], and is taken as either.
+    pub fn as_multi_complex(&self) -> Option<&[ScimComplexAttr]> {
+        match self {
+            ScimValue::MultiComplex(values) => Some(values),
+            ScimValue::MultiSimple(values) if values.is_empty() => Some(&[]),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "PascalCase")]
@@ -94,36 +370,551 @@ pub enum ScimResourceType {
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct ScimMeta {
     pub resource_type: ScimResourceType,
-    #[serde(with = "time::serde::rfc3339")]
+    #[serde(with = "datetime::lenient")]
     pub created: OffsetDateTime,
-    #[serde(with = "time::serde::rfc3339")]
+    #[serde(with = "datetime::lenient")]
     pub last_modified: OffsetDateTime,
     pub location: Url,
     pub version: String,
 }
 
+impl ScimMeta {
+    /// The meta of a resource created now. Its version is set by
+    /// [`ScimMeta::touch`] or [`ScimEntryGeneric::touch`] once the meta is
+    /// with its resource.
+    pub fn new(resource_type: ScimResourceType, location: Url) -> Self {
+        let now = OffsetDateTime::now_utc();
+        ScimMeta {
+            resource_type,
+            created: now,
+            last_modified: now,
+            location,
+            version: String::new(),
+        }
+    }
+
+    /// Set the version from the content of `entry`, this meta's resource,
+    /// and if it was already set and has changed, set lastModified to now.
+    /// Returns whether the version changed.
+    pub fn touch(&mut self, entry: &ScimEntryGeneric) -> Result<bool, error::ScimError> {
+        let version = entry.version()?;
+        if version == self.version {
+            return Ok(false);
+        }
+        if !self.version.is_empty() {
+            self.last_modified = OffsetDateTime::now_utc();
+        }
+        self.version = version;
+        Ok(true)
+    }
+}
+
+/// The id of a resource. RFC7643 makes ids opaque strings, but most service
+/// providers, and the builders of this crate, use UUIDs, which are kept as a
+/// [`Uuid`]. Only the lowercase hyphenated form is read as one, so that every
+/// id is written back exactly as it was read.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ScimId {
+    Uuid(Uuid),
+    String(String),
+}
+
+impl ScimId {
+    /// The id as a UUID, if it is one in any form [`Uuid::parse_str`]
+    /// accepts.
+    pub fn as_uuid(&self) -> Option<Uuid> {
+        match self {
+            ScimId::Uuid(id) => Some(*id),
+            ScimId::String(id) => Uuid::parse_str(id).ok(),
+        }
+    }
+
+    /// Whether this is the nil UUID, which builders use for ids the service
+    /// provider has yet to assign.
+    pub fn is_nil(&self) -> bool {
+        matches!(self, ScimId::Uuid(id) if id.is_nil())
+    }
+}
+
+impl Default for ScimId {
+    fn default() -> Self {
+        ScimId::Uuid(Uuid::nil())
+    }
+}
+
+impl From<Uuid> for ScimId {
+    fn from(id: Uuid) -> Self {
+        ScimId::Uuid(id)
+    }
+}
+
+impl From<&str> for ScimId {
+    fn from(id: &str) -> Self {
+        match Uuid::parse_str(id) {
+            Ok(uuid) if uuid.hyphenated().to_string() == id => ScimId::Uuid(uuid),
+            _ => ScimId::String(id.to_string()),
+        }
+    }
+}
+
+impl From<String> for ScimId {
+    fn from(id: String) -> Self {
+        match ScimId::from(id.as_str()) {
+            ScimId::Uuid(uuid) => ScimId::Uuid(uuid),
+            ScimId::String(_) => ScimId::String(id),
+        }
+    }
+}
+
+impl PartialEq<Uuid> for ScimId {
+    fn eq(&self, other: &Uuid) -> bool {
+        matches!(self, ScimId::Uuid(id) if id == other)
+    }
+}
+
+impl fmt::Display for ScimId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScimId::Uuid(id) => id.fmt(f),
+            ScimId::String(id) => f.write_str(id),
+        }
+    }
+}
+
+impl Serialize for ScimId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ScimId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(ScimId::from)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct ScimEntry {
     pub schemas: Vec<String>,
-    pub id: Uuid,
+    pub id: ScimId,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub external_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub meta: Option<ScimMeta>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
-#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq)]
 pub struct ScimEntryGeneric {
     pub schemas: Vec<String>,
-    pub id: Uuid,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: ScimId,
     pub external_id: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub meta: Option<ScimMeta>,
+    pub attrs: AttrMap<ScimValue>,
+}
+
+/// The values of sensitive attributes, such as `password`, aren't shown,
+/// see [`redact`].
+impl fmt::Debug for ScimEntryGeneric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScimEntryGeneric")
+            .field("schemas", &self.schemas)
+            .field("id", &self.id)
+            .field("external_id", &self.external_id)
+            .field("meta", &self.meta)
+            .field("attrs", &redact::AttrMapValues(&self.attrs))
+            .finish()
+    }
+}
+
+/// An extension present that `schemas` doesn't list is added to it when
+/// written, as RFC7643 section 3 requires every extension to be listed.
+/// Entries are written straight from their attributes, as a map of known
+/// length.
+impl Serialize for ScimEntryGeneric {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        struct Schemas<'a>(&'a [String], &'a [&'a str]);
+
+        impl Serialize for Schemas<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_seq(
+                    self.0
+                        .iter()
+                        .map(String::as_str)
+                        .chain(self.1.iter().copied()),
+                )
+            }
+        }
+
+        let undeclared = self.undeclared_extensions();
+        let schemas = Schemas(&self.schemas, &undeclared);
+        if !serializer.is_human_readable() {
+            return compact::serialize_entry(self, schemas, serializer);
+        }
+
+        let len = 2
+            + self.external_id.is_some() as usize
+            + self.meta.is_some() as usize
+            + self.attrs.len();
+        let mut map = serializer.serialize_map(Some(len))?;
+        map.serialize_entry("schemas", &schemas)?;
+        map.serialize_entry("id", &self.id)?;
+        if let Some(external_id) = &self.external_id {
+            map.serialize_entry("externalId", external_id)?;
+        }
+        if let Some(meta) = &self.meta {
+            map.serialize_entry("meta", meta)?;
+        }
+        for (name, value) in self.attrs.iter() {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for ScimEntryGeneric {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Entry {
+            schemas: Vec<String>,
+            id: ScimId,
+            external_id: Option<String>,
+            meta: Option<ScimMeta>,
+            #[serde(flatten)]
+            attrs: AttrMap<ScimValue>,
+        }
+
+        if !deserializer.is_human_readable() {
+            return compact::deserialize_entry(deserializer);
+        }
+        let entry = Entry::deserialize(deserializer)?;
+        Ok(ScimEntryGeneric {
+            schemas: entry.schemas,
+            id: entry.id,
+            external_id: entry.external_id,
+            meta: entry.meta,
+            attrs: entry.attrs,
+        })
+    }
+}
+
+// Attribute names are case insensitive, RFC7643 section 2.1, but keys of
+// `attrs` keep the case they were read or inserted with.
+impl ScimEntryGeneric {
+    /// The attribute `name`, in any case.
+    pub fn attr(&self, name: &str) -> Option<&ScimValue> {
+        schema::find_attr(&self.attrs, name)
+    }
+
+    /// The attribute `name`, in any case, as `entry[name]` is without
+    /// panicking. The same as [`ScimEntryGeneric::attr`].
+    pub fn get(&self, name: &str) -> Option<&ScimValue> {
+        self.attr(name)
+    }
+
+    pub fn attr_mut(&mut self, name: &str) -> Option<&mut ScimValue> {
+        schema::find_attr_mut(&mut self.attrs, name)
+    }
+
+    pub fn contains_attr(&self, name: &str) -> bool {
+        self.attr(name).is_some()
+    }
+
+    /// Set attribute `name`, replacing any attribute whose name differs only
+    /// by case, and keeping that attribute's name. Returns the replaced
+    /// value.
+    pub fn insert_attr(&mut self, name: &str, value: ScimValue) -> Option<ScimValue> {
+        schema::insert_attr(&mut self.attrs, name, value)
+    }
+
+    /// Remove attribute `name`, in any case, returning its value.
+    pub fn remove_attr(&mut self, name: &str) -> Option<ScimValue> {
+        let key = schema::key_of(&self.attrs, name)?;
+        attr_map::remove(&mut self.attrs, &key)
+    }
+
+    /// The attributes and their values, ordered by name, or with the
+    /// `indexmap` feature, as they were read or inserted.
+    pub fn iter(&self) -> attr_map::Iter<'_, String, ScimValue> {
+        self.attrs.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> attr_map::IterMut<'_, String, ScimValue> {
+        self.attrs.iter_mut()
+    }
+
+    /// Convert every value that can only be a date time or a URL, with
+    /// [`ScimAttr::promote`]. Prefer [`schema::ScimSchema::promote`] when the
+    /// schema is known, as it also finds binary values, and leaves strings
+    /// that merely look like dates alone.
+    pub fn promote(&mut self) {
+        self.attrs.values_mut().for_each(promote_value);
+    }
+}
+
+/// `entry["userName"]` is the attribute `userName`, in any case. It panics
+/// if there is no such attribute; see [`ScimEntryGeneric::get`].
+impl std::ops::Index<&str> for ScimEntryGeneric {
+    type Output = ScimValue;
+
+    #[allow(clippy::panic)]
+    fn index(&self, name: &str) -> &ScimValue {
+        match self.attr(name) {
+            Some(value) => value,
+            None => panic!("entry has no attribute {}", name),
+        }
+    }
+}
+
+/// A compact, multi-line form for people to read, such as in command line
+/// tools: an attribute per line, with sub-attributes indented below and
+/// each value of a multi-valued attribute listed with a `-`. It isn't meant
+/// to be parsed; use JSON for that.
+impl fmt::Display for ScimEntryGeneric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        display::write_resource(f, self)
+    }
+}
+
+impl IntoIterator for ScimEntryGeneric {
+    type Item = (String, ScimValue);
+    type IntoIter = attr_map::IntoIter<String, ScimValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.attrs.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ScimEntryGeneric {
+    type Item = (&'a String, &'a ScimValue);
+    type IntoIter = attr_map::Iter<'a, String, ScimValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.attrs.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut ScimEntryGeneric {
+    type Item = (&'a String, &'a mut ScimValue);
+    type IntoIter = attr_map::IterMut<'a, String, ScimValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.attrs.iter_mut()
+    }
+}
+
+fn promote_value(value: &mut ScimValue) {
+    match value {
+        ScimValue::Simple(attr) => attr.promote(),
+        ScimValue::MultiSimple(values) => values.iter_mut().for_each(ScimAttr::promote),
+        ScimValue::Complex(attrs) => attrs.values_mut().for_each(ScimAttr::promote),
+        ScimValue::MultiComplex(values) => {
+            for attrs in values.iter_mut() {
+                attrs.values_mut().for_each(ScimAttr::promote);
+            }
+        }
+        ScimValue::Extension(attrs) => attrs.values_mut().for_each(promote_value),
+    }
+}
+
+/// The body of a create request. Unlike [`ScimEntryGeneric`] it has no
+/// `id` or `meta`, which the service provider assigns.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimEntryCreate {
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
     #[serde(flatten)]
-    pub attrs: BTreeMap<String, ScimValue>,
+    pub attrs: AttrMap<ScimValue>,
+}
+
+/// The values of sensitive attributes, such as the `password` of
+/// [`user::User::to_create`], aren't shown, see [`redact`].
+impl fmt::Debug for ScimEntryCreate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScimEntryCreate")
+            .field("schemas", &self.schemas)
+            .field("external_id", &self.external_id)
+            .field("attrs", &redact::AttrMapValues(&self.attrs))
+            .finish()
+    }
+}
+
+impl ScimEntryCreate {
+    /// The request for creating a typed resource. For a
+    /// [`user::User`] with a password, see [`user::User::to_create`].
+    pub fn from_resource<R: resource::ScimResource>(resource: R) -> Result<Self, error::ScimError> {
+        resource.into_entry().map(ScimEntryCreate::from)
+    }
+
+    /// The entry the service provider creates, once it has assigned an id.
+    /// An `id` or `meta` sent by the client is dropped, as RFC7644 section
+    /// 3.3 has the service provider ignore them.
+    pub fn into_entry(mut self, id: ScimId, meta: Option<ScimMeta>) -> ScimEntryGeneric {
+        strip_server_attrs(&mut self.attrs);
+        ScimEntryGeneric {
+            schemas: self.schemas,
+            id,
+            external_id: self.external_id,
+            meta,
+            attrs: self.attrs,
+        }
+    }
+}
+
+fn strip_server_attrs(attrs: &mut AttrMap<ScimValue>) {
+    attrs.retain(|name, _| !name.eq_ignore_ascii_case("id") && !name.eq_ignore_ascii_case("meta"));
+}
+
+impl From<ScimEntryGeneric> for ScimEntryCreate {
+    fn from(entry: ScimEntryGeneric) -> Self {
+        let mut attrs = entry.attrs;
+        strip_server_attrs(&mut attrs);
+        ScimEntryCreate {
+            schemas: entry.schemas,
+            external_id: entry.external_id,
+            attrs,
+        }
+    }
+}
+
+impl ScimEntryGeneric {
+    /// Start building an entry of the resource schema `schema`.
+    pub fn builder(schema: &str) -> ScimEntryBuilder {
+        ScimEntryBuilder {
+            entry: ScimEntryGeneric {
+                schemas: vec![schema.to_string()],
+                id: ScimId::default(),
+                external_id: None,
+                meta: None,
+                attrs: AttrMap::new(),
+            },
+        }
+    }
+}
+
+/// Builds a [`ScimEntryGeneric`] an attribute at a time. Setting an
+/// attribute again, in any case, replaces it.
+#[derive(Debug, Clone)]
+pub struct ScimEntryBuilder {
+    entry: ScimEntryGeneric,
+}
+
+impl ScimEntryBuilder {
+    pub fn id(mut self, id: impl Into<ScimId>) -> Self {
+        self.entry.id = id.into();
+        self
+    }
+
+    pub fn external_id(mut self, external_id: &str) -> Self {
+        self.entry.external_id = Some(external_id.to_string());
+        self
+    }
+
+    pub fn meta(mut self, meta: ScimMeta) -> Self {
+        self.entry.meta = Some(meta);
+        self
+    }
+
+    pub fn attr(mut self, name: &str, value: ScimValue) -> Self {
+        self.entry.insert_attr(name, value);
+        self
+    }
+
+    pub fn attr_str(self, name: &str, value: &str) -> Self {
+        self.attr(name, ScimValue::Simple(ScimAttr::String(value.to_string())))
+    }
+
+    pub fn attr_bool(self, name: &str, value: bool) -> Self {
+        self.attr(name, ScimValue::Simple(ScimAttr::Bool(value)))
+    }
+
+    pub fn attr_integer(self, name: &str, value: i64) -> Self {
+        self.attr(name, ScimValue::Simple(ScimAttr::Integer(value)))
+    }
+
+    /// A complex attribute, such as `name`.
+    pub fn attr_complex(self, name: &str, value: ScimComplexAttr) -> Self {
+        self.attr(name, ScimValue::Complex(value))
+    }
+
+    /// A multi-valued complex attribute, such as `emails`.
+    pub fn attr_multi(self, name: &str, values: Vec<ScimComplexAttr>) -> Self {
+        self.attr(name, ScimValue::MultiComplex(values))
+    }
+
+    /// The attributes of the extension `urn`, which is added to `schemas`.
+    pub fn extension(mut self, urn: &str, attrs: AttrMap<ScimValue>) -> Self {
+        self.entry.set_extension(urn, attrs);
+        self
+    }
+
+    /// Check the entry has well formed attributes, and build it. Names must
+    /// follow RFC7643 section 2.1, the common attributes `id`, `meta` and
+    /// `schemas` must be set through their own methods, and at most one
+    /// value of each multi-valued attribute can be primary.
+    pub fn build(self) -> Result<ScimEntryGeneric, error::ScimError> {
+        let mut entry = self.entry;
+        let extensions: Vec<String> = entry.schemas.iter().skip(1).cloned().collect();
+        let common = |name: &str| {
+            ["id", "meta", "schemas", "externalId"]
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(name))
+        };
+
+        for (name, value) in entry.attrs.iter() {
+            if extensions.iter().any(|urn| urn.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+            let invalid_name = |path: String| {
+                schema::SchemaViolation::new(path, schema::ViolationKind::InvalidName)
+            };
+            if common(name) || !schema::is_valid_attr_name(name) {
+                return Err(invalid_name(name.clone()).into());
+            }
+            let sub_attrs = match value {
+                ScimValue::Complex(attrs) => std::slice::from_ref(attrs),
+                ScimValue::MultiComplex(values) => values.as_slice(),
+                _ => &[],
+            };
+            let invalid_sub = sub_attrs
+                .iter()
+                .flat_map(|attrs| attrs.keys())
+                .find(|n| !schema::is_valid_attr_name(n));
+            if let Some(sub) = invalid_sub {
+                return Err(invalid_name(format!("{}.{}", name, sub)).into());
+            }
+        }
+
+        if let Err(mut violations) = entry.check_primary(schema::PrimaryMode::Strict) {
+            return Err(violations.swap_remove(0).into());
+        }
+        Ok(entry)
+    }
+
+    /// As [`ScimEntryBuilder::build`], also validating the entry against its
+    /// resource schema.
+    pub fn build_with_schema(
+        self,
+        schema: &schema::ScimSchema,
+    ) -> Result<ScimEntryGeneric, error::ScimError> {
+        let entry = self.build()?;
+        match schema.validate(&entry) {
+            Ok(()) => Ok(entry),
+            // A missing attribute is reported before any other violation.
+            Err(mut violations) => {
+                let idx = violations
+                    .iter()
+                    .position(|v| v.kind == schema::ViolationKind::MissingRequired)
+                    .unwrap_or(0);
+                Err(violations.swap_remove(idx).into())
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -143,4 +934,436 @@ mod tests {
         let s = serde_json::to_string_pretty(&u).expect("Failed to serialise RFC7643_USER");
         eprintln!("{}", s);
     }
+
+    #[test]
+    fn scim_id() {
+        let uuid = Uuid::parse_str("2819c223-7f76-453a-919d-413861904646").expect("Invalid uuid");
+        let u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        assert_eq!(u.id, ScimId::Uuid(uuid));
+        assert_eq!(u.id, uuid);
+
+        let mut v: serde_json::Value =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        for id in [
+            "a5c2b1e0f",
+            "2819C223-7F76-453A-919D-413861904646",
+            "urn:uuid:2819c223-7f76-453a-919d-413861904646",
+        ] {
+            v["id"] = serde_json::json!(id);
+            let u: ScimEntryGeneric =
+                serde_json::from_value(v.clone()).expect("Failed to parse entry");
+            assert_eq!(u.id, ScimId::String(id.to_string()));
+            let out = serde_json::to_value(&u).expect("Failed to serialise entry");
+            assert_eq!(out["id"], id);
+        }
+
+        assert_eq!(ScimId::from("a5c2b1e0f").as_uuid(), None);
+        assert_eq!(
+            ScimId::from("2819C223-7F76-453A-919D-413861904646").as_uuid(),
+            Some(uuid)
+        );
+        assert!(ScimId::default().is_nil());
+    }
+
+    #[test]
+    fn scim_entry_create() {
+        let json = r#"{
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+            "id": "chosen-by-client",
+            "externalId": "701984",
+            "userName": "bjensen@example.com"
+        }"#;
+        let create: ScimEntryCreate = serde_json::from_str(json).expect("Failed to parse create");
+        assert_eq!(create.external_id.as_deref(), Some("701984"));
+
+        let id = ScimId::from("2819c223-7f76-453a-919d-413861904646");
+        let entry = create.into_entry(id.clone(), None);
+        assert_eq!(entry.id, id);
+        assert!(!entry.attrs.contains_key("id"));
+        assert!(entry.attrs.contains_key("userName"));
+
+        let u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let create = ScimEntryCreate::from_resource(
+            user::User::try_from(u).expect("Failed to convert user"),
+        )
+        .expect("Failed to convert user");
+        let out = serde_json::to_value(&create).expect("Failed to serialise create");
+        assert!(out.get("id").is_none());
+        assert!(out.get("meta").is_none());
+        assert_eq!(out["externalId"], "701984");
+        assert_eq!(out["userName"], "bjensen@example.com");
+    }
+
+    #[test]
+    fn scim_entry_attr_case() {
+        let mut u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+
+        assert!(u.contains_attr("USERNAME"));
+        assert_eq!(
+            u.attr("username"),
+            Some(&ScimValue::Simple(ScimAttr::String(
+                "bjensen@example.com".to_string()
+            )))
+        );
+
+        let nick = ScimValue::Simple(ScimAttr::String("Babs J".to_string()));
+        assert!(u.insert_attr("NICKNAME", nick.clone()).is_some());
+        assert_eq!(u.attrs.get("nickName"), Some(&nick));
+        assert!(!u.attrs.contains_key("NICKNAME"));
+
+        if let Some(ScimValue::Simple(ScimAttr::String(title))) = u.attr_mut("TITLE") {
+            title.push_str(" II");
+        }
+        assert_eq!(
+            u.attr("title"),
+            Some(&ScimValue::Simple(ScimAttr::String(
+                "Tour Guide II".to_string()
+            )))
+        );
+
+        assert_eq!(u.remove_attr("NICKNAME"), Some(nick));
+        assert!(!u.contains_attr("nickName"));
+        assert_eq!(u.remove_attr("NICKNAME"), None);
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn scim_entry_keeps_order() {
+        let json = r#"{"schemas":["urn:ietf:params:scim:schemas:core:2.0:User"],"id":"2819c223","userName":"bjensen","name":{"givenName":"Barbara","familyName":"Jensen"},"displayName":"Babs","active":true}"#;
+        let mut u: ScimEntryGeneric = serde_json::from_str(json).expect("Failed to parse");
+        assert_eq!(
+            serde_json::to_string(&u).expect("Failed to serialise"),
+            json
+        );
+
+        // Replacing keeps an attribute's place; removing keeps the others'.
+        u.insert_attr(
+            "DISPLAYNAME",
+            ScimValue::Simple(ScimAttr::String("B".to_string())),
+        );
+        u.remove_attr("name");
+        u.insert_attr(
+            "nickName",
+            ScimValue::Simple(ScimAttr::String("Babs".to_string())),
+        );
+        let names: Vec<&str> = u.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["userName", "displayName", "active", "nickName"]);
+    }
+
+    #[test]
+    fn scim_entry_display() {
+        let u: ScimEntryGeneric = serde_json::from_value(serde_json::json!({
+            "userName": "bjensen",
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+            "meta": {
+                "resourceType": "User",
+                "created": "2010-01-23T04:56:22Z",
+                "lastModified": "2011-05-13T04:42:34Z",
+                "location": "https://example.com/v2/Users/2819c223",
+                "version": "W/\"3694e05e9dff590\""
+            },
+            "id": "2819c223",
+            "name": {"givenName": "Barbara", "familyName": "Jensen"},
+            "emails": [
+                {"value": "bjensen@example.com", "type": "work", "primary": true},
+                {"value": "babs@jensen.org"}
+            ],
+            "nickName": null,
+            "roles": [],
+            "active": true
+        }))
+        .expect("Failed to parse");
+
+        let expected = "\
+schemas:
+  - urn:ietf:params:scim:schemas:core:2.0:User
+id: 2819c223
+active: true
+emails:
+  - primary: true
+    type: work
+    value: bjensen@example.com
+  - value: babs@jensen.org
+name:
+  familyName: Jensen
+  givenName: Barbara
+nickName: null
+roles: []
+userName: bjensen
+meta:
+  created: 2010-01-23T04:56:22Z
+  lastModified: 2011-05-13T04:42:34Z
+  location: https://example.com/v2/Users/2819c223
+  resourceType: User
+  version: W/\"3694e05e9dff590\"";
+        assert_eq!(u.to_string(), expected);
+    }
+
+    #[test]
+    fn scim_entry_iter() {
+        let mut u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+
+        let names: Vec<&str> = u.iter().map(|(name, _)| name.as_str()).collect();
+        assert!(names.contains(&"userName"));
+        assert_eq!(names.len(), u.attrs.len());
+
+        // Every string, single or multi-valued, wherever it is.
+        let mut strings = 0;
+        for (_, value) in &u {
+            strings += value
+                .values()
+                .chain(value.complex_values().flat_map(|attrs| attrs.values()))
+                .filter(|attr| matches!(attr, ScimAttr::String(_)))
+                .count();
+        }
+        assert!(strings > 20);
+
+        let user_name = u.attr("userName").expect("No userName");
+        assert_eq!(user_name.values().count(), 1);
+        assert_eq!(user_name.complex_values().count(), 0);
+        let emails = u.attr("emails").expect("No emails");
+        assert_eq!(emails.values().count(), 0);
+        assert_eq!(emails.complex_values().count(), 2);
+
+        for (_, value) in &mut u {
+            if let ScimValue::Simple(attr) = value {
+                attr.promote();
+            }
+        }
+        assert!(matches!(
+            u.attr("profileUrl"),
+            Some(ScimValue::Simple(ScimAttr::Reference(_)))
+        ));
+
+        let owned: AttrMap<ScimValue> = u.clone().into_iter().collect();
+        assert_eq!(owned, u.attrs);
+    }
+
+    #[test]
+    fn scim_value_accessors() {
+        let u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+
+        assert_eq!(u["userName"].as_str(), Some("bjensen@example.com"));
+        assert_eq!(u["active"].as_bool(), Some(true));
+        assert_eq!(u["active"].as_str(), None);
+        assert_eq!(
+            u["name"]
+                .as_complex()
+                .and_then(|name| name.get("givenName"))
+                .and_then(ScimAttr::as_str),
+            Some("Barbara")
+        );
+        assert_eq!(u["emails"].as_multi_complex().map(<[_]>::len), Some(2));
+        assert_eq!(u["emails"].as_multi(), None);
+        assert!(!u["emails"].is_empty());
+
+        assert_eq!(
+            ScimValue::MultiSimple(Vec::new()).as_multi_complex(),
+            Some(&[][..])
+        );
+        assert!(ScimValue::MultiSimple(Vec::new()).is_empty());
+        assert!(ScimValue::Simple(ScimAttr::Null).is_empty());
+
+        assert_eq!(ScimAttr::Decimal(2.0).as_i64(), Some(2));
+        assert_eq!(ScimAttr::Decimal(2.5).as_i64(), None);
+        assert_eq!(ScimAttr::Integer(2).as_f64(), Some(2.0));
+        assert!(ScimAttr::String("2011-08-01T18:29:49Z".to_string())
+            .as_datetime()
+            .is_some());
+        assert_eq!(ScimAttr::Binary(vec![1, 2]).as_bytes(), Some(&[1, 2][..]));
+    }
+
+    #[test]
+    fn scim_entry_index() {
+        let u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+
+        let user_name = ScimValue::Simple(ScimAttr::String("bjensen@example.com".to_string()));
+        assert_eq!(u["userName"], user_name);
+        assert_eq!(u["USERNAME"], user_name);
+        assert_eq!(u.get("username"), Some(&user_name));
+        assert_eq!(u.get("missing"), None);
+        assert!(std::panic::catch_unwind(|| u["missing"].clone()).is_err());
+    }
+
+    #[test]
+    fn scim_entry_builder() {
+        let schema = crate::builtin::user_schema();
+        let email = |value: &str, primary: bool| {
+            AttrMap::from([
+                ("value".to_string(), ScimAttr::String(value.to_string())),
+                ("primary".to_string(), ScimAttr::Bool(primary)),
+            ])
+        };
+        let enterprise = "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User";
+
+        let builder = ScimEntryGeneric::builder(constants::SCIM_SCHEMA_USER)
+            .id(Uuid::nil())
+            .external_id("701984")
+            .attr_str("userName", "bjensen")
+            .attr_bool("active", true)
+            .attr_complex(
+                "name",
+                AttrMap::from([(
+                    "givenName".to_string(),
+                    ScimAttr::String("Barbara".to_string()),
+                )]),
+            )
+            .attr_multi("emails", vec![email("bjensen@example.com", true)])
+            .extension(
+                enterprise,
+                AttrMap::from([(
+                    "employeeNumber".to_string(),
+                    ScimValue::Simple(ScimAttr::String("701984".to_string())),
+                )]),
+            );
+
+        let u = builder
+            .clone()
+            .build_with_schema(&schema)
+            .expect("Invalid entry");
+        assert_eq!(u.schemas, vec![constants::SCIM_SCHEMA_USER, enterprise]);
+        assert_eq!(u.external_id.as_deref(), Some("701984"));
+        assert!(u.contains_attr("USERNAME"));
+        assert!(u.extension(enterprise).is_some());
+
+        // Setting an attribute again replaces it.
+        let u = builder
+            .clone()
+            .attr_str("USERNAME", "babs")
+            .build()
+            .expect("Invalid entry");
+        assert_eq!(
+            u.attr("userName"),
+            Some(&ScimValue::Simple(ScimAttr::String("babs".to_string())))
+        );
+
+        let violation = |path: &str, kind| {
+            Err(error::ScimError::from(schema::SchemaViolation::new(
+                path, kind,
+            )))
+        };
+        assert_eq!(
+            builder.clone().attr_str("id", "x").build(),
+            violation("id", schema::ViolationKind::InvalidName)
+        );
+        assert_eq!(
+            builder.clone().attr_str("bad name", "x").build(),
+            violation("bad name", schema::ViolationKind::InvalidName)
+        );
+        assert_eq!(
+            builder
+                .clone()
+                .attr_complex(
+                    "name",
+                    AttrMap::from([(
+                        "given name".to_string(),
+                        ScimAttr::String("Babs".to_string()),
+                    )]),
+                )
+                .build(),
+            violation("name.given name", schema::ViolationKind::InvalidName)
+        );
+        assert_eq!(
+            builder
+                .clone()
+                .attr_multi(
+                    "emails",
+                    vec![email("a@example.com", true), email("b@example.com", true)]
+                )
+                .build(),
+            violation("emails", schema::ViolationKind::MultiplePrimary)
+        );
+        assert_eq!(
+            ScimEntryGeneric::builder(constants::SCIM_SCHEMA_USER)
+                .attr_bool("active", true)
+                .build_with_schema(&schema),
+            violation("userName", schema::ViolationKind::MissingRequired)
+        );
+    }
+
+    #[test]
+    fn scim_attr_null() {
+        let schema = crate::builtin::user_schema();
+        let mut v: serde_json::Value =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        v["nickName"] = serde_json::Value::Null;
+        v["name"]["middleName"] = serde_json::Value::Null;
+        let mut u: ScimEntryGeneric = serde_json::from_value(v).expect("Failed to parse entry");
+
+        assert_eq!(u.attr("nickName").map(ScimValue::is_null), Some(true));
+        assert_eq!(schema.validate(&u), Ok(()));
+        let filter: crate::filter::ScimFilter = "nickName pr".parse().expect("Invalid filter");
+        assert!(!filter.matches(&schema, &u));
+        let filter: crate::filter::ScimFilter =
+            "name.middleName pr".parse().expect("Invalid filter");
+        assert!(!filter.matches(&schema, &u));
+
+        // A null is written back as a null.
+        let json = serde_json::to_value(&u).expect("Failed to serialise");
+        assert_eq!(json["nickName"], serde_json::Value::Null);
+
+        u.set_path("title", ScimValue::Simple(ScimAttr::Null))
+            .expect("Failed to clear title");
+        assert!(!u.contains_attr("title"));
+
+        u.insert_attr("userName", ScimValue::Simple(ScimAttr::Null));
+        assert!(schema.validate(&u).is_err());
+    }
+
+    #[test]
+    fn scim_attr_promote() {
+        let mut u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let original = u.clone();
+
+        crate::builtin::user_schema().promote(&mut u);
+        assert!(matches!(
+            u.attr("profileUrl"),
+            Some(ScimValue::Simple(ScimAttr::Reference(_)))
+        ));
+        assert!(matches!(
+            u.attr("x509Certificates"),
+            Some(ScimValue::MultiComplex(certs)) if matches!(
+                certs[0].get("value"),
+                Some(ScimAttr::Binary(der)) if der.starts_with(&[0x30, 0x82])
+            )
+        ));
+        // Strings of a string type are left alone.
+        assert_eq!(u.attr("userName"), original.attr("userName"));
+
+        // Promoted values encode as the strings they came from.
+        let json = serde_json::to_value(&u).expect("Failed to serialise");
+        assert_eq!(
+            json,
+            serde_json::to_value(&original).expect("Failed to serialise")
+        );
+
+        let mut attr = ScimAttr::String("2011-08-01T18:29:49Z".to_string());
+        attr.promote();
+        assert!(matches!(attr, ScimAttr::DateTime(_)));
+        let mut attr = ScimAttr::String("Babs".to_string());
+        attr.promote();
+        attr.promote_as(AttributeType::DateTime);
+        assert_eq!(attr, ScimAttr::String("Babs".to_string()));
+        let mut attr = ScimAttr::Decimal(3.0);
+        attr.promote_as(AttributeType::Integer);
+        assert_eq!(attr, ScimAttr::Integer(3));
+
+        let mut h = original.clone();
+        h.promote();
+        assert!(matches!(
+            h.attr("profileUrl"),
+            Some(ScimValue::Simple(ScimAttr::Reference(_)))
+        ));
+        assert_eq!(
+            h.attr("x509Certificates"),
+            original.attr("x509Certificates")
+        );
+    }
 }
@@ -0,0 +1,725 @@
+use crate::constants::{
+    SCIM_MESSAGE_BULK_REQUEST, SCIM_MESSAGE_BULK_RESPONSE, SCIM_MESSAGE_ERROR,
+    SCIM_MESSAGE_LIST_RESPONSE, SCIM_MESSAGE_PATCH_OP, SCIM_MESSAGE_SEARCH_REQUEST,
+};
+use crate::error::{ScimError, ScimType};
+use crate::redact::{AttrValue, Attrs};
+use crate::ScimEntryGeneric;
+use serde::de::{self, DeserializeOwned};
+use serde::ser::{self, SerializeStruct};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::fmt;
+use std::str::FromStr;
+
+/// The schema URNs of the RFC7644 protocol messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScimMessageSchema {
+    ListResponse,
+    PatchOp,
+    BulkRequest,
+    BulkResponse,
+    SearchRequest,
+    Error,
+}
+
+impl ScimMessageSchema {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScimMessageSchema::ListResponse => SCIM_MESSAGE_LIST_RESPONSE,
+            ScimMessageSchema::PatchOp => SCIM_MESSAGE_PATCH_OP,
+            ScimMessageSchema::BulkRequest => SCIM_MESSAGE_BULK_REQUEST,
+            ScimMessageSchema::BulkResponse => SCIM_MESSAGE_BULK_RESPONSE,
+            ScimMessageSchema::SearchRequest => SCIM_MESSAGE_SEARCH_REQUEST,
+            ScimMessageSchema::Error => SCIM_MESSAGE_ERROR,
+        }
+    }
+
+    /// A `schemas` array containing only this message's URN.
+    pub fn schemas(&self) -> Vec<String> {
+        vec![self.as_str().to_string()]
+    }
+
+    /// Find the message schema declared by a `schemas` array, if any.
+    pub fn from_schemas<S: AsRef<str>>(schemas: &[S]) -> Option<Self> {
+        schemas.iter().find_map(|s| s.as_ref().parse().ok())
+    }
+}
+
+impl fmt::Display for ScimMessageSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ScimMessageSchema {
+    type Err = ScimError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Schema URNs are case insensitive.
+        [
+            ScimMessageSchema::ListResponse,
+            ScimMessageSchema::PatchOp,
+            ScimMessageSchema::BulkRequest,
+            ScimMessageSchema::BulkResponse,
+            ScimMessageSchema::SearchRequest,
+            ScimMessageSchema::Error,
+        ]
+        .into_iter()
+        .find(|m| m.as_str().eq_ignore_ascii_case(s))
+        .ok_or(ScimError::InvalidSyntax)
+    }
+}
+
+impl Serialize for ScimMessageSchema {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ScimMessageSchema {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse()
+            .map_err(|_| de::Error::custom(format!("unknown message schema {}", s)))
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7644#section-3.4.2
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimListResponse<T = ScimEntryGeneric> {
+    pub schemas: Vec<String>,
+    pub total_results: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items_per_page: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_index: Option<u64>,
+    // https://datatracker.ietf.org/doc/draft-ietf-scim-cursor-pagination/
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_cursor: Option<String>,
+    #[serde(rename = "Resources", default = "Vec::new")]
+    #[serde(bound(deserialize = "T: DeserializeOwned"))]
+    pub resources: Vec<T>,
+}
+
+impl<T> ScimListResponse<T> {
+    pub fn new(resources: Vec<T>) -> Self {
+        ScimListResponse {
+            schemas: ScimMessageSchema::ListResponse.schemas(),
+            total_results: resources.len() as u64,
+            items_per_page: None,
+            start_index: None,
+            next_cursor: None,
+            previous_cursor: None,
+            resources,
+        }
+    }
+}
+
+enum ListResources<'a, T> {
+    Slice(&'a [T]),
+    Iter(RefCell<Option<Box<dyn Iterator<Item = &'a T> + 'a>>>),
+}
+
+/// A list response that borrows its resources, so a server can write a large
+/// result set without cloning every entry into an owned [`ScimListResponse`].
+pub struct ScimListResponseRef<'a, T> {
+    pub total_results: u64,
+    pub items_per_page: Option<u64>,
+    pub start_index: Option<u64>,
+    pub next_cursor: Option<&'a str>,
+    pub previous_cursor: Option<&'a str>,
+    resources: ListResources<'a, T>,
+}
+
+impl<'a, T> ScimListResponseRef<'a, T> {
+    pub fn from_slice(resources: &'a [T]) -> Self {
+        ScimListResponseRef {
+            total_results: resources.len() as u64,
+            items_per_page: None,
+            start_index: None,
+            next_cursor: None,
+            previous_cursor: None,
+            resources: ListResources::Slice(resources),
+        }
+    }
+
+    /// Serialize resources as they are yielded. Since the length isn't known
+    /// ahead of time `total_results` must be provided. The iterator is
+    /// consumed by the first serialization.
+    pub fn from_iter<I>(total_results: u64, resources: I) -> Self
+    where
+        I: IntoIterator<Item = &'a T>,
+        I::IntoIter: 'a,
+    {
+        ScimListResponseRef {
+            total_results,
+            items_per_page: None,
+            start_index: None,
+            next_cursor: None,
+            previous_cursor: None,
+            resources: ListResources::Iter(RefCell::new(Some(Box::new(resources.into_iter())))),
+        }
+    }
+
+    pub fn with_page(mut self, start_index: u64, items_per_page: u64) -> Self {
+        self.start_index = Some(start_index);
+        self.items_per_page = Some(items_per_page);
+        self
+    }
+
+    pub fn with_cursors(
+        mut self,
+        items_per_page: u64,
+        next_cursor: Option<&'a str>,
+        previous_cursor: Option<&'a str>,
+    ) -> Self {
+        self.items_per_page = Some(items_per_page);
+        self.next_cursor = next_cursor;
+        self.previous_cursor = previous_cursor;
+        self
+    }
+}
+
+impl<T> fmt::Debug for ScimListResponseRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScimListResponseRef")
+            .field("total_results", &self.total_results)
+            .field("items_per_page", &self.items_per_page)
+            .field("start_index", &self.start_index)
+            .field("next_cursor", &self.next_cursor)
+            .field("previous_cursor", &self.previous_cursor)
+            .finish_non_exhaustive()
+    }
+}
+
+struct IterResources<'r, 'a, T>(&'r RefCell<Option<Box<dyn Iterator<Item = &'a T> + 'a>>>);
+
+impl<T: Serialize> Serialize for IterResources<'_, '_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let iter = self
+            .0
+            .borrow_mut()
+            .take()
+            .ok_or_else(|| ser::Error::custom("list resources were already serialized"))?;
+        serializer.collect_seq(iter)
+    }
+}
+
+impl<T: Serialize> Serialize for ScimListResponseRef<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let len = 3
+            + self.items_per_page.is_some() as usize
+            + self.start_index.is_some() as usize
+            + self.next_cursor.is_some() as usize
+            + self.previous_cursor.is_some() as usize;
+        let mut state = serializer.serialize_struct("ScimListResponse", len)?;
+        state.serialize_field("schemas", &[ScimMessageSchema::ListResponse])?;
+        state.serialize_field("totalResults", &self.total_results)?;
+        if let Some(items_per_page) = self.items_per_page {
+            state.serialize_field("itemsPerPage", &items_per_page)?;
+        }
+        if let Some(start_index) = self.start_index {
+            state.serialize_field("startIndex", &start_index)?;
+        }
+        if let Some(next_cursor) = self.next_cursor {
+            state.serialize_field("nextCursor", next_cursor)?;
+        }
+        if let Some(previous_cursor) = self.previous_cursor {
+            state.serialize_field("previousCursor", previous_cursor)?;
+        }
+        match &self.resources {
+            ListResources::Slice(s) => state.serialize_field("Resources", s)?,
+            ListResources::Iter(i) => state.serialize_field("Resources", &IterResources(i))?,
+        }
+        state.end()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+/// The query parameters of a list request, from RFC7644 3.4.2 and the
+/// cursor pagination draft. The attribute lists are comma separated.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimQueryParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_order: Option<SortOrder>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_index: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<u64>,
+    /// An empty cursor requests the first page of a cursor paginated result.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "comma_list")]
+    pub attributes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "comma_list")]
+    pub excluded_attributes: Vec<String>,
+}
+
+impl ScimQueryParams {
+    /// Request the page after the one described by `list`, following
+    /// `nextCursor` if the server uses cursors, or `startIndex` otherwise.
+    pub fn next_page<T>(&self, list: &ScimListResponse<T>) -> Option<Self> {
+        let mut next = self.clone();
+        if self.cursor.is_some() {
+            next.cursor = Some(list.next_cursor.clone()?);
+        } else {
+            // An empty page can't be followed, whatever itemsPerPage says.
+            if list.resources.is_empty() {
+                return None;
+            }
+            let start = list.start_index.unwrap_or(1);
+            let per_page = list.items_per_page.unwrap_or(list.resources.len() as u64);
+            // Counts that overflow, as a server may send, can't be followed.
+            let seen = start.saturating_sub(1).checked_add(per_page)?;
+            if per_page == 0 || seen >= list.total_results {
+                return None;
+            }
+            next.start_index = Some(start.checked_add(per_page)?);
+        }
+        Some(next)
+    }
+}
+
+/// The body of a POST to `.search`, from RFC7644 3.4.3, which has the
+/// parameters of [`ScimQueryParams`] with the attribute lists as arrays.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimSearchRequest {
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attributes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded_attributes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_order: Option<SortOrder>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_index: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+impl From<ScimQueryParams> for ScimSearchRequest {
+    fn from(params: ScimQueryParams) -> Self {
+        ScimSearchRequest {
+            schemas: ScimMessageSchema::SearchRequest.schemas(),
+            attributes: params.attributes,
+            excluded_attributes: params.excluded_attributes,
+            filter: params.filter,
+            sort_by: params.sort_by,
+            sort_order: params.sort_order,
+            start_index: params.start_index,
+            count: params.count,
+            cursor: params.cursor,
+        }
+    }
+}
+
+impl From<ScimSearchRequest> for ScimQueryParams {
+    fn from(req: ScimSearchRequest) -> Self {
+        ScimQueryParams {
+            filter: req.filter,
+            sort_by: req.sort_by,
+            sort_order: req.sort_order,
+            start_index: req.start_index,
+            count: req.count,
+            cursor: req.cursor,
+            attributes: req.attributes,
+            excluded_attributes: req.excluded_attributes,
+        }
+    }
+}
+
+mod comma_list {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[allow(clippy::ptr_arg)]
+    pub fn serialize<S: Serializer>(list: &Vec<String>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&list.join(","))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<String>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(s.split(',')
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7644#section-3.5.2
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScimPatchOpKind {
+    Add,
+    Remove,
+    Replace,
+}
+
+impl<'de> Deserialize<'de> for ScimPatchOpKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Some providers send "Add" or "Replace", so be case insensitive.
+        let s = String::deserialize(deserializer)?;
+        match s.to_ascii_lowercase().as_str() {
+            "add" => Ok(ScimPatchOpKind::Add),
+            "remove" => Ok(ScimPatchOpKind::Remove),
+            "replace" => Ok(ScimPatchOpKind::Replace),
+            _ => Err(de::Error::unknown_variant(
+                &s,
+                &["add", "remove", "replace"],
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimPatchOperation {
+    pub op: ScimPatchOpKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+}
+
+/// A value for a sensitive attribute, such as `password`, isn't shown, see
+/// [`crate::redact`].
+impl fmt::Debug for ScimPatchOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.value.as_ref();
+        let mut s = f.debug_struct("ScimPatchOperation");
+        s.field("op", &self.op).field("path", &self.path);
+        match &self.path {
+            Some(path) => s.field("value", &AttrValue(path, &value)),
+            None => s.field("value", &value.map(Attrs)),
+        };
+        s.finish()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimPatchRequest {
+    pub schemas: Vec<String>,
+    #[serde(rename = "Operations")]
+    pub operations: Vec<ScimPatchOperation>,
+}
+
+impl ScimPatchRequest {
+    pub fn new(operations: Vec<ScimPatchOperation>) -> Self {
+        ScimPatchRequest {
+            schemas: ScimMessageSchema::PatchOp.schemas(),
+            operations,
+        }
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7644#section-3.12
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimErrorResponse {
+    pub schemas: Vec<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "lenient_scim_type"
+    )]
+    pub scim_type: Option<ScimType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// The RFC encodes this as a JSON string, but many providers send a number.
+    #[serde(with = "status_string")]
+    pub status: u16,
+}
+
+impl ScimErrorResponse {
+    pub fn new(status: u16) -> Self {
+        ScimErrorResponse {
+            schemas: ScimMessageSchema::Error.schemas(),
+            scim_type: None,
+            detail: None,
+            status,
+        }
+    }
+}
+
+// Some providers send scimType keywords of their own. These are dropped
+// rather than failing the whole response.
+fn lenient_scim_type<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<ScimType>, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.and_then(|s| s.parse().ok()))
+}
+
+pub(crate) mod status_string {
+    use serde::de::{self, Deserializer};
+    use serde::{Deserialize, Serializer};
+    use serde_json::Value;
+
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    pub fn serialize<S: Serializer>(status: &u16, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&status.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::String(s) => s.parse().map_err(de::Error::custom),
+            Value::Number(n) => n
+                .as_u64()
+                .and_then(|n| u16::try_from(n).ok())
+                .ok_or_else(|| de::Error::custom("invalid status code")),
+            _ => Err(de::Error::custom("status must be a string or number")),
+        }
+    }
+}
+
+/// Any body a SCIM endpoint may respond with. Deserialization inspects the
+/// `schemas` URNs to decide which message was sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScimResponse {
+    Entry(ScimEntryGeneric),
+    List(ScimListResponse),
+    Error(ScimErrorResponse),
+    NoContent,
+}
+
+impl ScimResponse {
+    /// Decode a response body, treating an empty body (such as from a 204) as
+    /// [`ScimResponse::NoContent`].
+    pub fn from_slice(body: &[u8]) -> Result<Self, serde_json::Error> {
+        if body.iter().all(|b| b.is_ascii_whitespace()) {
+            Ok(ScimResponse::NoContent)
+        } else {
+            serde_json::from_slice(body)
+        }
+    }
+}
+
+impl Serialize for ScimResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ScimResponse::Entry(e) => e.serialize(serializer),
+            ScimResponse::List(l) => l.serialize(serializer),
+            ScimResponse::Error(e) => e.serialize(serializer),
+            ScimResponse::NoContent => serializer.serialize_unit(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ScimResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+
+        if value.is_null() {
+            return Ok(ScimResponse::NoContent);
+        }
+
+        let message = value
+            .get("schemas")
+            .and_then(Value::as_array)
+            .and_then(|schemas| {
+                let schemas: Vec<&str> = schemas.iter().filter_map(Value::as_str).collect();
+                ScimMessageSchema::from_schemas(&schemas)
+            });
+
+        match message {
+            Some(ScimMessageSchema::ListResponse) => serde_json::from_value(value)
+                .map(ScimResponse::List)
+                .map_err(de::Error::custom),
+            Some(ScimMessageSchema::Error) => serde_json::from_value(value)
+                .map(ScimResponse::Error)
+                .map_err(de::Error::custom),
+            _ => serde_json::from_value(value)
+                .map(ScimResponse::Entry)
+                .map_err(de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{RFC7643_GROUP, RFC7643_USER};
+
+    #[test]
+    fn parse_scim_response() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let r: ScimResponse =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        assert!(matches!(r, ScimResponse::Entry(_)));
+
+        let list = format!(
+            r#"{{
+              "schemas": ["urn:ietf:params:scim:api:messages:2.0:ListResponse"],
+              "totalResults": 2,
+              "Resources": [{}, {}]
+            }}"#,
+            RFC7643_USER, RFC7643_GROUP
+        );
+        let r: ScimResponse = serde_json::from_str(&list).expect("Failed to parse list");
+        assert!(matches!(
+            r,
+            ScimResponse::List(l) if l.total_results == 2 && l.resources.len() == 2
+        ));
+
+        let err = r#"{
+          "schemas": ["urn:ietf:params:scim:api:messages:2.0:Error"],
+          "scimType": "mutability",
+          "detail": "Attribute 'id' is readOnly",
+          "status": "400"
+        }"#;
+        let r: ScimResponse = serde_json::from_str(err).expect("Failed to parse error");
+        assert_eq!(
+            r,
+            ScimResponse::Error(ScimErrorResponse {
+                schemas: ScimMessageSchema::Error.schemas(),
+                scim_type: Some(ScimType::Mutability),
+                detail: Some("Attribute 'id' is readOnly".to_string()),
+                status: 400,
+            })
+        );
+
+        let s = serde_json::to_string(&r).expect("Failed to serialise error");
+        assert!(s.contains(r#""status":"400""#));
+
+        assert!(matches!(
+            ScimResponse::from_slice(b" "),
+            Ok(ScimResponse::NoContent)
+        ));
+    }
+
+    #[test]
+    fn message_schema() {
+        assert_eq!(
+            "urn:ietf:params:scim:api:messages:2.0:PatchOp".parse(),
+            Ok(ScimMessageSchema::PatchOp)
+        );
+        assert_eq!(
+            "URN:IETF:PARAMS:SCIM:API:MESSAGES:2.0:ERROR".parse(),
+            Ok(ScimMessageSchema::Error)
+        );
+        assert_eq!(
+            "urn:ietf:params:scim:schemas:core:2.0:User".parse::<ScimMessageSchema>(),
+            Err(ScimError::InvalidSyntax)
+        );
+        assert_eq!(
+            ScimMessageSchema::from_schemas(&[
+                "urn:ietf:params:scim:schemas:core:2.0:User",
+                "urn:ietf:params:scim:api:messages:2.0:SearchRequest"
+            ]),
+            Some(ScimMessageSchema::SearchRequest)
+        );
+        assert_eq!(
+            ScimMessageSchema::BulkResponse.to_string(),
+            "urn:ietf:params:scim:api:messages:2.0:BulkResponse"
+        );
+    }
+
+    #[test]
+    fn serialise_list_response_ref() {
+        let u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let g: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_GROUP).expect("Failed to parse RFC7643_GROUP");
+        let entries = vec![u, g];
+
+        let owned = serde_json::to_value(ScimListResponse::new(entries.clone()))
+            .expect("Failed to serialise list");
+
+        let borrowed = serde_json::to_value(ScimListResponseRef::from_slice(&entries))
+            .expect("Failed to serialise list ref");
+        assert_eq!(owned, borrowed);
+
+        let list_ref = ScimListResponseRef::from_iter(2, entries.iter());
+        let borrowed = serde_json::to_value(&list_ref).expect("Failed to serialise list ref");
+        assert_eq!(owned, borrowed);
+        // The iterator was consumed by the first pass.
+        assert!(serde_json::to_value(&list_ref).is_err());
+
+        let paged = serde_json::to_value(ScimListResponseRef::from_slice(&entries).with_page(1, 2))
+            .expect("Failed to serialise list ref");
+        assert_eq!(paged["startIndex"], 1);
+        assert_eq!(paged["itemsPerPage"], 2);
+
+        let cursor = serde_json::to_value(ScimListResponseRef::from_slice(&entries).with_cursors(
+            2,
+            Some("VZUTiy"),
+            None,
+        ))
+        .expect("Failed to serialise list ref");
+        assert_eq!(cursor["nextCursor"], "VZUTiy");
+        assert!(cursor.get("previousCursor").is_none());
+    }
+
+    #[test]
+    fn list_pagination() {
+        let params: ScimQueryParams = serde_json::from_str(
+            r#"{ "filter": "userName sw \"b\"", "count": 1, "cursor": "", "attributes": "userName, emails" }"#,
+        )
+        .expect("Failed to parse query params");
+        assert_eq!(params.attributes, vec!["userName", "emails"]);
+
+        let mut list: ScimListResponse<u64> = ScimListResponse::new(vec![1]);
+        list.total_results = 3;
+        list.items_per_page = Some(1);
+        list.next_cursor = Some("VZUTiy".to_string());
+
+        let next = params.next_page(&list).expect("No next page");
+        assert_eq!(next.cursor.as_deref(), Some("VZUTiy"));
+
+        list.next_cursor = None;
+        assert_eq!(params.next_page(&list), None);
+
+        let params = ScimQueryParams {
+            count: Some(1),
+            ..Default::default()
+        };
+        list.start_index = Some(1);
+        let next = params.next_page(&list).expect("No next page");
+        assert_eq!(next.start_index, Some(2));
+        list.start_index = Some(3);
+        assert_eq!(params.next_page(&list), None);
+        list.start_index = Some(1);
+        list.resources.clear();
+        assert_eq!(params.next_page(&list), None);
+
+        // A startIndex that would overflow ends the listing.
+        let mut list: ScimListResponse<u64> = ScimListResponse::new(vec![1, 2]);
+        list.total_results = u64::MAX;
+        list.start_index = Some(u64::MAX);
+        list.items_per_page = Some(2);
+        assert_eq!(params.next_page(&list), None);
+
+        let s = serde_json::to_string(&ScimQueryParams {
+            attributes: vec!["userName".to_string(), "emails".to_string()],
+            ..Default::default()
+        })
+        .expect("Failed to serialise query params");
+        assert_eq!(s, r#"{"attributes":"userName,emails"}"#);
+    }
+}
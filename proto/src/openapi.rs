@@ -0,0 +1,254 @@
+use crate::constants::SCIM_CONTENT_TYPE;
+use crate::registry::{ResourceTypeDefinition, SchemaRegistry};
+use serde_json::{json, Map, Value};
+
+// https://spec.openapis.org/oas/v3.1.0
+// OpenAPI 3.1 schema objects are JSON Schema 2020-12, so resource schemas
+// come from ScimSchema::json_schema_body.
+
+pub const OPENAPI_VERSION: &str = "3.1.0";
+
+fn component_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn scim_content(schema: Value) -> Value {
+    let mut media_type = Map::new();
+    media_type.insert("schema".to_string(), schema);
+    let mut content = Map::new();
+    content.insert(SCIM_CONTENT_TYPE.to_string(), Value::Object(media_type));
+    Value::Object(content)
+}
+
+fn response(description: &str, schema: Value) -> Value {
+    json!({ "description": description, "content": scim_content(schema) })
+}
+
+fn error_response() -> Value {
+    response("SCIM error", component_ref("Error"))
+}
+
+fn query_parameter(name: &str, schema: Value) -> Value {
+    let mut parameter = json!({ "name": name, "in": "query", "required": false });
+    if let Value::Object(parameter) = &mut parameter {
+        parameter.insert("schema".to_string(), schema);
+    }
+    parameter
+}
+
+fn projection_parameters() -> Vec<Value> {
+    vec![
+        query_parameter("attributes", json!({ "type": "string" })),
+        query_parameter("excludedAttributes", json!({ "type": "string" })),
+    ]
+}
+
+fn message_components() -> Map<String, Value> {
+    let mut components = Map::new();
+    components.insert(
+        "Error".to_string(),
+        json!({
+            "type": "object",
+            "properties": {
+                "schemas": { "type": "array", "items": { "type": "string" } },
+                "scimType": { "type": "string" },
+                "detail": { "type": "string" },
+                "status": { "type": "string" }
+            },
+            "required": ["schemas", "status"]
+        }),
+    );
+    components.insert(
+        "PatchOp".to_string(),
+        json!({
+            "type": "object",
+            "properties": {
+                "schemas": { "type": "array", "items": { "type": "string" } },
+                "Operations": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "op": { "type": "string", "enum": ["add", "remove", "replace"] },
+                            "path": { "type": "string" },
+                            "value": {}
+                        },
+                        "required": ["op"]
+                    }
+                }
+            },
+            "required": ["schemas", "Operations"]
+        }),
+    );
+    components
+}
+
+fn list_response(name: &str) -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "schemas": { "type": "array", "items": { "type": "string" } },
+            "totalResults": { "type": "integer" },
+            "itemsPerPage": { "type": "integer" },
+            "startIndex": { "type": "integer" },
+            "Resources": { "type": "array", "items": component_ref(name) }
+        },
+        "required": ["schemas", "totalResults"]
+    })
+}
+
+fn endpoint(rt: &ResourceTypeDefinition) -> String {
+    if rt.endpoint.starts_with('/') {
+        rt.endpoint.clone()
+    } else {
+        format!("/{}", rt.endpoint)
+    }
+}
+
+impl SchemaRegistry {
+    /// OpenAPI component schemas for each resource type, with its extensions
+    /// merged, its ListResponse, and the Error and PatchOp messages.
+    pub fn openapi_components(&self) -> Value {
+        let mut components = message_components();
+
+        for rt in self.resource_types() {
+            let Some(schema) = self.merged(&rt.name) else {
+                continue;
+            };
+            components.insert(rt.name.clone(), schema.json_schema_body());
+            components.insert(format!("{}ListResponse", rt.name), list_response(&rt.name));
+        }
+
+        Value::Object(components)
+    }
+
+    /// OpenAPI path items for the RFC7644 endpoints of each resource type.
+    pub fn openapi_paths(&self) -> Value {
+        let mut paths = Map::new();
+
+        for rt in self.resource_types() {
+            let name = rt.name.as_str();
+            let endpoint = endpoint(rt);
+
+            let mut list_parameters = vec![
+                query_parameter("filter", json!({ "type": "string" })),
+                query_parameter("sortBy", json!({ "type": "string" })),
+                query_parameter(
+                    "sortOrder",
+                    json!({ "type": "string", "enum": ["ascending", "descending"] }),
+                ),
+                query_parameter("startIndex", json!({ "type": "integer", "minimum": 1 })),
+                query_parameter("count", json!({ "type": "integer", "minimum": 0 })),
+            ];
+            list_parameters.extend(projection_parameters());
+
+            paths.insert(
+                endpoint.clone(),
+                json!({
+                    "get": {
+                        "operationId": format!("list{}", name),
+                        "parameters": list_parameters,
+                        "responses": {
+                            "200": response("Matching resources", component_ref(&format!("{}ListResponse", name))),
+                            "default": error_response()
+                        }
+                    },
+                    "post": {
+                        "operationId": format!("create{}", name),
+                        "requestBody": { "required": true, "content": scim_content(component_ref(name)) },
+                        "responses": {
+                            "201": response("Created resource", component_ref(name)),
+                            "default": error_response()
+                        }
+                    }
+                }),
+            );
+
+            paths.insert(
+                format!("{}/{{id}}", endpoint),
+                json!({
+                    "parameters": [
+                        { "name": "id", "in": "path", "required": true, "schema": { "type": "string" } }
+                    ],
+                    "get": {
+                        "operationId": format!("get{}", name),
+                        "parameters": projection_parameters(),
+                        "responses": {
+                            "200": response("The resource", component_ref(name)),
+                            "default": error_response()
+                        }
+                    },
+                    "put": {
+                        "operationId": format!("replace{}", name),
+                        "requestBody": { "required": true, "content": scim_content(component_ref(name)) },
+                        "responses": {
+                            "200": response("Replaced resource", component_ref(name)),
+                            "default": error_response()
+                        }
+                    },
+                    "patch": {
+                        "operationId": format!("patch{}", name),
+                        "requestBody": { "required": true, "content": scim_content(component_ref("PatchOp")) },
+                        "responses": {
+                            "200": response("Modified resource", component_ref(name)),
+                            "204": { "description": "Modified" },
+                            "default": error_response()
+                        }
+                    },
+                    "delete": {
+                        "operationId": format!("delete{}", name),
+                        "responses": {
+                            "204": { "description": "Deleted" },
+                            "default": error_response()
+                        }
+                    }
+                }),
+            );
+        }
+
+        Value::Object(paths)
+    }
+
+    /// A complete OpenAPI document for the registered resource types.
+    pub fn openapi(&self, title: &str, version: &str) -> Value {
+        json!({
+            "openapi": OPENAPI_VERSION,
+            "info": { "title": title, "version": version },
+            "paths": self.openapi_paths(),
+            "components": { "schemas": self.openapi_components() }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openapi_core() {
+        let doc = SchemaRegistry::core().openapi("SCIM", "1.0");
+
+        assert_eq!(doc["openapi"], OPENAPI_VERSION);
+        let schemas = &doc["components"]["schemas"];
+        assert_eq!(schemas["User"]["required"], json!(["schemas", "userName"]));
+        assert_eq!(
+            schemas["User"]["properties"]
+                ["urn:ietf:params:scim:schemas:extension:enterprise:2.0:User"]["properties"]
+                ["employeeNumber"],
+            json!({ "type": "string" })
+        );
+        assert_eq!(
+            schemas["GroupListResponse"]["properties"]["Resources"]["items"],
+            json!({ "$ref": "#/components/schemas/Group" })
+        );
+        assert!(schemas.get("Error").is_some());
+
+        let paths = &doc["paths"];
+        assert_eq!(paths["/Users"]["get"]["operationId"], "listUser");
+        assert_eq!(
+            paths["/Groups/{id}"]["patch"]["requestBody"]["content"][SCIM_CONTENT_TYPE]["schema"],
+            json!({ "$ref": "#/components/schemas/PatchOp" })
+        );
+        assert!(paths["/Users/{id}"].get("delete").is_some());
+    }
+}
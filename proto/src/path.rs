@@ -0,0 +1,697 @@
+use crate::attr_map::{self, AttrMap};
+use crate::convert;
+use crate::error::ScimError;
+use crate::filter::ScimFilter;
+use crate::messages::{ScimPatchOpKind, ScimPatchOperation, ScimPatchRequest};
+use crate::schema::{find_attr, insert_attr, is_primary, is_valid_attr_name, key_of, remove_attr};
+use crate::{ScimAttr, ScimComplexAttr, ScimEntryGeneric, ScimValue};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+// https://datatracker.ietf.org/doc/html/rfc7644#section-3.5.2
+
+/// An attribute path, as in the `path` of a PATCH operation: an attribute
+/// name, optionally prefixed with a schema URN, followed by an optional
+/// value filter and sub-attribute, such as `name.givenName`,
+/// `emails[type eq "work"].value` or
+/// `urn:ietf:params:scim:schemas:extension:enterprise:2.0:User:manager.value`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScimPath {
+    urn: Option<String>,
+    attr: String,
+    // The filter, and its text for Display.
+    filter: Option<ScimFilter>,
+    filter_text: Option<String>,
+    sub: Option<String>,
+}
+
+impl ScimPath {
+    pub fn parse(path: &str) -> Result<Self, ScimError> {
+        let path = path.trim();
+        let filter_start = path.find('[').unwrap_or(path.len());
+
+        // The URN may itself contain dots (2.0), so it ends at the last colon
+        // before any filter.
+        let (urn, rest) = match path[..filter_start].rfind(':') {
+            Some(idx) => (Some(&path[..idx]), &path[idx + 1..]),
+            None => (None, path),
+        };
+
+        let attr_end = rest.find(['.', '[']).unwrap_or(rest.len());
+        let attr = &rest[..attr_end];
+        let mut tail = &rest[attr_end..];
+
+        let mut filter = None;
+        let mut filter_text = None;
+        if let Some(inner) = tail.strip_prefix('[') {
+            let end = filter_end(inner).ok_or(ScimError::InvalidPath)?;
+            filter = Some(ScimFilter::from_str(&inner[..end]).map_err(|_| ScimError::InvalidPath)?);
+            filter_text = Some(inner[..end].to_string());
+            tail = &inner[end + 1..];
+        }
+
+        let sub = match tail {
+            "" => None,
+            _ => Some(tail.strip_prefix('.').ok_or(ScimError::InvalidPath)?),
+        };
+
+        if !is_valid_attr_name(attr)
+            || !sub.map(is_valid_attr_name).unwrap_or(true)
+            || urn == Some("")
+        {
+            return Err(ScimError::InvalidPath);
+        }
+
+        Ok(ScimPath {
+            urn: urn.map(str::to_string),
+            attr: attr.to_string(),
+            filter,
+            filter_text,
+            sub: sub.map(str::to_string),
+        })
+    }
+
+    pub fn urn(&self) -> Option<&str> {
+        self.urn.as_deref()
+    }
+
+    pub fn attr(&self) -> &str {
+        &self.attr
+    }
+
+    pub fn filter(&self) -> Option<&ScimFilter> {
+        self.filter.as_ref()
+    }
+
+    pub fn sub(&self) -> Option<&str> {
+        self.sub.as_deref()
+    }
+
+    fn keep(&self, attrs: &ScimComplexAttr) -> bool {
+        self.filter
+            .as_ref()
+            .map(|f| f.matches_value(attrs))
+            .unwrap_or(true)
+    }
+
+    fn keep_simple(&self, attr: &ScimAttr) -> bool {
+        let attrs = AttrMap::from([("value".to_string(), attr.clone())]);
+        self.keep(&attrs)
+    }
+
+    fn select<'a>(&self, value: &'a ScimValue) -> Vec<PathValue<'a>> {
+        match (value, &self.filter, self.sub.as_deref()) {
+            (value, None, None) => vec![PathValue::Value(value)],
+            (ScimValue::Complex(attrs), None, Some(sub)) => find_attr(attrs, sub)
+                .map(PathValue::Attr)
+                .into_iter()
+                .collect(),
+            (ScimValue::Extension(attrs), None, Some(sub)) => find_attr(attrs, sub)
+                .map(PathValue::Value)
+                .into_iter()
+                .collect(),
+            (ScimValue::MultiComplex(values), _, None) => values
+                .iter()
+                .filter(|attrs| self.keep(attrs))
+                .map(PathValue::Complex)
+                .collect(),
+            (ScimValue::MultiComplex(values), _, Some(sub)) => values
+                .iter()
+                .filter(|attrs| self.keep(attrs))
+                .filter_map(|attrs| find_attr(attrs, sub))
+                .map(PathValue::Attr)
+                .collect(),
+            (ScimValue::MultiSimple(values), Some(_), None) => values
+                .iter()
+                .filter(|attr| self.keep_simple(attr))
+                .map(PathValue::Attr)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+// The index of the `]` closing a value filter, skipping any in strings.
+fn filter_end(s: &str) -> Option<usize> {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (idx, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ']' if !quoted => return Some(idx),
+            _ => {}
+        }
+    }
+    None
+}
+
+impl FromStr for ScimPath {
+    type Err = ScimError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ScimPath::parse(s)
+    }
+}
+
+impl fmt::Display for ScimPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(urn) = &self.urn {
+            write!(f, "{}:", urn)?;
+        }
+        f.write_str(&self.attr)?;
+        if let Some(filter) = &self.filter_text {
+            write!(f, "[{}]", filter)?;
+        }
+        if let Some(sub) = &self.sub {
+            write!(f, ".{}", sub)?;
+        }
+        Ok(())
+    }
+}
+
+/// A value found by [`ScimEntryGeneric::get_path`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathValue<'a> {
+    /// A whole attribute.
+    Value(&'a ScimValue),
+    /// A sub-attribute, or one value of a multi-valued attribute.
+    Attr(&'a ScimAttr),
+    /// One value of a multi-valued complex attribute, selected by a filter.
+    Complex(&'a ScimComplexAttr),
+}
+
+// Where the attribute of a path lives: the entry's attributes, or those of
+// an extension held under its URN.
+enum Container<'a> {
+    Attrs(&'a AttrMap<ScimValue>),
+    Simple(&'a ScimComplexAttr),
+    Missing,
+}
+
+impl ScimEntryGeneric {
+    // Whether `urn` is the schema of the entry's own attributes, rather
+    // than an extension, which it is when the entry has no attribute named
+    // by it and it's the first of `schemas`.
+    fn is_core_urn(&self, urn: &str) -> bool {
+        find_attr(&self.attrs, urn).is_none()
+            && self
+                .schemas
+                .first()
+                .map(|core| core.eq_ignore_ascii_case(urn))
+                .unwrap_or(false)
+    }
+
+    fn container(&self, path: &ScimPath) -> Container<'_> {
+        let Some(urn) = path.urn().filter(|urn| !self.is_core_urn(urn)) else {
+            return Container::Attrs(&self.attrs);
+        };
+        match find_attr(&self.attrs, urn) {
+            Some(ScimValue::Extension(attrs)) => Container::Attrs(attrs),
+            Some(ScimValue::Complex(attrs)) => Container::Simple(attrs),
+            _ => Container::Missing,
+        }
+    }
+
+    // The attributes a path lives in, creating the extension it names if
+    // needed. An extension read as a complex value is converted, so that it
+    // can hold complex attributes.
+    fn container_mut(
+        &mut self,
+        path: &ScimPath,
+        create: bool,
+    ) -> Result<Option<&mut AttrMap<ScimValue>>, ScimError> {
+        let Some(urn) = path.urn().filter(|urn| !self.is_core_urn(urn)) else {
+            return Ok(Some(&mut self.attrs));
+        };
+
+        if create && !self.contains_attr(urn) {
+            self.set_extension(urn, AttrMap::new());
+        }
+        if self
+            .attr(urn)
+            .map(|value| !matches!(value, ScimValue::Complex(_) | ScimValue::Extension(_)))
+            .unwrap_or(false)
+        {
+            return Err(ScimError::InvalidPath);
+        }
+        Ok(self.extension_mut(urn))
+    }
+
+    /// The values at an attribute path. A path without a filter or
+    /// sub-attribute gives the whole attribute; with a filter, it gives each
+    /// matching value, or each matching value's sub-attribute. Nothing is
+    /// returned when the attribute isn't present.
+    pub fn get_path(&self, path: &str) -> Result<Vec<PathValue<'_>>, ScimError> {
+        let path = ScimPath::parse(path)?;
+        Ok(match self.container(&path) {
+            Container::Attrs(attrs) => find_attr(attrs, path.attr())
+                .map(|value| path.select(value))
+                .unwrap_or_default(),
+            Container::Simple(attrs) if path.filter.is_none() && path.sub.is_none() => {
+                find_attr(attrs, path.attr())
+                    .map(PathValue::Attr)
+                    .into_iter()
+                    .collect()
+            }
+            Container::Simple(_) | Container::Missing => Vec::new(),
+        })
+    }
+
+    /// Set the value at an attribute path, as a PATCH replace would. A
+    /// sub-attribute without a filter is set in every value of a
+    /// multi-valued attribute. With a filter, only the matching values are
+    /// changed, and it is an error if there are none. Making a value of a
+    /// multi-valued attribute primary makes the others not primary, as
+    /// RFC7644 section 3.5.2 asks. Setting a null clears
+    /// the path, as [`ScimEntryGeneric::remove_path`] does.
+    pub fn set_path(&mut self, path: &str, value: ScimValue) -> Result<(), ScimError> {
+        if value.is_null() {
+            return self.remove_path(path).map(|_| ());
+        }
+        let path = ScimPath::parse(path)?;
+        let attrs = self
+            .container_mut(&path, true)?
+            .ok_or(ScimError::InvalidPath)?;
+
+        let (filter, sub) = (path.filter.is_some(), path.sub.as_deref());
+        if !filter && sub.is_none() {
+            insert_attr(attrs, path.attr(), value);
+            return Ok(());
+        }
+
+        let key = key_of(attrs, path.attr());
+        let target = key.as_ref().and_then(|key| attrs.get_mut(key));
+
+        match (target, filter, sub, value) {
+            (None, false, Some(sub), ScimValue::Simple(attr)) => {
+                let complex = AttrMap::from([(sub.to_string(), attr)]);
+                attrs.insert(path.attr().to_string(), ScimValue::Complex(complex));
+                Ok(())
+            }
+            (Some(ScimValue::Complex(complex)), false, Some(sub), ScimValue::Simple(attr)) => {
+                insert_attr(complex, sub, attr);
+                Ok(())
+            }
+            (Some(ScimValue::Extension(ext)), false, Some(sub), value) => {
+                insert_attr(ext, sub, value);
+                Ok(())
+            }
+            (Some(ScimValue::MultiComplex(values)), _, Some(sub), ScimValue::Simple(attr)) => {
+                let matched: Vec<usize> = (0..values.len())
+                    .filter(|idx| path.keep(&values[*idx]))
+                    .collect();
+                for idx in matched.iter() {
+                    insert_attr(&mut values[*idx], sub, attr.clone());
+                }
+                demote_unmatched(values, &matched);
+                (!matched.is_empty())
+                    .then_some(())
+                    .ok_or(ScimError::NoTarget)
+            }
+            (Some(ScimValue::MultiComplex(values)), true, None, ScimValue::Complex(new)) => {
+                let matched: Vec<usize> = (0..values.len())
+                    .filter(|idx| path.keep(&values[*idx]))
+                    .collect();
+                for idx in matched.iter() {
+                    values[*idx] = new.clone();
+                }
+                demote_unmatched(values, &matched);
+                (!matched.is_empty())
+                    .then_some(())
+                    .ok_or(ScimError::NoTarget)
+            }
+            (Some(ScimValue::MultiSimple(values)), true, None, ScimValue::Simple(new)) => {
+                let mut matched = false;
+                for attr in values.iter_mut().filter(|a| path.keep_simple(a)) {
+                    *attr = new.clone();
+                    matched = true;
+                }
+                matched.then_some(()).ok_or(ScimError::NoTarget)
+            }
+            (None, true, _, _) => Err(ScimError::NoTarget),
+            (_, _, _, ScimValue::Simple(_) | ScimValue::Complex(_)) => Err(ScimError::InvalidPath),
+            _ => Err(ScimError::InvalidAttribute),
+        }
+    }
+
+    /// Remove the values at an attribute path, returning whether anything
+    /// was removed. A multi-valued attribute left with no values is removed.
+    pub fn remove_path(&mut self, path: &str) -> Result<bool, ScimError> {
+        let path = ScimPath::parse(path)?;
+        let Some(attrs) = self.container_mut(&path, false)? else {
+            return Ok(false);
+        };
+
+        let (filter, sub) = (path.filter.is_some(), path.sub.as_deref());
+        if !filter && sub.is_none() {
+            return Ok(remove_attr(attrs, path.attr()));
+        }
+
+        let Some(key) = key_of(attrs, path.attr()) else {
+            return Ok(false);
+        };
+        let Some(target) = attrs.get_mut(&key) else {
+            return Ok(false);
+        };
+
+        let removed = match (target, sub) {
+            (ScimValue::Complex(complex), Some(sub)) if !filter => remove_attr(complex, sub),
+            (ScimValue::Extension(ext), Some(sub)) if !filter => remove_attr(ext, sub),
+            (ScimValue::MultiComplex(values), Some(sub)) => {
+                let mut removed = false;
+                for complex in values.iter_mut().filter(|c| path.keep(c)) {
+                    removed |= remove_attr(complex, sub);
+                }
+                removed
+            }
+            (ScimValue::MultiComplex(values), None) => {
+                let before = values.len();
+                values.retain(|c| !path.keep(c));
+                values.len() != before
+            }
+            (ScimValue::MultiSimple(values), None) => {
+                let before = values.len();
+                values.retain(|a| !path.keep_simple(a));
+                values.len() != before
+            }
+            _ => return Err(ScimError::InvalidPath),
+        };
+
+        let empty = match attrs.get(&key) {
+            Some(ScimValue::MultiComplex(values)) => values.is_empty(),
+            Some(ScimValue::MultiSimple(values)) => values.is_empty(),
+            _ => false,
+        };
+        if empty {
+            attr_map::remove(attrs, &key);
+        }
+        Ok(removed)
+    }
+}
+
+impl ScimEntryGeneric {
+    /// Apply the operations of a PATCH request in order, as a service
+    /// provider would, RFC7644 section 3.5.2. Nothing is checked against a
+    /// schema, so this is for a client that has to make a change itself,
+    /// such as by a GET and PUT when PATCH isn't supported. The entry may be
+    /// partly changed if an operation fails.
+    pub fn apply_patch(&mut self, patch: &ScimPatchRequest) -> Result<(), ScimError> {
+        patch
+            .operations
+            .iter()
+            .try_for_each(|op| self.apply_patch_operation(op))
+    }
+
+    fn apply_patch_operation(&mut self, op: &ScimPatchOperation) -> Result<(), ScimError> {
+        let path = op.path.as_deref().filter(|path| !path.trim().is_empty());
+        match (op.op, path, &op.value) {
+            (ScimPatchOpKind::Remove, Some(path), _) => self.remove_path(path).map(|_| ()),
+            (ScimPatchOpKind::Remove, None, _) => Err(ScimError::NoTarget),
+            (_, _, None) => Err(ScimError::InvalidSyntax),
+            // Without a path, the value holds the attributes to change, and
+            // those of an extension are under its URN.
+            (kind, None, Some(Value::Object(attrs))) => {
+                attrs.iter().try_for_each(|(name, value)| match value {
+                    Value::Object(ext) if name.contains(':') => {
+                        ext.iter().try_for_each(|(sub, value)| {
+                            self.patch_path(kind, &format!("{}:{}", name, sub), value)
+                        })
+                    }
+                    value => self.patch_path(kind, name, value),
+                })
+            }
+            (_, None, Some(_)) => Err(ScimError::InvalidSyntax),
+            (kind, Some(path), Some(value)) => self.patch_path(kind, path, value),
+        }
+    }
+
+    fn patch_path(
+        &mut self,
+        kind: ScimPatchOpKind,
+        path: &str,
+        value: &Value,
+    ) -> Result<(), ScimError> {
+        let value: ScimValue = convert::from_value_at(value.clone(), path)?;
+        if kind != ScimPatchOpKind::Add {
+            return self.set_path(path, value);
+        }
+
+        // An add appends to a multi-valued attribute, and merges the
+        // sub-attributes of a complex one.
+        let existing = match self.get_path(path)?.first() {
+            Some(PathValue::Value(existing)) => Some((*existing).clone()),
+            _ => None,
+        };
+        let value = match (existing, value) {
+            (Some(ScimValue::MultiComplex(mut values)), ScimValue::MultiComplex(new)) => {
+                values.extend(new);
+                ScimValue::MultiComplex(values)
+            }
+            (Some(ScimValue::MultiComplex(mut values)), ScimValue::Complex(new)) => {
+                values.push(new);
+                ScimValue::MultiComplex(values)
+            }
+            (Some(ScimValue::MultiSimple(mut values)), ScimValue::MultiSimple(new)) => {
+                values.extend(new);
+                ScimValue::MultiSimple(values)
+            }
+            (Some(ScimValue::MultiSimple(mut values)), ScimValue::Simple(new))
+                if !values.is_empty() =>
+            {
+                values.push(new);
+                ScimValue::MultiSimple(values)
+            }
+            (Some(ScimValue::Complex(mut attrs)), ScimValue::Complex(new)) => {
+                for (name, attr) in new {
+                    insert_attr(&mut attrs, &name, attr);
+                }
+                ScimValue::Complex(attrs)
+            }
+            (_, value) => value,
+        };
+        self.set_path(path, value)
+    }
+}
+
+// Making a value primary makes the others not primary, RFC7644 section
+// 3.5.2.
+fn demote_unmatched(values: &mut [ScimComplexAttr], matched: &[usize]) {
+    if !matched.iter().any(|idx| is_primary(&values[*idx])) {
+        return;
+    }
+    for (idx, complex) in values.iter_mut().enumerate() {
+        if !matched.contains(&idx) && is_primary(complex) {
+            insert_attr(complex, "primary", ScimAttr::Bool(false));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{RFC7643_USER, SCIM_SCHEMA_ENTERPRISE_USER};
+
+    fn user() -> ScimEntryGeneric {
+        serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER")
+    }
+
+    fn string(s: &str) -> ScimValue {
+        ScimValue::Simple(ScimAttr::String(s.to_string()))
+    }
+
+    fn strings<'a>(values: &[PathValue<'a>]) -> Vec<&'a str> {
+        values
+            .iter()
+            .filter_map(|v| match v {
+                PathValue::Attr(ScimAttr::String(s)) => Some(s.as_str()),
+                PathValue::Value(ScimValue::Simple(ScimAttr::String(s))) => Some(s.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn path_parse() {
+        let path = ScimPath::parse(r#"emails[type eq "work" and value ew "]"].display"#)
+            .expect("Failed to parse path");
+        assert_eq!(path.urn(), None);
+        assert_eq!(path.attr(), "emails");
+        assert!(path.filter().is_some());
+        assert_eq!(path.sub(), Some("display"));
+        assert_eq!(
+            path.to_string(),
+            r#"emails[type eq "work" and value ew "]"].display"#
+        );
+
+        let path = ScimPath::parse(
+            "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User:manager.value",
+        )
+        .expect("Failed to parse path");
+        assert_eq!(path.urn(), Some(SCIM_SCHEMA_ENTERPRISE_USER));
+        assert_eq!(path.attr(), "manager");
+        assert_eq!(path.sub(), Some("value"));
+        assert_eq!(
+            path.to_string(),
+            "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User:manager.value"
+        );
+
+        for path in [
+            "",
+            "name.",
+            "emails[type eq \"work\"",
+            "emails[type]",
+            "emails[type eq \"work\"]value",
+            "name givenName",
+            ":userName",
+            "1name",
+            "_name",
+            "name.$value",
+        ] {
+            assert_eq!(
+                ScimPath::parse(path),
+                Err(ScimError::InvalidPath),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn entry_get_path() {
+        let u = user();
+
+        let values = u.get_path("NAME.givenname").expect("Failed to get path");
+        assert_eq!(strings(&values), vec!["Barbara"]);
+
+        let values = u
+            .get_path(r#"emails[type eq "home"].value"#)
+            .expect("Failed to get path");
+        assert_eq!(strings(&values), vec!["babs@jensen.org"]);
+
+        let values = u.get_path("emails.value").expect("Failed to get path");
+        assert_eq!(values.len(), 2);
+
+        let values = u
+            .get_path(r#"emails[type eq "work"]"#)
+            .expect("Failed to get path");
+        assert!(matches!(values.as_slice(), [PathValue::Complex(_)]));
+
+        let values = u
+            .get_path("urn:ietf:params:scim:schemas:core:2.0:User:userName")
+            .expect("Failed to get path");
+        assert_eq!(strings(&values), vec!["bjensen@example.com"]);
+
+        assert!(u
+            .get_path(r#"emails[type eq "other"].value"#)
+            .expect("Failed to get path")
+            .is_empty());
+        assert!(u
+            .get_path("urn:ietf:params:scim:schemas:extension:enterprise:2.0:User:department")
+            .expect("Failed to get path")
+            .is_empty());
+    }
+
+    #[test]
+    fn entry_set_remove_path() {
+        let mut u = user();
+
+        u.set_path("name.givenName", string("Babs"))
+            .expect("Failed to set path");
+        u.set_path(r#"emails[type eq "work"].display"#, string("Work"))
+            .expect("Failed to set path");
+        assert_eq!(
+            strings(&u.get_path("name.givenName").expect("Failed to get path")),
+            vec!["Babs"]
+        );
+        assert_eq!(
+            strings(&u.get_path("emails.display").expect("Failed to get path")),
+            vec!["Work"]
+        );
+        assert_eq!(
+            u.set_path(r#"emails[type eq "other"].display"#, string("Other")),
+            Err(ScimError::NoTarget)
+        );
+
+        let department = "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User:department";
+        let manager = "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User:manager.value";
+        u.set_path(department, string("Tour Operations"))
+            .expect("Failed to set path");
+        u.set_path(manager, string("26118915-6090-4610-87e4-49d8ca9f808d"))
+            .expect("Failed to set path");
+        assert!(u.schemas.iter().any(|s| s == SCIM_SCHEMA_ENTERPRISE_USER));
+        assert_eq!(
+            strings(&u.get_path(department).expect("Failed to get path")),
+            vec!["Tour Operations"]
+        );
+        assert_eq!(
+            strings(&u.get_path(manager).expect("Failed to get path")),
+            vec!["26118915-6090-4610-87e4-49d8ca9f808d"]
+        );
+
+        assert_eq!(u.remove_path(r#"emails[type eq "work"]"#), Ok(true));
+        assert_eq!(u.get_path("emails").map(|v| v.len()), Ok(1));
+        assert_eq!(u.remove_path(r#"emails[type eq "home"]"#), Ok(true));
+        assert!(!u.attrs.contains_key("emails"));
+
+        assert_eq!(u.remove_path("name.middleName"), Ok(true));
+        assert_eq!(u.remove_path("name.middleName"), Ok(false));
+        assert_eq!(u.remove_path(department), Ok(true));
+        assert_eq!(u.remove_path("nickName"), Ok(true));
+        assert_eq!(u.remove_path("nickName"), Ok(false));
+    }
+
+    #[test]
+    fn apply_patch_request() {
+        let mut u = user();
+        let patch: ScimPatchRequest = serde_json::from_value(serde_json::json!({
+            "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+            "Operations": [
+                {"op": "add", "path": "emails", "value": [{"value": "babs@example.org", "type": "other"}]},
+                {"op": "add", "path": "name", "value": {"middleName": "J"}},
+                {"op": "remove", "path": "emails[type eq \"home\"]"},
+                {"op": "replace", "value": {
+                    "nickName": "Barbie",
+                    "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User": {"department": "Tours"}
+                }}
+            ]
+        }))
+        .expect("Failed to parse patch");
+        u.apply_patch(&patch).expect("Failed to apply patch");
+
+        assert_eq!(
+            strings(&u.get_path("emails.value").expect("Failed to get path")),
+            vec!["bjensen@example.com", "babs@example.org"]
+        );
+        assert_eq!(
+            strings(&u.get_path("name.middleName").expect("Failed to get path")),
+            vec!["J"]
+        );
+        assert_eq!(
+            strings(&u.get_path("name.familyName").expect("Failed to get path")),
+            vec!["Jensen"]
+        );
+        assert_eq!(
+            strings(&u.get_path("nickName").expect("Failed to get path")),
+            vec!["Barbie"]
+        );
+        assert_eq!(
+            strings(
+                &u.get_path(
+                    "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User:department"
+                )
+                .expect("Failed to get path")
+            ),
+            vec!["Tours"]
+        );
+
+        let remove_all: ScimPatchRequest = serde_json::from_value(serde_json::json!({
+            "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+            "Operations": [{"op": "remove"}]
+        }))
+        .expect("Failed to parse patch");
+        assert_eq!(u.apply_patch(&remove_all), Err(ScimError::NoTarget));
+    }
+}
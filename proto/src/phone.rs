@@ -0,0 +1,147 @@
+use crate::error::ScimError;
+use std::fmt;
+use std::str::FromStr;
+
+// https://datatracker.ietf.org/doc/html/rfc3966#section-3
+// https://www.itu.int/rec/T-REC-E.164
+
+// Characters RFC3966 calls visual-separator, plus the space and brackets
+// people write numbers with.
+const SEPARATORS: &[char] = &['-', '.', '(', ')', ' '];
+
+/// A global phone number, parsed from an RFC3966 `tel:` URI such as
+/// `tel:+1-201-555-0123;ext=42`, or a written number such as
+/// `+1 (201) 555-0123`. Local numbers have no country code, so they can't be
+/// normalised and are rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhoneNumber {
+    digits: String,
+    extension: Option<String>,
+}
+
+fn strip_separators(s: &str) -> String {
+    s.chars().filter(|c| !SEPARATORS.contains(c)).collect()
+}
+
+fn is_digits(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+impl PhoneNumber {
+    pub fn parse(number: &str) -> Result<Self, ScimError> {
+        let number = number.trim();
+        let number = match number.get(..4) {
+            Some(scheme) if scheme.eq_ignore_ascii_case("tel:") => &number[4..],
+            _ => number,
+        };
+
+        let mut parts = number.split(';');
+        let global = parts.next().unwrap_or_default();
+
+        let mut extension = None;
+        for param in parts {
+            let (name, value) = param.split_once('=').unwrap_or((param, ""));
+            if name.eq_ignore_ascii_case("ext") {
+                let value = strip_separators(value);
+                if extension.is_some() || !is_digits(&value) {
+                    return Err(ScimError::InvalidAttribute);
+                }
+                extension = Some(value);
+            }
+            // Other parameters, such as isub or phone-context, don't change
+            // the number and are dropped.
+        }
+
+        let digits = global
+            .strip_prefix('+')
+            .map(strip_separators)
+            .ok_or(ScimError::InvalidAttribute)?;
+
+        // E.164 numbers have at most 15 digits, and country codes don't
+        // start with 0.
+        if !is_digits(&digits) || digits.len() > 15 || digits.starts_with('0') {
+            return Err(ScimError::InvalidAttribute);
+        }
+
+        Ok(PhoneNumber { digits, extension })
+    }
+
+    /// The number in E.164 form, such as `+12015550123`. Any extension is
+    /// not included.
+    pub fn e164(&self) -> String {
+        format!("+{}", self.digits)
+    }
+
+    pub fn extension(&self) -> Option<&str> {
+        self.extension.as_deref()
+    }
+
+    /// The number as an RFC3966 URI without separators, which is the form
+    /// RFC7643 recommends for `phoneNumbers`.
+    pub fn to_tel_uri(&self) -> String {
+        match &self.extension {
+            Some(ext) => format!("tel:+{};ext={}", self.digits, ext),
+            None => format!("tel:+{}", self.digits),
+        }
+    }
+}
+
+impl FromStr for PhoneNumber {
+    type Err = ScimError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PhoneNumber::parse(s)
+    }
+}
+
+impl fmt::Display for PhoneNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_tel_uri())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phone_parse() {
+        for (number, e164, tel) in [
+            ("tel:+1-201-555-0123", "+12015550123", "tel:+12015550123"),
+            ("+1 (201) 555-0123", "+12015550123", "tel:+12015550123"),
+            ("TEL:+61.7.3000.0000", "+61730000000", "tel:+61730000000"),
+            (
+                "tel:+1-201-555-0123;ext=42",
+                "+12015550123",
+                "tel:+12015550123;ext=42",
+            ),
+            (
+                "tel:+44-20-7946-0000;isub=1",
+                "+442079460000",
+                "tel:+442079460000",
+            ),
+        ] {
+            let phone = PhoneNumber::parse(number).expect("Failed to parse number");
+            assert_eq!(phone.e164(), e164);
+            assert_eq!(phone.to_tel_uri(), tel);
+        }
+
+        for number in [
+            "",
+            "555-0123",
+            "tel:555-0123;phone-context=example.com",
+            "+",
+            "+0 201 555 0123",
+            "+1 201 555 0123 45678",
+            "+1 201 CALL NOW",
+            "tel:+12015550123;ext=",
+        ] {
+            assert_eq!(
+                PhoneNumber::parse(number),
+                Err(ScimError::InvalidAttribute),
+                "{}",
+                number
+            );
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use crate::messages::ScimQueryParams;
+use crate::schema::{split_patch_path, Returned, ScimAttributeDefinition, ScimSchema};
+use crate::{ScimComplexAttr, ScimEntryGeneric, ScimValue};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+
+// https://datatracker.ietf.org/doc/html/rfc7643#section-2.2 - returned
+// https://datatracker.ietf.org/doc/html/rfc7644#section-3.4.2.5
+
+/// Serializes an entry with the attributes the schema says must not be
+/// returned removed. `returned: never` attributes are always dropped, and
+/// `returned: request` attributes are only included when named in
+/// `attributes`. The `attributes` and `excludedAttributes` parameters are
+/// applied to `returned: default` attributes.
+#[derive(Debug, Clone, Copy)]
+pub struct EntryProjection<'a> {
+    schema: &'a ScimSchema,
+    entry: &'a ScimEntryGeneric,
+    attributes: &'a [String],
+    excluded_attributes: &'a [String],
+}
+
+impl<'a> EntryProjection<'a> {
+    pub fn new(schema: &'a ScimSchema, entry: &'a ScimEntryGeneric) -> Self {
+        EntryProjection {
+            schema,
+            entry,
+            attributes: &[],
+            excluded_attributes: &[],
+        }
+    }
+
+    pub fn with_query(mut self, params: &'a ScimQueryParams) -> Self {
+        self.attributes = &params.attributes;
+        self.excluded_attributes = &params.excluded_attributes;
+        self
+    }
+
+    /// The names from `paths` that refer to `attr` of this schema, as
+    /// `(whole attribute named, sub-attributes named)`.
+    fn named(&self, paths: &'a [String], attr: &str) -> (bool, Vec<&'a str>) {
+        let mut whole = false;
+        let mut subs = Vec::new();
+        for path in paths {
+            let (urn, name, sub) = split_patch_path(path);
+            if urn
+                .map(|u| !u.eq_ignore_ascii_case(&self.schema.id))
+                .unwrap_or(false)
+                || !name.eq_ignore_ascii_case(attr)
+            {
+                continue;
+            }
+            match sub {
+                Some(sub) => subs.push(sub),
+                None => whole = true,
+            }
+        }
+        (whole, subs)
+    }
+
+    /// Decide if an attribute is returned, and if so how to filter its
+    /// sub-attributes.
+    fn filter(&self, def: &'a ScimAttributeDefinition) -> Option<SubFilter<'a>> {
+        let projected = !self.attributes.is_empty();
+        let (listed, listed_subs) = self.named(self.attributes, &def.name);
+        let (excluded, excluded_subs) = self.named(self.excluded_attributes, &def.name);
+        let requested = listed || !listed_subs.is_empty();
+
+        let keep = match def.returned {
+            Returned::Never => false,
+            Returned::Always => true,
+            Returned::Request => requested,
+            Returned::Default if projected => requested,
+            Returned::Default => !excluded,
+        };
+
+        keep.then_some(SubFilter {
+            def,
+            projected: projected && !listed,
+            listed_subs,
+            excluded_subs,
+        })
+    }
+}
+
+struct SubFilter<'a> {
+    def: &'a ScimAttributeDefinition,
+    /// Only some sub-attributes were named in `attributes`.
+    projected: bool,
+    listed_subs: Vec<&'a str>,
+    excluded_subs: Vec<&'a str>,
+}
+
+impl SubFilter<'_> {
+    fn keep(&self, name: &str) -> bool {
+        let returned = self
+            .def
+            .sub_attribute(name)
+            .map(|d| d.returned)
+            .unwrap_or_default();
+        let listed = self
+            .listed_subs
+            .iter()
+            .any(|s| s.eq_ignore_ascii_case(name));
+
+        match returned {
+            Returned::Never => false,
+            Returned::Always => true,
+            Returned::Request => listed,
+            Returned::Default if self.projected => listed,
+            Returned::Default => !self
+                .excluded_subs
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(name)),
+        }
+    }
+}
+
+struct FilteredComplex<'f, 'a>(&'f SubFilter<'a>, &'f ScimComplexAttr);
+
+impl Serialize for FilteredComplex<'_, '_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for (k, v) in self.1.iter().filter(|(k, _)| self.0.keep(k)) {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+struct FilteredMultiComplex<'f, 'a>(&'f SubFilter<'a>, &'f [ScimComplexAttr]);
+
+impl Serialize for FilteredMultiComplex<'_, '_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.1.len()))?;
+        for value in self.1 {
+            seq.serialize_element(&FilteredComplex(self.0, value))?;
+        }
+        seq.end()
+    }
+}
+
+impl Serialize for EntryProjection<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let entry = self.entry;
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("schemas", &entry.schemas)?;
+        map.serialize_entry("id", &entry.id)?;
+        if let Some(external_id) = &entry.external_id {
+            map.serialize_entry("externalId", external_id)?;
+        }
+        if let Some(meta) = &entry.meta {
+            map.serialize_entry("meta", meta)?;
+        }
+
+        for (name, value) in entry.attrs.iter() {
+            let Some(def) = self.schema.attribute(name) else {
+                // Not described by this schema, such as an extension.
+                map.serialize_entry(name, value)?;
+                continue;
+            };
+
+            let Some(filter) = self.filter(def) else {
+                continue;
+            };
+
+            match value {
+                ScimValue::Complex(attrs) => {
+                    map.serialize_entry(name, &FilteredComplex(&filter, attrs))?
+                }
+                ScimValue::MultiComplex(values) => {
+                    map.serialize_entry(name, &FilteredMultiComplex(&filter, values))?
+                }
+                ScimValue::Simple(_) | ScimValue::MultiSimple(_) | ScimValue::Extension(_) => {
+                    map.serialize_entry(name, value)?
+                }
+            }
+        }
+
+        map.end()
+    }
+}
+
+impl ScimSchema {
+    /// Serialize `entry` with the attributes this schema marks as not
+    /// returned removed.
+    pub fn project<'a>(&'a self, entry: &'a ScimEntryGeneric) -> EntryProjection<'a> {
+        EntryProjection::new(self, entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{RFC7643_SCHEMA_GROUP, RFC7643_USER};
+    use crate::schema::AttributeType;
+
+    fn user_schema() -> ScimSchema {
+        let attr = |name: &str, returned: Returned| ScimAttributeDefinition {
+            returned,
+            ..ScimAttributeDefinition::new(name, AttributeType::String)
+        };
+
+        let mut name = attr("name", Returned::Default);
+        name.type_ = AttributeType::Complex;
+        name.sub_attributes = vec![
+            attr("givenName", Returned::Default),
+            attr("familyName", Returned::Default),
+            attr("middleName", Returned::Request),
+        ];
+
+        ScimSchema {
+            id: "urn:ietf:params:scim:schemas:core:2.0:User".to_string(),
+            name: Some("User".to_string()),
+            description: None,
+            attributes: vec![
+                attr("userName", Returned::Always),
+                attr("password", Returned::Never),
+                attr("title", Returned::Request),
+                attr("nickName", Returned::Default),
+                name,
+            ],
+            meta: None,
+        }
+    }
+
+    #[test]
+    fn project_returned() {
+        let schema = user_schema();
+        let u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+
+        let v = serde_json::to_value(schema.project(&u)).expect("Failed to serialise");
+        assert_eq!(v["userName"], "bjensen@example.com");
+        assert!(v.get("password").is_none());
+        assert!(v.get("title").is_none());
+        assert_eq!(v["nickName"], "Babs");
+        assert_eq!(v["name"]["givenName"], "Barbara");
+        assert!(v["name"].get("middleName").is_none());
+        // Not in the schema, so untouched.
+        assert_eq!(v["displayName"], "Babs Jensen");
+        assert_eq!(v["id"], "2819c223-7f76-453a-919d-413861904646");
+
+        let params = ScimQueryParams {
+            attributes: vec!["title".to_string(), "name.middleName".to_string()],
+            ..Default::default()
+        };
+        let v = serde_json::to_value(schema.project(&u).with_query(&params))
+            .expect("Failed to serialise");
+        assert_eq!(v["userName"], "bjensen@example.com");
+        assert_eq!(v["title"], "Tour Guide");
+        assert!(v.get("nickName").is_none());
+        assert_eq!(v["name"]["middleName"], "Jane");
+        assert!(v["name"].get("givenName").is_none());
+
+        let params = ScimQueryParams {
+            excluded_attributes: vec![
+                "nickName".to_string(),
+                "userName".to_string(),
+                "urn:ietf:params:scim:schemas:core:2.0:User:name.familyName".to_string(),
+            ],
+            ..Default::default()
+        };
+        let v = serde_json::to_value(schema.project(&u).with_query(&params))
+            .expect("Failed to serialise");
+        assert_eq!(v["userName"], "bjensen@example.com");
+        assert!(v.get("nickName").is_none());
+        assert_eq!(v["name"]["givenName"], "Barbara");
+        assert!(v["name"].get("familyName").is_none());
+    }
+
+    #[test]
+    fn project_multi_complex() {
+        let mut schema =
+            ScimSchema::parse(RFC7643_SCHEMA_GROUP).expect("Failed to parse group schema");
+        schema.attributes[1].sub_attributes[1].returned = Returned::Never;
+
+        let g: ScimEntryGeneric =
+            serde_json::from_str(crate::constants::RFC7643_GROUP).expect("Failed to parse group");
+        let v = serde_json::to_value(schema.project(&g)).expect("Failed to serialise");
+
+        let members = v["members"].as_array().expect("members missing");
+        assert!(!members.is_empty());
+        assert!(members.iter().all(|m| m.get("$ref").is_none()));
+        assert!(members.iter().all(|m| m.get("value").is_some()));
+    }
+}
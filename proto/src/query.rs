@@ -0,0 +1,173 @@
+//! A builder of list queries, for clients. A [`ListQuery`] renders to the
+//! parameters of a GET, [`ScimQueryParams`], or the body of a POST to
+//! `.search`, [`ScimSearchRequest`], with the filter written in the syntax
+//! of RFC7644 section 3.4.2.2.
+
+use crate::filter::ScimFilter;
+use crate::messages::{ScimQueryParams, ScimSearchRequest, SortOrder};
+
+/// The attribute to sort by, and in which order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortSpec {
+    pub by: String,
+    pub order: SortOrder,
+}
+
+impl SortSpec {
+    pub fn ascending(by: impl Into<String>) -> Self {
+        SortSpec {
+            by: by.into(),
+            order: SortOrder::Ascending,
+        }
+    }
+
+    pub fn descending(by: impl Into<String>) -> Self {
+        SortSpec {
+            by: by.into(),
+            order: SortOrder::Descending,
+        }
+    }
+}
+
+/// A query of a list of resources. Without a filter, all resources match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListQuery {
+    filter: Option<ScimFilter>,
+    attributes: Vec<String>,
+    excluded_attributes: Vec<String>,
+    sort: Option<SortSpec>,
+    start_index: Option<u64>,
+    count: Option<u64>,
+    cursor: Option<String>,
+}
+
+impl ListQuery {
+    pub fn new() -> Self {
+        ListQuery::default()
+    }
+
+    pub fn filter(mut self, filter: ScimFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Return only these attributes, as well as those always returned.
+    pub fn attributes<I, S>(mut self, attrs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.attributes.extend(attrs.into_iter().map(Into::into));
+        self
+    }
+
+    /// Don't return these attributes, unless they are always returned.
+    pub fn excluded_attributes<I, S>(mut self, attrs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.excluded_attributes
+            .extend(attrs.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn sort(mut self, sort: SortSpec) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    /// The 1-based index of the first result.
+    pub fn start_index(mut self, start_index: u64) -> Self {
+        self.start_index = Some(start_index);
+        self
+    }
+
+    /// The most results to return in a page.
+    pub fn count(mut self, count: u64) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    /// Page by cursor. An empty cursor requests the first page.
+    pub fn cursor(mut self, cursor: impl Into<String>) -> Self {
+        self.cursor = Some(cursor.into());
+        self
+    }
+
+    /// The query parameters of a GET, which serialise to a query string.
+    pub fn to_params(&self) -> ScimQueryParams {
+        ScimQueryParams {
+            filter: self.filter.as_ref().map(ToString::to_string),
+            sort_by: self.sort.as_ref().map(|s| s.by.clone()),
+            sort_order: self.sort.as_ref().map(|s| s.order),
+            start_index: self.start_index,
+            count: self.count,
+            cursor: self.cursor.clone(),
+            attributes: self.attributes.clone(),
+            excluded_attributes: self.excluded_attributes.clone(),
+        }
+    }
+
+    /// The body of a POST to `.search`.
+    pub fn to_search_request(&self) -> ScimSearchRequest {
+        self.to_params().into()
+    }
+}
+
+impl From<ListQuery> for ScimQueryParams {
+    fn from(query: ListQuery) -> Self {
+        query.to_params()
+    }
+}
+
+impl From<&ListQuery> for ScimQueryParams {
+    fn from(query: &ListQuery) -> Self {
+        query.to_params()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_query_render() {
+        let filter: ScimFilter = r#"userName sw "j" and (active eq true or title pr)"#
+            .parse()
+            .expect("Invalid filter");
+        let query = ListQuery::new()
+            .filter(filter)
+            .attributes(["userName", "emails"])
+            .excluded_attributes(["groups"])
+            .sort(SortSpec::descending("meta.lastModified"))
+            .count(50);
+
+        assert_eq!(
+            query.to_params(),
+            ScimQueryParams {
+                filter: Some(r#"userName sw "j" and (active eq true or title pr)"#.to_string()),
+                sort_by: Some("meta.lastModified".to_string()),
+                sort_order: Some(SortOrder::Descending),
+                count: Some(50),
+                attributes: vec!["userName".to_string(), "emails".to_string()],
+                excluded_attributes: vec!["groups".to_string()],
+                ..ScimQueryParams::default()
+            }
+        );
+
+        let body = serde_json::to_value(query.to_search_request()).expect("Failed to serialise");
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "schemas": ["urn:ietf:params:scim:api:messages:2.0:SearchRequest"],
+                "attributes": ["userName", "emails"],
+                "excludedAttributes": ["groups"],
+                "filter": "userName sw \"j\" and (active eq true or title pr)",
+                "sortBy": "meta.lastModified",
+                "sortOrder": "descending",
+                "count": 50
+            })
+        );
+    }
+}
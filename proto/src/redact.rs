@@ -0,0 +1,189 @@
+//! Debug output that leaves out secrets, such as passwords, so that they
+//! don't end up in logs. Entries, patch operations and bulk operations hide
+//! the values of [`SENSITIVE_ATTRS`], and a [`crate::user::Password`] hides
+//! itself. [`Unredacted`] shows them, for debugging locally.
+
+use crate::attr_map::AttrMap;
+use serde_json::Value;
+use std::cell::Cell;
+use std::fmt;
+
+/// The attributes whose values aren't shown, compared case insensitively.
+pub const SENSITIVE_ATTRS: &[&str] = &["password"];
+
+thread_local! {
+    static UNREDACTED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether the attribute at `path`, such as `password` or
+/// `urn:ietf:params:scim:schemas:core:2.0:User:password`, is sensitive.
+pub fn is_sensitive(path: &str) -> bool {
+    let path = path.split('[').next().unwrap_or(path);
+    let attr = path.rsplit(':').next().unwrap_or(path);
+    let attr = attr.split('.').next().unwrap_or(attr);
+    SENSITIVE_ATTRS
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(attr))
+}
+
+/// Formats the value it wraps with its [`fmt::Debug`] impl, secrets and
+/// all, as in `tracing::debug!("{:?}", Unredacted(&user))`.
+pub struct Unredacted<'a, T: ?Sized>(pub &'a T);
+
+// Restores the previous state when formatting ends, even by a panic.
+struct Reset(bool);
+
+impl Drop for Reset {
+    fn drop(&mut self) {
+        UNREDACTED.with(|u| u.set(self.0));
+    }
+}
+
+impl<T: fmt::Debug + ?Sized> fmt::Debug for Unredacted<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let _reset = Reset(UNREDACTED.with(|u| u.replace(true)));
+        self.0.fmt(f)
+    }
+}
+
+pub(crate) fn unredacted() -> bool {
+    UNREDACTED.with(Cell::get)
+}
+
+/// A secret value, shown only within [`Unredacted`].
+pub(crate) struct Secret<'a, T: ?Sized>(pub &'a T);
+
+impl<T: fmt::Debug + ?Sized> fmt::Debug for Secret<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if unredacted() {
+            self.0.fmt(f)
+        } else {
+            f.write_str("<redacted>")
+        }
+    }
+}
+
+/// The value of the attribute at `path`, hidden if it is sensitive.
+pub(crate) struct AttrValue<'a, T: ?Sized>(pub &'a str, pub &'a T);
+
+impl<T: fmt::Debug + ?Sized> fmt::Debug for AttrValue<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if is_sensitive(self.0) {
+            Secret(self.1).fmt(f)
+        } else {
+            self.1.fmt(f)
+        }
+    }
+}
+
+/// A map of attributes, with the sensitive ones hidden.
+pub(crate) struct AttrMapValues<'a, V>(pub &'a AttrMap<V>);
+
+impl<V: fmt::Debug> fmt::Debug for AttrMapValues<'_, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        AttrPairs(self.0.iter()).fmt(f)
+    }
+}
+
+/// Attributes by name, from any map or list of them, with the sensitive
+/// ones hidden.
+pub(crate) struct AttrPairs<I>(pub I);
+
+impl<'a, I, K, V> fmt::Debug for AttrPairs<I>
+where
+    I: Iterator<Item = (&'a K, &'a V)> + Clone,
+    K: AsRef<str> + fmt::Debug + ?Sized + 'a,
+    V: fmt::Debug + 'a,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.clone().map(|(k, v)| (k, AttrValue(k.as_ref(), v))))
+            .finish()
+    }
+}
+
+/// A JSON object of attributes, such as the data of a request, with the
+/// sensitive ones hidden.
+pub(crate) struct Attrs<'a>(pub &'a Value);
+
+impl fmt::Debug for Attrs<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Value::Object(attrs) => f
+                .debug_map()
+                .entries(attrs.iter().map(|(k, v)| (k, AttrValue(k, v))))
+                .finish(),
+            value => value.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{ScimPatchOpKind, ScimPatchOperation};
+
+    #[test]
+    fn redacted_attrs() {
+        assert!(is_sensitive("password"));
+        assert!(is_sensitive(
+            "urn:ietf:params:scim:schemas:core:2.0:User:PASSWORD"
+        ));
+        assert!(!is_sensitive(
+            "urn:ietf:params:scim:schemas:core:2.0:User:name.givenName"
+        ));
+
+        let data = serde_json::json!({ "userName": "bjensen", "password": "t1meMa$heen" });
+        let shown = format!("{:?}", Attrs(&data));
+        assert!(shown.contains("bjensen"));
+        assert!(!shown.contains("t1meMa$heen"));
+        assert!(format!("{:?}", Unredacted(&Attrs(&data))).contains("t1meMa$heen"));
+        assert!(!format!("{:?}", Attrs(&data)).contains("t1meMa$heen"));
+
+        let entry: crate::ScimEntryGeneric = serde_json::from_value(serde_json::json!({
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+            "id": "2819c223",
+            "userName": "bjensen",
+            "password": "t1meMa$heen"
+        }))
+        .expect("Failed to parse entry");
+        assert!(!format!("{:?}", entry).contains("t1meMa$heen"));
+        assert!(format!("{:?}", Unredacted(&entry)).contains("t1meMa$heen"));
+        let interned = crate::intern::Interner::new().entry(entry);
+        assert!(!format!("{:?}", interned).contains("t1meMa$heen"));
+        assert!(format!("{:?}", Unredacted(&interned)).contains("t1meMa$heen"));
+
+        let json = serde_json::to_string(&data).expect("Failed to serialise");
+        let borrowed: crate::borrowed::ScimEntryRef =
+            serde_json::from_str(&json).expect("Failed to parse entry");
+        assert!(!format!("{:?}", borrowed).contains("t1meMa$heen"));
+
+        let create = crate::user::User::builder("bjensen")
+            .password("t1meMa$heen")
+            .build()
+            .and_then(|user| user.to_create())
+            .expect("Failed to build create request");
+        assert!(!format!("{:?}", create).contains("t1meMa$heen"));
+        assert!(format!("{:?}", Unredacted(&create)).contains("t1meMa$heen"));
+
+        let op = ScimPatchOperation {
+            op: ScimPatchOpKind::Replace,
+            path: Some("password".to_string()),
+            value: Some(Value::String("t1meMa$heen".to_string())),
+        };
+        assert!(!format!("{:?}", op).contains("t1meMa$heen"));
+        let op = ScimPatchOperation {
+            path: None,
+            value: Some(data),
+            ..op
+        };
+        assert!(!format!("{:?}", op).contains("t1meMa$heen"));
+
+        let password = crate::user::Password::new("t1meMa$heen");
+        assert_eq!(format!("{:?}", password), "Password(<redacted>)");
+        assert_eq!(
+            format!("{:?}", Unredacted(&password)),
+            r#"Password("t1meMa$heen")"#
+        );
+    }
+}
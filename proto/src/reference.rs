@@ -0,0 +1,247 @@
+use crate::error::ScimError;
+use crate::group::{Member, MemberType};
+use crate::schema::SchemaViolation;
+use crate::user::{Group as UserGroup, Manager};
+use crate::ScimId;
+use url::Url;
+
+pub const USERS_ENDPOINT: &str = "Users";
+pub const GROUPS_ENDPOINT: &str = "Groups";
+
+pub(crate) fn percent_decode_bytes(s: &str) -> Result<Vec<u8>, ScimError> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = tail
+                .get(..2)
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or(ScimError::InvalidAttribute)?;
+            bytes.push(hex);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    Ok(bytes)
+}
+
+// Undo the encoding of RefBuilder::resource, which url applies to path
+// segments.
+fn percent_decode(s: &str) -> Result<String, ScimError> {
+    String::from_utf8(percent_decode_bytes(s)?)
+        .map_err(|err| SchemaViolation::unreadable("", err).into())
+}
+
+/// Builds and checks the `$ref` values of resources served under a base
+/// URL, such as `https://example.com/v2`. A resource's reference is
+/// `<base>/<endpoint>/<id>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefBuilder {
+    base: Url,
+}
+
+impl RefBuilder {
+    /// The base must be a URL that can have a path, such as an http URL.
+    pub fn new(base: &Url) -> Result<Self, ScimError> {
+        if base.cannot_be_a_base() {
+            return Err(ScimError::InvalidAttribute);
+        }
+        let mut base = base.clone();
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+        base.set_query(None);
+        base.set_fragment(None);
+        Ok(RefBuilder { base })
+    }
+
+    /// The base URL, which always ends with `/`.
+    pub fn base(&self) -> &Url {
+        &self.base
+    }
+
+    /// The reference of resource `id` under `endpoint`, such as `Users`. The
+    /// id is percent-encoded as needed.
+    pub fn resource(&self, endpoint: &str, id: &str) -> Url {
+        let mut url = self.base.clone();
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments
+                .pop_if_empty()
+                .push(endpoint.trim_matches('/'))
+                .push(id);
+        }
+        url
+    }
+
+    pub fn user(&self, id: impl Into<ScimId>) -> Url {
+        self.resource(USERS_ENDPOINT, &id.into().to_string())
+    }
+
+    pub fn group(&self, id: impl Into<ScimId>) -> Url {
+        self.resource(GROUPS_ENDPOINT, &id.into().to_string())
+    }
+
+    /// A group member with its `$ref` and `type` set. Members of a type
+    /// other than User or Group are referenced as users.
+    pub fn member(&self, type_: MemberType, id: impl Into<ScimId>, display: &str) -> Member {
+        let id = id.into();
+        let ref_ = match type_ {
+            MemberType::Group => self.group(id.clone()),
+            MemberType::User | MemberType::Custom(_) => self.user(id.clone()),
+        };
+        Member {
+            type_: Some(type_),
+            ..Member::new(id, ref_, display)
+        }
+    }
+
+    /// An entry of a user's `groups`.
+    pub fn user_group(&self, id: impl Into<ScimId>, display: &str) -> UserGroup {
+        let id = id.into();
+        UserGroup::new(id.clone(), self.group(id), display)
+    }
+
+    /// An enterprise user's manager.
+    pub fn manager(&self, id: impl Into<ScimId>) -> Manager {
+        let id = id.into();
+        Manager {
+            value: Some(id.to_string()),
+            ref_: Some(self.user(id).to_string()),
+            display_name: None,
+        }
+    }
+
+    /// Check that `reference` names a resource under `endpoint` of this
+    /// base, and return the resource's id. Relative references are resolved
+    /// against the base.
+    pub fn validate(&self, reference: &str, endpoint: &str) -> Result<String, ScimError> {
+        let url = Url::parse(reference)
+            .or_else(|_| self.base.join(reference))
+            .map_err(|err| SchemaViolation::unreadable("", err))?;
+        if url.query().is_some() || url.fragment().is_some() {
+            return Err(ScimError::InvalidAttribute);
+        }
+
+        let id = url
+            .as_str()
+            .strip_prefix(self.base.as_str())
+            .and_then(|rest| rest.split_once('/'))
+            .filter(|(e, _)| e.eq_ignore_ascii_case(endpoint.trim_matches('/')))
+            .map(|(_, id)| id)
+            .filter(|id| !id.is_empty() && !id.contains('/'))
+            .ok_or(ScimError::InvalidAttribute)?;
+
+        percent_decode(id)
+    }
+
+    /// Check that a member's `$ref` is the reference of its `value`, under
+    /// the endpoint of its `type`, or of either endpoint when it has none or
+    /// another type.
+    pub fn validate_member(&self, member: &Member) -> Result<(), ScimError> {
+        let endpoints: &[&str] = match member.type_ {
+            Some(MemberType::User) => &[USERS_ENDPOINT],
+            Some(MemberType::Group) => &[GROUPS_ENDPOINT],
+            Some(MemberType::Custom(_)) | None => &[USERS_ENDPOINT, GROUPS_ENDPOINT],
+        };
+        let value = member.value.to_string();
+
+        endpoints
+            .iter()
+            .filter_map(|endpoint| self.validate(member.ref_.as_str(), endpoint).ok())
+            .any(|id| id.eq_ignore_ascii_case(&value))
+            .then_some(())
+            .ok_or(ScimError::InvalidAttribute)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn builder() -> RefBuilder {
+        let base = Url::parse("https://example.com/v2").expect("Invalid url");
+        RefBuilder::new(&base).expect("Invalid base")
+    }
+
+    #[test]
+    fn ref_builder() {
+        let refs = builder();
+        let id = Uuid::parse_str("2819c223-7f76-453a-919d-413861904646").expect("Invalid uuid");
+
+        assert_eq!(refs.base().as_str(), "https://example.com/v2/");
+        assert_eq!(
+            refs.user(id).as_str(),
+            "https://example.com/v2/Users/2819c223-7f76-453a-919d-413861904646"
+        );
+        assert_eq!(
+            refs.resource("/Devices/", "a b/c").as_str(),
+            "https://example.com/v2/Devices/a%20b%2Fc"
+        );
+
+        let member = refs.member(MemberType::Group, id, "Tour Guides");
+        assert_eq!(member.ref_, refs.group(id));
+        assert_eq!(refs.validate_member(&member), Ok(()));
+
+        let manager = refs.manager(id);
+        assert_eq!(manager.ref_, Some(refs.user(id).to_string()));
+        assert_eq!(refs.user_group(id, "Tour Guides").ref_, refs.group(id));
+
+        let mailto = Url::parse("mailto:someone@example.com").expect("Invalid url");
+        assert_eq!(RefBuilder::new(&mailto), Err(ScimError::InvalidAttribute));
+    }
+
+    #[test]
+    fn ref_validate() {
+        let refs = builder();
+        let id = "2819c223-7f76-453a-919d-413861904646";
+
+        for reference in [
+            "https://example.com/v2/Users/2819c223-7f76-453a-919d-413861904646",
+            "https://EXAMPLE.com/v2/users/2819c223-7f76-453a-919d-413861904646",
+            "Users/2819c223-7f76-453a-919d-413861904646",
+            "../v2/Users/2819c223-7f76-453a-919d-413861904646",
+        ] {
+            assert_eq!(
+                refs.validate(reference, USERS_ENDPOINT),
+                Ok(id.to_string()),
+                "{}",
+                reference
+            );
+        }
+
+        for reference in [
+            "https://example.com/v2/Groups/2819c223-7f76-453a-919d-413861904646",
+            "https://example.org/v2/Users/2819c223-7f76-453a-919d-413861904646",
+            "https://example.com/v1/Users/2819c223-7f76-453a-919d-413861904646",
+            "https://example.com/v2/Users/",
+            "https://example.com/v2/Users/a/b",
+            "https://example.com/v2/Users/a?attributes=userName",
+        ] {
+            assert_eq!(
+                refs.validate(reference, USERS_ENDPOINT),
+                Err(ScimError::InvalidAttribute),
+                "{}",
+                reference
+            );
+        }
+
+        assert_eq!(
+            refs.validate("https://example.com/v2/Devices/a%20b%2Fc", "Devices"),
+            Ok("a b/c".to_string())
+        );
+
+        let uuid = Uuid::parse_str(id).expect("Invalid uuid");
+        let mut member = refs.member(MemberType::User, uuid, "Babs Jensen");
+        member.type_ = Some(MemberType::Group);
+        assert_eq!(
+            refs.validate_member(&member),
+            Err(ScimError::InvalidAttribute)
+        );
+        member.type_ = None;
+        assert_eq!(refs.validate_member(&member), Ok(()));
+    }
+}
@@ -0,0 +1,281 @@
+use crate::schema::{
+    split_patch_path, AttributeType, SchemaError, ScimAttributeDefinition, ScimSchema,
+};
+use crate::ScimEntryGeneric;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+// https://datatracker.ietf.org/doc/html/rfc7643#section-6
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaExtension {
+    pub schema: String,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// A `ResourceType` document, naming the base schema and extensions of a
+/// resource.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTypeDefinition {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    pub endpoint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub schema: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schema_extensions: Vec<SchemaExtension>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Value>,
+}
+
+/// The core and extension schemas known to a service provider, and the
+/// resource types built from them. Schema URNs and resource type names are
+/// looked up case insensitively.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaRegistry {
+    schemas: BTreeMap<String, ScimSchema>,
+    resource_types: BTreeMap<String, ResourceTypeDefinition>,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        SchemaRegistry::default()
+    }
+
+    /// Add a schema, replacing any schema with the same id.
+    pub fn add_schema(&mut self, schema: ScimSchema) -> Result<(), SchemaError> {
+        schema.check_definition()?;
+        self.insert_schema(schema);
+        Ok(())
+    }
+
+    pub(crate) fn insert_schema(&mut self, schema: ScimSchema) {
+        self.schemas.insert(schema.id.to_ascii_lowercase(), schema);
+    }
+
+    /// Add a resource type. Its base schema and extensions must already be
+    /// registered.
+    pub fn add_resource_type(
+        &mut self,
+        resource_type: ResourceTypeDefinition,
+    ) -> Result<(), SchemaError> {
+        let urns = std::iter::once(&resource_type.schema).chain(
+            resource_type
+                .schema_extensions
+                .iter()
+                .map(|ext| &ext.schema),
+        );
+        for urn in urns {
+            if self.schema(urn).is_none() {
+                return Err(SchemaError::UnknownSchema(urn.clone()));
+            }
+        }
+
+        self.insert_resource_type(resource_type);
+        Ok(())
+    }
+
+    pub(crate) fn insert_resource_type(&mut self, resource_type: ResourceTypeDefinition) {
+        self.resource_types
+            .insert(resource_type.name.to_ascii_lowercase(), resource_type);
+    }
+
+    pub fn schema(&self, urn: &str) -> Option<&ScimSchema> {
+        self.schemas.get(&urn.to_ascii_lowercase())
+    }
+
+    pub fn schemas(&self) -> impl Iterator<Item = &ScimSchema> {
+        self.schemas.values()
+    }
+
+    pub fn resource_type(&self, name: &str) -> Option<&ResourceTypeDefinition> {
+        self.resource_types.get(&name.to_ascii_lowercase())
+    }
+
+    pub fn resource_types(&self) -> impl Iterator<Item = &ResourceTypeDefinition> {
+        self.resource_types.values()
+    }
+
+    /// The resource type whose base schema is listed in the entry's `schemas`.
+    pub fn resource_type_for(&self, entry: &ScimEntryGeneric) -> Option<&ResourceTypeDefinition> {
+        self.resource_types.values().find(|rt| {
+            entry
+                .schemas
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(&rt.schema))
+        })
+    }
+
+    /// Resolve a fully qualified attribute path such as
+    /// `urn:ietf:params:scim:schemas:core:2.0:User:name.givenName`.
+    pub fn attribute(&self, path: &str) -> Option<&ScimAttributeDefinition> {
+        let (urn, attr, sub) = split_patch_path(path);
+        let def = self.schema(urn?)?.attribute(attr)?;
+        match sub {
+            Some(sub) => def.sub_attribute(sub),
+            None => Some(def),
+        }
+    }
+
+    /// Resolve an attribute path of a resource type. Qualified paths must
+    /// name one of the resource type's schemas. Unqualified paths are looked
+    /// up in the base schema, then in each extension.
+    pub fn resolve(&self, resource_type: &str, path: &str) -> Option<&ScimAttributeDefinition> {
+        let rt = self.resource_type(resource_type)?;
+        let (urn, attr, sub) = split_patch_path(path);
+
+        let mut urns = std::iter::once(&rt.schema)
+            .chain(rt.schema_extensions.iter().map(|ext| &ext.schema))
+            .filter(|s| urn.map(|urn| s.eq_ignore_ascii_case(urn)).unwrap_or(true));
+
+        let def = urns.find_map(|urn| self.schema(urn)?.attribute(attr))?;
+        match sub {
+            Some(sub) => def.sub_attribute(sub),
+            None => Some(def),
+        }
+    }
+
+    /// Merge a resource type's base schema and extensions into one schema.
+    /// Each extension becomes a complex attribute named by its URN, which is
+    /// how extension attributes appear in an entry, so the result can be
+    /// used with [`ScimSchema::validate`] and [`ScimSchema::project`].
+    pub fn merged(&self, resource_type: &str) -> Option<ScimSchema> {
+        let rt = self.resource_type(resource_type)?;
+        let mut schema = self.schema(&rt.schema)?.clone();
+
+        for ext in rt.schema_extensions.iter() {
+            let ext_schema = self.schema(&ext.schema)?;
+            let mut attr = ScimAttributeDefinition::new(&ext_schema.id, AttributeType::Complex);
+            attr.description = ext_schema.description.clone();
+            attr.required = ext.required;
+            attr.sub_attributes = ext_schema.attributes.clone();
+            schema.attributes.push(attr);
+        }
+
+        Some(schema)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::RFC7643_SCHEMA_GROUP;
+    use crate::schema::ViolationKind;
+
+    const EXT_SCHEMA: &str = r#"{
+      "id": "urn:example:params:scim:schemas:extension:team:2.0:Group",
+      "name": "TeamGroup",
+      "attributes": [
+        { "name": "costCenter", "type": "string", "required": true },
+        { "name": "division", "type": "string" }
+      ]
+    }"#;
+
+    const RESOURCE_TYPE: &str = r#"{
+      "schemas": ["urn:ietf:params:scim:schemas:core:2.0:ResourceType"],
+      "id": "Group",
+      "name": "Group",
+      "endpoint": "/Groups",
+      "schema": "urn:ietf:params:scim:schemas:core:2.0:Group",
+      "schemaExtensions": [
+        { "schema": "urn:example:params:scim:schemas:extension:team:2.0:Group", "required": true }
+      ]
+    }"#;
+
+    fn registry() -> SchemaRegistry {
+        let mut registry = SchemaRegistry::new();
+        registry
+            .add_schema(ScimSchema::parse(RFC7643_SCHEMA_GROUP).expect("Failed to parse group"))
+            .expect("Failed to add group");
+        registry
+            .add_schema(ScimSchema::parse(EXT_SCHEMA).expect("Failed to parse extension"))
+            .expect("Failed to add extension");
+        registry
+            .add_resource_type(serde_json::from_str(RESOURCE_TYPE).expect("Failed to parse rt"))
+            .expect("Failed to add resource type");
+        registry
+    }
+
+    #[test]
+    fn registry_lookup() {
+        let registry = registry();
+
+        assert!(registry
+            .schema("URN:ietf:params:scim:schemas:core:2.0:group")
+            .is_some());
+        assert_eq!(
+            registry
+                .attribute("urn:ietf:params:scim:schemas:core:2.0:Group:members.type")
+                .map(|a| a.name.as_str()),
+            Some("type")
+        );
+        assert!(registry.attribute("members.type").is_none());
+
+        assert!(registry.resolve("group", "displayName").is_some());
+        assert!(registry.resolve("Group", "costCenter").is_some());
+        assert!(registry
+            .resolve(
+                "Group",
+                "urn:example:params:scim:schemas:extension:team:2.0:Group:division"
+            )
+            .is_some());
+        assert!(registry
+            .resolve(
+                "Group",
+                "urn:ietf:params:scim:schemas:core:2.0:Group:division"
+            )
+            .is_none());
+
+        let mut rt: ResourceTypeDefinition =
+            serde_json::from_str(RESOURCE_TYPE).expect("Failed to parse rt");
+        rt.schema_extensions[0].schema = "urn:example:missing".to_string();
+        assert_eq!(
+            SchemaRegistry::new().add_resource_type(rt),
+            Err(SchemaError::UnknownSchema(
+                "urn:ietf:params:scim:schemas:core:2.0:Group".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn registry_merged() {
+        let registry = registry();
+        let schema = registry.merged("Group").expect("Failed to merge");
+
+        let g: ScimEntryGeneric = serde_json::from_str(
+            r#"{
+              "schemas": [
+                "urn:ietf:params:scim:schemas:core:2.0:Group",
+                "urn:example:params:scim:schemas:extension:team:2.0:Group"
+              ],
+              "id": "e9e30dba-f08f-4109-8486-d5c6a331660a",
+              "displayName": "Tour Guides",
+              "urn:example:params:scim:schemas:extension:team:2.0:Group": {
+                "division": "Tours"
+              }
+            }"#,
+        )
+        .expect("Failed to parse group");
+
+        assert_eq!(
+            registry.resource_type_for(&g).map(|rt| rt.name.as_str()),
+            Some("Group")
+        );
+
+        let err = schema.validate(&g).expect_err("costCenter is required");
+        assert_eq!(err.len(), 1);
+        assert_eq!(
+            err[0].path,
+            "urn:example:params:scim:schemas:extension:team:2.0:Group:costCenter"
+        );
+        assert_eq!(err[0].kind, ViolationKind::MissingRequired);
+    }
+}
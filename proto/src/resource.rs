@@ -0,0 +1,500 @@
+use crate::attr_map::{self, AttrMap};
+use crate::convert;
+use crate::error::ScimError;
+use crate::{ScimComplexAttr, ScimEntryGeneric, ScimValue};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::borrow::Cow;
+
+#[cfg(feature = "derive")]
+pub use scim_proto_derive::ScimResource;
+
+/// A typed resource, such as [`crate::user::User`], that converts to and
+/// from a generic entry. Code generic over resource types, such as clients
+/// and list handling, can be written against this trait, and types made by
+/// [`crate::codegen`] implement it. With the `derive` feature, it can be
+/// derived for a struct; see the `scim_proto_derive` crate.
+pub trait ScimResource: Sized + TryFrom<ScimEntryGeneric, Error = ScimError> {
+    /// The URN of the resource's core schema.
+    const SCHEMA_URN: &'static str;
+
+    /// The name of the resource type, such as `User`.
+    fn resource_type() -> &'static str;
+
+    fn into_entry(self) -> Result<ScimEntryGeneric, ScimError>;
+
+    /// Convert a generic entry, checking that it lists [`Self::SCHEMA_URN`]
+    /// in its schemas first.
+    fn from_entry_checked(entry: ScimEntryGeneric) -> Result<Self, ScimError> {
+        if entry
+            .schemas
+            .iter()
+            .any(|urn| urn.eq_ignore_ascii_case(Self::SCHEMA_URN))
+        {
+            Self::try_from(entry)
+        } else {
+            Err(ScimError::InvalidAttribute)
+        }
+    }
+}
+
+/// A schema extension, such as [`crate::user::EnterpriseUser`], which a
+/// resource holds as an attribute named by the extension's URN.
+pub trait ScimExtension: Serialize + DeserializeOwned {
+    /// The URN of the extension's schema.
+    const SCHEMA_URN: &'static str;
+}
+
+// Extensions with only simple attributes are read as ScimValue::Complex, so
+// both forms are accepted wherever an extension is read.
+fn extension_attrs(attrs: ScimComplexAttr) -> AttrMap<ScimValue> {
+    attrs
+        .into_iter()
+        .map(|(name, attr)| (name, ScimValue::Simple(attr)))
+        .collect()
+}
+
+impl ScimEntryGeneric {
+    /// The attributes of the extension stored under `urn`.
+    pub fn extension(&self, urn: &str) -> Option<Cow<'_, AttrMap<ScimValue>>> {
+        match self.attr(urn)? {
+            ScimValue::Extension(attrs) => Some(Cow::Borrowed(attrs)),
+            ScimValue::Complex(attrs) => Some(Cow::Owned(extension_attrs(attrs.clone()))),
+            _ => None,
+        }
+    }
+
+    /// The attributes of the extension stored under `urn`, for changing.
+    pub fn extension_mut(&mut self, urn: &str) -> Option<&mut AttrMap<ScimValue>> {
+        let value = self.attr_mut(urn)?;
+        if let ScimValue::Complex(attrs) = value {
+            *value = ScimValue::Extension(extension_attrs(std::mem::take(attrs)));
+        }
+        match value {
+            ScimValue::Extension(attrs) => Some(attrs),
+            _ => None,
+        }
+    }
+
+    /// Store `attrs` as the extension under `urn`, and list the URN in
+    /// `schemas`.
+    pub fn set_extension(&mut self, urn: &str, attrs: AttrMap<ScimValue>) {
+        self.insert_attr(urn, ScimValue::Extension(attrs));
+        if !self.schemas.iter().any(|s| s.eq_ignore_ascii_case(urn)) {
+            self.schemas.push(urn.to_string());
+        }
+    }
+
+    /// Remove the extension under `urn`, and its URN from `schemas`.
+    pub fn remove_extension(&mut self, urn: &str) -> Option<AttrMap<ScimValue>> {
+        self.schemas.retain(|s| !s.eq_ignore_ascii_case(urn));
+        match self.remove_attr(urn)? {
+            ScimValue::Extension(attrs) => Some(attrs),
+            ScimValue::Complex(attrs) => Some(extension_attrs(attrs)),
+            _ => None,
+        }
+    }
+
+    /// The extension URNs in `schemas`, after the resource's own schema,
+    /// that the entry has attributes for.
+    pub fn extensions(&self) -> Vec<&str> {
+        self.schemas
+            .iter()
+            .skip(1)
+            .filter(|urn| self.extension(urn).is_some())
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// The extension URNs in `schemas` that the entry has no attributes
+    /// for. These are allowed, as an extension may have no values set.
+    pub fn missing_extensions(&self) -> Vec<&str> {
+        self.schemas
+            .iter()
+            .skip(1)
+            .filter(|urn| self.extension(urn).is_none())
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Attributes named by a URN that isn't in `schemas`, which RFC7643
+    /// section 3 requires every extension present to be.
+    pub fn undeclared_extensions(&self) -> Vec<&str> {
+        self.attrs
+            .keys()
+            .filter(|name| {
+                name.get(..4)
+                    .map(|p| p.eq_ignore_ascii_case("urn:"))
+                    .unwrap_or(false)
+            })
+            .filter(|name| !self.schemas.iter().any(|s| s.eq_ignore_ascii_case(name)))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Store `extension` under its URN, and list the URN in `schemas`.
+    pub fn add_extension<E: ScimExtension>(&mut self, extension: &E) -> Result<(), ScimError> {
+        let attrs = convert::convert(extension)?;
+        self.set_extension(E::SCHEMA_URN, attrs);
+        Ok(())
+    }
+
+    /// Remove and convert the extension stored under its URN, if there is
+    /// one. `schemas` is left as it is.
+    pub fn take_extension<E: ScimExtension>(&mut self) -> Result<Option<E>, ScimError> {
+        take_attr(&mut self.attrs, E::SCHEMA_URN)
+    }
+}
+
+/// A resource composed with an extension it doesn't know about. The
+/// extension is read from, and written under, its URN, and its URN is kept
+/// in `schemas` while it is present. Nest these to compose several
+/// extensions, as in `Extended<Extended<User, A>, B>`.
+#[derive(Debug, Clone)]
+pub struct Extended<R, E> {
+    pub resource: R,
+    pub extension: Option<E>,
+}
+
+impl<R, E> Extended<R, E> {
+    pub fn new(resource: R, extension: E) -> Self {
+        Extended {
+            resource,
+            extension: Some(extension),
+        }
+    }
+}
+
+impl<R: ScimResource, E: ScimExtension> TryFrom<ScimEntryGeneric> for Extended<R, E> {
+    type Error = ScimError;
+
+    fn try_from(mut entry: ScimEntryGeneric) -> Result<Self, Self::Error> {
+        let extension = entry.take_extension()?;
+        let resource = R::try_from(entry)?;
+        Ok(Extended {
+            resource,
+            extension,
+        })
+    }
+}
+
+impl<R: ScimResource, E: ScimExtension> TryFrom<Extended<R, E>> for ScimEntryGeneric {
+    type Error = ScimError;
+
+    fn try_from(value: Extended<R, E>) -> Result<Self, Self::Error> {
+        let mut entry = value.resource.into_entry()?;
+        match &value.extension {
+            Some(extension) => entry.add_extension(extension)?,
+            None => entry
+                .schemas
+                .retain(|urn| !urn.eq_ignore_ascii_case(E::SCHEMA_URN)),
+        }
+        Ok(entry)
+    }
+}
+
+impl<R: ScimResource, E: ScimExtension> ScimResource for Extended<R, E> {
+    const SCHEMA_URN: &'static str = R::SCHEMA_URN;
+
+    fn resource_type() -> &'static str {
+        R::resource_type()
+    }
+
+    fn into_entry(self) -> Result<ScimEntryGeneric, ScimError> {
+        ScimEntryGeneric::try_from(self)
+    }
+}
+
+// Remove attribute `name`, in any case, and convert it to a field's type. A
+// null is taken as missing, and a value that doesn't convert is an
+// InvalidValue naming the attribute.
+#[doc(hidden)]
+pub fn take_attr<T: DeserializeOwned>(
+    attrs: &mut AttrMap<ScimValue>,
+    name: &str,
+) -> Result<Option<T>, ScimError> {
+    let Some(key) = attrs
+        .keys()
+        .find(|key| key.eq_ignore_ascii_case(name))
+        .cloned()
+    else {
+        return Ok(None);
+    };
+    attr_map::remove(attrs, &key)
+        .filter(|value| !value.is_null())
+        .map(|value| convert::from_value_at(convert::to_value(&value)?, name))
+        .transpose()
+}
+
+// Add a field as attribute `name`. A field that serialises to null or an
+// empty list, as None and empty Vecs do, is left out.
+#[doc(hidden)]
+pub fn put_attr<T: Serialize>(
+    attrs: &mut AttrMap<ScimValue>,
+    name: &str,
+    value: &T,
+) -> Result<(), ScimError> {
+    let value = convert::to_value(value)?;
+    match &value {
+        serde_json::Value::Null => return Ok(()),
+        serde_json::Value::Array(values) if values.is_empty() => return Ok(()),
+        _ => {}
+    }
+    let value = convert::from_value_at(value, name)?;
+    attrs.insert(name.to_string(), value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{RFC7643_GROUP, RFC7643_USER};
+    use crate::group::Group;
+    use crate::schema::{SchemaViolation, SchemasMode, ViolationKind};
+    use crate::user::{EnterpriseUser, Manager, User};
+    use serde::Deserialize;
+
+    fn round_trip<T: ScimResource>(json: &str) -> Result<T, ScimError> {
+        let entry: ScimEntryGeneric = convert::parse(json)?;
+        let resource = T::from_entry_checked(entry)?;
+        T::try_from(resource.into_entry()?)
+    }
+
+    #[test]
+    fn resource_trait() {
+        let u: User = round_trip(RFC7643_USER).expect("Failed to convert user");
+        assert_eq!(u.user_name, "bjensen@example.com");
+        assert_eq!(User::resource_type(), "User");
+
+        let g: Group = round_trip(RFC7643_GROUP).expect("Failed to convert group");
+        assert_eq!(g.members.len(), 2);
+        assert_eq!(
+            Group::SCHEMA_URN,
+            "urn:ietf:params:scim:schemas:core:2.0:Group"
+        );
+
+        assert!(matches!(
+            round_trip::<Group>(RFC7643_USER),
+            Err(ScimError::InvalidAttribute)
+        ));
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+    #[serde(rename_all = "camelCase")]
+    struct Badge {
+        badge_number: String,
+    }
+
+    impl ScimExtension for Badge {
+        const SCHEMA_URN: &'static str = "urn:example:params:scim:schemas:extension:badge:1.0:User";
+    }
+
+    #[test]
+    fn resource_extensions() {
+        let user = User::builder("bjensen")
+            .enterprise(EnterpriseUser {
+                employee_number: Some("701984".to_string()),
+                manager: Some(Manager {
+                    value: Some("26118915-6090-4610-87e4-49d8ca9f808d".to_string()),
+                    ref_: None,
+                    display_name: None,
+                }),
+                ..Default::default()
+            })
+            .build()
+            .expect("Failed to build user");
+        let user = Extended::new(
+            user,
+            Badge {
+                badge_number: "B-42".to_string(),
+            },
+        );
+
+        let entry = user.into_entry().expect("Failed to convert user");
+        assert_eq!(
+            entry.schemas,
+            vec![
+                crate::constants::SCIM_SCHEMA_USER,
+                crate::constants::SCIM_SCHEMA_ENTERPRISE_USER,
+                Badge::SCHEMA_URN,
+            ]
+        );
+        assert!(matches!(
+            entry
+                .attrs
+                .get(crate::constants::SCIM_SCHEMA_ENTERPRISE_USER),
+            Some(ScimValue::Extension(_))
+        ));
+        assert!(entry.attrs.contains_key(Badge::SCHEMA_URN));
+
+        let mut user =
+            Extended::<User, Badge>::from_entry_checked(entry).expect("Failed to convert user");
+        assert_eq!(
+            user.extension.as_ref().map(|b| b.badge_number.as_str()),
+            Some("B-42")
+        );
+        let manager = user
+            .resource
+            .enterprise
+            .as_ref()
+            .and_then(|e| e.manager.as_ref())
+            .and_then(|m| m.value.as_deref());
+        assert_eq!(manager, Some("26118915-6090-4610-87e4-49d8ca9f808d"));
+        assert!(!user.resource.extra.contains_key(Badge::SCHEMA_URN));
+
+        user.extension = None;
+        let mut entry = user.into_entry().expect("Failed to convert user");
+        assert_eq!(entry.schemas.len(), 2);
+        assert!(!entry.attrs.contains_key(Badge::SCHEMA_URN));
+
+        // A value that doesn't convert says which attribute it was, and why.
+        let badge = AttrMap::from_iter([(
+            "badgeNumber".to_string(),
+            ScimValue::Simple(crate::ScimAttr::Bool(true)),
+        )]);
+        entry.set_extension(Badge::SCHEMA_URN, badge);
+        let err = entry.take_extension::<Badge>().map(|_| ());
+        assert!(matches!(
+            &err,
+            Err(ScimError::InvalidValue(SchemaViolation { path, kind: ViolationKind::Unreadable { .. }, .. }))
+                if *path == format!("{}:badgeNumber", Badge::SCHEMA_URN)
+        ));
+        let err = err.err();
+        let message = err.as_ref().map(|e| e.to_string()).unwrap_or_default();
+        assert!(message.starts_with(&format!(
+            "attribute '{}:badgeNumber' can't be read",
+            Badge::SCHEMA_URN
+        )));
+        assert!(err.as_ref().and_then(std::error::Error::source).is_some());
+    }
+
+    #[test]
+    fn entry_extensions() {
+        use crate::constants::SCIM_SCHEMA_ENTERPRISE_USER;
+        use crate::ScimAttr;
+
+        let mut entry: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let string = |s: &str| ScimValue::Simple(ScimAttr::String(s.to_string()));
+
+        assert!(entry.extension(SCIM_SCHEMA_ENTERPRISE_USER).is_none());
+        entry.set_extension(
+            SCIM_SCHEMA_ENTERPRISE_USER,
+            AttrMap::from([("department".to_string(), string("Tour Operations"))]),
+        );
+        assert_eq!(entry.extensions(), vec![SCIM_SCHEMA_ENTERPRISE_USER]);
+        assert!(entry.missing_extensions().is_empty());
+
+        // Read back from JSON, an extension of simple attributes is complex.
+        let json = serde_json::to_string(&entry).expect("Failed to serialise entry");
+        let mut entry: ScimEntryGeneric =
+            serde_json::from_str(&json).expect("Failed to parse entry");
+        assert!(matches!(
+            entry.attr(SCIM_SCHEMA_ENTERPRISE_USER),
+            Some(ScimValue::Complex(_))
+        ));
+        let ext = entry
+            .extension(SCIM_SCHEMA_ENTERPRISE_USER)
+            .expect("Missing extension");
+        assert_eq!(ext.get("department"), Some(&string("Tour Operations")));
+
+        entry
+            .extension_mut(SCIM_SCHEMA_ENTERPRISE_USER)
+            .expect("Missing extension")
+            .insert("division".to_string(), string("Theme Park"));
+        assert_eq!(
+            entry
+                .extension(SCIM_SCHEMA_ENTERPRISE_USER)
+                .map(|ext| ext.len()),
+            Some(2)
+        );
+
+        entry.attrs.insert(
+            "urn:example:params:scim:schemas:extension:badge:1.0:User".to_string(),
+            ScimValue::Complex(AttrMap::new()),
+        );
+        assert_eq!(
+            entry.undeclared_extensions(),
+            vec!["urn:example:params:scim:schemas:extension:badge:1.0:User"]
+        );
+
+        assert!(entry
+            .remove_extension(SCIM_SCHEMA_ENTERPRISE_USER)
+            .is_some());
+        assert!(entry.extensions().is_empty());
+        assert_eq!(entry.schemas.len(), 1);
+
+        entry.schemas.push(SCIM_SCHEMA_ENTERPRISE_USER.to_string());
+        assert_eq!(
+            entry.missing_extensions(),
+            vec![SCIM_SCHEMA_ENTERPRISE_USER]
+        );
+
+        let violation = |path: &str, kind| SchemaViolation::new(path, kind);
+        let badge = "urn:example:params:scim:schemas:extension:badge:1.0:User";
+        let unlisted = violation(badge, ViolationKind::UnlistedExtension);
+        assert_eq!(
+            entry.check_schemas(SchemasMode::Listed),
+            Err(vec![unlisted.clone()])
+        );
+        assert_eq!(
+            entry.check_schemas(SchemasMode::Exact),
+            Err(vec![
+                unlisted,
+                violation(SCIM_SCHEMA_ENTERPRISE_USER, ViolationKind::MissingExtension)
+            ])
+        );
+
+        // The unlisted extension is listed when written.
+        let json = serde_json::to_value(&entry).expect("Failed to serialise entry");
+        assert_eq!(json["schemas"][2], badge);
+        let entry: ScimEntryGeneric = serde_json::from_value(json).expect("Failed to parse");
+        assert_eq!(entry.check_schemas(SchemasMode::Listed), Ok(()));
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derive_resource() {
+        use crate::ScimEntry;
+
+        #[derive(Debug, ScimResource)]
+        #[scim(schema = "urn:example:params:scim:schemas:Device")]
+        struct Device {
+            entry: ScimEntry,
+            #[scim(rename = "serialNumber", required)]
+            serial: String,
+            display_name: Option<String>,
+            tags: Vec<String>,
+            #[scim(extra)]
+            extra: AttrMap<ScimValue>,
+        }
+
+        let json = r#"{
+            "schemas": ["urn:example:params:scim:schemas:Device"],
+            "id": "2819c223-7f76-453a-919d-413861904646",
+            "SERIALNUMBER": "A1234",
+            "displayName": "Front door",
+            "location": "Lobby"
+        }"#;
+        let device: Device = round_trip(json).expect("Failed to convert device");
+        assert_eq!(device.serial, "A1234");
+        assert_eq!(device.display_name.as_deref(), Some("Front door"));
+        assert!(device.tags.is_empty());
+        assert!(device.extra.contains_key("location"));
+        assert_eq!(Device::resource_type(), "Device");
+
+        let entry = device.into_entry().expect("Failed to convert device");
+        assert!(entry.attrs.contains_key("serialNumber"));
+        assert!(!entry.attrs.contains_key("tags"));
+
+        let json = r#"{
+            "schemas": ["urn:example:params:scim:schemas:Device"],
+            "id": "2819c223-7f76-453a-919d-413861904646",
+            "displayName": "Front door"
+        }"#;
+        assert!(matches!(
+            round_trip::<Device>(json),
+            Err(ScimError::MissingRequiredAttribute(name)) if name == "serialNumber"
+        ));
+    }
+}
@@ -0,0 +1,2896 @@
+use crate::attr_map::AttrMap;
+use crate::constants::{SCIM_SCHEMA_ENTERPRISE_USER, SCIM_SCHEMA_GROUP, SCIM_SCHEMA_USER};
+use crate::convert;
+use crate::datetime::{self, DateTimeParsing};
+use crate::error::{ErrorSource, ScimError, ScimErrors, ScimType};
+use crate::messages::{ScimListResponse, ScimPatchOpKind, ScimPatchRequest};
+use crate::warning::{ScimWarning, WarningKind};
+use crate::{ScimAttr, ScimComplexAttr, ScimEntryGeneric, ScimId, ScimMeta, ScimValue};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt;
+use url::Url;
+
+// https://datatracker.ietf.org/doc/html/rfc7643#section-7
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum AttributeType {
+    #[default]
+    String,
+    Boolean,
+    Decimal,
+    Integer,
+    DateTime,
+    Reference,
+    Complex,
+    Binary,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum Mutability {
+    ReadOnly,
+    #[default]
+    ReadWrite,
+    Immutable,
+    WriteOnly,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum Returned {
+    Always,
+    Never,
+    #[default]
+    Default,
+    Request,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum Uniqueness {
+    #[default]
+    None,
+    Server,
+    Global,
+}
+
+/// An attribute of a schema. Omitted characteristics take the defaults from
+/// RFC7643 2.2.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimAttributeDefinition {
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub type_: AttributeType,
+    #[serde(default)]
+    pub multi_valued: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub canonical_values: Vec<String>,
+    #[serde(default)]
+    pub case_exact: bool,
+    #[serde(default)]
+    pub mutability: Mutability,
+    #[serde(default)]
+    pub returned: Returned,
+    #[serde(default)]
+    pub uniqueness: Uniqueness,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reference_types: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sub_attributes: Vec<ScimAttributeDefinition>,
+}
+
+impl ScimAttributeDefinition {
+    /// A single-valued attribute with the default characteristics.
+    pub fn new(name: &str, type_: AttributeType) -> Self {
+        ScimAttributeDefinition {
+            name: name.to_string(),
+            type_,
+            multi_valued: false,
+            description: None,
+            required: false,
+            canonical_values: Vec::new(),
+            case_exact: false,
+            mutability: Mutability::default(),
+            returned: Returned::default(),
+            uniqueness: Uniqueness::default(),
+            reference_types: Vec::new(),
+            sub_attributes: Vec::new(),
+        }
+    }
+
+    /// The canonical value matching `value`, compared according to `caseExact`.
+    pub fn canonical_value(&self, value: &str) -> Option<&str> {
+        self.canonical_values
+            .iter()
+            .find(|c| {
+                if self.case_exact {
+                    c.as_str() == value
+                } else {
+                    c.eq_ignore_ascii_case(value)
+                }
+            })
+            .map(String::as_str)
+    }
+
+    /// Compare two values of this attribute. Strings are compared case
+    /// insensitively unless the attribute is `caseExact`.
+    pub fn attr_eq(&self, a: &ScimAttr, b: &ScimAttr) -> bool {
+        match (a, b) {
+            (ScimAttr::String(l), ScimAttr::String(r)) if !self.case_exact => {
+                l.to_lowercase() == r.to_lowercase()
+            }
+            _ => a == b,
+        }
+    }
+
+    /// As [`ScimAttributeDefinition::attr_eq`], applying the `caseExact` of
+    /// each sub-attribute to complex values.
+    pub fn value_eq(&self, a: &ScimValue, b: &ScimValue) -> bool {
+        match (a, b) {
+            (ScimValue::Simple(l), ScimValue::Simple(r)) => self.attr_eq(l, r),
+            (ScimValue::MultiSimple(l), ScimValue::MultiSimple(r)) => {
+                l.len() == r.len() && l.iter().zip(r).all(|(l, r)| self.attr_eq(l, r))
+            }
+            (ScimValue::Complex(l), ScimValue::Complex(r)) => self.complex_eq(l, r),
+            (ScimValue::MultiComplex(l), ScimValue::MultiComplex(r)) => {
+                l.len() == r.len() && l.iter().zip(r).all(|(l, r)| self.complex_eq(l, r))
+            }
+            _ => false,
+        }
+    }
+
+    fn complex_eq(&self, a: &ScimComplexAttr, b: &ScimComplexAttr) -> bool {
+        a.len() == b.len()
+            && a.iter().all(|(name, l)| {
+                let Some(r) = find_attr(b, name) else {
+                    return false;
+                };
+                match self.sub_attribute(name) {
+                    Some(def) => def.attr_eq(l, r),
+                    None => l == r,
+                }
+            })
+    }
+
+    /// Check a reference against this attribute's `referenceTypes`. `external`
+    /// accepts any absolute URL and `uri` any URI, while a resource type such
+    /// as `User` only accepts `<base>/Users/<id>`. Relative references are
+    /// resolved against `base`.
+    pub fn reference_allowed(&self, reference: &str, base: &Url) -> bool {
+        let base = if base.path().ends_with('/') {
+            Cow::Borrowed(base)
+        } else {
+            let mut base = base.clone();
+            base.set_path(&format!("{}/", base.path()));
+            Cow::Owned(base)
+        };
+
+        let absolute = Url::parse(reference).ok();
+        let resolved = absolute.clone().or_else(|| base.join(reference).ok());
+
+        self.reference_types.iter().any(|rt| {
+            if rt.eq_ignore_ascii_case("external") {
+                absolute.as_ref().map(|u| u.has_host()).unwrap_or(false)
+            } else if rt.eq_ignore_ascii_case("uri") {
+                absolute.is_some()
+            } else {
+                resolved
+                    .as_ref()
+                    .and_then(|u| u.as_str().strip_prefix(base.as_str()))
+                    .and_then(|rest| rest.split_once('/'))
+                    .map(|(endpoint, id)| {
+                        endpoint.eq_ignore_ascii_case(&format!("{}s", rt))
+                            && !id.is_empty()
+                            && !id.contains(['/', '?', '#'])
+                    })
+                    .unwrap_or(false)
+            }
+        })
+    }
+
+    /// Find a sub-attribute by name. Attribute names are case insensitive.
+    pub fn sub_attribute(&self, name: &str) -> Option<&ScimAttributeDefinition> {
+        self.sub_attributes
+            .iter()
+            .find(|a| a.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimSchema {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub attributes: Vec<ScimAttributeDefinition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SchemaDocument {
+    List(ScimListResponse<ScimSchema>),
+    Many(Vec<ScimSchema>),
+    One(Box<ScimSchema>),
+}
+
+impl ScimSchema {
+    /// Parse and check a single schema document.
+    pub fn parse(json: &str) -> Result<Self, SchemaError> {
+        let schema: ScimSchema =
+            serde_json::from_str(json).map_err(|e| SchemaError::Parse(e.to_string()))?;
+        schema.check_definition()?;
+        Ok(schema)
+    }
+
+    /// Parse the response of a `/Schemas` endpoint, which may be a
+    /// ListResponse, a bare array, or a single schema.
+    pub fn parse_list(json: &str) -> Result<Vec<Self>, SchemaError> {
+        let schemas =
+            match serde_json::from_str(json).map_err(|e| SchemaError::Parse(e.to_string()))? {
+                SchemaDocument::List(list) => list.resources,
+                SchemaDocument::Many(schemas) => schemas,
+                SchemaDocument::One(schema) => vec![*schema],
+            };
+
+        schemas.iter().try_for_each(ScimSchema::check_definition)?;
+        Ok(schemas)
+    }
+
+    /// Find an attribute by name. Attribute names are case insensitive.
+    pub fn attribute(&self, name: &str) -> Option<&ScimAttributeDefinition> {
+        self.attributes
+            .iter()
+            .find(|a| a.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Validate the attributes of an entry whose base resource schema is this
+    /// schema, returning every violation found.
+    pub fn validate(&self, entry: &ScimEntryGeneric) -> Result<(), Vec<SchemaViolation>> {
+        let mut violations = Vec::new();
+        validate_attrs(&self.attributes, &entry.attrs, None, &mut violations);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Validate the attributes of an entry that are namespaced under this
+    /// extension schema's URN.
+    pub fn validate_extension(&self, entry: &ScimEntryGeneric) -> Result<(), Vec<SchemaViolation>> {
+        let mut violations = Vec::new();
+        let ext = entry
+            .attrs
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(&self.id))
+            .map(|(_, v)| v);
+
+        match ext {
+            Some(ScimValue::Complex(attrs)) => {
+                validate_ext(&self.attributes, attrs, &self.id, &mut violations)
+            }
+            Some(value) => violations.push(SchemaViolation::new(
+                self.id.clone(),
+                ViolationKind::ExpectedComplex {
+                    found: JsonType::of_value(value),
+                },
+            )),
+            None => {
+                for def in &self.attributes {
+                    let path = ext_path(&self.id, &def.name);
+                    validate_attr(def, &AttrMap::new(), path, &mut violations);
+                }
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Decode and validate a JSON entry, reporting every problem at once
+    /// rather than stopping at the first attribute that doesn't decode, as
+    /// parsing into [`ScimEntryGeneric`] does. Besides the violations of
+    /// [`ScimSchema::validate`], values that can't be held, such as complex
+    /// attributes nested in complex attributes, and more than one primary
+    /// value of an attribute are reported. Each is a
+    /// [`ScimError::InvalidValue`], so that they can be returned together
+    /// as one error response.
+    pub fn validate_all(&self, value: &Value) -> Result<ScimEntryGeneric, ScimErrors> {
+        let Value::Object(fields) = value else {
+            return Err(ScimError::InvalidValue(SchemaViolation::new(
+                "",
+                ViolationKind::InvalidType {
+                    expected: AttributeType::Complex,
+                    found: JsonType::of(value),
+                },
+            ))
+            .into());
+        };
+
+        let mut violations = Vec::new();
+        let mut schemas = None;
+        let mut id = None;
+        let mut external_id = None;
+        let mut meta = None;
+        let mut attrs = AttrMap::new();
+
+        let invalid = |path: &str, expected, value: &Value| {
+            SchemaViolation::new(
+                path,
+                ViolationKind::InvalidType {
+                    expected,
+                    found: JsonType::of(value),
+                },
+            )
+        };
+
+        for (name, value) in fields.iter() {
+            if name.eq_ignore_ascii_case("schemas") {
+                match serde_json::from_value(value.clone()) {
+                    Ok(s) => schemas = Some(s),
+                    Err(_) => violations.push(invalid("schemas", AttributeType::Reference, value)),
+                }
+            } else if name.eq_ignore_ascii_case("id") {
+                match value.as_str() {
+                    Some(s) => id = Some(ScimId::from(s)),
+                    None => violations.push(invalid("id", AttributeType::String, value)),
+                }
+            } else if name.eq_ignore_ascii_case("externalId") {
+                match value.as_str() {
+                    Some(s) => external_id = Some(s.to_string()),
+                    None => violations.push(invalid("externalId", AttributeType::String, value)),
+                }
+            } else if name.eq_ignore_ascii_case("meta") {
+                match serde_json::from_value::<ScimMeta>(value.clone()) {
+                    Ok(m) => meta = Some(m),
+                    Err(_) => validate_meta(value, &mut violations),
+                }
+            } else {
+                // Only an extension, named by its URN, can hold objects
+                // within objects.
+                match serde_json::from_value::<ScimValue>(value.clone()) {
+                    Ok(ScimValue::Extension(_)) if !name.contains(':') => {
+                        find_unheld(value, name, &mut violations)
+                    }
+                    Ok(v) => {
+                        attrs.insert(name.clone(), v);
+                    }
+                    Err(_) => find_unheld(value, name, &mut violations),
+                }
+            }
+        }
+
+        if schemas.is_none() && !violations.iter().any(|v| v.path == "schemas") {
+            violations.push(SchemaViolation::new(
+                "schemas",
+                ViolationKind::MissingRequired,
+            ));
+        }
+        if id.is_none() && !violations.iter().any(|v| v.path == "id") {
+            violations.push(SchemaViolation::new("id", ViolationKind::MissingRequired));
+        }
+
+        let mut entry = ScimEntryGeneric {
+            schemas: schemas.unwrap_or_default(),
+            id: id.unwrap_or_default(),
+            external_id,
+            meta,
+            attrs,
+        };
+
+        // An attribute that didn't decode has been reported, and isn't
+        // missing.
+        let reported: Vec<String> = violations
+            .iter()
+            .map(|v| attr_of(&v.path).to_ascii_lowercase())
+            .collect();
+        let later = self
+            .validate(&entry)
+            .err()
+            .into_iter()
+            .chain(entry.check_primary(PrimaryMode::Strict).err())
+            .flatten()
+            .filter(|v| !reported.contains(&attr_of(&v.path).to_ascii_lowercase()));
+        for violation in later {
+            if !violations.contains(&violation) {
+                violations.push(violation);
+            }
+        }
+
+        match ScimErrors::from_violations(violations) {
+            Some(errs) => Err(errs),
+            None => Ok(entry),
+        }
+    }
+
+    /// Check that a replacement (PUT) of `stored` by `incoming` doesn't alter
+    /// any readOnly or immutable attribute. Clients often send back readOnly
+    /// values they previously read, so unchanged values are accepted. For an
+    /// extension schema, the attributes under its URN are checked.
+    ///
+    /// The values of a multi-valued attribute are matched by their `value`
+    /// sub-attribute, so that the sub-attributes of a value already stored
+    /// are checked against it, and those of a new value as being set.
+    pub fn check_replace(
+        &self,
+        stored: &ScimEntryGeneric,
+        incoming: &ScimEntryGeneric,
+    ) -> Result<(), ScimError> {
+        let extension = self.is_extension_of(stored) || self.is_extension_of(incoming);
+        for def in &self.attributes {
+            let old = self.schema_attr(stored, extension, &def.name);
+            let new = self.schema_attr(incoming, extension, &def.name);
+            if old == new {
+                continue;
+            }
+            check_mutability(
+                &def.name,
+                None,
+                def.mutability,
+                old.as_deref(),
+                new.as_deref(),
+            )?;
+
+            match (old.as_deref(), new.as_deref()) {
+                (old, Some(ScimValue::Complex(new))) => {
+                    let old = match old {
+                        Some(ScimValue::Complex(old)) => Some(old),
+                        _ => None,
+                    };
+                    check_sub_mutability(def, old, new)?;
+                }
+                (old, Some(ScimValue::MultiComplex(new))) => {
+                    let old = match old {
+                        Some(ScimValue::MultiComplex(old)) => old.as_slice(),
+                        _ => &[],
+                    };
+                    for new in new {
+                        let old = find_attr(new, "value").and_then(|value| {
+                            old.iter()
+                                .find(|old| find_attr(old, "value") == Some(value))
+                        });
+                        check_sub_mutability(def, old, new)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Whether this schema's attributes are held under its URN in `entry`,
+    // as an extension's are, rather than being the entry's own.
+    fn is_extension_of(&self, entry: &ScimEntryGeneric) -> bool {
+        find_attr(&entry.attrs, &self.id).is_some()
+            || entry
+                .schemas
+                .iter()
+                .skip(1)
+                .any(|urn| urn.eq_ignore_ascii_case(&self.id))
+    }
+
+    // The value of attribute `name` of this schema in `entry`, found under
+    // the schema's URN for an extension, as `promote` does.
+    fn schema_attr<'a>(
+        &self,
+        entry: &'a ScimEntryGeneric,
+        extension: bool,
+        name: &str,
+    ) -> Option<Cow<'a, ScimValue>> {
+        if !extension {
+            return find_attr(&entry.attrs, name).map(Cow::Borrowed);
+        }
+        match find_attr(&entry.attrs, &self.id)? {
+            ScimValue::Extension(attrs) => find_attr(attrs, name).map(Cow::Borrowed),
+            ScimValue::Complex(attrs) => {
+                find_attr(attrs, name).map(|attr| Cow::Owned(ScimValue::Simple(attr.clone())))
+            }
+            _ => None,
+        }
+    }
+
+    /// Check that no operation of a PATCH request modifies a readOnly
+    /// attribute, or an immutable attribute that `stored` already has a value for.
+    /// For an extension schema, the attributes under its URN are checked.
+    pub fn check_patch(
+        &self,
+        stored: &ScimEntryGeneric,
+        patch: &ScimPatchRequest,
+    ) -> Result<(), ScimError> {
+        let extension = self.is_extension_of(stored);
+        for op in &patch.operations {
+            match op.path.as_deref() {
+                Some(path) => {
+                    let (urn, attr, sub) = split_patch_path(path);
+                    let ours = match urn {
+                        Some(urn) => urn.eq_ignore_ascii_case(&self.id),
+                        None => !extension,
+                    };
+                    if !ours {
+                        // Belongs to another schema.
+                        continue;
+                    }
+                    if let Some(def) = self.attribute(attr) {
+                        let value = if sub.is_none() {
+                            op.value.as_ref()
+                        } else {
+                            None
+                        };
+                        self.check_patch_target(stored, extension, op.op, def, sub, value)?;
+                    }
+                }
+                None => {
+                    let Some(Value::Object(attrs)) = &op.value else {
+                        continue;
+                    };
+                    // An extension's attributes are given under its URN.
+                    let nested = attrs
+                        .iter()
+                        .find(|(name, _)| name.eq_ignore_ascii_case(&self.id))
+                        .and_then(|(_, value)| value.as_object());
+                    let (attrs, extension) = match nested {
+                        Some(nested) => (nested, true),
+                        None if extension => continue,
+                        None => (attrs, false),
+                    };
+                    for (name, value) in attrs {
+                        let Some(def) = self.attribute(name) else {
+                            continue;
+                        };
+                        self.check_patch_target(stored, extension, op.op, def, None, Some(value))?;
+                        if let Value::Object(sub_attrs) = value {
+                            for sub in sub_attrs.keys() {
+                                self.check_patch_target(
+                                    stored,
+                                    extension,
+                                    op.op,
+                                    def,
+                                    Some(sub),
+                                    None,
+                                )?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_patch_target(
+        &self,
+        stored: &ScimEntryGeneric,
+        extension: bool,
+        op: ScimPatchOpKind,
+        def: &ScimAttributeDefinition,
+        sub: Option<&str>,
+        value: Option<&Value>,
+    ) -> Result<(), ScimError> {
+        let stored_value = self.schema_attr(stored, extension, &def.name);
+        let stored_value = stored_value.as_deref();
+
+        // A readOnly parent makes all its sub-attributes readOnly.
+        let mutability = match (def.mutability, sub.and_then(|s| def.sub_attribute(s))) {
+            (Mutability::ReadOnly, _) => Mutability::ReadOnly,
+            (_, Some(sub_def)) => sub_def.mutability,
+            (m, None) => m,
+        };
+
+        let is_set = match (stored_value, sub) {
+            (Some(ScimValue::Complex(attrs)), Some(sub)) => find_attr(attrs, sub).is_some(),
+            (Some(v), _) => !v.is_empty(),
+            (None, _) => false,
+        };
+
+        // Replacing a value with itself isn't a modification.
+        let unchanged = op == ScimPatchOpKind::Replace
+            && match (stored_value, value) {
+                (Some(stored_value), Some(value)) => serde_json::to_value(stored_value)
+                    .map(|v| &v == value)
+                    .unwrap_or(false),
+                _ => false,
+            };
+
+        match mutability {
+            Mutability::ReadOnly if !unchanged => Err(mutability_error(&def.name, sub)),
+            Mutability::Immutable if is_set && !unchanged => Err(mutability_error(&def.name, sub)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Convert the values of `entry` to the types this schema gives them,
+    /// with [`ScimAttr::promote_as`], so that a dateTime attribute holds a
+    /// [`ScimAttr::DateTime`] rather than its string. For an extension schema,
+    /// the attributes under its URN are converted.
+    pub fn promote(&self, entry: &mut ScimEntryGeneric) {
+        promote_attrs(&self.attributes, &mut entry.attrs);
+        match find_attr_mut(&mut entry.attrs, &self.id) {
+            Some(ScimValue::Complex(attrs)) => promote_complex(&self.attributes, attrs),
+            Some(ScimValue::Extension(attrs)) => promote_attrs(&self.attributes, attrs),
+            _ => {}
+        }
+    }
+
+    /// Check every string value of an attribute with canonicalValues, such as
+    /// `emails[].type`. With [`CanonicalMode::CoerceCase`], values that only
+    /// differ from a canonical value by case are rewritten to it.
+    pub fn check_canonical(
+        &self,
+        entry: &mut ScimEntryGeneric,
+        mode: CanonicalMode,
+    ) -> Result<(), Vec<SchemaViolation>> {
+        let mut violations = Vec::new();
+
+        for def in &self.attributes {
+            let Some(value) = find_attr_mut(&mut entry.attrs, &def.name) else {
+                continue;
+            };
+            let path = def.name.as_str();
+
+            match value {
+                ScimValue::Simple(ScimAttr::String(s)) => {
+                    canonicalize(def, s, mode, path, &mut violations)
+                }
+                ScimValue::MultiSimple(values) => {
+                    for (idx, attr) in values.iter_mut().enumerate() {
+                        if let ScimAttr::String(s) = attr {
+                            let path = format!("{}[{}]", path, idx);
+                            canonicalize(def, s, mode, &path, &mut violations);
+                        }
+                    }
+                }
+                ScimValue::Complex(attrs) => {
+                    canonicalize_complex(def, attrs, mode, path, &mut violations)
+                }
+                ScimValue::MultiComplex(values) => {
+                    for (idx, attrs) in values.iter_mut().enumerate() {
+                        let path = format!("{}[{}]", path, idx);
+                        canonicalize_complex(def, attrs, mode, &path, &mut violations);
+                    }
+                }
+                ScimValue::Simple(_) | ScimValue::Extension(_) => {}
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// As [`ScimSchema::check_canonical`], for the values of a PATCH request.
+    pub fn check_patch_canonical(
+        &self,
+        patch: &mut ScimPatchRequest,
+        mode: CanonicalMode,
+    ) -> Result<(), Vec<SchemaViolation>> {
+        let mut violations = Vec::new();
+
+        for op in patch.operations.iter_mut() {
+            let Some(value) = op.value.as_mut() else {
+                continue;
+            };
+
+            match op.path.as_deref() {
+                Some(path) => {
+                    let (urn, attr, sub) = split_patch_path(path);
+                    if urn
+                        .map(|u| !u.eq_ignore_ascii_case(&self.id))
+                        .unwrap_or(false)
+                    {
+                        continue;
+                    }
+                    let def = self.attribute(attr).and_then(|def| match sub {
+                        Some(sub) => def.sub_attribute(sub),
+                        None => Some(def),
+                    });
+                    if let Some(def) = def {
+                        canonicalize_json(def, value, mode, path, &mut violations);
+                    }
+                }
+                None => {
+                    if let Value::Object(attrs) = value {
+                        for (name, value) in attrs.iter_mut() {
+                            if let Some(def) = self.attribute(name) {
+                                canonicalize_json(def, value, mode, name, &mut violations);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Check that every reference attribute in `entry`, such as
+    /// `members[].$ref`, points at a resource type it allows. See
+    /// [`ScimAttributeDefinition::reference_allowed`].
+    pub fn check_references(
+        &self,
+        entry: &ScimEntryGeneric,
+        base: &Url,
+    ) -> Result<(), Vec<SchemaViolation>> {
+        let mut violations = Vec::new();
+
+        for def in &self.attributes {
+            let Some(value) = find_attr(&entry.attrs, &def.name) else {
+                continue;
+            };
+            let path = def.name.as_str();
+
+            match value {
+                ScimValue::Simple(attr) => check_reference(def, attr, base, path, &mut violations),
+                ScimValue::MultiSimple(values) => {
+                    for (idx, attr) in values.iter().enumerate() {
+                        let path = format!("{}[{}]", path, idx);
+                        check_reference(def, attr, base, &path, &mut violations);
+                    }
+                }
+                ScimValue::Complex(attrs) => {
+                    check_complex_references(def, attrs, base, path, &mut violations)
+                }
+                ScimValue::MultiComplex(values) => {
+                    for (idx, attrs) in values.iter().enumerate() {
+                        let path = format!("{}[{}]", path, idx);
+                        check_complex_references(def, attrs, base, &path, &mut violations);
+                    }
+                }
+                ScimValue::Extension(_) => {}
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Compare two entries, treating attribute names as case insensitive and
+    /// comparing values according to the `caseExact` of their definition.
+    /// Attributes this schema doesn't describe must be identical.
+    pub fn entry_eq(&self, a: &ScimEntryGeneric, b: &ScimEntryGeneric) -> bool {
+        let external_id_eq = match (&a.external_id, &b.external_id) {
+            (Some(l), Some(r)) => match self.attribute("externalId") {
+                Some(def) => {
+                    def.attr_eq(&ScimAttr::String(l.clone()), &ScimAttr::String(r.clone()))
+                }
+                None => l == r,
+            },
+            (l, r) => l == r,
+        };
+
+        a.schemas == b.schemas
+            && a.id == b.id
+            && external_id_eq
+            && a.meta == b.meta
+            && a.attrs.len() == b.attrs.len()
+            && a.attrs.iter().all(|(name, l)| {
+                let Some(r) = find_attr(&b.attrs, name) else {
+                    return false;
+                };
+                match self.attribute(name) {
+                    Some(def) => def.value_eq(l, r),
+                    None => l == r,
+                }
+            })
+    }
+
+    /// Check that no entry in `existing`, other than `entry` itself, already
+    /// holds a value of an attribute this schema marks as unique. Values are
+    /// compared according to `caseExact`, so `userName` collisions that only
+    /// differ by case are found.
+    pub fn check_uniqueness<'a, I>(
+        &self,
+        entry: &ScimEntryGeneric,
+        existing: I,
+    ) -> Result<(), ScimError>
+    where
+        I: IntoIterator<Item = &'a ScimEntryGeneric>,
+    {
+        let unique: Vec<_> = self
+            .attributes
+            .iter()
+            .filter(|def| def.uniqueness != Uniqueness::None)
+            .filter_map(|def| find_attr(&entry.attrs, &def.name).map(|value| (def, value)))
+            .collect();
+
+        if unique.is_empty() {
+            return Ok(());
+        }
+
+        for other in existing.into_iter().filter(|other| other.id != entry.id) {
+            for (def, value) in unique.iter() {
+                if find_attr(&other.attrs, &def.name)
+                    .map(|other| def.value_eq(value, other))
+                    .unwrap_or(false)
+                {
+                    return Err(ScimError::Uniqueness);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the structural rules of RFC7643 that serde can't express.
+    pub fn check_definition(&self) -> Result<(), SchemaError> {
+        if !self.id.to_ascii_lowercase().starts_with("urn:") {
+            return Err(SchemaError::InvalidId(self.id.clone()));
+        }
+        check_attributes(&self.attributes, None)
+    }
+}
+
+/// Whether `name` is an attribute name by the ABNF of RFC7643 section 2.1:
+/// a letter, then letters, digits, `-` or `_`. `$ref` is also allowed, as
+/// the RFC uses it for references.
+pub fn is_valid_attr_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    name == "$ref"
+        || (chars
+            .next()
+            .map(|c| c.is_ascii_alphabetic())
+            .unwrap_or(false)
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+}
+
+/// As [`is_valid_attr_name`], failing with [`ScimError::InvalidAttribute`].
+pub fn validate_attr_name(name: &str) -> Result<(), ScimError> {
+    if is_valid_attr_name(name) {
+        Ok(())
+    } else {
+        Err(ScimError::InvalidAttribute)
+    }
+}
+
+fn check_attributes(
+    attrs: &[ScimAttributeDefinition],
+    parent: Option<&str>,
+) -> Result<(), SchemaError> {
+    let mut seen = BTreeSet::new();
+
+    for attr in attrs {
+        let path = match parent {
+            Some(parent) => format!("{}.{}", parent, attr.name),
+            None => attr.name.clone(),
+        };
+
+        if !is_valid_attr_name(&attr.name) {
+            return Err(SchemaError::InvalidName(path));
+        }
+        if !seen.insert(attr.name.to_ascii_lowercase()) {
+            return Err(SchemaError::DuplicateAttribute(path));
+        }
+
+        match attr.type_ {
+            AttributeType::Complex if parent.is_some() => {
+                // 2.3.8 - a complex attribute must not contain sub-attributes
+                // that have sub-attributes.
+                return Err(SchemaError::NestedComplex(path));
+            }
+            AttributeType::Complex if attr.sub_attributes.is_empty() => {
+                return Err(SchemaError::MissingSubAttributes(path));
+            }
+            AttributeType::Complex => {}
+            _ if !attr.sub_attributes.is_empty() => {
+                return Err(SchemaError::UnexpectedSubAttributes(path));
+            }
+            _ => {}
+        }
+
+        match attr.type_ {
+            AttributeType::Reference if attr.reference_types.is_empty() => {
+                return Err(SchemaError::MissingReferenceTypes(path));
+            }
+            AttributeType::Reference => {}
+            _ if !attr.reference_types.is_empty() => {
+                return Err(SchemaError::UnexpectedReferenceTypes(path));
+            }
+            _ => {}
+        }
+
+        check_attributes(&attr.sub_attributes, Some(&path))?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn find_attr<'a, V>(attrs: &'a AttrMap<V>, name: &str) -> Option<&'a V> {
+    attrs
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v)
+}
+
+pub(crate) fn find_attr_mut<'a, V>(attrs: &'a mut AttrMap<V>, name: &str) -> Option<&'a mut V> {
+    attrs
+        .iter_mut()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v)
+}
+
+pub(crate) fn key_of<V>(attrs: &AttrMap<V>, name: &str) -> Option<String> {
+    attrs
+        .keys()
+        .find(|key| key.eq_ignore_ascii_case(name))
+        .cloned()
+}
+
+// Insert, replacing any value whose name only differs by case, and keeping
+// the existing name.
+pub(crate) fn insert_attr<V>(attrs: &mut AttrMap<V>, name: &str, value: V) -> Option<V> {
+    let key = key_of(attrs, name).unwrap_or_else(|| name.to_string());
+    attrs.insert(key, value)
+}
+
+pub(crate) fn remove_attr<V>(attrs: &mut AttrMap<V>, name: &str) -> bool {
+    let before = attrs.len();
+    attrs.retain(|key, _| !key.eq_ignore_ascii_case(name));
+    attrs.len() != before
+}
+
+fn check_reference(
+    def: &ScimAttributeDefinition,
+    attr: &ScimAttr,
+    base: &Url,
+    path: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    if def.type_ != AttributeType::Reference {
+        return;
+    }
+
+    let value = match attr {
+        ScimAttr::String(s) => s.as_str(),
+        ScimAttr::Reference(u) => u.as_str(),
+        // The wrong type is reported by validate.
+        _ => return,
+    };
+
+    if !def.reference_allowed(value, base) {
+        violations.push(SchemaViolation::new(
+            path,
+            ViolationKind::InvalidReference {
+                value: value.to_string(),
+            },
+        ));
+    }
+}
+
+fn check_complex_references(
+    def: &ScimAttributeDefinition,
+    attrs: &ScimComplexAttr,
+    base: &Url,
+    path: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    for sub in def.sub_attributes.iter() {
+        if let Some(attr) = find_attr(attrs, &sub.name) {
+            let path = join_path(Some(path), &sub.name);
+            check_reference(sub, attr, base, &path, violations);
+        }
+    }
+}
+
+/// How to treat values that don't match an attribute's canonicalValues.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CanonicalMode {
+    /// Report every value that isn't canonical.
+    #[default]
+    Strict,
+    /// Rewrite values that match a canonical value when compared case
+    /// insensitively, and report the rest.
+    CoerceCase,
+}
+
+/// How to treat a multi-valued attribute with more than one primary value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PrimaryMode {
+    /// Report every attribute with more than one primary value.
+    #[default]
+    Strict,
+    /// Keep the first primary value, and set primary to false on the rest.
+    DemoteExtras,
+}
+
+/// How closely `schemas` must match the extensions an entry has, for
+/// [`ScimEntryGeneric::check_schemas`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SchemasMode {
+    /// Every extension present must be listed, as RFC7643 section 3
+    /// requires.
+    #[default]
+    Listed,
+    /// As well, every extension listed must be present.
+    Exact,
+}
+
+impl ScimEntryGeneric {
+    /// Check that `schemas` lists the extensions the entry has. Mismatches
+    /// are a frequent cause of interoperability failures. An extension
+    /// that isn't listed is added to `schemas` when the entry is written,
+    /// but a service provider reading the entry should reject it.
+    pub fn check_schemas(&self, mode: SchemasMode) -> Result<(), Vec<SchemaViolation>> {
+        let mut violations: Vec<_> = self
+            .undeclared_extensions()
+            .into_iter()
+            .map(|urn| SchemaViolation::new(urn, ViolationKind::UnlistedExtension))
+            .collect();
+        if mode == SchemasMode::Exact {
+            violations.extend(
+                self.missing_extensions()
+                    .into_iter()
+                    .map(|urn| SchemaViolation::new(urn, ViolationKind::MissingExtension)),
+            );
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Check that at most one value of each multi-valued attribute, in the
+    /// entry or its extensions, has `primary` set to true, as RFC7643
+    /// section 2.4 requires. In [`PrimaryMode::DemoteExtras`] each value
+    /// demoted is returned as a warning.
+    pub fn check_primary(
+        &mut self,
+        mode: PrimaryMode,
+    ) -> Result<Vec<ScimWarning>, Vec<SchemaViolation>> {
+        let mut violations = Vec::new();
+        let mut warnings = Vec::new();
+        check_primary_attrs(&mut self.attrs, None, mode, &mut violations, &mut warnings);
+        if violations.is_empty() {
+            Ok(warnings)
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Move the primary value of each multi-valued attribute, in the entry or
+    /// its extensions, to the front, keeping the others in order. Clients
+    /// often take the first value when none is marked, so this makes that
+    /// agree with the primary value, and makes the order serialised fixed.
+    pub fn primary_first(&mut self) {
+        self.attrs.values_mut().for_each(primary_first);
+    }
+}
+
+fn primary_first(value: &mut ScimValue) {
+    match value {
+        // The sort is stable, so only the primary values move.
+        ScimValue::MultiComplex(values) => values.sort_by_key(|v| !is_primary(v)),
+        ScimValue::Extension(attrs) => attrs.values_mut().for_each(primary_first),
+        _ => {}
+    }
+}
+
+pub(crate) fn is_primary(attrs: &ScimComplexAttr) -> bool {
+    matches!(find_attr(attrs, "primary"), Some(ScimAttr::Bool(true)))
+}
+
+fn check_primary_attrs(
+    attrs: &mut AttrMap<ScimValue>,
+    urn: Option<&str>,
+    mode: PrimaryMode,
+    violations: &mut Vec<SchemaViolation>,
+    warnings: &mut Vec<ScimWarning>,
+) {
+    for (name, value) in attrs.iter_mut() {
+        let path = match urn {
+            Some(urn) => format!("{}:{}", urn, name),
+            None => name.clone(),
+        };
+        match value {
+            ScimValue::MultiComplex(values)
+                if values.iter().filter(|v| is_primary(v)).count() > 1 =>
+            {
+                match mode {
+                    PrimaryMode::Strict => {
+                        violations.push(SchemaViolation::new(path, ViolationKind::MultiplePrimary))
+                    }
+                    PrimaryMode::DemoteExtras => {
+                        let demoted = values
+                            .iter_mut()
+                            .enumerate()
+                            .filter(|(_, v)| is_primary(v))
+                            .skip(1);
+                        for (idx, attrs) in demoted {
+                            insert_attr(attrs, "primary", ScimAttr::Bool(false));
+                            warnings.push(ScimWarning::new(
+                                format!("{}[{}].primary", path, idx),
+                                WarningKind::PrimaryDemoted,
+                            ));
+                        }
+                    }
+                }
+            }
+            ScimValue::Extension(ext) if urn.is_none() => {
+                check_primary_attrs(ext, Some(name), mode, violations, warnings)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// How to treat an entry that can't be fully held, or that names schemas
+/// not known, when parsing it with [`ScimEntryGeneric::parse`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Reject the entry, as an authoritative service provider would.
+    Strict,
+    /// Accept the entry, dropping what can't be held, and report each
+    /// problem as a warning, as a gateway passing entries on would.
+    #[default]
+    Lenient,
+}
+
+/// Options for [`ScimEntryGeneric::parse`]. The defaults parse leniently,
+/// knowing the core User and Group schemas and the enterprise User
+/// extension.
+#[derive(Debug, Clone)]
+pub struct EntryParseOptions {
+    mode: ParseMode,
+    schemas: Vec<String>,
+}
+
+impl Default for EntryParseOptions {
+    fn default() -> Self {
+        EntryParseOptions {
+            mode: ParseMode::default(),
+            schemas: vec![
+                SCIM_SCHEMA_USER.to_string(),
+                SCIM_SCHEMA_GROUP.to_string(),
+                SCIM_SCHEMA_ENTERPRISE_USER.to_string(),
+            ],
+        }
+    }
+}
+
+impl EntryParseOptions {
+    pub fn mode(mut self, mode: ParseMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Also know the schema `urn`, so that it may be in `schemas`.
+    pub fn schema(mut self, urn: impl Into<String>) -> Self {
+        self.schemas.push(urn.into());
+        self
+    }
+
+    fn knows(&self, urn: &str) -> bool {
+        self.schemas.iter().any(|s| s.eq_ignore_ascii_case(urn))
+    }
+}
+
+impl ScimEntryGeneric {
+    /// Parse an entry from JSON, looking for:
+    ///
+    /// * schemas in `schemas` that `options` doesn't know,
+    /// * attribute values that can't be held, such as an array of arrays,
+    ///   or a complex attribute within a complex attribute,
+    /// * attributes or sub-attributes whose names differ only by case.
+    ///
+    /// In [`ParseMode::Strict`] any of these fail with the first found, as a
+    /// [`ScimError::InvalidValue`]. In [`ParseMode::Lenient`] the entry is
+    /// returned with each as a warning; values that can't be held are
+    /// dropped, as is all but one of the names that differ by case. JSON
+    /// that isn't an entry at all fails in either mode.
+    pub fn parse(
+        json: &str,
+        options: &EntryParseOptions,
+    ) -> Result<(ScimEntryGeneric, Vec<ScimWarning>), ScimError> {
+        let mut value: Value =
+            serde_json::from_str(json).map_err(|e| ScimError::InvalidJson(ErrorSource::new(e)))?;
+        let mut warnings = Vec::new();
+        drop_duplicate_names(&mut value, None, &mut warnings);
+        let Value::Object(fields) = &mut value else {
+            return Err(ScimError::InvalidSyntax);
+        };
+        drop_unheld(fields, None, &mut warnings);
+
+        let entry: ScimEntryGeneric = convert::from_value(value)?;
+        for urn in entry.schemas.iter().filter(|urn| !options.knows(urn)) {
+            warnings.push(SchemaViolation::new(
+                "schemas",
+                ViolationKind::UnknownSchema { urn: urn.clone() },
+            ));
+        }
+
+        match options.mode {
+            ParseMode::Strict if !warnings.is_empty() => Err(warnings.swap_remove(0).into()),
+            _ => Ok((entry, warnings.into_iter().map(ScimWarning::from).collect())),
+        }
+    }
+}
+
+// Remove names that differ only by case from another name in the same
+// object, anywhere in `value`.
+fn drop_duplicate_names(
+    value: &mut Value,
+    path: Option<&str>,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    match value {
+        Value::Object(fields) => {
+            let top = path.is_none();
+            drop_duplicate_fields(fields, &|name| join_path(path, name), top, violations)
+        }
+        Value::Array(values) => {
+            for (idx, value) in values.iter_mut().enumerate() {
+                let path = format!("{}[{}]", path.unwrap_or_default(), idx);
+                drop_duplicate_names(value, Some(&path), violations);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Remove the duplicate names of an object whose fields are at `path_of`
+// their name. The fields of the entry itself, the `top`, may be extensions.
+fn drop_duplicate_fields(
+    fields: &mut serde_json::Map<String, Value>,
+    path_of: &dyn Fn(&str) -> String,
+    top: bool,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let mut seen = BTreeSet::new();
+    let duplicates: Vec<String> = fields
+        .keys()
+        .filter(|name| !seen.insert(name.to_ascii_lowercase()))
+        .cloned()
+        .collect();
+    for name in duplicates {
+        fields.remove(&name);
+        violations.push(SchemaViolation::new(
+            path_of(&name),
+            ViolationKind::DuplicateName,
+        ));
+    }
+    for (name, value) in fields.iter_mut() {
+        match value {
+            Value::Object(ext) if top && name.contains(':') => {
+                drop_duplicate_fields(ext, &|attr| ext_path(name, attr), false, violations)
+            }
+            value => drop_duplicate_names(value, Some(&path_of(name)), violations),
+        }
+    }
+}
+
+// Remove attributes that can't be held, from the entry or, with `urn`, an
+// extension of it.
+fn drop_unheld(
+    fields: &mut serde_json::Map<String, Value>,
+    urn: Option<&str>,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let mut unheld = Vec::new();
+    for (name, value) in fields.iter_mut() {
+        if urn.is_none()
+            && ["schemas", "id", "externalId", "meta"]
+                .iter()
+                .any(|n| n.eq_ignore_ascii_case(name))
+        {
+            continue;
+        }
+        if let (None, true, Value::Object(ext)) = (urn, name.contains(':'), &mut *value) {
+            drop_unheld(ext, Some(name), violations);
+            continue;
+        }
+
+        let path = match urn {
+            Some(urn) => format!("{}:{}", urn, name),
+            None => name.clone(),
+        };
+        match serde_json::from_value::<ScimValue>(value.clone()) {
+            Ok(ScimValue::Extension(_)) | Err(_) => {
+                let before = violations.len();
+                find_unheld(value, &path, violations);
+                if violations.len() == before {
+                    violations.push(SchemaViolation::new(
+                        path,
+                        ViolationKind::ExpectedSimple {
+                            found: JsonType::of(value),
+                        },
+                    ));
+                }
+                unheld.push(name.clone());
+            }
+            Ok(_) => {}
+        }
+    }
+    for name in unheld {
+        fields.remove(&name);
+    }
+}
+
+fn canonicalize(
+    def: &ScimAttributeDefinition,
+    value: &mut String,
+    mode: CanonicalMode,
+    path: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    if def.canonical_values.is_empty() || def.canonical_value(value).is_some() {
+        return;
+    }
+
+    let coerced = match mode {
+        CanonicalMode::CoerceCase => def
+            .canonical_values
+            .iter()
+            .find(|c| c.eq_ignore_ascii_case(value)),
+        CanonicalMode::Strict => None,
+    };
+
+    match coerced {
+        Some(c) => *value = c.clone(),
+        None => violations.push(SchemaViolation::new(
+            path,
+            ViolationKind::NotCanonical {
+                value: value.clone(),
+            },
+        )),
+    }
+}
+
+fn promote_attrs(defs: &[ScimAttributeDefinition], attrs: &mut AttrMap<ScimValue>) {
+    for def in defs {
+        let Some(value) = find_attr_mut(attrs, &def.name) else {
+            continue;
+        };
+        match value {
+            ScimValue::Simple(attr) => attr.promote_as(def.type_),
+            ScimValue::MultiSimple(values) => {
+                for attr in values.iter_mut() {
+                    attr.promote_as(def.type_);
+                }
+            }
+            ScimValue::Complex(attrs) => promote_complex(&def.sub_attributes, attrs),
+            ScimValue::MultiComplex(values) => {
+                for attrs in values.iter_mut() {
+                    promote_complex(&def.sub_attributes, attrs);
+                }
+            }
+            ScimValue::Extension(_) => {}
+        }
+    }
+}
+
+fn promote_complex(defs: &[ScimAttributeDefinition], attrs: &mut ScimComplexAttr) {
+    for def in defs {
+        if let Some(attr) = find_attr_mut(attrs, &def.name) {
+            attr.promote_as(def.type_);
+        }
+    }
+}
+
+fn canonicalize_complex(
+    def: &ScimAttributeDefinition,
+    attrs: &mut ScimComplexAttr,
+    mode: CanonicalMode,
+    path: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    for sub in def.sub_attributes.iter() {
+        if let Some(ScimAttr::String(s)) = find_attr_mut(attrs, &sub.name) {
+            let path = join_path(Some(path), &sub.name);
+            canonicalize(sub, s, mode, &path, violations);
+        }
+    }
+}
+
+fn canonicalize_json(
+    def: &ScimAttributeDefinition,
+    value: &mut Value,
+    mode: CanonicalMode,
+    path: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    match value {
+        Value::String(s) => canonicalize(def, s, mode, path, violations),
+        Value::Array(values) => {
+            for (idx, value) in values.iter_mut().enumerate() {
+                let path = format!("{}[{}]", path, idx);
+                canonicalize_json(def, value, mode, &path, violations);
+            }
+        }
+        Value::Object(attrs) => {
+            for sub in def.sub_attributes.iter() {
+                let value = attrs
+                    .iter_mut()
+                    .find(|(k, _)| k.eq_ignore_ascii_case(&sub.name))
+                    .map(|(_, v)| v);
+                if let Some(value) = value {
+                    let path = join_path(Some(path), &sub.name);
+                    canonicalize_json(sub, value, mode, &path, violations);
+                }
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+// Check the sub-attributes of a complex value, or one value of a
+// multi-valued attribute, against those of the value it replaces.
+fn check_sub_mutability(
+    def: &ScimAttributeDefinition,
+    old: Option<&ScimComplexAttr>,
+    new: &ScimComplexAttr,
+) -> Result<(), ScimError> {
+    for sub in &def.sub_attributes {
+        check_mutability(
+            &def.name,
+            Some(&sub.name),
+            sub.mutability,
+            old.and_then(|old| find_attr(old, &sub.name)),
+            find_attr(new, &sub.name),
+        )?;
+    }
+    Ok(())
+}
+
+fn check_mutability<V: PartialEq>(
+    name: &str,
+    sub: Option<&str>,
+    mutability: Mutability,
+    old: Option<&V>,
+    new: Option<&V>,
+) -> Result<(), ScimError> {
+    match (mutability, old, new) {
+        (Mutability::ReadOnly, old, Some(new)) if old != Some(new) => {
+            Err(mutability_error(name, sub))
+        }
+        (Mutability::Immutable, Some(old), Some(new)) if old != new => {
+            Err(mutability_error(name, sub))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn mutability_error(name: &str, sub: Option<&str>) -> ScimError {
+    match sub {
+        Some(sub) => ScimError::Mutability(format!("{}.{}", name, sub)),
+        None => ScimError::Mutability(name.to_string()),
+    }
+}
+
+/// Split a PATCH path into its schema URN prefix, attribute name and
+/// sub-attribute name, dropping any value filter. For example
+/// `members[value eq "2819c223"].display` is `(None, "members", Some("display"))`.
+pub(crate) fn split_patch_path(path: &str) -> (Option<&str>, &str, Option<&str>) {
+    // The URN may itself contain dots (2.0), so split it off before the
+    // first filter, at the final colon.
+    let filter_start = path.find('[').unwrap_or(path.len());
+    let (urn, rest) = match path[..filter_start].rfind(':') {
+        Some(idx) => (Some(&path[..idx]), &path[idx + 1..]),
+        None => (None, path),
+    };
+
+    let attr_end = rest.find(['.', '[']).unwrap_or(rest.len());
+    let attr = &rest[..attr_end];
+    let tail = &rest[attr_end..];
+
+    let tail = match tail.strip_prefix('[') {
+        Some(t) => t.find(']').map(|idx| &t[idx + 1..]).unwrap_or(""),
+        None => tail,
+    };
+    let sub = tail.strip_prefix('.').filter(|s| !s.is_empty());
+
+    (urn, attr, sub)
+}
+
+fn join_path(parent: Option<&str>, name: &str) -> String {
+    match parent {
+        Some(parent) => format!("{}.{}", parent, name),
+        None => name.to_string(),
+    }
+}
+
+// The attributes of an extension follow its URN after a colon.
+fn ext_path(urn: &str, name: &str) -> String {
+    format!("{}:{}", urn, name)
+}
+
+// The attribute a violation's path is in, such as name for name.givenName
+// or emails for emails[0].value.
+fn attr_of(path: &str) -> &str {
+    path.split(['.', '[']).next().unwrap_or(path)
+}
+
+fn validate_meta(value: &Value, violations: &mut Vec<SchemaViolation>) {
+    let Value::Object(fields) = value else {
+        violations.push(SchemaViolation::new(
+            "meta",
+            ViolationKind::ExpectedComplex {
+                found: JsonType::of(value),
+            },
+        ));
+        return;
+    };
+    let subs = [
+        ("resourceType", AttributeType::String),
+        ("created", AttributeType::DateTime),
+        ("lastModified", AttributeType::DateTime),
+        ("location", AttributeType::Reference),
+        ("version", AttributeType::String),
+    ];
+    let before = violations.len();
+    for (name, type_) in subs {
+        let path = join_path(Some("meta"), name);
+        let found = fields.iter().find(|(k, _)| k.eq_ignore_ascii_case(name));
+        let Some((_, value)) = found else {
+            violations.push(SchemaViolation::new(path, ViolationKind::MissingRequired));
+            continue;
+        };
+        let ok = match (type_, value) {
+            (AttributeType::DateTime, Value::String(s)) => {
+                datetime::parse(s, DateTimeParsing::Lenient).is_ok()
+            }
+            (AttributeType::Reference, Value::String(s)) => Url::parse(s).is_ok(),
+            (_, Value::String(_)) => true,
+            _ => false,
+        };
+        if !ok {
+            violations.push(SchemaViolation::new(
+                path,
+                ViolationKind::InvalidType {
+                    expected: type_,
+                    found: JsonType::of(value),
+                },
+            ));
+        }
+    }
+    // Anything else that stops it decoding.
+    if violations.len() == before {
+        violations.push(SchemaViolation::new(
+            "meta",
+            ViolationKind::InvalidType {
+                expected: AttributeType::Complex,
+                found: JsonType::Object,
+            },
+        ));
+    }
+}
+
+// Report the parts of an attribute that no ScimValue can hold: objects and
+// arrays within a complex value, arrays within arrays, and arrays mixing
+// simple and complex values.
+fn find_unheld(value: &Value, path: &str, violations: &mut Vec<SchemaViolation>) {
+    match value {
+        Value::Object(subs) => {
+            for (name, value) in subs.iter() {
+                if matches!(value, Value::Object(_) | Value::Array(_)) {
+                    violations.push(SchemaViolation::new(
+                        join_path(Some(path), name),
+                        ViolationKind::ExpectedSimple {
+                            found: JsonType::of(value),
+                        },
+                    ));
+                }
+            }
+        }
+        Value::Array(values) => {
+            let complex = values.iter().any(Value::is_object);
+            for (idx, value) in values.iter().enumerate() {
+                let path = format!("{}[{}]", path, idx);
+                match value {
+                    Value::Object(_) => find_unheld(value, &path, violations),
+                    Value::Array(_) => violations.push(SchemaViolation::new(
+                        path,
+                        ViolationKind::ExpectedSimple {
+                            found: JsonType::Array,
+                        },
+                    )),
+                    _ if complex => violations.push(SchemaViolation::new(
+                        path,
+                        ViolationKind::ExpectedComplex {
+                            found: JsonType::of(value),
+                        },
+                    )),
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn validate_attrs(
+    defs: &[ScimAttributeDefinition],
+    attrs: &AttrMap<ScimValue>,
+    parent: Option<&str>,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    for def in defs {
+        validate_attr(def, attrs, join_path(parent, &def.name), violations);
+    }
+}
+
+fn validate_attr(
+    def: &ScimAttributeDefinition,
+    attrs: &AttrMap<ScimValue>,
+    path: String,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let value = attrs
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(&def.name))
+        .map(|(_, v)| v)
+        // A null is unassigned, RFC7643 section 2.5.
+        .filter(|v| !v.is_null());
+
+    match value {
+        Some(value) => validate_value(def, value, &path, violations),
+        None if def.required => {
+            violations.push(SchemaViolation::new(path, ViolationKind::MissingRequired))
+        }
+        None => {}
+    }
+}
+
+fn validate_complex(
+    defs: &[ScimAttributeDefinition],
+    attrs: &ScimComplexAttr,
+    parent: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    for def in defs {
+        validate_sub_attr(def, attrs, join_path(Some(parent), &def.name), violations);
+    }
+}
+
+fn validate_ext(
+    defs: &[ScimAttributeDefinition],
+    attrs: &ScimComplexAttr,
+    urn: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    for def in defs {
+        validate_sub_attr(def, attrs, ext_path(urn, &def.name), violations);
+    }
+}
+
+fn validate_sub_attr(
+    def: &ScimAttributeDefinition,
+    attrs: &ScimComplexAttr,
+    path: String,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let value = attrs
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(&def.name))
+        .map(|(_, v)| v)
+        .filter(|v| !v.is_null());
+
+    match value {
+        Some(attr) if def.type_ == AttributeType::Complex => violations.push(SchemaViolation::new(
+            path,
+            ViolationKind::ExpectedComplex {
+                found: JsonType::of_attr(attr),
+            },
+        )),
+        Some(attr) if def.multi_valued => violations.push(SchemaViolation::new(
+            path,
+            ViolationKind::ExpectedMultiValue {
+                expected: def.type_,
+                found: JsonType::of_attr(attr),
+            },
+        )),
+        Some(attr) => validate_simple(def, attr, &path, violations),
+        None if def.required => {
+            violations.push(SchemaViolation::new(path, ViolationKind::MissingRequired))
+        }
+        None => {}
+    }
+}
+
+fn validate_value(
+    def: &ScimAttributeDefinition,
+    value: &ScimValue,
+    path: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let complex = def.type_ == AttributeType::Complex;
+
+    match (value, def.multi_valued, complex) {
+        (ScimValue::Simple(attr), false, false) => validate_simple(def, attr, path, violations),
+        // An extension merged into a schema, which is at the top and named
+        // by its URN.
+        (ScimValue::Complex(attrs), false, true) if path == def.name && path.contains(':') => {
+            validate_ext(&def.sub_attributes, attrs, path, violations)
+        }
+        (ScimValue::Complex(attrs), false, true) => {
+            validate_complex(&def.sub_attributes, attrs, path, violations)
+        }
+        (ScimValue::MultiSimple(values), true, false) => {
+            if def.required && values.is_empty() {
+                violations.push(SchemaViolation::new(path, ViolationKind::MissingRequired));
+            }
+            for (idx, attr) in values.iter().enumerate() {
+                validate_simple(def, attr, &format!("{}[{}]", path, idx), violations);
+            }
+        }
+        (ScimValue::MultiComplex(values), true, true) => {
+            if values.iter().filter(|v| is_primary(v)).count() > 1 {
+                violations.push(SchemaViolation::new(path, ViolationKind::MultiplePrimary));
+            }
+            for (idx, attrs) in values.iter().enumerate() {
+                let path = format!("{}[{}]", path, idx);
+                validate_complex(&def.sub_attributes, attrs, &path, violations);
+            }
+        }
+        // An empty array can't be told apart from an empty list of complex values.
+        (ScimValue::MultiSimple(values), true, true) if values.is_empty() => {
+            if def.required {
+                violations.push(SchemaViolation::new(path, ViolationKind::MissingRequired));
+            }
+        }
+        (ScimValue::MultiSimple(_) | ScimValue::MultiComplex(_), false, _) => violations.push(
+            SchemaViolation::new(path, ViolationKind::ExpectedSingleValue),
+        ),
+        (ScimValue::Simple(_) | ScimValue::Complex(_), true, _) => {
+            violations.push(SchemaViolation::new(
+                path,
+                ViolationKind::ExpectedMultiValue {
+                    expected: def.type_,
+                    found: JsonType::of_value(value),
+                },
+            ))
+        }
+        (_, _, true) => violations.push(SchemaViolation::new(
+            path,
+            ViolationKind::ExpectedComplex {
+                found: JsonType::of_value(value),
+            },
+        )),
+        (_, _, false) => violations.push(SchemaViolation::new(
+            path,
+            ViolationKind::InvalidType {
+                expected: def.type_,
+                found: JsonType::of_value(value),
+            },
+        )),
+    }
+}
+
+fn validate_simple(
+    def: &ScimAttributeDefinition,
+    attr: &ScimAttr,
+    path: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    // Values are decoded without knowledge of the schema, so a number may be
+    // held as either a Decimal or Integer, and most types arrive as strings.
+    let type_ok = match (def.type_, attr) {
+        (AttributeType::String, ScimAttr::String(_)) => true,
+        (AttributeType::Boolean, ScimAttr::Bool(_)) => true,
+        (AttributeType::Decimal, ScimAttr::Decimal(_) | ScimAttr::Integer(_)) => true,
+        (AttributeType::Integer, ScimAttr::Integer(_)) => true,
+        (AttributeType::Integer, ScimAttr::Decimal(d)) => d.fract() == 0.0,
+        (AttributeType::DateTime, ScimAttr::DateTime(_)) => true,
+        (AttributeType::DateTime, ScimAttr::String(s)) => {
+            datetime::parse(s, DateTimeParsing::Lenient).is_ok()
+        }
+        (AttributeType::Reference, ScimAttr::Reference(_) | ScimAttr::String(_)) => true,
+        (AttributeType::Binary, ScimAttr::Binary(_)) => true,
+        (AttributeType::Binary, ScimAttr::String(s)) => s
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+/=-_".contains(c)),
+        _ => false,
+    };
+
+    if !type_ok {
+        violations.push(SchemaViolation::new(
+            path,
+            ViolationKind::InvalidType {
+                expected: def.type_,
+                found: JsonType::of_attr(attr),
+            },
+        ));
+        return;
+    }
+
+    if let ScimAttr::String(s) = attr {
+        if !def.canonical_values.is_empty() && def.canonical_value(s).is_none() {
+            violations.push(SchemaViolation::new(
+                path,
+                ViolationKind::NotCanonical { value: s.clone() },
+            ));
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    MissingRequired,
+    /// A multi-valued attribute was given a single value.
+    ExpectedMultiValue {
+        expected: AttributeType,
+        found: JsonType,
+    },
+    /// A single-valued attribute was given an array.
+    ExpectedSingleValue,
+    /// A complex attribute was given a simple value.
+    ExpectedComplex {
+        found: JsonType,
+    },
+    /// A simple attribute or sub-attribute was given an object or array,
+    /// which can't be held.
+    ExpectedSimple {
+        found: JsonType,
+    },
+    InvalidType {
+        expected: AttributeType,
+        found: JsonType,
+    },
+    NotCanonical {
+        value: String,
+    },
+    /// A reference to a resource type the attribute doesn't allow.
+    InvalidReference {
+        value: String,
+    },
+    /// More than one value of a multi-valued attribute is primary.
+    MultiplePrimary,
+    /// A schema URN in `schemas` that isn't known.
+    UnknownSchema {
+        urn: String,
+    },
+    /// A name that differs only by case from another in the same object.
+    DuplicateName,
+    /// A name that isn't an attribute name, or is that of a common
+    /// attribute where one can't be given.
+    InvalidName,
+    /// A value given more than once in a multi-valued attribute.
+    DuplicateValue {
+        value: String,
+    },
+    /// A string that isn't of the form the attribute holds, such as an
+    /// email address.
+    InvalidFormat {
+        expected: &'static str,
+    },
+    /// An extension present that `schemas` doesn't list.
+    UnlistedExtension,
+    /// An extension listed in `schemas` that isn't present.
+    MissingExtension,
+    /// A value that can't be read as the type that holds it, such as a
+    /// field of a resource.
+    Unreadable {
+        reason: String,
+    },
+}
+
+/// The JSON type of a value, to say what was found where another type was
+/// expected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonType {
+    Null,
+    Boolean,
+    Number,
+    String,
+    Object,
+    Array,
+}
+
+impl JsonType {
+    pub fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => JsonType::Null,
+            Value::Bool(_) => JsonType::Boolean,
+            Value::Number(_) => JsonType::Number,
+            Value::String(_) => JsonType::String,
+            Value::Array(_) => JsonType::Array,
+            Value::Object(_) => JsonType::Object,
+        }
+    }
+
+    /// The type an attribute value is written as.
+    pub fn of_attr(attr: &ScimAttr) -> Self {
+        match attr {
+            ScimAttr::Null => JsonType::Null,
+            ScimAttr::Bool(_) => JsonType::Boolean,
+            ScimAttr::Decimal(_) | ScimAttr::Integer(_) => JsonType::Number,
+            ScimAttr::String(_)
+            | ScimAttr::DateTime(_)
+            | ScimAttr::Binary(_)
+            | ScimAttr::Reference(_) => JsonType::String,
+        }
+    }
+
+    pub fn of_value(value: &ScimValue) -> Self {
+        match value {
+            ScimValue::Simple(attr) => JsonType::of_attr(attr),
+            ScimValue::Complex(_) | ScimValue::Extension(_) => JsonType::Object,
+            ScimValue::MultiSimple(_) | ScimValue::MultiComplex(_) => JsonType::Array,
+        }
+    }
+}
+
+/// The type as RFC7643 names it, such as `dateTime`.
+impl fmt::Display for AttributeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AttributeType::String => "string",
+            AttributeType::Boolean => "boolean",
+            AttributeType::Decimal => "decimal",
+            AttributeType::Integer => "integer",
+            AttributeType::DateTime => "dateTime",
+            AttributeType::Reference => "reference",
+            AttributeType::Complex => "complex",
+            AttributeType::Binary => "binary",
+        })
+    }
+}
+
+impl fmt::Display for JsonType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            JsonType::Null => "null",
+            JsonType::Boolean => "a boolean",
+            JsonType::Number => "a number",
+            JsonType::String => "a string",
+            JsonType::Object => "an object",
+            JsonType::Array => "an array",
+        })
+    }
+}
+
+/// A single problem found while validating an entry against a schema.
+/// Violations are compared by their path and kind, whatever their source.
+#[derive(Debug, Clone)]
+pub struct SchemaViolation {
+    /// The attribute path, such as `emails[0].type`.
+    pub path: String,
+    pub kind: ViolationKind,
+    source: Option<ErrorSource>,
+}
+
+impl SchemaViolation {
+    pub(crate) fn new(path: impl Into<String>, kind: ViolationKind) -> Self {
+        SchemaViolation {
+            path: path.into(),
+            kind,
+            source: None,
+        }
+    }
+
+    /// A value at `path` that `err` says can't be read.
+    pub(crate) fn unreadable<E>(path: impl Into<String>, err: E) -> Self
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let reason = err.to_string();
+        SchemaViolation::new(path, ViolationKind::Unreadable { reason }).with_source(err)
+    }
+
+    pub(crate) fn with_source<E>(mut self, err: E) -> Self
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        self.source = Some(ErrorSource::new(err));
+        self
+    }
+
+    /// Every violation is reported to clients as `invalidValue`.
+    pub fn scim_type(&self) -> ScimType {
+        ScimType::InvalidValue
+    }
+}
+
+impl PartialEq for SchemaViolation {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.kind == other.kind
+    }
+}
+
+impl Eq for SchemaViolation {}
+
+impl std::error::Error for SchemaViolation {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| source.get() as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ViolationKind::MissingRequired => {
+                write!(f, "required attribute '{}' is missing", self.path)
+            }
+            ViolationKind::ExpectedMultiValue { expected, found } => write!(
+                f,
+                "attribute '{}' must be multi-valued {}, found {}",
+                self.path, expected, found
+            ),
+            ViolationKind::ExpectedSingleValue => {
+                write!(f, "attribute '{}' must be single-valued", self.path)
+            }
+            ViolationKind::ExpectedComplex { found } => {
+                write!(
+                    f,
+                    "attribute '{}' must be complex, found {}",
+                    self.path, found
+                )
+            }
+            ViolationKind::ExpectedSimple { found } => {
+                write!(
+                    f,
+                    "attribute '{}' must be simple, found {}",
+                    self.path, found
+                )
+            }
+            ViolationKind::InvalidType { expected, found } => {
+                write!(
+                    f,
+                    "attribute '{}' must be of type {}, found {}",
+                    self.path, expected, found
+                )
+            }
+            ViolationKind::NotCanonical { value } => write!(
+                f,
+                "'{}' is not a canonical value of attribute '{}'",
+                value, self.path
+            ),
+            ViolationKind::InvalidReference { value } => write!(
+                f,
+                "'{}' is not an allowed reference for attribute '{}'",
+                value, self.path
+            ),
+            ViolationKind::MultiplePrimary => write!(
+                f,
+                "attribute '{}' has more than one primary value",
+                self.path
+            ),
+            ViolationKind::UnknownSchema { urn } => {
+                write!(f, "schema '{}' in '{}' is not known", urn, self.path)
+            }
+            ViolationKind::DuplicateName => write!(
+                f,
+                "attribute '{}' is given more than once, in different cases",
+                self.path
+            ),
+            ViolationKind::InvalidName => {
+                write!(
+                    f,
+                    "'{}' is not an attribute name that can be given",
+                    self.path
+                )
+            }
+            ViolationKind::DuplicateValue { value } => write!(
+                f,
+                "'{}' is given more than once in attribute '{}'",
+                value, self.path
+            ),
+            ViolationKind::InvalidFormat { expected } if self.path.is_empty() => {
+                write!(f, "the value is not {}", expected)
+            }
+            ViolationKind::InvalidFormat { expected } => {
+                write!(f, "attribute '{}' is not {}", self.path, expected)
+            }
+            ViolationKind::UnlistedExtension => {
+                write!(f, "extension '{}' is not listed in schemas", self.path)
+            }
+            ViolationKind::MissingExtension => write!(
+                f,
+                "extension '{}' is listed in schemas but not present",
+                self.path
+            ),
+            ViolationKind::Unreadable { reason } if self.path.is_empty() => {
+                write!(f, "the value can't be read: {}", reason)
+            }
+            ViolationKind::Unreadable { reason } => {
+                write!(f, "attribute '{}' can't be read: {}", self.path, reason)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaError {
+    /// The document is not valid JSON, or has an unknown metadata value.
+    Parse(String),
+    /// The schema id is not a URN.
+    InvalidId(String),
+    InvalidName(String),
+    DuplicateAttribute(String),
+    /// A complex attribute has no sub-attributes.
+    MissingSubAttributes(String),
+    /// A sub-attribute is itself complex.
+    NestedComplex(String),
+    /// A non-complex attribute declares sub-attributes.
+    UnexpectedSubAttributes(String),
+    /// A reference attribute has no referenceTypes.
+    MissingReferenceTypes(String),
+    /// A non-reference attribute declares referenceTypes.
+    UnexpectedReferenceTypes(String),
+    /// A resource type names a schema that isn't registered.
+    UnknownSchema(String),
+    /// An object identifier is not a dotted sequence of numbers.
+    InvalidOid(String),
+    /// An attribute path has no entry in `LdapSchemaOptions::oids`.
+    MissingOid(String),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Parse(e) => write!(f, "invalid schema document: {}", e),
+            SchemaError::InvalidId(id) => write!(f, "schema id '{}' is not a URN", id),
+            SchemaError::InvalidName(p) => write!(f, "'{}' is not a valid attribute name", p),
+            SchemaError::DuplicateAttribute(p) => write!(f, "attribute '{}' is defined twice", p),
+            SchemaError::MissingSubAttributes(p) => {
+                write!(f, "complex attribute '{}' has no subAttributes", p)
+            }
+            SchemaError::NestedComplex(p) => {
+                write!(f, "sub-attribute '{}' must not be complex", p)
+            }
+            SchemaError::UnexpectedSubAttributes(p) => {
+                write!(f, "attribute '{}' is not complex but has subAttributes", p)
+            }
+            SchemaError::MissingReferenceTypes(p) => {
+                write!(f, "reference attribute '{}' has no referenceTypes", p)
+            }
+            SchemaError::UnexpectedReferenceTypes(p) => {
+                write!(
+                    f,
+                    "attribute '{}' is not a reference but has referenceTypes",
+                    p
+                )
+            }
+            SchemaError::UnknownSchema(id) => write!(f, "schema '{}' is not registered", id),
+            SchemaError::InvalidOid(oid) => write!(f, "'{}' is not a valid OID", oid),
+            SchemaError::MissingOid(p) => write!(f, "attribute '{}' has no assigned OID", p),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{RFC7643_GROUP, RFC7643_SCHEMA_GROUP};
+    use crate::messages::ScimErrorResponse;
+
+    #[test]
+    fn parse_schema() {
+        let _ = tracing_subscriber::fmt::try_init();
+
+        let s = ScimSchema::parse(RFC7643_SCHEMA_GROUP).expect("Failed to parse group schema");
+
+        tracing::trace!(?s);
+
+        let members = s.attribute("MEMBERS").expect("members not found");
+        assert_eq!(members.type_, AttributeType::Complex);
+        assert!(members.multi_valued);
+        assert_eq!(members.uniqueness, Uniqueness::None);
+
+        let ref_ = members.sub_attribute("$ref").expect("$ref not found");
+        assert_eq!(ref_.type_, AttributeType::Reference);
+        assert_eq!(ref_.mutability, Mutability::Immutable);
+        assert_eq!(ref_.reference_types, vec!["User", "Group"]);
+
+        let list = format!(
+            r#"{{
+              "schemas": ["urn:ietf:params:scim:api:messages:2.0:ListResponse"],
+              "totalResults": 1,
+              "Resources": [{}]
+            }}"#,
+            RFC7643_SCHEMA_GROUP
+        );
+        let l = ScimSchema::parse_list(&list).expect("Failed to parse schema list");
+        assert_eq!(l, vec![s]);
+    }
+
+    #[test]
+    fn validate_entry() {
+        let schema = ScimSchema::parse(RFC7643_SCHEMA_GROUP).expect("Failed to parse group schema");
+        let g: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_GROUP).expect("Failed to parse RFC7643_GROUP");
+        assert_eq!(schema.validate(&g), Ok(()));
+
+        let mut schema = schema;
+        schema.attributes[0].required = true;
+
+        let g: ScimEntryGeneric = serde_json::from_str(
+            r#"{
+              "schemas": ["urn:ietf:params:scim:schemas:core:2.0:Group"],
+              "id": "e9e30dba-f08f-4109-8486-d5c6a331660a",
+              "members": [
+                { "value": "2819c223-7f76-453a-919d-413861904646", "type": "User" },
+                { "value": true, "type": "Robot" }
+              ]
+            }"#,
+        )
+        .expect("Failed to parse group");
+
+        assert_eq!(
+            schema.validate(&g),
+            Err(vec![
+                SchemaViolation::new("displayName", ViolationKind::MissingRequired),
+                SchemaViolation::new(
+                    "members[1].value",
+                    ViolationKind::InvalidType {
+                        expected: AttributeType::String,
+                        found: JsonType::Boolean,
+                    }
+                ),
+                SchemaViolation::new(
+                    "members[1].type",
+                    ViolationKind::NotCanonical {
+                        value: "Robot".to_string()
+                    }
+                ),
+            ])
+        );
+
+        let g: ScimEntryGeneric = serde_json::from_str(
+            r#"{
+              "schemas": ["urn:ietf:params:scim:schemas:core:2.0:Group"],
+              "id": "e9e30dba-f08f-4109-8486-d5c6a331660a",
+              "displayName": ["Tour Guides"],
+              "members": { "value": "2819c223-7f76-453a-919d-413861904646" }
+            }"#,
+        )
+        .expect("Failed to parse group");
+
+        let violations = schema.validate(&g).expect_err("Group is invalid");
+        assert_eq!(
+            violations,
+            vec![
+                SchemaViolation::new("displayName", ViolationKind::ExpectedSingleValue),
+                SchemaViolation::new(
+                    "members",
+                    ViolationKind::ExpectedMultiValue {
+                        expected: AttributeType::Complex,
+                        found: JsonType::Object,
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            violations[1].to_string(),
+            "attribute 'members' must be multi-valued complex, found an object"
+        );
+    }
+
+    #[test]
+    fn patch_path_split() {
+        assert_eq!(split_patch_path("members"), (None, "members", None));
+        assert_eq!(
+            split_patch_path("name.givenName"),
+            (None, "name", Some("givenName"))
+        );
+        assert_eq!(
+            split_patch_path(r#"members[value eq "2819c223"].display"#),
+            (None, "members", Some("display"))
+        );
+        assert_eq!(
+            split_patch_path(r#"emails[type eq "work" and value ew "example.com"]"#),
+            (None, "emails", None)
+        );
+        assert_eq!(
+            split_patch_path("urn:ietf:params:scim:schemas:core:2.0:User:name.familyName"),
+            (
+                Some("urn:ietf:params:scim:schemas:core:2.0:User"),
+                "name",
+                Some("familyName")
+            )
+        );
+    }
+
+    #[test]
+    fn mutability_checks() {
+        let mut schema =
+            ScimSchema::parse(RFC7643_SCHEMA_GROUP).expect("Failed to parse group schema");
+        // Make displayName set-once for the purposes of the test.
+        schema.attributes[0].mutability = Mutability::Immutable;
+
+        let stored: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_GROUP).expect("Failed to parse RFC7643_GROUP");
+
+        let mut incoming = stored.clone();
+        assert_eq!(schema.check_replace(&stored, &incoming), Ok(()));
+
+        incoming.attrs.insert(
+            "displayName".to_string(),
+            ScimValue::Simple(ScimAttr::String("Tour Leaders".to_string())),
+        );
+        assert_eq!(
+            schema.check_replace(&stored, &incoming),
+            Err(ScimError::Mutability("displayName".to_string()))
+        );
+
+        let patch = |ops: Value| -> ScimPatchRequest {
+            serde_json::from_value(serde_json::json!({
+                "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+                "Operations": ops
+            }))
+            .expect("Failed to parse patch")
+        };
+
+        // Adding members is fine, but existing member values can't be changed.
+        let p = patch(serde_json::json!([
+            { "op": "Add", "path": "members", "value": [{ "value": "2819c223-7f76-453a-919d-413861904646" }] }
+        ]));
+        assert_eq!(schema.check_patch(&stored, &p), Ok(()));
+
+        let p = patch(serde_json::json!([
+            { "op": "replace", "path": "members[value eq \"2819c223-7f76-453a-919d-413861904646\"].value", "value": "x" }
+        ]));
+        assert_eq!(
+            schema.check_patch(&stored, &p),
+            Err(ScimError::Mutability("members.value".to_string()))
+        );
+
+        let p = patch(serde_json::json!([
+            { "op": "replace", "value": { "displayName": "Tour Leaders" } }
+        ]));
+        assert_eq!(
+            schema.check_patch(&stored, &p),
+            Err(ScimError::Mutability("displayName".to_string()))
+        );
+
+        let p = patch(serde_json::json!([
+            { "op": "replace", "value": { "displayName": "Tour Guides" } }
+        ]));
+        assert_eq!(schema.check_patch(&stored, &p), Ok(()));
+
+        // Members are matched by value, so new ones can be added, but the
+        // immutable sub-attributes of existing ones can't be changed.
+        let mut incoming = stored.clone();
+        if let Some(ScimValue::MultiComplex(members)) = incoming.attrs.get_mut("members") {
+            let mut member = members[0].clone();
+            member.insert("value".to_string(), ScimAttr::String("a".to_string()));
+            members.push(member);
+        }
+        assert_eq!(schema.check_replace(&stored, &incoming), Ok(()));
+        if let Some(ScimValue::MultiComplex(members)) = incoming.attrs.get_mut("members") {
+            members[0].insert(
+                "$ref".to_string(),
+                ScimAttr::String("https://example.com/v2/Users/a".to_string()),
+            );
+        }
+        assert_eq!(
+            schema.check_replace(&stored, &incoming),
+            Err(ScimError::Mutability("members.$ref".to_string()))
+        );
+    }
+
+    #[test]
+    fn extension_mutability_checks() {
+        let schema = crate::builtin::enterprise_user_schema();
+        let enterprise = SCIM_SCHEMA_ENTERPRISE_USER;
+        let stored: ScimEntryGeneric = serde_json::from_value(serde_json::json!({
+            "schemas": [SCIM_SCHEMA_USER, enterprise],
+            "id": "2819c223",
+            "userName": "bjensen",
+            enterprise: {
+                "department": "Tour Operations",
+                "manager": { "value": "26118915", "displayName": "John Smith" }
+            }
+        }))
+        .expect("Failed to parse entry");
+
+        let mut incoming = stored.clone();
+        incoming
+            .set_path(
+                &format!("{}:department", enterprise),
+                ScimValue::Simple(ScimAttr::String("Theme Park".to_string())),
+            )
+            .expect("Failed to set path");
+        assert_eq!(schema.check_replace(&stored, &incoming), Ok(()));
+        incoming
+            .set_path(
+                &format!("{}:manager.displayName", enterprise),
+                ScimValue::Simple(ScimAttr::String("Babs".to_string())),
+            )
+            .expect("Failed to set path");
+        assert_eq!(
+            schema.check_replace(&stored, &incoming),
+            Err(ScimError::Mutability("manager.displayName".to_string()))
+        );
+
+        let check = |ops: Value| {
+            let patch: ScimPatchRequest = serde_json::from_value(serde_json::json!({
+                "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+                "Operations": ops
+            }))
+            .expect("Failed to parse patch");
+            schema.check_patch(&stored, &patch)
+        };
+        assert_eq!(
+            check(serde_json::json!([
+                { "op": "replace", "path": format!("{}:department", enterprise), "value": "x" },
+                { "op": "replace", "path": "displayName", "value": "Babs" }
+            ])),
+            Ok(())
+        );
+        assert_eq!(
+            check(serde_json::json!([
+                { "op": "replace", "path": format!("{}:manager.displayName", enterprise), "value": "x" }
+            ])),
+            Err(ScimError::Mutability("manager.displayName".to_string()))
+        );
+        assert_eq!(
+            check(serde_json::json!([
+                { "op": "replace", "value": { enterprise: { "manager": { "displayName": "x" } } } }
+            ])),
+            Err(ScimError::Mutability("manager.displayName".to_string()))
+        );
+    }
+
+    #[test]
+    fn canonical_values() {
+        let schema = ScimSchema::parse(RFC7643_SCHEMA_GROUP).expect("Failed to parse group schema");
+
+        let mut g: ScimEntryGeneric = serde_json::from_str(
+            r#"{
+              "schemas": ["urn:ietf:params:scim:schemas:core:2.0:Group"],
+              "id": "e9e30dba-f08f-4109-8486-d5c6a331660a",
+              "members": [
+                { "value": "2819c223-7f76-453a-919d-413861904646", "type": "USER" },
+                { "value": "fc348aa8-3835-40eb-a20b-c726e15c55b5", "type": "Robot" }
+              ]
+            }"#,
+        )
+        .expect("Failed to parse group");
+
+        // The schema isn't caseExact, so USER is already acceptable.
+        let err = schema
+            .check_canonical(&mut g, CanonicalMode::Strict)
+            .expect_err("Robot is not canonical");
+        assert_eq!(err.len(), 1);
+        assert_eq!(err[0].path, "members[1].type");
+        assert_eq!(err[0].scim_type(), ScimType::InvalidValue);
+
+        let mut schema = schema;
+        schema.attributes[1].sub_attributes[2].case_exact = true;
+        let err = schema
+            .check_canonical(&mut g, CanonicalMode::Strict)
+            .expect_err("USER is not canonical");
+        assert_eq!(err.len(), 2);
+
+        let err = schema
+            .check_canonical(&mut g, CanonicalMode::CoerceCase)
+            .expect_err("Robot is not canonical");
+        assert_eq!(err.len(), 1);
+        assert_eq!(
+            g.attrs.get("members"),
+            Some(&ScimValue::MultiComplex(vec![
+                [
+                    (
+                        "value".to_string(),
+                        ScimAttr::String("2819c223-7f76-453a-919d-413861904646".to_string())
+                    ),
+                    ("type".to_string(), ScimAttr::String("User".to_string())),
+                ]
+                .into_iter()
+                .collect(),
+                [
+                    (
+                        "value".to_string(),
+                        ScimAttr::String("fc348aa8-3835-40eb-a20b-c726e15c55b5".to_string())
+                    ),
+                    ("type".to_string(), ScimAttr::String("Robot".to_string())),
+                ]
+                .into_iter()
+                .collect(),
+            ]))
+        );
+
+        let mut patch: ScimPatchRequest = serde_json::from_value(serde_json::json!({
+            "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+            "Operations": [
+                { "op": "add", "path": "members", "value": [{ "value": "a", "type": "group" }] },
+                { "op": "replace", "path": "members[value eq \"a\"].type", "value": "Robot" }
+            ]
+        }))
+        .expect("Failed to parse patch");
+
+        let err = schema
+            .check_patch_canonical(&mut patch, CanonicalMode::CoerceCase)
+            .expect_err("Robot is not canonical");
+        assert_eq!(err.len(), 1);
+        assert_eq!(err[0].path, "members[value eq \"a\"].type");
+        assert_eq!(
+            patch.operations[0].value,
+            Some(serde_json::json!([{ "value": "a", "type": "Group" }]))
+        );
+    }
+
+    #[test]
+    fn case_exact_compare() {
+        let mut schema =
+            ScimSchema::parse(RFC7643_SCHEMA_GROUP).expect("Failed to parse group schema");
+        schema.attributes[0].uniqueness = Uniqueness::Server;
+
+        let g: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_GROUP).expect("Failed to parse group");
+
+        let mut other = g.clone();
+        if let Some(ScimValue::Simple(ScimAttr::String(name))) = other.attrs.get_mut("displayName")
+        {
+            *name = name.to_ascii_uppercase();
+        }
+        assert_ne!(g, other);
+        assert!(schema.entry_eq(&g, &other));
+        assert!(schema.attributes[0].attr_eq(
+            &ScimAttr::String("Ärzte".to_string()),
+            &ScimAttr::String("ärzte".to_string())
+        ));
+
+        schema.attributes[1].sub_attributes[0].case_exact = true;
+        let mut member = other.clone();
+        if let Some(ScimValue::MultiComplex(members)) = member.attrs.get_mut("members") {
+            if let Some(ScimAttr::String(value)) = members[0].get_mut("value") {
+                *value = value.to_ascii_uppercase();
+            }
+        }
+        assert!(!schema.entry_eq(&g, &member));
+
+        assert!(schema.check_uniqueness(&g, [&g]).is_ok());
+        other.id = uuid::Uuid::nil().into();
+        assert_eq!(
+            schema.check_uniqueness(&g, [&other]),
+            Err(ScimError::Uniqueness)
+        );
+        schema.attributes[0].case_exact = true;
+        assert!(schema.check_uniqueness(&g, [&other]).is_ok());
+    }
+
+    #[test]
+    fn check_references() {
+        let schema = ScimSchema::parse(RFC7643_SCHEMA_GROUP).expect("Failed to parse group schema");
+        let base = Url::parse("https://example.com/v2").expect("Invalid url");
+
+        let mut g: ScimEntryGeneric = serde_json::from_str(
+            r#"{
+              "schemas": ["urn:ietf:params:scim:schemas:core:2.0:Group"],
+              "id": "e9e30dba-f08f-4109-8486-d5c6a331660a",
+              "members": [
+                { "value": "a", "$ref": "https://example.com/v2/Users/a" },
+                { "value": "b", "$ref": "Groups/b" },
+                { "value": "c", "$ref": "https://example.com/v2/Devices/c" },
+                { "value": "d", "$ref": "https://evil.example.org/v2/Users/d" }
+              ]
+            }"#,
+        )
+        .expect("Failed to parse group");
+
+        let err = schema
+            .check_references(&g, &base)
+            .expect_err("References are not allowed");
+        let paths: Vec<_> = err.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, vec!["members[2].$ref", "members[3].$ref"]);
+
+        if let Some(ScimValue::MultiComplex(members)) = g.attrs.get_mut("members") {
+            members.truncate(2);
+        }
+        assert!(schema.check_references(&g, &base).is_ok());
+
+        let mut photo = ScimAttributeDefinition::new("value", AttributeType::Reference);
+        photo.reference_types = vec!["external".to_string()];
+        assert!(photo.reference_allowed("https://photos.example.com/a.jpg", &base));
+        assert!(!photo.reference_allowed("a.jpg", &base));
+    }
+
+    #[test]
+    fn attr_name_abnf() {
+        for name in [
+            "userName",
+            "x509Certificates",
+            "a",
+            "employee-number",
+            "cost_center",
+            "$ref",
+        ] {
+            assert!(is_valid_attr_name(name), "{}", name);
+            assert_eq!(validate_attr_name(name), Ok(()));
+        }
+        for name in [
+            "",
+            "1st",
+            "-name",
+            "_name",
+            "user name",
+            "name.given",
+            "$value",
+            "naïve",
+        ] {
+            assert!(!is_valid_attr_name(name), "{}", name);
+            assert_eq!(validate_attr_name(name), Err(ScimError::InvalidAttribute));
+        }
+    }
+
+    #[test]
+    fn entry_parse_modes() {
+        let lenient = EntryParseOptions::default();
+        let strict = EntryParseOptions::default().mode(ParseMode::Strict);
+
+        let (u, warnings) = ScimEntryGeneric::parse(crate::constants::RFC7643_USER, &strict)
+            .expect("Failed to parse RFC7643_USER");
+        assert!(warnings.is_empty());
+        assert!(u.contains_attr("userName"));
+
+        let json = r#"{
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User", "urn:example:Device"],
+            "id": "2819c223",
+            "userName": "bjensen",
+            "USERNAME": "babs",
+            "name": {"givenName": "Barbara", "formatted": {"text": "Ms. Barbara J Jensen"}},
+            "emails": [{"value": "bjensen@example.com", "Type": "work", "type": "home"}],
+            "x509Certificates": [["MIIDQzCCAqygAwIBAgICEAAwDQYJKoZIhvcNAQEFBQAw"]],
+            "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User": {
+                "employeeNumber": "701984",
+                "EmployeeNumber": "701985",
+                "manager": {"value": "26118915", "ref": {"href": "../Users/26118915"}}
+            }
+        }"#;
+        assert_eq!(
+            ScimEntryGeneric::parse(json, &strict),
+            Err(ScimError::InvalidValue(SchemaViolation::new(
+                "userName",
+                ViolationKind::DuplicateName
+            )))
+        );
+
+        let (u, warnings) = ScimEntryGeneric::parse(json, &lenient).expect("Failed to parse");
+        let enterprise = "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User";
+        let violations: Vec<_> = warnings
+            .into_iter()
+            .filter_map(|w| match w.kind {
+                WarningKind::Violation(kind) => Some(SchemaViolation::new(w.path, kind)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                SchemaViolation::new("userName", ViolationKind::DuplicateName),
+                SchemaViolation::new("emails[0].type", ViolationKind::DuplicateName),
+                SchemaViolation::new(
+                    format!("{}:employeeNumber", enterprise),
+                    ViolationKind::DuplicateName
+                ),
+                SchemaViolation::new(
+                    "name.formatted",
+                    ViolationKind::ExpectedSimple {
+                        found: JsonType::Object
+                    }
+                ),
+                SchemaViolation::new(
+                    format!("{}:manager.ref", enterprise),
+                    ViolationKind::ExpectedSimple {
+                        found: JsonType::Object
+                    }
+                ),
+                SchemaViolation::new(
+                    "x509Certificates[0]",
+                    ViolationKind::ExpectedSimple {
+                        found: JsonType::Array
+                    }
+                ),
+                SchemaViolation::new(
+                    "schemas",
+                    ViolationKind::UnknownSchema {
+                        urn: "urn:example:Device".to_string()
+                    }
+                ),
+            ]
+        );
+        assert!(u.contains_attr("userName"));
+        assert!(!u.contains_attr("name"));
+        assert!(!u.contains_attr("x509Certificates"));
+        assert!(u.contains_attr("emails"));
+        assert!(u.contains_attr(enterprise));
+
+        let known = lenient.schema("urn:example:Device");
+        let (_, warnings) = ScimEntryGeneric::parse(json, &known).expect("Failed to parse");
+        assert_eq!(warnings.len(), 6);
+        assert!(ScimEntryGeneric::parse("[]", &known).is_err());
+    }
+
+    #[test]
+    fn validate_all_entry() {
+        let schema = crate::builtin::user_schema();
+        let u: Value = serde_json::from_str(crate::constants::RFC7643_USER)
+            .expect("Failed to parse RFC7643_USER");
+        assert!(schema.validate_all(&u).is_ok());
+
+        let bad = serde_json::json!({
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+            "externalId": 701984,
+            "name": { "givenName": { "first": "Barbara" }, "familyName": "Jensen" },
+            "active": "yes",
+            "emails": [
+                { "value": "bjensen@example.com", "primary": true },
+                "babs@example.com"
+            ],
+            "phoneNumbers": [
+                { "value": "555-555-5555", "primary": true },
+                { "value": "555-555-4444", "primary": true }
+            ]
+        });
+        let errs = schema.validate_all(&bad).expect_err("Entry is invalid");
+        assert_eq!(ScimErrorResponse::from(&errs).status, 400);
+        let violations: Vec<_> = errs
+            .into_iter()
+            .filter_map(|(_, err)| match err {
+                ScimError::InvalidValue(v) => Some(v),
+                _ => None,
+            })
+            .collect();
+        let invalid_type = |path: &str, expected, found| {
+            SchemaViolation::new(path, ViolationKind::InvalidType { expected, found })
+        };
+        assert_eq!(
+            violations,
+            vec![
+                SchemaViolation::new(
+                    "emails[1]",
+                    ViolationKind::ExpectedComplex {
+                        found: JsonType::String
+                    }
+                ),
+                invalid_type("externalId", AttributeType::String, JsonType::Number),
+                SchemaViolation::new(
+                    "name.givenName",
+                    ViolationKind::ExpectedSimple {
+                        found: JsonType::Object
+                    }
+                ),
+                SchemaViolation::new("id", ViolationKind::MissingRequired),
+                SchemaViolation::new("userName", ViolationKind::MissingRequired),
+                invalid_type("active", AttributeType::Boolean, JsonType::String),
+                SchemaViolation::new("phoneNumbers", ViolationKind::MultiplePrimary),
+            ]
+        );
+        assert_eq!(
+            violations[5].to_string(),
+            "attribute 'active' must be of type boolean, found a string"
+        );
+    }
+
+    #[test]
+    fn primary_uniqueness() {
+        let schema = crate::builtin::user_schema();
+        let mut u: ScimEntryGeneric = serde_json::from_str(
+            r#"{
+              "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+              "id": "2819c223-7f76-453a-919d-413861904646",
+              "userName": "bjensen",
+              "emails": [
+                { "value": "bjensen@example.com", "type": "work", "primary": true },
+                { "value": "babs@jensen.org", "type": "home", "primary": true },
+                { "value": "babs@example.org", "type": "other" }
+              ]
+            }"#,
+        )
+        .expect("Failed to parse user");
+
+        let violation = SchemaViolation::new("emails", ViolationKind::MultiplePrimary);
+        assert_eq!(schema.validate(&u), Err(vec![violation.clone()]));
+        assert_eq!(
+            u.clone().check_primary(PrimaryMode::Strict),
+            Err(vec![violation])
+        );
+
+        let warnings = u
+            .check_primary(PrimaryMode::DemoteExtras)
+            .expect("Failed to demote");
+        assert_eq!(
+            warnings,
+            vec![ScimWarning::new(
+                "emails[1].primary",
+                WarningKind::PrimaryDemoted
+            )]
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "'emails[1].primary' was set to false, as an earlier value is primary"
+        );
+        assert_eq!(schema.validate(&u), Ok(()));
+        let primary = |u: &ScimEntryGeneric| -> Vec<bool> {
+            match u.attr("emails") {
+                Some(ScimValue::MultiComplex(values)) => values.iter().map(is_primary).collect(),
+                _ => Vec::new(),
+            }
+        };
+        assert_eq!(primary(&u), vec![true, false, false]);
+
+        // Making another value primary, as a PATCH would, demotes the first.
+        u.set_path(
+            r#"emails[type eq "home"].primary"#,
+            ScimValue::Simple(ScimAttr::Bool(true)),
+        )
+        .expect("Failed to set primary");
+        assert_eq!(primary(&u), vec![false, true, false]);
+        assert_eq!(u.check_primary(PrimaryMode::Strict), Ok(Vec::new()));
+
+        u.primary_first();
+        assert_eq!(primary(&u), vec![true, false, false]);
+        let types: Vec<_> = u["emails"]
+            .complex_values()
+            .filter_map(|e| find_attr(e, "type").and_then(ScimAttr::as_str))
+            .collect();
+        assert_eq!(types, vec!["home", "work", "other"]);
+    }
+
+    #[test]
+    fn parse_schema_invalid() {
+        let schema = |attrs: &str| {
+            ScimSchema::parse(&format!(
+                r#"{{ "id": "urn:example:schema", "attributes": {} }}"#,
+                attrs
+            ))
+        };
+
+        assert!(matches!(
+            schema(r#"[{ "name": "a", "mutability": "sometimes" }]"#),
+            Err(SchemaError::Parse(_))
+        ));
+        assert_eq!(
+            schema(r#"[{ "name": "0a" }]"#),
+            Err(SchemaError::InvalidName("0a".to_string()))
+        );
+        assert_eq!(
+            schema(r#"[{ "name": "a" }, { "name": "A" }]"#),
+            Err(SchemaError::DuplicateAttribute("A".to_string()))
+        );
+        assert_eq!(
+            schema(r#"[{ "name": "a", "type": "complex" }]"#),
+            Err(SchemaError::MissingSubAttributes("a".to_string()))
+        );
+        assert_eq!(
+            schema(
+                r#"[{ "name": "a", "type": "complex", "subAttributes": [
+                    { "name": "b", "type": "complex", "subAttributes": [{ "name": "c" }] }
+                ]}]"#
+            ),
+            Err(SchemaError::NestedComplex("a.b".to_string()))
+        );
+        assert_eq!(
+            schema(r#"[{ "name": "a", "subAttributes": [{ "name": "b" }] }]"#),
+            Err(SchemaError::UnexpectedSubAttributes("a".to_string()))
+        );
+        assert_eq!(
+            schema(r#"[{ "name": "a", "type": "reference" }]"#),
+            Err(SchemaError::MissingReferenceTypes("a".to_string()))
+        );
+        assert_eq!(
+            ScimSchema::parse(r#"{ "id": "User", "attributes": [] }"#),
+            Err(SchemaError::InvalidId("User".to_string()))
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use crate::schema::{
+    AttributeType, Mutability, Returned, ScimAttributeDefinition, ScimSchema, Uniqueness,
+};
+
+/// A change to one characteristic of an attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttributeChange {
+    Type {
+        from: AttributeType,
+        to: AttributeType,
+    },
+    MultiValued {
+        from: bool,
+        to: bool,
+    },
+    Required {
+        from: bool,
+        to: bool,
+    },
+    CaseExact {
+        from: bool,
+        to: bool,
+    },
+    Mutability {
+        from: Mutability,
+        to: Mutability,
+    },
+    Returned {
+        from: Returned,
+        to: Returned,
+    },
+    Uniqueness {
+        from: Uniqueness,
+        to: Uniqueness,
+    },
+    CanonicalValues {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    ReferenceTypes {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeDiff {
+    /// The attribute path, such as `name.givenName`.
+    pub path: String,
+    pub changes: Vec<AttributeChange>,
+}
+
+/// The differences between two versions of a schema, from
+/// [`ScimSchema::diff`]. Attribute names are compared case insensitively.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaDiff {
+    pub id_changed: bool,
+    pub name_changed: bool,
+    pub description_changed: bool,
+    /// Paths of attributes only present in the other schema.
+    pub added: Vec<String>,
+    /// Paths of attributes only present in this schema.
+    pub removed: Vec<String>,
+    pub changed: Vec<AttributeDiff>,
+}
+
+impl SchemaDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == SchemaDiff::default()
+    }
+}
+
+fn path(parent: Option<&str>, name: &str) -> String {
+    match parent {
+        Some(parent) => format!("{}.{}", parent, name),
+        None => name.to_string(),
+    }
+}
+
+/// The values added to and removed from a list, if any were.
+fn set_diff(from: &[String], to: &[String]) -> Option<(Vec<String>, Vec<String>)> {
+    let added: Vec<_> = to.iter().filter(|v| !from.contains(v)).cloned().collect();
+    let removed: Vec<_> = from.iter().filter(|v| !to.contains(v)).cloned().collect();
+    if added.is_empty() && removed.is_empty() {
+        None
+    } else {
+        Some((added, removed))
+    }
+}
+
+fn find<'a>(
+    attrs: &'a [ScimAttributeDefinition],
+    name: &str,
+) -> Option<&'a ScimAttributeDefinition> {
+    attrs.iter().find(|a| a.name.eq_ignore_ascii_case(name))
+}
+
+fn compare(from: &ScimAttributeDefinition, to: &ScimAttributeDefinition) -> Vec<AttributeChange> {
+    let mut changes = Vec::new();
+
+    if from.type_ != to.type_ {
+        changes.push(AttributeChange::Type {
+            from: from.type_,
+            to: to.type_,
+        });
+    }
+    if from.multi_valued != to.multi_valued {
+        changes.push(AttributeChange::MultiValued {
+            from: from.multi_valued,
+            to: to.multi_valued,
+        });
+    }
+    if from.required != to.required {
+        changes.push(AttributeChange::Required {
+            from: from.required,
+            to: to.required,
+        });
+    }
+    if from.case_exact != to.case_exact {
+        changes.push(AttributeChange::CaseExact {
+            from: from.case_exact,
+            to: to.case_exact,
+        });
+    }
+    if from.mutability != to.mutability {
+        changes.push(AttributeChange::Mutability {
+            from: from.mutability,
+            to: to.mutability,
+        });
+    }
+    if from.returned != to.returned {
+        changes.push(AttributeChange::Returned {
+            from: from.returned,
+            to: to.returned,
+        });
+    }
+    if from.uniqueness != to.uniqueness {
+        changes.push(AttributeChange::Uniqueness {
+            from: from.uniqueness,
+            to: to.uniqueness,
+        });
+    }
+    if let Some((added, removed)) = set_diff(&from.canonical_values, &to.canonical_values) {
+        changes.push(AttributeChange::CanonicalValues { added, removed });
+    }
+    if let Some((added, removed)) = set_diff(&from.reference_types, &to.reference_types) {
+        changes.push(AttributeChange::ReferenceTypes { added, removed });
+    }
+
+    changes
+}
+
+fn diff_attributes(
+    from: &[ScimAttributeDefinition],
+    to: &[ScimAttributeDefinition],
+    parent: Option<&str>,
+    diff: &mut SchemaDiff,
+) {
+    for old in from {
+        let path = path(parent, &old.name);
+        let Some(new) = find(to, &old.name) else {
+            diff.removed.push(path);
+            continue;
+        };
+
+        let changes = compare(old, new);
+        if !changes.is_empty() {
+            diff.changed.push(AttributeDiff {
+                path: path.clone(),
+                changes,
+            });
+        }
+        diff_attributes(&old.sub_attributes, &new.sub_attributes, Some(&path), diff);
+    }
+
+    for new in to {
+        if find(from, &new.name).is_none() {
+            diff.added.push(path(parent, &new.name));
+        }
+    }
+}
+
+impl ScimSchema {
+    /// Report how `other` differs from this schema, such as a provider's
+    /// current schema compared to the one a mapping was written against.
+    pub fn diff(&self, other: &ScimSchema) -> SchemaDiff {
+        let mut diff = SchemaDiff {
+            id_changed: !self.id.eq_ignore_ascii_case(&other.id),
+            name_changed: self.name != other.name,
+            description_changed: self.description != other.description,
+            ..Default::default()
+        };
+        diff_attributes(&self.attributes, &other.attributes, None, &mut diff);
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::user_schema;
+
+    #[test]
+    fn schema_diff() {
+        let old = user_schema();
+        assert!(old.diff(&old).is_empty());
+
+        let mut new = user_schema();
+        new.description = Some("Users".to_string());
+        new.attributes.retain(|a| a.name != "nickName");
+        new.attributes.push(ScimAttributeDefinition::new(
+            "badgeNumber",
+            AttributeType::Integer,
+        ));
+        if let Some(title) = new.attributes.iter_mut().find(|a| a.name == "title") {
+            title.required = true;
+            title.mutability = Mutability::Immutable;
+            title.name = "TITLE".to_string();
+        }
+        if let Some(emails) = new.attributes.iter_mut().find(|a| a.name == "emails") {
+            emails.sub_attributes[2].canonical_values =
+                vec!["work".to_string(), "school".to_string()];
+            emails.sub_attributes.retain(|a| a.name != "display");
+        }
+
+        let diff = old.diff(&new);
+        assert!(!diff.id_changed);
+        assert!(!diff.name_changed);
+        assert!(diff.description_changed);
+        assert_eq!(diff.added, vec!["badgeNumber"]);
+        assert_eq!(diff.removed, vec!["nickName", "emails.display"]);
+        assert_eq!(
+            diff.changed,
+            vec![
+                AttributeDiff {
+                    path: "title".to_string(),
+                    changes: vec![
+                        AttributeChange::Required {
+                            from: false,
+                            to: true
+                        },
+                        AttributeChange::Mutability {
+                            from: Mutability::ReadWrite,
+                            to: Mutability::Immutable
+                        },
+                    ],
+                },
+                AttributeDiff {
+                    path: "emails.type".to_string(),
+                    changes: vec![AttributeChange::CanonicalValues {
+                        added: vec!["school".to_string()],
+                        removed: vec!["home".to_string(), "other".to_string()],
+                    }],
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,43 @@
+//! Serialising attribute values. In JSON, and other human readable formats,
+//! a value is written as its content alone; see the compact module for the
+//! others.
+
+use crate::compact;
+use crate::{datetime, ScimAttr, ScimValue};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Serialize, Serializer};
+
+impl Serialize for ScimAttr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return compact::serialize_attr(self, serializer);
+        }
+        match self {
+            ScimAttr::Null => serializer.serialize_unit(),
+            ScimAttr::Bool(b) => serializer.serialize_bool(*b),
+            ScimAttr::Decimal(d) => serializer.serialize_f64(*d),
+            ScimAttr::Integer(i) => serializer.serialize_i64(*i),
+            ScimAttr::String(s) => serializer.serialize_str(s),
+            ScimAttr::DateTime(dt) => datetime::lenient::serialize(dt, serializer),
+            // Binary attributes are base64 encoded, RFC7643 section 2.3.6.
+            ScimAttr::Binary(b) => serializer.serialize_str(&STANDARD.encode(b)),
+            ScimAttr::Reference(url) => url.serialize(serializer),
+        }
+    }
+}
+
+impl Serialize for ScimValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return compact::serialize_value(self, serializer);
+        }
+        match self {
+            ScimValue::Simple(attr) => attr.serialize(serializer),
+            ScimValue::Complex(attrs) => attrs.serialize(serializer),
+            ScimValue::MultiSimple(values) => values.serialize(serializer),
+            ScimValue::MultiComplex(values) => values.serialize(serializer),
+            ScimValue::Extension(attrs) => attrs.serialize(serializer),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use crate::ScimEntryGeneric;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// An entry shared between its clones, for fanning the same entry out to
+/// projection, filtering and serialisation without copying it. Cloning is
+/// O(1); the entry is copied on the first change through
+/// [`SharedEntry::make_mut`], and only if another clone still holds it.
+///
+/// It reads as the [`ScimEntryGeneric`] it holds, and is written and parsed
+/// as one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedEntry(Arc<ScimEntryGeneric>);
+
+impl SharedEntry {
+    pub fn new(entry: ScimEntryGeneric) -> Self {
+        SharedEntry(Arc::new(entry))
+    }
+
+    /// The entry to change, copied first if it is shared.
+    pub fn make_mut(&mut self) -> &mut ScimEntryGeneric {
+        Arc::make_mut(&mut self.0)
+    }
+
+    /// The entry, copied if it is shared.
+    pub fn into_inner(self) -> ScimEntryGeneric {
+        Arc::try_unwrap(self.0).unwrap_or_else(|shared| (*shared).clone())
+    }
+
+    /// Whether both hold the same entry, rather than equal ones.
+    pub fn ptr_eq(&self, other: &SharedEntry) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Deref for SharedEntry {
+    type Target = ScimEntryGeneric;
+
+    fn deref(&self) -> &ScimEntryGeneric {
+        &self.0
+    }
+}
+
+impl AsRef<ScimEntryGeneric> for SharedEntry {
+    fn as_ref(&self) -> &ScimEntryGeneric {
+        &self.0
+    }
+}
+
+impl From<ScimEntryGeneric> for SharedEntry {
+    fn from(entry: ScimEntryGeneric) -> Self {
+        SharedEntry::new(entry)
+    }
+}
+
+impl From<SharedEntry> for ScimEntryGeneric {
+    fn from(shared: SharedEntry) -> Self {
+        shared.into_inner()
+    }
+}
+
+impl Serialize for SharedEntry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SharedEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        ScimEntryGeneric::deserialize(deserializer).map(SharedEntry::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::RFC7643_USER;
+    use crate::{ScimAttr, ScimValue};
+
+    #[test]
+    fn shared_entry() {
+        let shared: SharedEntry =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let copy = shared.clone();
+        assert!(copy.ptr_eq(&shared));
+        assert_eq!(copy.attr("userName"), shared.attr("userName"));
+
+        // Changing a clone copies it, leaving the others as they were.
+        let mut changed = copy.clone();
+        changed.make_mut().insert_attr(
+            "nickName",
+            ScimValue::Simple(ScimAttr::String("Barb".to_string())),
+        );
+        assert!(!changed.ptr_eq(&shared));
+        assert!(copy.ptr_eq(&shared));
+        assert_eq!(
+            shared.attr("nickName"),
+            Some(&ScimValue::Simple(ScimAttr::String("Babs".to_string())))
+        );
+
+        // An entry that isn't shared isn't copied.
+        let before: *const ScimEntryGeneric = &*changed;
+        changed.make_mut().remove_attr("title");
+        assert_eq!(before, &*changed as *const _);
+
+        assert_eq!(
+            serde_json::to_value(&shared).expect("Failed to serialise"),
+            serde_json::to_value(copy.into_inner()).expect("Failed to serialise")
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! Estimates of the memory held by parsed values, for budgeting caches of
+//! entries. These count what the values own, by the capacity of their
+//! strings and vectors, and add a fixed overhead for each map entry. They
+//! are estimates: allocator overhead, and the spare room of map nodes, are
+//! not known.
+
+use crate::attr_map::AttrMap;
+use crate::{ScimAttr, ScimComplexAttr, ScimEntryGeneric, ScimId, ScimMeta, ScimValue};
+use std::mem::size_of;
+
+// The bookkeeping of a map for each entry: node links and spare slots of a
+// BTreeMap, or the hash index of an IndexMap.
+const MAP_ENTRY_OVERHEAD: usize = 2 * size_of::<usize>();
+
+fn map_heap<V>(attrs: &AttrMap<V>, heap: fn(&V) -> usize) -> usize {
+    attrs
+        .iter()
+        .map(|(name, value)| {
+            size_of::<(String, V)>() + MAP_ENTRY_OVERHEAD + name.capacity() + heap(value)
+        })
+        .sum()
+}
+
+fn vec_heap<T>(values: &Vec<T>, heap: fn(&T) -> usize) -> usize {
+    values.capacity() * size_of::<T>() + values.iter().map(heap).sum::<usize>()
+}
+
+impl ScimAttr {
+    /// The estimated memory this attribute uses, including itself.
+    pub fn estimated_size(&self) -> usize {
+        size_of::<Self>() + self.heap_size()
+    }
+
+    fn heap_size(&self) -> usize {
+        match self {
+            ScimAttr::String(s) => s.capacity(),
+            ScimAttr::Binary(b) => b.capacity(),
+            ScimAttr::Reference(url) => url.as_str().len(),
+            ScimAttr::Null
+            | ScimAttr::Bool(_)
+            | ScimAttr::Decimal(_)
+            | ScimAttr::Integer(_)
+            | ScimAttr::DateTime(_) => 0,
+        }
+    }
+}
+
+fn complex_heap(attrs: &ScimComplexAttr) -> usize {
+    map_heap(attrs, ScimAttr::heap_size)
+}
+
+impl ScimValue {
+    /// The estimated memory this value uses, including itself and every
+    /// value within it.
+    pub fn estimated_size(&self) -> usize {
+        size_of::<Self>() + self.heap_size()
+    }
+
+    fn heap_size(&self) -> usize {
+        match self {
+            ScimValue::Simple(attr) => attr.heap_size(),
+            ScimValue::Complex(attrs) => complex_heap(attrs),
+            ScimValue::MultiSimple(values) => vec_heap(values, ScimAttr::heap_size),
+            ScimValue::MultiComplex(values) => vec_heap(values, complex_heap),
+            ScimValue::Extension(attrs) => map_heap(attrs, ScimValue::heap_size),
+        }
+    }
+}
+
+fn meta_heap(meta: &ScimMeta) -> usize {
+    meta.location.as_str().len() + meta.version.capacity()
+}
+
+impl ScimEntryGeneric {
+    /// The estimated memory this entry uses, including itself, its common
+    /// attributes, and every attribute value. Parsing the same JSON gives
+    /// about the same estimate, so this can be used to keep a cache of
+    /// entries within a budget.
+    pub fn estimated_size(&self) -> usize {
+        let id = match &self.id {
+            ScimId::String(id) => id.capacity(),
+            ScimId::Uuid(_) => 0,
+        };
+        size_of::<Self>()
+            + vec_heap(&self.schemas, String::capacity)
+            + id
+            + self.external_id.as_ref().map(String::capacity).unwrap_or(0)
+            + self.meta.as_ref().map(meta_heap).unwrap_or(0)
+            + map_heap(&self.attrs, ScimValue::heap_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::RFC7643_USER;
+
+    #[test]
+    fn entry_estimated_size() {
+        let mut u: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let size = u.estimated_size();
+        // At least the text of every string is counted.
+        let strings: usize = u
+            .iter()
+            .flat_map(|(_, value)| {
+                value
+                    .values()
+                    .chain(value.complex_values().flat_map(|attrs| attrs.values()))
+            })
+            .filter_map(ScimAttr::as_str)
+            .map(str::len)
+            .sum();
+        assert!(size > size_of::<ScimEntryGeneric>() + strings);
+
+        let photos = u.remove_attr("photos").expect("No photos");
+        assert!(photos.estimated_size() > size_of::<ScimValue>());
+        assert_eq!(
+            u.estimated_size() + photos.estimated_size(),
+            size + size_of::<ScimValue>()
+                - size_of::<(String, ScimValue)>()
+                - MAP_ENTRY_OVERHEAD
+                - "photos".len()
+        );
+
+        let attr = ScimAttr::String("bjensen".to_string());
+        assert_eq!(attr.estimated_size(), size_of::<ScimAttr>() + 7);
+        assert_eq!(ScimAttr::Bool(true).estimated_size(), size_of::<ScimAttr>());
+    }
+}
@@ -1,5 +1,23 @@
-use crate::ScimEntry;
+use crate::attr_map::AttrMap;
+use crate::constants::{SCIM_SCHEMA_ENTERPRISE_USER, SCIM_SCHEMA_USER};
+use crate::convert;
+use crate::error::ScimError;
+pub use crate::language::{Locale, PreferredLanguage};
+use crate::messages::{ScimPatchOpKind, ScimPatchOperation, ScimPatchRequest};
+#[cfg(feature = "phone")]
+use crate::phone::PhoneNumber;
+use crate::redact::Secret;
+use crate::reference::percent_decode_bytes;
+use crate::resource::{ScimExtension, ScimResource};
+use crate::schema::{JsonType, SchemaViolation, ViolationKind};
+use crate::warning::{ScimWarning, WarningKind};
+#[cfg(feature = "x509")]
+use crate::x509::Certificate;
+use crate::{ScimAttr, ScimEntry, ScimEntryCreate, ScimEntryGeneric, ScimId, ScimValue};
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
 use base64urlsafedata::Base64UrlSafeData;
+use std::collections::BTreeSet;
 use std::fmt;
 use url::Url;
 use uuid::Uuid;
@@ -8,56 +26,25 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-struct Name {
+pub struct Name {
     // The full name including all middle names and titles
-    formatted: Option<String>,
-    family_name: Option<String>,
-    given_name: Option<String>,
-    middle_name: Option<String>,
-    honorific_prefix: Option<String>,
-    honorific_suffix: Option<String>,
-}
-
-/*
-// https://datatracker.ietf.org/doc/html/rfc7231#section-5.3.5
-//
-// https://www.iana.org/assignments/language-subtag-registry/language-subtag-registry
-// Same as locale?
-#[derive(Serialize, Deserialize, Debug, Clone)]
-enum Language {
-    en,
-}
-*/
-
-// https://datatracker.ietf.org/doc/html/rfc5646
-#[allow(non_camel_case_types)]
-#[derive(Serialize, Deserialize, Debug, Clone)]
-enum Locale {
-    en,
-    #[serde(rename = "en-AU")]
-    en_AU,
-    #[serde(rename = "en-US")]
-    en_US,
-    de,
-    #[serde(rename = "en-DE")]
-    de_DE,
-}
-
-impl fmt::Display for Locale {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Locale::en => write!(f, "en"),
-            Locale::en_AU => write!(f, "en-AU"),
-            Locale::en_US => write!(f, "en-US"),
-            Locale::de => write!(f, "de"),
-            Locale::de_DE => write!(f, "de-DE"),
-        }
-    }
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formatted: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub given_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub middle_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub honorific_prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub honorific_suffix: Option<String>,
 }
 
 #[allow(non_camel_case_types)]
 #[derive(Serialize, Deserialize, Debug, Clone)]
-enum Timezone {
+pub enum Timezone {
     #[serde(rename = "Australia/Brisbane")]
     australia_brisbane,
     #[serde(rename = "America/Los_Angeles")]
@@ -73,53 +60,348 @@ impl fmt::Display for Timezone {
     }
 }
 
+/// A user's password. The attribute is `returned: never`, so a [`User`]
+/// accepts it on input but leaves it out when serialised, see
+/// [`User::to_request`]. With the `zeroize` feature the secret is wiped from
+/// memory on drop.
+#[derive(Deserialize, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Password(String);
+
+impl Password {
+    pub fn new(password: &str) -> Self {
+        Password(password.to_string())
+    }
+
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Shown only within [`crate::redact::Unredacted`].
+impl fmt::Debug for Password {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Password").field(&Secret(&self.0)).finish()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Password {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.0);
+    }
+}
+
+// The canonicalValues of the type sub-attributes, from
+// https://datatracker.ietf.org/doc/html/rfc7643#section-8.7.1
+macro_rules! canonical_type {
+    ($(#[$doc:meta])* $name:ident { $($variant:ident => $value:literal),* $(,)? }) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant,)*
+            /// A value that isn't canonical, kept as it was given.
+            Custom(String),
+        }
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $($name::$variant => $value,)*
+                    $name::Custom(value) => value,
+                }
+            }
+        }
+
+        // Canonical values are compared case insensitively, as the type
+        // sub-attributes aren't caseExact.
+        impl From<&str> for $name {
+            fn from(value: &str) -> Self {
+                $(if value.eq_ignore_ascii_case($value) {
+                    return $name::$variant;
+                })*
+                $name::Custom(value.to_string())
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> Self {
+                match value {
+                    $name::Custom(value) => value,
+                    value => value.as_str().to_string(),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                String::deserialize(deserializer).map(|value| $name::from(value.as_str()))
+            }
+        }
+    };
+}
+
+pub(crate) use canonical_type;
+
+canonical_type! {
+    /// The `type` of an `emails` value.
+    EmailType { Work => "work", Home => "home", Other => "other" }
+}
+
+canonical_type! {
+    /// The `type` of a `phoneNumbers` value.
+    PhoneType {
+        Work => "work",
+        Home => "home",
+        Mobile => "mobile",
+        Fax => "fax",
+        Pager => "pager",
+        Other => "other",
+    }
+}
+
+canonical_type! {
+    /// The `type` of an `ims` value, which names the messaging service.
+    ImType {
+        Aim => "aim",
+        Gtalk => "gtalk",
+        Icq => "icq",
+        Xmpp => "xmpp",
+        Msn => "msn",
+        Skype => "skype",
+        Qq => "qq",
+        Yahoo => "yahoo",
+    }
+}
+
+canonical_type! {
+    /// The `type` of a `photos` value.
+    PhotoType { Photo => "photo", Thumbnail => "thumbnail" }
+}
+
+canonical_type! {
+    /// The `type` of an `addresses` value.
+    AddressType { Work => "work", Home => "home", Other => "other" }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct MultiValueAttr {
-    #[serde(rename = "type")]
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
     pub type_: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub primary: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub display: Option<String>,
-    #[serde(rename = "$ref")]
+    /// Also read from `ref` or `reference`, which some providers write, but
+    /// always written as `$ref`.
+    #[serde(rename = "$ref", default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "ref", alias = "reference")]
     pub ref_: Option<Url>,
     pub value: String,
 }
 
+// https://datatracker.ietf.org/doc/html/rfc2397#section-3
+
+/// The contents of a `data:` URI, which `photos` may hold in place of a
+/// link to the image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataUri {
+    /// The media type with any parameters, such as `image/png`.
+    pub media_type: String,
+    pub data: Vec<u8>,
+}
+
+impl DataUri {
+    pub fn parse(uri: &str) -> Result<Self, ScimError> {
+        let not_data = || {
+            invalid(
+                "",
+                ViolationKind::InvalidFormat {
+                    expected: "a data URI",
+                },
+            )
+        };
+        let rest = match uri.get(..5) {
+            Some(scheme) if scheme.eq_ignore_ascii_case("data:") => &uri[5..],
+            _ => return Err(not_data()),
+        };
+        let (header, data) = rest.split_once(',').ok_or_else(not_data)?;
+        // A media type and its parameters are ASCII, so can be split by byte.
+        if !header.is_ascii() {
+            return Err(not_data());
+        }
+
+        let (media_type, base64) = match header.len().checked_sub(7) {
+            Some(i) if header[i..].eq_ignore_ascii_case(";base64") => (&header[..i], true),
+            _ => (header, false),
+        };
+
+        let data = if base64 {
+            let data: String = percent_decode_bytes(data)?
+                .into_iter()
+                .map(char::from)
+                .filter(|c| !c.is_ascii_whitespace())
+                .collect();
+            STANDARD_NO_PAD
+                .decode(data.trim_end_matches('='))
+                .map_err(|err| SchemaViolation::unreadable("", err))?
+        } else {
+            percent_decode_bytes(data)?
+        };
+
+        let media_type = if media_type.is_empty() {
+            "text/plain;charset=US-ASCII".to_string()
+        } else {
+            media_type.to_string()
+        };
+
+        Ok(DataUri { media_type, data })
+    }
+
+    /// The URI with the data in base64.
+    pub fn to_url(&self) -> Result<Url, ScimError> {
+        Url::parse(&format!(
+            "data:{};base64,{}",
+            self.media_type,
+            STANDARD.encode(&self.data)
+        ))
+        .map_err(|err| SchemaViolation::unreadable("", err).into())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-struct Photo {
-    #[serde(rename = "type")]
-    type_: Option<String>,
-    primary: Option<bool>,
-    display: Option<String>,
-    #[serde(rename = "$ref")]
-    ref_: Option<Url>,
-    value: Url,
+pub struct Photo {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+    #[serde(rename = "$ref", default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "ref", alias = "reference")]
+    pub ref_: Option<Url>,
+    pub value: Url,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct Binary {
-    #[serde(rename = "type")]
-    type_: Option<String>,
-    primary: Option<bool>,
-    display: Option<String>,
-    #[serde(rename = "$ref")]
-    ref_: Option<Url>,
-    value: Base64UrlSafeData,
+impl Photo {
+    pub fn new(value: Url) -> Self {
+        Photo {
+            type_: None,
+            primary: None,
+            display: None,
+            ref_: None,
+            value,
+        }
+    }
+
+    /// A photo held inline as a `data:` URI.
+    pub fn from_data(media_type: &str, data: &[u8]) -> Result<Self, ScimError> {
+        DataUri {
+            media_type: media_type.to_string(),
+            data: data.to_vec(),
+        }
+        .to_url()
+        .map(Photo::new)
+    }
+
+    pub fn is_data(&self) -> bool {
+        self.value.scheme() == "data"
+    }
+
+    /// Decode the photo if it is held inline, or `None` if the value is a
+    /// link to the image.
+    pub fn data(&self) -> Result<Option<DataUri>, ScimError> {
+        if self.is_data() {
+            DataUri::parse(self.value.as_str()).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn photo_type(&self) -> Option<PhotoType> {
+        self.type_.as_deref().map(PhotoType::from)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Binary {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+    #[serde(rename = "$ref", default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "ref", alias = "reference")]
+    pub ref_: Option<Url>,
+    pub value: Base64UrlSafeData,
+}
+
+impl Binary {
+    pub fn new(value: Base64UrlSafeData) -> Self {
+        Binary {
+            type_: None,
+            primary: None,
+            display: None,
+            ref_: None,
+            value,
+        }
+    }
+
+    /// Parse the value as a DER encoded X.509 certificate.
+    #[cfg(feature = "x509")]
+    pub fn certificate(&self) -> Result<Certificate, ScimError> {
+        Certificate::parse(self.value.as_ref())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
-struct Address {
-    #[serde(rename = "type")]
-    type_: Option<String>,
-    primary: Option<bool>,
-    formatted: Option<String>,
-    street_address: Option<String>,
-    locality: Option<String>,
-    region: Option<String>,
-    postal_code: Option<String>,
-    country: Option<String>,
+pub struct Address {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formatted: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub street_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locality: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub postal_code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+}
+
+impl Address {
+    /// An empty address of the given type, such as `work` or `home`.
+    pub fn new(type_: &str) -> Self {
+        Address {
+            type_: Some(type_.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn address_type(&self) -> Option<AddressType> {
+        self.type_.as_deref().map(AddressType::from)
+    }
 }
 
 /*
@@ -132,55 +414,1053 @@ enum Membership {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-struct Group {
-    #[serde(rename = "type")]
-    type_: Option<String>,
+pub struct Group {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
     #[serde(rename = "$ref")]
-    ref_: Url,
-    value: Uuid,
-    display: String,
+    #[serde(alias = "ref", alias = "reference")]
+    pub ref_: Url,
+    pub value: ScimId,
+    pub display: String,
+}
+
+impl Group {
+    pub fn new(value: impl Into<ScimId>, ref_: Url, display: &str) -> Self {
+        Group {
+            type_: None,
+            ref_,
+            value: value.into(),
+            display: display.to_string(),
+        }
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7643#section-4.3
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Manager {
+    /// The id of the manager's User resource.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// RFC7643 shows this as a relative URI, so it isn't parsed as a [`Url`].
+    #[serde(rename = "$ref", default, skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "ref", alias = "reference")]
+    pub ref_: Option<String>,
+    /// Set by the service provider, and ignored when written.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+}
+
+impl Manager {
+    /// The id of the manager's User resource, from `value`, or else from the
+    /// last segment of `$ref`.
+    pub fn id(&self) -> Option<ScimId> {
+        if let Some(value) = self.value.as_deref().filter(|v| !v.is_empty()) {
+            return Some(ScimId::from(value));
+        }
+        let ref_ = self.ref_.as_deref()?;
+        let path = ref_.split(['?', '#']).next().unwrap_or_default();
+        let segment = path.trim_end_matches('/').rsplit('/').next()?;
+        percent_decode_bytes(segment)
+            .ok()
+            .and_then(|id| String::from_utf8(id).ok())
+            .filter(|id| !id.is_empty())
+            .map(ScimId::from)
+    }
+}
+
+/// Why a [`ManagerChain`] ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManagerChainEnd {
+    /// The last user has no manager.
+    Top,
+    /// The last user's manager is already in the chain, or is the user the
+    /// chain started from.
+    Cycle(ScimId),
+    /// The chain reached the depth limit, and the last user has a manager.
+    DepthLimit,
+    /// The resolver didn't find the last user's manager.
+    Unresolved(ScimId),
+}
+
+/// The managers above a user, nearest first. See [`User::manager_chain`].
+#[derive(Debug, Clone)]
+pub struct ManagerChain {
+    pub managers: Vec<User>,
+    pub end: ManagerChainEnd,
+}
+
+/// The `urn:ietf:params:scim:schemas:extension:enterprise:2.0:User`
+/// extension, held by a [`User`] under that key.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct EnterpriseUser {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub employee_number: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_center: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub division: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub department: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manager: Option<Manager>,
+}
+
+impl ScimExtension for EnterpriseUser {
+    const SCHEMA_URN: &'static str = SCIM_SCHEMA_ENTERPRISE_USER;
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-struct User {
+pub struct User {
     #[serde(flatten)]
-    entry: ScimEntry,
+    pub entry: ScimEntry,
     // required, must be unique, string.
-    user_name: String,
+    pub user_name: String,
     // Components of the users name.
-    name: Option<Name>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<Name>,
     // required, must be unique, string.
-    display_name: Option<String>,
-    nick_name: Option<String>,
-    profile_url: Option<Url>,
-    title: Option<String>,
-    user_type: Option<String>,
-    preferred_language: Option<Locale>,
-    locale: Option<Locale>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nick_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile_url: Option<Url>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preferred_language: Option<PreferredLanguage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<Locale>,
     // https://datatracker.ietf.org/doc/html/rfc6557
     // How can we validate this? https://docs.rs/iana-time-zone/0.1.51/iana_time_zone/fn.get_timezone.html
-    timezone: Option<Timezone>,
-    active: bool,
-    password: Option<String>,
-    emails: Vec<MultiValueAttr>,
-    phone_numbers: Vec<MultiValueAttr>,
-    ims: Vec<MultiValueAttr>,
-    photos: Vec<Photo>,
-    addresses: Vec<Address>,
-    groups: Vec<Group>,
-    #[serde(default)]
-    entitlements: Vec<MultiValueAttr>,
-    #[serde(default)]
-    roles: Vec<MultiValueAttr>,
-    #[serde(default)]
-    x509certificates: Vec<Binary>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<Timezone>,
+    pub active: bool,
+    #[serde(default, skip_serializing)]
+    pub password: Option<Password>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub emails: Vec<MultiValueAttr>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub phone_numbers: Vec<MultiValueAttr>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ims: Vec<MultiValueAttr>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub photos: Vec<Photo>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<Address>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<Group>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entitlements: Vec<MultiValueAttr>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<MultiValueAttr>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub x509_certificates: Vec<Binary>,
+    #[serde(
+        rename = "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub enterprise: Option<EnterpriseUser>,
+    /// Attributes this type doesn't define, such as other extensions, kept
+    /// so that they survive a round trip.
+    #[serde(flatten)]
+    pub extra: AttrMap<ScimValue>,
+}
+
+/// The user in the compact form of [`ScimEntryGeneric`]'s `Display`. The
+/// password is never shown.
+impl fmt::Display for User {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::display::write_resource(f, self)
+    }
+}
+
+/// What to do with client supplied values of the readOnly `groups` and
+/// `meta` attributes, which a service provider must not take from a create
+/// or replace request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadOnlyMode {
+    /// Keep them, as when reading a user from a service provider.
+    #[default]
+    Accept,
+    /// Remove them, as RFC7644 section 3.3 allows.
+    Strip,
+    /// Fail with [`ScimError::Mutability`] if either is present.
+    Reject,
+}
+
+/// Options for [`User::parse`]. The defaults accept anything that
+/// deserialises.
+#[derive(Debug, Clone, Default)]
+pub struct UserParseOptions {
+    validate_emails: bool,
+    read_only: ReadOnlyMode,
+    default_active: Option<bool>,
+    drop_invalid_locale: bool,
+    demote_extra_primaries: bool,
+    #[cfg(feature = "phone")]
+    validate_phone_numbers: bool,
+}
+
+impl UserParseOptions {
+    /// Reject users with an `emails` value that isn't an RFC5322 addr-spec.
+    pub fn validate_emails(mut self, validate: bool) -> Self {
+        self.validate_emails = validate;
+        self
+    }
+
+    /// The value to use when `active` is absent. Without one, a user
+    /// without `active` fails with a [`ViolationKind::MissingRequired`].
+    pub fn default_active(mut self, active: bool) -> Self {
+        self.default_active = Some(active);
+        self
+    }
+
+    /// Drop a `locale` that isn't a language tag, rather than failing.
+    pub fn drop_invalid_locale(mut self, drop: bool) -> Self {
+        self.drop_invalid_locale = drop;
+        self
+    }
+
+    /// Where more than one value of a multi-valued attribute is primary,
+    /// keep the first, and set `primary` to false on the rest.
+    pub fn demote_extra_primaries(mut self, demote: bool) -> Self {
+        self.demote_extra_primaries = demote;
+        self
+    }
+
+    /// How to treat `groups` and `meta`. Use [`ReadOnlyMode::Strip`] or
+    /// [`ReadOnlyMode::Reject`] for the body of a create or replace request.
+    pub fn read_only(mut self, mode: ReadOnlyMode) -> Self {
+        self.read_only = mode;
+        self
+    }
+
+    /// Reject users with a `phoneNumbers` value that isn't a global number,
+    /// see [`crate::phone::PhoneNumber`].
+    #[cfg(feature = "phone")]
+    pub fn validate_phone_numbers(mut self, validate: bool) -> Self {
+        self.validate_phone_numbers = validate;
+        self
+    }
+}
+
+fn drop_invalid_locale(
+    attrs: &mut serde_json::Map<String, serde_json::Value>,
+    warnings: &mut Vec<ScimWarning>,
+) {
+    let value = match attrs.get("locale") {
+        Some(serde_json::Value::String(tag)) if Locale::parse(tag).is_err() => tag.clone(),
+        Some(serde_json::Value::String(_) | serde_json::Value::Null) | None => return,
+        Some(value) => value.to_string(),
+    };
+    attrs.remove("locale");
+    warnings.push(ScimWarning::new("locale", WarningKind::Dropped { value }));
+}
+
+fn demote_extra_primaries(
+    attrs: &mut serde_json::Map<String, serde_json::Value>,
+    warnings: &mut Vec<ScimWarning>,
+) {
+    let is_primary = |value: &serde_json::Value| value.get("primary") == Some(&true.into());
+    for (name, value) in attrs.iter_mut() {
+        let serde_json::Value::Array(values) = value else {
+            continue;
+        };
+        let demoted = values
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, v)| is_primary(v))
+            .skip(1);
+        for (idx, value) in demoted {
+            if let serde_json::Value::Object(sub_attrs) = value {
+                sub_attrs.insert("primary".to_string(), false.into());
+            }
+            warnings.push(ScimWarning::new(
+                format!("{}[{}].primary", name, idx),
+                WarningKind::PrimaryDemoted,
+            ));
+        }
+    }
+}
+
+// atext from https://datatracker.ietf.org/doc/html/rfc5322#section-3.2.3
+fn is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c)
+}
+
+fn is_dot_atom(s: &str) -> bool {
+    !s.is_empty()
+        && s.split('.')
+            .all(|atom| !atom.is_empty() && atom.chars().all(is_atext))
+}
+
+/// A basic check of the addr-spec syntax of RFC5322 section 3.4.1. Comments
+/// and folding whitespace aren't accepted, and the domain must be a host
+/// name or an address literal.
+pub fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return false;
+    };
+
+    let local_ok = if local.len() >= 2 && local.starts_with('"') && local.ends_with('"') {
+        let quoted = &local[1..local.len() - 1];
+        let mut escaped = false;
+        quoted.chars().all(|c| {
+            let ok = escaped || (c != '"' && c.is_ascii() && !c.is_ascii_control());
+            escaped = !escaped && c == '\\';
+            ok
+        }) && !escaped
+    } else {
+        is_dot_atom(local)
+    };
+
+    let domain_ok = if domain.starts_with('[') && domain.ends_with(']') {
+        domain.len() > 2
+            && domain[1..domain.len() - 1]
+                .chars()
+                .all(|c| c.is_ascii_graphic() && !"[]\\".contains(c))
+    } else {
+        domain.len() <= 253
+            && domain.split('.').all(|label| {
+                (1..=63).contains(&label.len())
+                    && !label.starts_with('-')
+                    && !label.ends_with('-')
+                    && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            })
+    };
+
+    local_ok && local.len() <= 64 && domain_ok
+}
+
+impl User {
+    /// Parse a user from JSON, applying the checks enabled in `options`.
+    pub fn parse(json: &str, options: &UserParseOptions) -> Result<Self, ScimError> {
+        User::parse_with_warnings(json, options).map(|(user, _)| user)
+    }
+
+    /// As [`User::parse`], also returning a warning for each value that
+    /// `options` defaulted, dropped or changed to accept the user.
+    pub fn parse_with_warnings(
+        json: &str,
+        options: &UserParseOptions,
+    ) -> Result<(Self, Vec<ScimWarning>), ScimError> {
+        let value = convert::parse(json)?;
+        let mut warnings = Vec::new();
+        let user = User::from_value(value, options, &mut warnings)?;
+        Ok((user, warnings))
+    }
+
+    /// Convert a generic entry, such as an inbound request body, to a user,
+    /// applying the checks enabled in `options`.
+    pub fn from_entry(
+        entry: &ScimEntryGeneric,
+        options: &UserParseOptions,
+    ) -> Result<Self, ScimError> {
+        let value = convert::to_value(entry)?;
+        User::from_value(value, options, &mut Vec::new())
+    }
+
+    pub(crate) fn from_value(
+        mut value: serde_json::Value,
+        options: &UserParseOptions,
+        warnings: &mut Vec<ScimWarning>,
+    ) -> Result<Self, ScimError> {
+        if let serde_json::Value::Object(attrs) = &mut value {
+            if !attrs.contains_key("active") {
+                let active = options
+                    .default_active
+                    .ok_or_else(|| invalid("active", ViolationKind::MissingRequired))?;
+                attrs.insert("active".to_string(), serde_json::Value::Bool(active));
+                warnings.push(ScimWarning::new(
+                    "active",
+                    WarningKind::Defaulted {
+                        value: active.to_string(),
+                    },
+                ));
+            }
+            if options.drop_invalid_locale {
+                drop_invalid_locale(attrs, warnings);
+            }
+            if options.demote_extra_primaries {
+                demote_extra_primaries(attrs, warnings);
+            }
+        }
+        let user = convert::from_value(value)?;
+        User::checked(user, options)
+    }
+
+    fn checked(mut user: User, options: &UserParseOptions) -> Result<Self, ScimError> {
+        match options.read_only {
+            ReadOnlyMode::Accept => {}
+            ReadOnlyMode::Strip => {
+                user.groups.clear();
+                user.entry.meta = None;
+            }
+            ReadOnlyMode::Reject => {
+                if !user.groups.is_empty() {
+                    return Err(ScimError::Mutability("groups".to_string()));
+                }
+                if user.entry.meta.is_some() {
+                    return Err(ScimError::Mutability("meta".to_string()));
+                }
+            }
+        }
+        if options.validate_emails {
+            user.validate_emails()?;
+        }
+        #[cfg(feature = "phone")]
+        if options.validate_phone_numbers {
+            user.validate_phone_numbers()?;
+        }
+        Ok(user)
+    }
+
+    /// Check that every `emails` value is an addr-spec, see [`is_valid_email`].
+    pub fn validate_emails(&self) -> Result<(), ScimError> {
+        match self
+            .emails
+            .iter()
+            .position(|email| !is_valid_email(&email.value))
+        {
+            Some(idx) => Err(invalid(
+                format!("emails[{}].value", idx),
+                ViolationKind::InvalidFormat {
+                    expected: "an email address",
+                },
+            )),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "phone")]
+    pub fn validate_phone_numbers(&self) -> Result<(), ScimError> {
+        self.phone_numbers
+            .iter()
+            .enumerate()
+            .try_for_each(|(idx, phone)| {
+                PhoneNumber::parse(&phone.value)
+                    .map(|_| ())
+                    .map_err(|err| invalid_value("phoneNumbers", idx, "a phone number", err))
+            })
+    }
+
+    /// Rewrite each `phoneNumbers` value as a `tel:` URI in E.164 form. No
+    /// value is changed unless all of them are valid.
+    #[cfg(feature = "phone")]
+    pub fn normalize_phone_numbers(&mut self) -> Result<(), ScimError> {
+        let normalized = self
+            .phone_numbers
+            .iter()
+            .enumerate()
+            .map(|(idx, phone)| {
+                PhoneNumber::parse(&phone.value)
+                    .map(|p| p.to_tel_uri())
+                    .map_err(|err| invalid_value("phoneNumbers", idx, "a phone number", err))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for (phone, value) in self.phone_numbers.iter_mut().zip(normalized) {
+            phone.value = value;
+        }
+        Ok(())
+    }
+
+    /// Parse every `x509Certificates` value, see [`Binary::certificate`].
+    #[cfg(feature = "x509")]
+    pub fn certificates(&self) -> Result<Vec<Certificate>, ScimError> {
+        self.x509_certificates
+            .iter()
+            .enumerate()
+            .map(|(idx, cert)| {
+                cert.certificate().map_err(|err| {
+                    invalid_value("x509Certificates", idx, "an X.509 certificate", err)
+                })
+            })
+            .collect()
+    }
+
+    /// The enterprise manager, if the user has one.
+    pub fn manager(&self) -> Option<&Manager> {
+        self.enterprise.as_ref().and_then(|e| e.manager.as_ref())
+    }
+
+    /// Follow the enterprise managers above this user, looking each one up
+    /// with `resolve`, for at most `max_depth` managers. A manager that is
+    /// already in the chain ends it, rather than looping.
+    pub fn manager_chain<F>(
+        &self,
+        max_depth: usize,
+        mut resolve: F,
+    ) -> Result<ManagerChain, ScimError>
+    where
+        F: FnMut(&ScimId) -> Result<Option<User>, ScimError>,
+    {
+        let mut seen = BTreeSet::from([self.entry.id.clone()]);
+        let mut managers: Vec<User> = Vec::new();
+
+        let end = loop {
+            let current = managers.last().unwrap_or(self);
+            let Some(id) = current.manager().and_then(Manager::id) else {
+                break ManagerChainEnd::Top;
+            };
+            if seen.contains(&id) {
+                break ManagerChainEnd::Cycle(id);
+            }
+            if managers.len() >= max_depth {
+                break ManagerChainEnd::DepthLimit;
+            }
+            let Some(manager) = resolve(&id)? else {
+                break ManagerChainEnd::Unresolved(id);
+            };
+            seen.insert(id);
+            seen.insert(manager.entry.id.clone());
+            managers.push(manager);
+        };
+
+        Ok(ManagerChain { managers, end })
+    }
+
+    /// The email marked primary, or the first email if none is.
+    pub fn primary_email(&self) -> Option<&str> {
+        self.emails
+            .iter()
+            .find(|email| email.primary == Some(true))
+            .or_else(|| self.emails.first())
+            .map(|email| email.value.as_str())
+    }
+
+    /// Move the primary value of each multi-valued attribute to the front,
+    /// keeping the others in order, as
+    /// [`ScimEntryGeneric::primary_first`] does.
+    pub fn primary_first(&mut self) {
+        let not_primary = |primary: Option<bool>| primary != Some(true);
+        for values in [
+            &mut self.emails,
+            &mut self.phone_numbers,
+            &mut self.ims,
+            &mut self.entitlements,
+            &mut self.roles,
+        ] {
+            values.sort_by_key(|v| not_primary(v.primary));
+        }
+        self.photos.sort_by_key(|v| not_primary(v.primary));
+        self.addresses.sort_by_key(|v| not_primary(v.primary));
+        self.x509_certificates
+            .sort_by_key(|v| not_primary(v.primary));
+    }
+
+    /// Serialise the user for a create or replace request. Unlike the
+    /// [`Serialize`] impl this includes the password, so the result must not
+    /// be logged.
+    pub fn to_request(&self) -> Result<serde_json::Value, ScimError> {
+        let mut value = convert::to_value(self)?;
+        if let (Some(password), serde_json::Value::Object(attrs)) = (&self.password, &mut value) {
+            attrs.insert(
+                "password".to_string(),
+                serde_json::Value::String(password.expose_secret().to_string()),
+            );
+        }
+        Ok(value)
+    }
+
+    /// The body of a request creating this user. As with
+    /// [`User::to_request`], this includes the password.
+    pub fn to_create(&self) -> Result<ScimEntryCreate, ScimError> {
+        let mut create = ScimEntryCreate::from_resource(self.clone())?;
+        if let Some(password) = &self.password {
+            create.attrs.insert(
+                "password".to_string(),
+                ScimValue::Simple(ScimAttr::String(password.expose_secret().to_string())),
+            );
+        }
+        Ok(create)
+    }
+
+    /// Start building a user. See [`UserBuilder`].
+    pub fn builder(user_name: &str) -> UserBuilder {
+        UserBuilder {
+            user: User {
+                entry: ScimEntry {
+                    schemas: vec![SCIM_SCHEMA_USER.to_string()],
+                    id: Uuid::nil().into(),
+                    external_id: None,
+                    meta: None,
+                },
+                user_name: user_name.to_string(),
+                name: None,
+                display_name: None,
+                nick_name: None,
+                profile_url: None,
+                title: None,
+                user_type: None,
+                preferred_language: None,
+                locale: None,
+                timezone: None,
+                active: true,
+                password: None,
+                emails: Vec::new(),
+                phone_numbers: Vec::new(),
+                ims: Vec::new(),
+                photos: Vec::new(),
+                addresses: Vec::new(),
+                groups: Vec::new(),
+                entitlements: Vec::new(),
+                roles: Vec::new(),
+                x509_certificates: Vec::new(),
+                enterprise: None,
+                extra: AttrMap::new(),
+            },
+        }
+    }
+}
+
+impl MultiValueAttr {
+    /// The `type` as one of the canonical type enums, such as [`EmailType`]
+    /// for an `emails` value.
+    pub fn type_as<T>(&self) -> Option<T>
+    where
+        T: for<'a> From<&'a str>,
+    {
+        self.type_.as_deref().map(T::from)
+    }
+}
+
+fn invalid(path: impl Into<String>, kind: ViolationKind) -> ScimError {
+    SchemaViolation::new(path, kind).into()
+}
+
+// The `value` at `idx` of multi-valued attribute `attr` isn't `expected`,
+// as `err` says.
+#[cfg(any(feature = "phone", feature = "x509"))]
+fn invalid_value(attr: &str, idx: usize, expected: &'static str, err: ScimError) -> ScimError {
+    let path = format!("{}[{}].value", attr, idx);
+    let kind = ViolationKind::InvalidFormat { expected };
+    SchemaViolation::new(path, kind).with_source(err).into()
+}
+
+fn multi_value(value: &str) -> MultiValueAttr {
+    MultiValueAttr {
+        type_: None,
+        primary: None,
+        display: None,
+        ref_: None,
+        value: value.to_string(),
+    }
+}
+
+impl TryFrom<ScimEntryGeneric> for User {
+    type Error = ScimError;
+
+    fn try_from(entry: ScimEntryGeneric) -> Result<Self, Self::Error> {
+        User::from_entry(&entry, &UserParseOptions::default())
+    }
+}
+
+/// The password is left out, as it is when a user is serialised, so that an
+/// entry made from a user can be returned in a response. To send it, use
+/// [`User::to_create`] or [`User::to_request`].
+impl TryFrom<User> for ScimEntryGeneric {
+    type Error = ScimError;
+
+    fn try_from(mut user: User) -> Result<Self, Self::Error> {
+        let enterprise = user.enterprise.take();
+        let mut entry: ScimEntryGeneric = convert::convert(&user)?;
+        if let Some(enterprise) = &enterprise {
+            entry.add_extension(enterprise)?;
+        }
+        Ok(entry)
+    }
+}
+
+impl ScimResource for User {
+    const SCHEMA_URN: &'static str = SCIM_SCHEMA_USER;
+
+    fn resource_type() -> &'static str {
+        "User"
+    }
+
+    /// The password is left out; use [`User::to_create`] to send it.
+    fn into_entry(self) -> Result<ScimEntryGeneric, ScimError> {
+        ScimEntryGeneric::try_from(self)
+    }
+}
+
+/// A [`User`] with every attribute optional, for create requests that leave
+/// out server assigned attributes and for the fragments a PATCH carries.
+/// Attributes that are `None` are absent, and are left unchanged by
+/// [`PartialUser::apply_to`] and [`PartialUser::to_patch`]. There is no
+/// `groups` or `meta`, as clients can't write them.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialUser {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<ScimId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<Name>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nick_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile_url: Option<Url>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preferred_language: Option<PreferredLanguage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<Locale>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<Timezone>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active: Option<bool>,
+    #[serde(default, skip_serializing)]
+    pub password: Option<Password>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emails: Option<Vec<MultiValueAttr>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phone_numbers: Option<Vec<MultiValueAttr>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ims: Option<Vec<MultiValueAttr>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub photos: Option<Vec<Photo>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub addresses: Option<Vec<Address>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entitlements: Option<Vec<MultiValueAttr>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roles: Option<Vec<MultiValueAttr>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x509_certificates: Option<Vec<Binary>>,
+    #[serde(
+        rename = "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub enterprise: Option<EnterpriseUser>,
+    #[serde(flatten)]
+    pub extra: AttrMap<ScimValue>,
+}
+
+impl PartialUser {
+    pub fn parse(json: &str) -> Result<Self, ScimError> {
+        convert::parse(json)
+    }
+
+    /// One replace operation for each attribute that is present. Enterprise
+    /// attributes are replaced one at a time, by their URN-qualified path,
+    /// so that the rest of the extension is kept. The password is included,
+    /// so the result must not be logged.
+    pub fn to_patch(&self) -> Result<ScimPatchRequest, ScimError> {
+        let value = convert::to_value(self)?;
+        let serde_json::Value::Object(mut attrs) = value else {
+            let found = JsonType::of(&value);
+            return Err(invalid("", ViolationKind::ExpectedComplex { found }));
+        };
+        // The id is assigned by the service provider, and schemas describes
+        // the request rather than an attribute to change.
+        attrs.remove("id");
+        attrs.remove("schemas");
+        if let Some(password) = &self.password {
+            attrs.insert(
+                "password".to_string(),
+                serde_json::Value::String(password.expose_secret().to_string()),
+            );
+        }
+
+        let mut operations = Vec::with_capacity(attrs.len());
+        for (name, value) in attrs {
+            match value {
+                serde_json::Value::Object(sub_attrs)
+                    if name.eq_ignore_ascii_case(SCIM_SCHEMA_ENTERPRISE_USER) =>
+                {
+                    operations.extend(sub_attrs.into_iter().map(|(sub, value)| {
+                        ScimPatchOperation {
+                            op: ScimPatchOpKind::Replace,
+                            path: Some(format!("{}:{}", name, sub)),
+                            value: Some(value),
+                        }
+                    }))
+                }
+                value => operations.push(ScimPatchOperation {
+                    op: ScimPatchOpKind::Replace,
+                    path: Some(name),
+                    value: Some(value),
+                }),
+            }
+        }
+        Ok(ScimPatchRequest::new(operations))
+    }
+
+    /// Replace the attributes of `user` that are present, as
+    /// [`PartialUser::to_patch`] would on a service provider. The id and
+    /// schemas are not changed.
+    pub fn apply_to(self, user: &mut User) {
+        let PartialUser {
+            schemas: _,
+            id: _,
+            external_id,
+            user_name,
+            name,
+            display_name,
+            nick_name,
+            profile_url,
+            title,
+            user_type,
+            preferred_language,
+            locale,
+            timezone,
+            active,
+            password,
+            emails,
+            phone_numbers,
+            ims,
+            photos,
+            addresses,
+            entitlements,
+            roles,
+            x509_certificates,
+            enterprise,
+            extra,
+        } = self;
+
+        fn set<T>(field: &mut Option<T>, value: Option<T>) {
+            if value.is_some() {
+                *field = value;
+            }
+        }
+        fn set_vec<T>(field: &mut Vec<T>, value: Option<Vec<T>>) {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+
+        set(&mut user.entry.external_id, external_id);
+        if let Some(user_name) = user_name {
+            user.user_name = user_name;
+        }
+        set(&mut user.name, name);
+        set(&mut user.display_name, display_name);
+        set(&mut user.nick_name, nick_name);
+        set(&mut user.profile_url, profile_url);
+        set(&mut user.title, title);
+        set(&mut user.user_type, user_type);
+        set(&mut user.preferred_language, preferred_language);
+        set(&mut user.locale, locale);
+        set(&mut user.timezone, timezone);
+        if let Some(active) = active {
+            user.active = active;
+        }
+        set(&mut user.password, password);
+        set_vec(&mut user.emails, emails);
+        set_vec(&mut user.phone_numbers, phone_numbers);
+        set_vec(&mut user.ims, ims);
+        set_vec(&mut user.photos, photos);
+        set_vec(&mut user.addresses, addresses);
+        set_vec(&mut user.entitlements, entitlements);
+        set_vec(&mut user.roles, roles);
+        set_vec(&mut user.x509_certificates, x509_certificates);
+
+        if let Some(partial) = enterprise {
+            let current = user.enterprise.get_or_insert_with(Default::default);
+            set(&mut current.employee_number, partial.employee_number);
+            set(&mut current.cost_center, partial.cost_center);
+            set(&mut current.organization, partial.organization);
+            set(&mut current.division, partial.division);
+            set(&mut current.department, partial.department);
+            set(&mut current.manager, partial.manager);
+            if !user
+                .entry
+                .schemas
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(SCIM_SCHEMA_ENTERPRISE_USER))
+            {
+                user.entry
+                    .schemas
+                    .push(SCIM_SCHEMA_ENTERPRISE_USER.to_string());
+            }
+        }
+
+        user.extra.extend(extra);
+    }
+}
+
+/// Builds a [`User`]. Users start active, with a nil id that the service
+/// provider replaces on creation, and with only the core User schema.
+#[derive(Debug, Clone)]
+pub struct UserBuilder {
+    user: User,
+}
+
+impl UserBuilder {
+    pub fn id(mut self, id: impl Into<ScimId>) -> Self {
+        self.user.entry.id = id.into();
+        self
+    }
+
+    pub fn external_id(mut self, external_id: &str) -> Self {
+        self.user.entry.external_id = Some(external_id.to_string());
+        self
+    }
+
+    pub fn name(mut self, name: Name) -> Self {
+        self.user.name = Some(name);
+        self
+    }
+
+    pub fn display_name(mut self, display_name: &str) -> Self {
+        self.user.display_name = Some(display_name.to_string());
+        self
+    }
+
+    pub fn nick_name(mut self, nick_name: &str) -> Self {
+        self.user.nick_name = Some(nick_name.to_string());
+        self
+    }
+
+    pub fn profile_url(mut self, profile_url: Url) -> Self {
+        self.user.profile_url = Some(profile_url);
+        self
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.user.title = Some(title.to_string());
+        self
+    }
+
+    pub fn user_type(mut self, user_type: &str) -> Self {
+        self.user.user_type = Some(user_type.to_string());
+        self
+    }
+
+    pub fn preferred_language(mut self, preferred_language: PreferredLanguage) -> Self {
+        self.user.preferred_language = Some(preferred_language);
+        self
+    }
+
+    pub fn locale(mut self, locale: Locale) -> Self {
+        self.user.locale = Some(locale);
+        self
+    }
+
+    pub fn timezone(mut self, timezone: Timezone) -> Self {
+        self.user.timezone = Some(timezone);
+        self
+    }
+
+    pub fn active(mut self, active: bool) -> Self {
+        self.user.active = active;
+        self
+    }
+
+    pub fn password(mut self, password: &str) -> Self {
+        self.user.password = Some(Password::new(password));
+        self
+    }
+
+    /// Add an email address. The first address added is marked as primary.
+    pub fn email(self, email: &str) -> Self {
+        self.email_attr(multi_value(email))
+    }
+
+    pub fn email_attr(mut self, mut email: MultiValueAttr) -> Self {
+        if self.user.emails.is_empty() && email.primary.is_none() {
+            email.primary = Some(true);
+        }
+        self.user.emails.push(email);
+        self
+    }
+
+    pub fn phone_number(mut self, phone_number: &str) -> Self {
+        self.user.phone_numbers.push(multi_value(phone_number));
+        self
+    }
+
+    pub fn im(mut self, im: &str) -> Self {
+        self.user.ims.push(multi_value(im));
+        self
+    }
+
+    pub fn photo(mut self, photo: Photo) -> Self {
+        self.user.photos.push(photo);
+        self
+    }
+
+    pub fn address(mut self, address: Address) -> Self {
+        self.user.addresses.push(address);
+        self
+    }
+
+    pub fn entitlement(mut self, entitlement: &str) -> Self {
+        self.user.entitlements.push(multi_value(entitlement));
+        self
+    }
+
+    pub fn role(mut self, role: &str) -> Self {
+        self.user.roles.push(multi_value(role));
+        self
+    }
+
+    pub fn enterprise(mut self, enterprise: EnterpriseUser) -> Self {
+        self.user.enterprise = Some(enterprise);
+        self
+    }
+
+    /// Check the required attributes and produce the user.
+    pub fn build(self) -> Result<User, ScimError> {
+        let mut user = self.user;
+
+        if user.user_name.trim().is_empty() {
+            return Err(invalid("userName", ViolationKind::MissingRequired));
+        }
+
+        if user
+            .emails
+            .iter()
+            .filter(|e| e.primary == Some(true))
+            .count()
+            > 1
+        {
+            return Err(invalid("emails", ViolationKind::MultiplePrimary));
+        }
+
+        if user.enterprise.is_some()
+            && !user
+                .entry
+                .schemas
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(SCIM_SCHEMA_ENTERPRISE_USER))
+        {
+            user.entry
+                .schemas
+                .push(SCIM_SCHEMA_ENTERPRISE_USER.to_string());
+        }
+
+        Ok(user)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::constants::RFC7643_USER;
+    use crate::error::ScimType;
+    use std::collections::BTreeMap;
 
     #[test]
     fn parse_user() {
@@ -192,5 +1472,664 @@ mod tests {
 
         let s = serde_json::to_string_pretty(&u).expect("Failed to serialise RFC7643_USER");
         eprintln!("{}", s);
+
+        assert_eq!(u.user_name, "bjensen@example.com");
+        assert_eq!(
+            u.name.as_ref().and_then(|n| n.given_name.as_deref()),
+            Some("Barbara")
+        );
+        assert_eq!(u.emails.len(), 2);
+        assert_eq!(u.x509_certificates.len(), 1);
+        assert_eq!(u.addresses.len(), 2);
+        assert_eq!(u.addresses[0].locality.as_deref(), Some("Hollywood"));
+        assert_eq!(u.photos[1].type_.as_deref(), Some("thumbnail"));
+        assert_eq!(u.groups[0].display, "Tour Guides");
+    }
+
+    #[test]
+    fn parse_enterprise_user() {
+        let mut v: serde_json::Value =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        v["urn:ietf:params:scim:schemas:extension:enterprise:2.0:User"] = serde_json::json!({
+          "employeeNumber": "701984",
+          "costCenter": "4130",
+          "organization": "Universal Studios",
+          "division": "Theme Park",
+          "department": "Tour Operations",
+          "manager": {
+            "value": "26118915-6090-4610-87e4-49d8ca9f808d",
+            "$ref": "../Users/26118915-6090-4610-87e4-49d8ca9f808d",
+            "displayName": "John Smith"
+          }
+        });
+
+        let u: User = serde_json::from_value(v.clone()).expect("Failed to parse enterprise user");
+        let ent = u.enterprise.as_ref().expect("Missing enterprise extension");
+        assert_eq!(ent.employee_number.as_deref(), Some("701984"));
+        let manager = ent.manager.as_ref().expect("Missing manager");
+        assert_eq!(manager.display_name.as_deref(), Some("John Smith"));
+        assert_eq!(
+            manager.ref_.as_deref(),
+            Some("../Users/26118915-6090-4610-87e4-49d8ca9f808d")
+        );
+
+        let out = serde_json::to_value(&u).expect("Failed to serialise");
+        assert_eq!(
+            out["urn:ietf:params:scim:schemas:extension:enterprise:2.0:User"]["costCenter"],
+            "4130"
+        );
+    }
+
+    #[test]
+    fn user_emails() {
+        for email in [
+            "bjensen@example.com",
+            "babs.jensen+scim@sub.example.org",
+            "\"babs jensen\"@example.com",
+            "admin@[192.0.2.1]",
+            "o'brien@example.ie",
+        ] {
+            assert!(is_valid_email(email), "{}", email);
+        }
+        for email in [
+            "",
+            "bjensen",
+            "@example.com",
+            "bjensen@",
+            "b..jensen@example.com",
+            ".bjensen@example.com",
+            "bjensen@-example.com",
+            "bjensen@example..com",
+            "b jensen@example.com",
+            "bjensen@exa_mple.com",
+        ] {
+            assert!(!is_valid_email(email), "{}", email);
+        }
+
+        let u = User::parse(RFC7643_USER, &UserParseOptions::default())
+            .expect("Failed to parse RFC7643_USER");
+        assert_eq!(u.primary_email(), Some("bjensen@example.com"));
+
+        let mut v: serde_json::Value =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        v["emails"][1]["value"] = serde_json::json!("not an email");
+        let json = v.to_string();
+        assert!(User::parse(&json, &UserParseOptions::default()).is_ok());
+        let strict = UserParseOptions::default().validate_emails(true);
+        assert_eq!(
+            User::parse(&json, &strict).map(|_| ()),
+            Err(invalid(
+                "emails[1].value",
+                ViolationKind::InvalidFormat {
+                    expected: "an email address"
+                }
+            ))
+        );
+
+        let mut u = User::builder("alice")
+            .email("a@example.com")
+            .email("b@example.com")
+            .build()
+            .expect("Failed to build user");
+        u.emails[0].primary = None;
+        assert_eq!(u.primary_email(), Some("a@example.com"));
+        u.emails[1].primary = Some(true);
+        assert_eq!(u.primary_email(), Some("b@example.com"));
+
+        u.primary_first();
+        assert_eq!(u.emails[0].value, "b@example.com");
+        assert_eq!(u.emails[0].primary, Some(true));
+    }
+
+    #[test]
+    fn user_error_paths() {
+        let unreadable = |json: &str| match User::parse(json, &UserParseOptions::default()) {
+            Err(ScimError::InvalidValue(v))
+                if matches!(v.kind, ViolationKind::Unreadable { .. }) =>
+            {
+                Some(v.path)
+            }
+            _ => None,
+        };
+
+        let mut v: serde_json::Value =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        v["emails"][1]["value"] = serde_json::json!(5);
+        assert_eq!(
+            unreadable(&v.to_string()).as_deref(),
+            Some("emails[1].value")
+        );
+
+        let entry: ScimEntryGeneric = serde_json::from_value(v).expect("Failed to parse entry");
+        assert!(matches!(
+            User::try_from(entry),
+            Err(ScimError::InvalidValue(v)) if v.path == "emails[1].value"
+        ));
+
+        let mut v: serde_json::Value =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        v["photos"][0]["value"] = serde_json::json!("not a url");
+        assert_eq!(
+            unreadable(&v.to_string()).as_deref(),
+            Some("photos[0].value")
+        );
+        v["photos"] = serde_json::json!("not a list");
+        assert_eq!(unreadable(&v.to_string()).as_deref(), Some("photos"));
+
+        assert!(matches!(
+            User::parse("{", &UserParseOptions::default()),
+            Err(ScimError::InvalidJson(_))
+        ));
+    }
+
+    #[cfg(feature = "phone")]
+    #[test]
+    fn user_phone_numbers() {
+        let mut u = User::builder("alice")
+            .phone_number("+1 (201) 555-0123")
+            .phone_number("tel:+61-7-3000-0000")
+            .build()
+            .expect("Failed to build user");
+        assert_eq!(u.validate_phone_numbers(), Ok(()));
+        u.normalize_phone_numbers()
+            .expect("Failed to normalize phone numbers");
+        assert_eq!(u.phone_numbers[0].value, "tel:+12015550123");
+        assert_eq!(u.phone_numbers[1].value, "tel:+61730000000");
+
+        u.phone_numbers.push(multi_value("555-0123"));
+        assert_eq!(
+            u.normalize_phone_numbers(),
+            Err(invalid(
+                "phoneNumbers[2].value",
+                ViolationKind::InvalidFormat {
+                    expected: "a phone number"
+                }
+            ))
+        );
+        assert_eq!(u.phone_numbers[0].value, "tel:+12015550123");
+
+        // The RFC7643 example uses local numbers.
+        assert!(User::parse(RFC7643_USER, &UserParseOptions::default()).is_ok());
+        let strict = UserParseOptions::default().validate_phone_numbers(true);
+        assert_eq!(
+            User::parse(RFC7643_USER, &strict).map(|_| ()),
+            Err(invalid(
+                "phoneNumbers[0].value",
+                ViolationKind::InvalidFormat {
+                    expected: "a phone number"
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn user_read_only() {
+        let accept = UserParseOptions::default();
+        let u = User::parse(RFC7643_USER, &accept).expect("Failed to parse RFC7643_USER");
+        assert_eq!(u.groups.len(), 3);
+        assert!(u.entry.meta.is_some());
+
+        let strip = UserParseOptions::default().read_only(ReadOnlyMode::Strip);
+        let u = User::parse(RFC7643_USER, &strip).expect("Failed to parse RFC7643_USER");
+        assert!(u.groups.is_empty());
+        assert!(u.entry.meta.is_none());
+        assert_eq!(u.user_name, "bjensen@example.com");
+
+        let reject = UserParseOptions::default().read_only(ReadOnlyMode::Reject);
+        assert!(matches!(
+            User::parse(RFC7643_USER, &reject),
+            Err(ScimError::Mutability(name)) if name == "groups"
+        ));
+
+        let entry: ScimEntryGeneric =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        assert!(matches!(
+            User::from_entry(&entry, &reject),
+            Err(ScimError::Mutability(name)) if name == "groups"
+        ));
+        let u = User::from_entry(&entry, &strip).expect("Failed to convert entry");
+        assert!(u.groups.is_empty());
+
+        let json = serde_json::to_string(&u).expect("Failed to serialise");
+        assert!(User::parse(&json, &reject).is_ok());
+    }
+
+    #[test]
+    fn user_parse_warnings() {
+        let json = r#"{
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+            "id": "2819c223-7f76-453a-919d-413861904646",
+            "userName": "bjensen",
+            "locale": "en_US!",
+            "emails": [
+              { "value": "bjensen@example.com", "primary": true },
+              { "value": "babs@jensen.org", "primary": true }
+            ]
+        }"#;
+        let options = UserParseOptions::default().default_active(true);
+        assert!(User::parse(json, &options).is_err());
+
+        let options = options
+            .drop_invalid_locale(true)
+            .demote_extra_primaries(true);
+        let (u, warnings) = User::parse_with_warnings(json, &options).expect("Failed to parse");
+        assert!(u.active);
+        assert_eq!(u.locale, None);
+        assert_eq!(u.emails[1].primary, Some(false));
+        assert_eq!(
+            warnings,
+            vec![
+                ScimWarning::new(
+                    "active",
+                    WarningKind::Defaulted {
+                        value: "true".to_string()
+                    }
+                ),
+                ScimWarning::new(
+                    "locale",
+                    WarningKind::Dropped {
+                        value: "en_US!".to_string()
+                    }
+                ),
+                ScimWarning::new("emails[1].primary", WarningKind::PrimaryDemoted),
+            ]
+        );
+        assert_eq!(
+            warnings[1].to_string(),
+            "invalid value 'en_US!' of attribute 'locale' was dropped"
+        );
+    }
+
+    #[test]
+    fn canonical_types() {
+        let u: User = serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        assert_eq!(u.emails[0].type_as(), Some(EmailType::Work));
+        assert_eq!(u.emails[1].type_as(), Some(EmailType::Home));
+        assert_eq!(u.phone_numbers[0].type_as(), Some(PhoneType::Work));
+        assert_eq!(u.ims[0].type_as(), Some(ImType::Aim));
+        assert_eq!(u.photos[1].photo_type(), Some(PhotoType::Thumbnail));
+        assert_eq!(u.addresses[1].address_type(), Some(AddressType::Home));
+
+        assert_eq!(EmailType::from("WORK"), EmailType::Work);
+        let custom = PhoneType::from("Satellite");
+        assert_eq!(custom, PhoneType::Custom("Satellite".to_string()));
+        assert_eq!(String::from(custom), "Satellite");
+        assert_eq!(ImType::Xmpp.to_string(), "xmpp");
+
+        let types: Vec<ImType> =
+            serde_json::from_str(r#"["skype", "Signal"]"#).expect("Failed to parse");
+        assert_eq!(
+            types,
+            vec![ImType::Skype, ImType::Custom("Signal".to_string())]
+        );
+        assert_eq!(
+            serde_json::to_string(&types).expect("Failed to serialise"),
+            r#"["skype","Signal"]"#
+        );
+    }
+
+    #[test]
+    fn photo_data() {
+        let png = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+        let photo = Photo::from_data("image/png", &png).expect("Failed to build photo");
+        assert_eq!(photo.value.as_str(), "data:image/png;base64,iVBORw0KGgo=");
+        assert!(photo.is_data());
+        let data = photo.data().expect("Invalid data").expect("Missing data");
+        assert_eq!(data.media_type, "image/png");
+        assert_eq!(data.data, png);
+
+        let v = serde_json::json!({ "value": "data:,Hello%2C%20World!", "type": "photo" });
+        let photo: Photo = serde_json::from_value(v).expect("Failed to parse photo");
+        let data = photo.data().expect("Invalid data").expect("Missing data");
+        assert_eq!(data.media_type, "text/plain;charset=US-ASCII");
+        assert_eq!(data.data, b"Hello, World!");
+        assert_eq!(
+            serde_json::to_value(&photo).expect("Failed to serialise")["value"],
+            "data:,Hello%2C%20World!"
+        );
+
+        let u: User = serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        assert!(!u.photos[0].is_data());
+        assert_eq!(u.photos[0].data(), Ok(None));
+
+        assert_eq!(
+            DataUri::parse("data:image/png;base64,!!!").map_err(|e| e.scim_type()),
+            Err(Some(ScimType::InvalidValue))
+        );
+        assert_eq!(
+            DataUri::parse("data:éabcdef,xx"),
+            Err(invalid(
+                "",
+                ViolationKind::InvalidFormat {
+                    expected: "a data URI"
+                }
+            ))
+        );
+        assert_eq!(
+            DataUri::parse("https://example.com"),
+            Err(invalid(
+                "",
+                ViolationKind::InvalidFormat {
+                    expected: "a data URI"
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn user_ref_aliases() {
+        let mut v: serde_json::Value =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let groups = v["groups"].as_array_mut().expect("No groups");
+        for (group, key) in groups.iter_mut().zip(["$ref", "ref", "reference"]) {
+            if let serde_json::Value::Object(attrs) = group {
+                let ref_ = attrs.remove("$ref").expect("No $ref");
+                attrs.insert(key.to_string(), ref_);
+            }
+        }
+
+        let u = User::parse(&v.to_string(), &UserParseOptions::default())
+            .expect("Failed to parse user");
+        assert_eq!(u.groups.len(), 3);
+        assert!(u.groups[2]
+            .ref_
+            .as_str()
+            .ends_with("71ddacd2-a8e7-49b8-a5db-ae50d0a5bfd7"));
+
+        // Always written as $ref.
+        let json = serde_json::to_value(&u).expect("Failed to serialise");
+        for group in json["groups"].as_array().expect("No groups") {
+            assert!(group.get("$ref").is_some());
+            assert!(group.get("ref").is_none() && group.get("reference").is_none());
+        }
+    }
+
+    #[test]
+    fn user_display() {
+        let u = User::parse(RFC7643_USER, &UserParseOptions::default())
+            .expect("Failed to parse RFC7643_USER");
+        assert!(u.password.is_some());
+
+        let shown = u.to_string();
+        assert!(shown.starts_with("schemas:\n  - urn:ietf:params:scim:schemas:core:2.0:User\nid: "));
+        assert!(shown.contains("\nuserName: bjensen@example.com\n"));
+        assert!(shown.contains("\nname:\n  familyName: Jensen\n"));
+        assert!(!shown.contains("password"));
+    }
+
+    #[test]
+    fn user_missing_active() {
+        let mut v: serde_json::Value =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        if let serde_json::Value::Object(attrs) = &mut v {
+            attrs.remove("active");
+        }
+        let json = v.to_string();
+
+        assert_eq!(
+            User::parse(&json, &UserParseOptions::default()).map(|_| ()),
+            Err(invalid("active", ViolationKind::MissingRequired))
+        );
+
+        let options = UserParseOptions::default().default_active(true);
+        let u = User::parse(&json, &options).expect("Failed to parse user");
+        assert!(u.active);
+
+        let options = UserParseOptions::default().default_active(false);
+        let u = User::parse(&json, &options).expect("Failed to parse user");
+        assert!(!u.active);
+
+        // A value that is present is kept.
+        let u = User::parse(RFC7643_USER, &options).expect("Failed to parse RFC7643_USER");
+        assert!(u.active);
+    }
+
+    #[test]
+    fn user_extra_attributes() {
+        let mut v: serde_json::Value =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        v["schemas"]
+            .as_array_mut()
+            .expect("Missing schemas")
+            .push(serde_json::json!("urn:example:params:scim:schemas:Badge"));
+        v["urn:example:params:scim:schemas:Badge"] = serde_json::json!({ "badgeId": "B-1138" });
+        v["vendorFlags"] = serde_json::json!(["a", "b"]);
+
+        let entry: ScimEntryGeneric = serde_json::from_value(v).expect("Failed to parse entry");
+        let u = User::from_entry(&entry, &UserParseOptions::default())
+            .expect("Failed to convert entry");
+        assert_eq!(u.extra.len(), 2);
+        assert!(u.extra.contains_key("vendorFlags"));
+        assert!(!u.extra.contains_key("userName"));
+
+        let round_trip: ScimEntryGeneric = serde_json::to_value(&u)
+            .and_then(serde_json::from_value)
+            .expect("Failed to convert user");
+        assert_eq!(
+            round_trip
+                .attrs
+                .get("urn:example:params:scim:schemas:Badge"),
+            entry.attrs.get("urn:example:params:scim:schemas:Badge")
+        );
+        assert_eq!(
+            round_trip.attrs.get("vendorFlags"),
+            entry.attrs.get("vendorFlags")
+        );
+    }
+
+    #[test]
+    fn user_password() {
+        let mut v: serde_json::Value =
+            serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        v["password"] = serde_json::json!("t1meMa$heen");
+
+        let u: User = serde_json::from_value(v).expect("Failed to parse user");
+        assert_eq!(
+            u.password.as_ref().map(Password::expose_secret),
+            Some("t1meMa$heen")
+        );
+        assert!(!format!("{:?}", u).contains("t1meMa$heen"));
+
+        let v = serde_json::to_value(&u).expect("Failed to serialise");
+        assert!(v.get("password").is_none());
+
+        let v = u.to_request().expect("Failed to serialise");
+        assert_eq!(v["password"], "t1meMa$heen");
+        assert_eq!(v["userName"], "bjensen@example.com");
+
+        let create = u.to_create().expect("Failed to serialise");
+        let v = serde_json::to_value(&create).expect("Failed to serialise");
+        assert_eq!(v["password"], "t1meMa$heen");
+        assert!(v.get("id").is_none());
+
+        let entry = u.into_entry().expect("Failed to convert");
+        assert!(!entry.contains_attr("password"));
+    }
+
+    #[test]
+    fn user_manager_chain() {
+        let user = |id: &str, manager: Option<&str>| {
+            User::builder(id)
+                .id(id)
+                .enterprise(EnterpriseUser {
+                    manager: manager.map(|m| Manager {
+                        ref_: Some(format!("../Users/{}", m)),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .build()
+                .expect("Failed to build user")
+        };
+        let users: BTreeMap<ScimId, User> = [
+            user("ceo", None),
+            user("cto", Some("ceo")),
+            user("lead", Some("cto")),
+            user("dev", Some("lead")),
+            user("a", Some("b")),
+            user("b", Some("a")),
+            user("orphan", Some("gone")),
+        ]
+        .into_iter()
+        .map(|u| (u.entry.id.clone(), u))
+        .collect();
+        let find = |users: &BTreeMap<ScimId, User>, id: &str| {
+            users.get(&ScimId::from(id)).cloned().expect("Missing user")
+        };
+        let names = |chain: &ManagerChain| -> Vec<String> {
+            chain.managers.iter().map(|u| u.user_name.clone()).collect()
+        };
+
+        let dev = find(&users, "dev");
+        let chain = dev
+            .manager_chain(10, |id| Ok(users.get(id).cloned()))
+            .expect("Failed to resolve managers");
+        assert_eq!(names(&chain), vec!["lead", "cto", "ceo"]);
+        assert_eq!(chain.end, ManagerChainEnd::Top);
+
+        let chain = dev
+            .manager_chain(2, |id| Ok(users.get(id).cloned()))
+            .expect("Failed to resolve managers");
+        assert_eq!(names(&chain), vec!["lead", "cto"]);
+        assert_eq!(chain.end, ManagerChainEnd::DepthLimit);
+
+        let chain = find(&users, "a")
+            .manager_chain(10, |id| Ok(users.get(id).cloned()))
+            .expect("Failed to resolve managers");
+        assert_eq!(names(&chain), vec!["b"]);
+        assert_eq!(chain.end, ManagerChainEnd::Cycle(ScimId::from("a")));
+
+        let chain = find(&users, "orphan")
+            .manager_chain(10, |id| Ok(users.get(id).cloned()))
+            .expect("Failed to resolve managers");
+        assert!(chain.managers.is_empty());
+        assert_eq!(chain.end, ManagerChainEnd::Unresolved(ScimId::from("gone")));
+
+        assert_eq!(
+            dev.manager_chain(10, |_| Err(ScimError::InvalidAttribute))
+                .map(|chain| chain.end),
+            Err(ScimError::InvalidAttribute)
+        );
+    }
+
+    #[test]
+    fn partial_user() {
+        let partial = PartialUser::parse(
+            r#"{
+                "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+                "displayName": "Babs",
+                "active": false,
+                "password": "t1meMa$heen",
+                "emails": [{"value": "babs@example.com", "type": "work"}],
+                "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User": {
+                    "department": "Tour Operations"
+                }
+            }"#,
+        )
+        .expect("Failed to parse partial user");
+        assert!(partial.user_name.is_none());
+        assert!(partial.id.is_none());
+
+        let patch = partial.to_patch().expect("Failed to build patch");
+        let paths: Vec<_> = patch
+            .operations
+            .iter()
+            .map(|op| op.path.as_deref().unwrap_or_default())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "active",
+                "displayName",
+                "emails",
+                "password",
+                "urn:ietf:params:scim:schemas:extension:enterprise:2.0:User:department",
+            ]
+        );
+        assert!(patch
+            .operations
+            .iter()
+            .all(|op| op.op == ScimPatchOpKind::Replace));
+
+        let mut u: User = serde_json::from_str(RFC7643_USER).expect("Failed to parse user");
+        partial.apply_to(&mut u);
+        assert_eq!(u.user_name, "bjensen@example.com");
+        assert_eq!(u.display_name.as_deref(), Some("Babs"));
+        assert!(!u.active);
+        assert_eq!(u.emails.len(), 1);
+        assert_eq!(u.nick_name.as_deref(), Some("Babs"));
+        assert_eq!(
+            u.password.as_ref().map(Password::expose_secret),
+            Some("t1meMa$heen")
+        );
+        assert_eq!(
+            u.enterprise.as_ref().and_then(|e| e.department.as_deref()),
+            Some("Tour Operations")
+        );
+        assert!(u
+            .entry
+            .schemas
+            .iter()
+            .any(|s| s == SCIM_SCHEMA_ENTERPRISE_USER));
+    }
+
+    #[test]
+    fn build_user() {
+        let u = User::builder("alice")
+            .display_name("Alice")
+            .email("alice@example.com")
+            .email("alice@example.org")
+            .active(false)
+            .enterprise(EnterpriseUser {
+                employee_number: Some("42".to_string()),
+                ..Default::default()
+            })
+            .build()
+            .expect("Failed to build user");
+
+        assert_eq!(u.user_name, "alice");
+        assert_eq!(u.display_name.as_deref(), Some("Alice"));
+        assert!(!u.active);
+        assert_eq!(u.emails[0].primary, Some(true));
+        assert_eq!(u.emails[1].primary, None);
+        assert_eq!(
+            u.entry.schemas,
+            vec![SCIM_SCHEMA_USER, SCIM_SCHEMA_ENTERPRISE_USER]
+        );
+
+        let v = serde_json::to_value(&u).expect("Failed to serialise");
+        assert_eq!(v["userName"], "alice");
+        assert_eq!(v["emails"][0]["value"], "alice@example.com");
+
+        let mut address = Address::new("work");
+        address.locality = Some("Brisbane".to_string());
+        let photo = Url::parse("https://photos.example.com/alice.jpg").expect("Invalid url");
+        let u = User::builder("alice")
+            .address(address)
+            .photo(Photo::new(photo))
+            .build()
+            .expect("Failed to build user");
+        let v = serde_json::to_value(&u).expect("Failed to serialise");
+        assert_eq!(v["addresses"][0]["type"], "work");
+        assert_eq!(v["addresses"][0]["locality"], "Brisbane");
+        assert_eq!(
+            v["photos"][0]["value"],
+            "https://photos.example.com/alice.jpg"
+        );
+
+        assert_eq!(
+            User::builder(" ").build().map(|_| ()),
+            Err(invalid("userName", ViolationKind::MissingRequired))
+        );
+        let mut primary = multi_value("b@example.com");
+        primary.primary = Some(true);
+        assert_eq!(
+            User::builder("bob")
+                .email("a@example.com")
+                .email_attr(primary)
+                .build()
+                .map(|_| ()),
+            Err(invalid("emails", ViolationKind::MultiplePrimary))
+        );
     }
 }
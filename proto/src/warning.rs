@@ -0,0 +1,68 @@
+//! Warnings for data that a lenient parse changed or dropped so that it
+//! could accept its input. They are returned alongside the result, so that
+//! problems in the data an identity provider sends can be monitored rather
+//! than passing unnoticed.
+
+use crate::schema::{SchemaViolation, ViolationKind};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScimWarning {
+    /// The attribute path, such as `emails[1].primary`.
+    pub path: String,
+    pub kind: WarningKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WarningKind {
+    /// A value that isn't valid was removed, such as a `locale` that isn't a
+    /// language tag.
+    Dropped { value: String },
+    /// An absent attribute was given a default value.
+    Defaulted { value: String },
+    /// `primary` was set to false, as an earlier value was primary.
+    PrimaryDemoted,
+    /// A schema violation that was accepted. Values that can't be held are
+    /// dropped.
+    Violation(ViolationKind),
+}
+
+impl ScimWarning {
+    pub(crate) fn new(path: impl Into<String>, kind: WarningKind) -> Self {
+        ScimWarning {
+            path: path.into(),
+            kind,
+        }
+    }
+}
+
+impl From<SchemaViolation> for ScimWarning {
+    fn from(violation: SchemaViolation) -> Self {
+        ScimWarning::new(violation.path, WarningKind::Violation(violation.kind))
+    }
+}
+
+impl fmt::Display for ScimWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            WarningKind::Dropped { value } => write!(
+                f,
+                "invalid value '{}' of attribute '{}' was dropped",
+                value, self.path
+            ),
+            WarningKind::Defaulted { value } => write!(
+                f,
+                "attribute '{}' is missing, and was set to {}",
+                self.path, value
+            ),
+            WarningKind::PrimaryDemoted => write!(
+                f,
+                "'{}' was set to false, as an earlier value is primary",
+                self.path
+            ),
+            WarningKind::Violation(kind) => {
+                SchemaViolation::new(self.path.clone(), kind.clone()).fmt(f)
+            }
+        }
+    }
+}
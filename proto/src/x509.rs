@@ -0,0 +1,423 @@
+//! A minimal reader for the DER of `x509Certificates` values, exposing the
+//! fields needed to decide whether to accept a certificate. Signatures and
+//! extensions are not checked.
+
+use crate::error::ScimError;
+use crate::schema::SchemaViolation;
+use std::fmt;
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time};
+
+// https://datatracker.ietf.org/doc/html/rfc5280#section-4.1
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OID: u8 = 0x06;
+const TAG_UTF8_STRING: u8 = 0x0c;
+const TAG_PRINTABLE_STRING: u8 = 0x13;
+const TAG_T61_STRING: u8 = 0x14;
+const TAG_IA5_STRING: u8 = 0x16;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_BMP_STRING: u8 = 0x1e;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_VERSION: u8 = 0xa0;
+
+// Short names from RFC4514 section 3, and the PKCS #9 email address.
+const ATTRIBUTE_NAMES: &[(&str, &str)] = &[
+    ("2.5.4.3", "CN"),
+    ("2.5.4.6", "C"),
+    ("2.5.4.7", "L"),
+    ("2.5.4.8", "ST"),
+    ("2.5.4.9", "STREET"),
+    ("2.5.4.10", "O"),
+    ("2.5.4.11", "OU"),
+    ("0.9.2342.19200300.100.1.1", "UID"),
+    ("0.9.2342.19200300.100.1.25", "DC"),
+    ("1.2.840.113549.1.9.1", "emailAddress"),
+];
+
+struct Der<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Der { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn peek_tag(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    /// Read one element, returning its tag and contents.
+    fn read_any(&mut self) -> Result<(u8, &'a [u8]), ScimError> {
+        let (&tag, rest) = self.data.split_first().ok_or(ScimError::InvalidAttribute)?;
+        let (&first, rest) = rest.split_first().ok_or(ScimError::InvalidAttribute)?;
+
+        let (len, rest) = if first < 0x80 {
+            (first as usize, rest)
+        } else {
+            let n = (first & 0x7f) as usize;
+            if n == 0 || n > 4 || rest.len() < n {
+                return Err(ScimError::InvalidAttribute);
+            }
+            let len = rest[..n]
+                .iter()
+                .fold(0usize, |len, b| (len << 8) | *b as usize);
+            (len, &rest[n..])
+        };
+
+        if rest.len() < len {
+            return Err(ScimError::InvalidAttribute);
+        }
+        let (content, rest) = rest.split_at(len);
+        self.data = rest;
+        Ok((tag, content))
+    }
+
+    fn read(&mut self, tag: u8) -> Result<&'a [u8], ScimError> {
+        match self.read_any()? {
+            (t, content) if t == tag => Ok(content),
+            _ => Err(ScimError::InvalidAttribute),
+        }
+    }
+
+    fn read_sequence(&mut self) -> Result<Der<'a>, ScimError> {
+        self.read(TAG_SEQUENCE).map(Der::new)
+    }
+}
+
+fn decode_oid(content: &[u8]) -> Result<String, ScimError> {
+    let (&first, rest) = content.split_first().ok_or(ScimError::InvalidAttribute)?;
+    let mut arcs = vec![(first / 40).min(2) as u64];
+    arcs.push(first as u64 - arcs[0] * 40);
+
+    let mut arc: u64 = 0;
+    for b in rest {
+        arc = arc.checked_mul(128).ok_or(ScimError::InvalidAttribute)? | (b & 0x7f) as u64;
+        if b & 0x80 == 0 {
+            arcs.push(arc);
+            arc = 0;
+        }
+    }
+    if rest.last().map(|b| b & 0x80 != 0).unwrap_or(false) {
+        return Err(ScimError::InvalidAttribute);
+    }
+
+    Ok(arcs
+        .iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join("."))
+}
+
+fn decode_string(tag: u8, content: &[u8]) -> Option<String> {
+    match tag {
+        TAG_UTF8_STRING | TAG_PRINTABLE_STRING | TAG_IA5_STRING => {
+            String::from_utf8(content.to_vec()).ok()
+        }
+        // Treated as Latin-1, which is what T61String is used for in practice.
+        TAG_T61_STRING => Some(content.iter().map(|b| *b as char).collect()),
+        TAG_BMP_STRING if content.len() % 2 == 0 => {
+            let units: Vec<u16> = content
+                .chunks(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16(&units).ok()
+        }
+        _ => None,
+    }
+}
+
+fn digits(s: &[u8]) -> Result<u32, ScimError> {
+    s.iter().try_fold(0u32, |n, b| {
+        if b.is_ascii_digit() {
+            Ok(n * 10 + (b - b'0') as u32)
+        } else {
+            Err(ScimError::InvalidAttribute)
+        }
+    })
+}
+
+// RFC5280 requires times in UTC, with seconds and without fractions.
+fn decode_time(tag: u8, content: &[u8]) -> Result<OffsetDateTime, ScimError> {
+    let (year, rest) = match (tag, content.len()) {
+        (TAG_UTC_TIME, 13) => {
+            let year = digits(&content[..2])? as i32;
+            (if year >= 50 { 1900 } else { 2000 } + year, &content[2..])
+        }
+        (TAG_GENERALIZED_TIME, 15) => (digits(&content[..4])? as i32, &content[4..]),
+        _ => return Err(ScimError::InvalidAttribute),
+    };
+    if rest[10] != b'Z' {
+        return Err(ScimError::InvalidAttribute);
+    }
+
+    let invalid = |err| SchemaViolation::unreadable("", err);
+    let month = Month::try_from(digits(&rest[0..2])? as u8).map_err(invalid)?;
+    let date =
+        Date::from_calendar_date(year, month, digits(&rest[2..4])? as u8).map_err(invalid)?;
+    let time = Time::from_hms(
+        digits(&rest[4..6])? as u8,
+        digits(&rest[6..8])? as u8,
+        digits(&rest[8..10])? as u8,
+    )
+    .map_err(invalid)?;
+
+    Ok(PrimitiveDateTime::new(date, time).assume_utc())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// An X.500 distinguished name, such as a certificate's subject.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistinguishedName {
+    // In the order they are encoded, the reverse of the RFC4514 form.
+    attributes: Vec<(String, String)>,
+}
+
+impl DistinguishedName {
+    fn decode(mut der: Der<'_>) -> Result<Self, ScimError> {
+        let mut attributes = Vec::new();
+        while !der.is_empty() {
+            let mut rdn = Der::new(der.read(TAG_SET)?);
+            while !rdn.is_empty() {
+                let mut atv = rdn.read_sequence()?;
+                let oid = decode_oid(atv.read(TAG_OID)?)?;
+                let element = atv.data;
+                let (tag, content) = atv.read_any()?;
+                let element = &element[..element.len() - atv.data.len()];
+
+                let name = ATTRIBUTE_NAMES
+                    .iter()
+                    .find(|(o, _)| *o == oid)
+                    .map(|(_, name)| name.to_string())
+                    .unwrap_or(oid);
+                // Values of types that aren't strings are written as the hex
+                // of their encoding, as RFC4514 section 2.4 does.
+                let value =
+                    decode_string(tag, content).unwrap_or_else(|| format!("#{}", hex(element)));
+                attributes.push((name, value));
+            }
+        }
+        Ok(DistinguishedName { attributes })
+    }
+
+    /// The attributes of the name as short names or OIDs and their values,
+    /// most significant first.
+    pub fn attributes(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.attributes
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// The first value of an attribute, by short name such as `CN`, or by
+    /// OID for attributes without a short name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.attributes()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    pub fn common_name(&self) -> Option<&str> {
+        self.get("CN")
+    }
+}
+
+/// The RFC4514 string form, such as `CN=example.com,O=Example,C=US`.
+impl fmt::Display for DistinguishedName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, value)) in self.attributes.iter().rev().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}=", name)?;
+            let last = value.chars().count().saturating_sub(1);
+            for (j, c) in value.chars().enumerate() {
+                let escape = matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';')
+                    || (j == 0 && (c == '#' || c == ' '))
+                    || (j == last && c == ' ');
+                if escape {
+                    f.write_str("\\")?;
+                }
+                write!(f, "{}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The fields of an X.509 certificate that admission checks need.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Certificate {
+    serial: Vec<u8>,
+    issuer: DistinguishedName,
+    subject: DistinguishedName,
+    not_before: OffsetDateTime,
+    not_after: OffsetDateTime,
+}
+
+impl Certificate {
+    /// Read a DER encoded certificate.
+    pub fn parse(der: &[u8]) -> Result<Self, ScimError> {
+        let mut outer = Der::new(der);
+        let mut certificate = outer.read_sequence()?;
+        if !outer.is_empty() {
+            return Err(ScimError::InvalidAttribute);
+        }
+        let mut tbs = certificate.read_sequence()?;
+
+        if tbs.peek_tag() == Some(TAG_VERSION) {
+            tbs.read(TAG_VERSION)?;
+        }
+        let serial = tbs.read(TAG_INTEGER)?.to_vec();
+        // The signature algorithm.
+        tbs.read_sequence()?;
+        let issuer = DistinguishedName::decode(tbs.read_sequence()?)?;
+
+        let mut validity = tbs.read_sequence()?;
+        let (tag, content) = validity.read_any()?;
+        let not_before = decode_time(tag, content)?;
+        let (tag, content) = validity.read_any()?;
+        let not_after = decode_time(tag, content)?;
+
+        let subject = DistinguishedName::decode(tbs.read_sequence()?)?;
+
+        Ok(Certificate {
+            serial,
+            issuer,
+            subject,
+            not_before,
+            not_after,
+        })
+    }
+
+    /// The serial number as its big endian two's complement bytes.
+    pub fn serial(&self) -> &[u8] {
+        &self.serial
+    }
+
+    /// The serial number as lower case hex, without the leading zero byte
+    /// DER adds to keep large serials positive.
+    pub fn serial_hex(&self) -> String {
+        match self.serial.split_first() {
+            Some((0, rest)) if !rest.is_empty() => hex(rest),
+            _ => hex(&self.serial),
+        }
+    }
+
+    pub fn issuer(&self) -> &DistinguishedName {
+        &self.issuer
+    }
+
+    pub fn subject(&self) -> &DistinguishedName {
+        &self.subject
+    }
+
+    pub fn not_before(&self) -> OffsetDateTime {
+        self.not_before
+    }
+
+    pub fn not_after(&self) -> OffsetDateTime {
+        self.not_after
+    }
+
+    /// If `at` is within the validity period, inclusive of both ends.
+    pub fn is_valid_at(&self, at: OffsetDateTime) -> bool {
+        self.not_before <= at && at <= self.not_after
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::RFC7643_USER;
+    use crate::user::User;
+    use time::format_description::well_known::Rfc3339;
+
+    fn datetime(s: &str) -> OffsetDateTime {
+        OffsetDateTime::parse(s, &Rfc3339).expect("Invalid datetime")
+    }
+
+    #[test]
+    fn x509_parse() {
+        let u: User = serde_json::from_str(RFC7643_USER).expect("Failed to parse RFC7643_USER");
+        let cert = u.x509_certificates[0]
+            .certificate()
+            .expect("Failed to parse certificate");
+
+        assert_eq!(cert.serial_hex(), "1000");
+        assert_eq!(
+            cert.issuer().to_string(),
+            "CN=example.com,O=example.com,ST=California,C=US"
+        );
+        assert_eq!(
+            cert.subject().common_name(),
+            Some("Ms. Barbara J Jensen III")
+        );
+        assert_eq!(
+            cert.subject().get("emailAddress"),
+            Some("bjensen@example.com")
+        );
+        assert_eq!(cert.not_before(), datetime("2011-10-22T06:24:31Z"));
+        assert_eq!(cert.not_after(), datetime("2012-10-04T06:24:31Z"));
+        assert!(cert.is_valid_at(datetime("2012-01-01T00:00:00Z")));
+        assert!(!cert.is_valid_at(datetime("2013-01-01T00:00:00Z")));
+
+        assert_eq!(u.certificates().map(|c| c.len()), Ok(1));
+
+        assert_eq!(Certificate::parse(&[]), Err(ScimError::InvalidAttribute));
+        assert_eq!(
+            Certificate::parse(&[0x30, 0x05, 0x02]),
+            Err(ScimError::InvalidAttribute)
+        );
+    }
+
+    #[test]
+    fn x509_name_display() {
+        let name = DistinguishedName {
+            attributes: vec![
+                ("C".to_string(), "AU".to_string()),
+                ("O".to_string(), "Example, Pty Ltd".to_string()),
+                ("CN".to_string(), "#1 ".to_string()),
+            ],
+        };
+        assert_eq!(name.to_string(), "CN=\\#1\\ ,O=Example\\, Pty Ltd,C=AU");
+    }
+
+    #[test]
+    fn x509_name_hex_value() {
+        fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+            let mut der = vec![tag];
+            match content.len() {
+                len if len < 0x80 => der.push(len as u8),
+                len if len < 0x100 => der.extend([0x81, len as u8]),
+                len => der.extend([0x82, (len >> 8) as u8, len as u8]),
+            }
+            der.extend_from_slice(content);
+            der
+        }
+        // An x500UniqueIdentifier, which is a BIT STRING.
+        fn rdn(value: &[u8]) -> Vec<u8> {
+            let mut atv = tlv(TAG_OID, &[0x55, 0x04, 0x2d]);
+            atv.extend(tlv(0x03, value));
+            tlv(TAG_SET, &tlv(TAG_SEQUENCE, &atv))
+        }
+
+        let short = DistinguishedName::decode(Der::new(&rdn(&[0x00, 0xab, 0xcd])))
+            .expect("Failed to decode name");
+        assert_eq!(short.get("2.5.4.45"), Some("#030300abcd"));
+
+        let value = vec![0x5a; 300];
+        let long =
+            DistinguishedName::decode(Der::new(&rdn(&value))).expect("Failed to decode name");
+        let expected = format!("#0382012c{}", "5a".repeat(300));
+        assert_eq!(long.get("2.5.4.45"), Some(expected.as_str()));
+    }
+}
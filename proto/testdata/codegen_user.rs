@@ -0,0 +1,220 @@
+// Generated by scim_proto::codegen from urn:ietf:params:scim:schemas:core:2.0:User. Do not edit.
+
+#[derive(::scim_proto::__private::serde::Serialize, ::scim_proto::__private::serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "::scim_proto::__private::serde", rename_all = "camelCase")]
+pub struct UserName {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formatted: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub given_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub middle_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub honorific_prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub honorific_suffix: Option<String>,
+}
+
+#[derive(::scim_proto::__private::serde::Serialize, ::scim_proto::__private::serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "::scim_proto::__private::serde", rename_all = "camelCase")]
+pub struct UserEmails {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+}
+
+#[derive(::scim_proto::__private::serde::Serialize, ::scim_proto::__private::serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "::scim_proto::__private::serde", rename_all = "camelCase")]
+pub struct UserPhoneNumbers {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+}
+
+#[derive(::scim_proto::__private::serde::Serialize, ::scim_proto::__private::serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "::scim_proto::__private::serde", rename_all = "camelCase")]
+pub struct UserIms {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+}
+
+#[derive(::scim_proto::__private::serde::Serialize, ::scim_proto::__private::serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "::scim_proto::__private::serde", rename_all = "camelCase")]
+pub struct UserPhotos {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<::scim_proto::__private::url::Url>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+}
+
+#[derive(::scim_proto::__private::serde::Serialize, ::scim_proto::__private::serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "::scim_proto::__private::serde", rename_all = "camelCase")]
+pub struct UserAddresses {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formatted: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub street_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locality: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub postal_code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+}
+
+#[derive(::scim_proto::__private::serde::Serialize, ::scim_proto::__private::serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "::scim_proto::__private::serde", rename_all = "camelCase")]
+pub struct UserGroups {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(rename = "$ref", alias = "ref", alias = "reference", default, skip_serializing_if = "Option::is_none")]
+    pub ref_: Option<::scim_proto::__private::url::Url>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+}
+
+#[derive(::scim_proto::__private::serde::Serialize, ::scim_proto::__private::serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "::scim_proto::__private::serde", rename_all = "camelCase")]
+pub struct UserEntitlements {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+}
+
+#[derive(::scim_proto::__private::serde::Serialize, ::scim_proto::__private::serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "::scim_proto::__private::serde", rename_all = "camelCase")]
+pub struct UserRoles {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+}
+
+#[derive(::scim_proto::__private::serde::Serialize, ::scim_proto::__private::serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "::scim_proto::__private::serde", rename_all = "camelCase")]
+pub struct UserX509Certificates {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+}
+
+/// User Account
+#[derive(::scim_proto::__private::serde::Serialize, ::scim_proto::__private::serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "::scim_proto::__private::serde", rename_all = "camelCase")]
+pub struct User {
+    #[serde(flatten)]
+    pub entry: ::scim_proto::ScimEntry,
+    pub user_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<UserName>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nick_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile_url: Option<::scim_proto::__private::url::Url>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preferred_language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub emails: Vec<UserEmails>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub phone_numbers: Vec<UserPhoneNumbers>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ims: Vec<UserIms>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub photos: Vec<UserPhotos>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<UserAddresses>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<UserGroups>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entitlements: Vec<UserEntitlements>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<UserRoles>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub x509_certificates: Vec<UserX509Certificates>,
+}
+
+impl ::std::convert::TryFrom<::scim_proto::ScimEntryGeneric> for User {
+    type Error = ::scim_proto::error::ScimError;
+
+    fn try_from(entry: ::scim_proto::ScimEntryGeneric) -> Result<Self, Self::Error> {
+        ::scim_proto::__private::convert(&entry)
+    }
+}
+
+impl ::std::convert::TryFrom<User> for ::scim_proto::ScimEntryGeneric {
+    type Error = ::scim_proto::error::ScimError;
+
+    fn try_from(resource: User) -> Result<Self, Self::Error> {
+        ::scim_proto::__private::convert(&resource)
+    }
+}
+
+impl ::scim_proto::resource::ScimResource for User {
+    const SCHEMA_URN: &'static str = "urn:ietf:params:scim:schemas:core:2.0:User";
+
+    fn resource_type() -> &'static str {
+        "User"
+    }
+
+    fn into_entry(self) -> Result<::scim_proto::ScimEntryGeneric, ::scim_proto::error::ScimError> {
+        ::scim_proto::ScimEntryGeneric::try_from(self)
+    }
+}